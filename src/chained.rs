@@ -0,0 +1,132 @@
+use crate::snapshot::Snapshot;
+use crate::{HandlesObservations, Observation, PutsSnapshot};
+
+/// Two `HandlesObservations` acting as one.
+///
+/// An `Observation` is handled by both and the number of
+/// updated instruments is the sum of both. The `Snapshot`
+/// contains the values of both in order.
+///
+/// Created with `HandlesObservations::chain`.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Instant;
+/// use metrix::instruments::*;
+/// use metrix::{HandlesObservations, Observation};
+///
+/// let mut handler = Counter::new("a")
+///     .for_label("request")
+///     .chain(Counter::new("b").for_label("request"));
+///
+/// let observation = Observation::ObservedOne {
+///     label: "request",
+///     timestamp: Instant::now(),
+/// };
+/// assert_eq!(handler.handle_observation(&observation), 2);
+/// ```
+pub struct Chained<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<A, B> Chained<A, B>
+where
+    A: HandlesObservations,
+    B: HandlesObservations<Label = A::Label>,
+{
+    pub fn new(first: A, second: B) -> Self {
+        Chained { first, second }
+    }
+
+    pub fn first(&self) -> &A {
+        &self.first
+    }
+
+    pub fn first_mut(&mut self) -> &mut A {
+        &mut self.first
+    }
+
+    pub fn second(&self) -> &B {
+        &self.second
+    }
+
+    pub fn second_mut(&mut self) -> &mut B {
+        &mut self.second
+    }
+
+    pub fn into_inner(self) -> (A, B) {
+        (self.first, self.second)
+    }
+}
+
+impl<A, B> HandlesObservations for Chained<A, B>
+where
+    A: HandlesObservations,
+    B: HandlesObservations<Label = A::Label>,
+{
+    type Label = A::Label;
+
+    fn handle_observation(&mut self, observation: &Observation<Self::Label>) -> usize {
+        self.first.handle_observation(observation) + self.second.handle_observation(observation)
+    }
+
+    fn reset(&mut self) {
+        self.first.reset();
+        self.second.reset();
+    }
+}
+
+impl<A, B> PutsSnapshot for Chained<A, B>
+where
+    A: PutsSnapshot,
+    B: PutsSnapshot,
+{
+    fn put_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
+        self.first.put_snapshot(into, descriptive);
+        self.second.put_snapshot(into, descriptive);
+    }
+
+    fn put_exported_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
+        self.first.put_exported_snapshot(into, descriptive);
+        self.second.put_exported_snapshot(into, descriptive);
+    }
+
+    fn put_names(&self, into: &mut Snapshot) {
+        self.first.put_names(into);
+        self.second.put_names(into);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::instruments::Counter;
+    use crate::snapshot::{FindItem, ItemKind};
+
+    #[test]
+    fn one_observation_updates_both_counters() {
+        let mut chained = Counter::new("a")
+            .for_label(1)
+            .chain(Counter::new("b").for_label(1));
+
+        let updates = chained.handle_observation(&Observation::ObservedOne {
+            label: 1,
+            timestamp: Instant::now(),
+        });
+        assert_eq!(updates, 2);
+        assert_eq!(chained.first().instrument().get(), 1);
+        assert_eq!(chained.second().instrument().get(), 1);
+
+        let mut snapshot = Snapshot::default();
+        chained.put_snapshot(&mut snapshot, false);
+        assert_eq!(snapshot.find("a"), FindItem::Found(&ItemKind::UInt(1)));
+        assert_eq!(snapshot.find("b"), FindItem::Found(&ItemKind::UInt(1)));
+
+        chained.reset();
+        assert_eq!(chained.second().instrument().get(), 0);
+    }
+}
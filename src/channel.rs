@@ -0,0 +1,721 @@
+//! The channel between the transmitters and a `TelemetryProcessor`
+//!
+//! Unbounded channels are plain crossbeam channels. A bounded channel
+//! has to evict queued observations while letting other messages pass
+//! when it is full which a crossbeam channel can not do. So it is a
+//! queue of its own.
+use std::collections::VecDeque;
+use std::fmt;
+use std::future;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::task::{Poll, Waker};
+
+use crossbeam_channel::{self as crossbeam, TryRecvError};
+
+use crate::processor::{OverflowPolicy, Priority, TelemetryMessage};
+use crate::TransmitError;
+
+pub(crate) fn unbounded<L>() -> (MessageSender<L>, MessageReceiver<L>) {
+    let (tx, rx) = crossbeam::unbounded();
+    (MessageSender::Unbounded(tx), MessageReceiver::Unbounded(rx))
+}
+
+/// Creates a channel which queues at most `capacity` messages
+/// containing observations.
+pub(crate) fn bounded<L>(
+    capacity: usize,
+    policy: OverflowPolicy,
+) -> (MessageSender<L>, MessageReceiver<L>) {
+    let queue = Arc::new(BoundedQueue {
+        capacity,
+        policy,
+        state: Mutex::new(QueueState {
+            messages: VecDeque::new(),
+            queued_observations: 0,
+            dropped: 0,
+            senders: 1,
+            receiver_alive: true,
+            waiters: Vec::new(),
+        }),
+        has_capacity: Condvar::new(),
+    });
+    (
+        MessageSender::Bounded(Arc::clone(&queue)),
+        MessageReceiver::Bounded(queue),
+    )
+}
+
+/// A message could not be sent
+pub(crate) enum SendError<L> {
+    /// The channel is full and its `OverflowPolicy` rejects the message
+    Full(TelemetryMessage<L>),
+    /// The channel is full and the sender has to wait. It will be
+    /// woken once there is capacity. See `MessageSender::try_send`.
+    Pending(TelemetryMessage<L>),
+    /// The receiving `TelemetryProcessor` has been dropped
+    Disconnected(TelemetryMessage<L>),
+}
+
+impl<L> SendError<L> {
+    /// Hands back the `Observation` of a message sent by `try_transmit`
+    pub fn into_transmit_error(self) -> TransmitError<L> {
+        match self {
+            SendError::Full(TelemetryMessage::Observation(observation)) => {
+                TransmitError::Full(observation)
+            }
+            SendError::Disconnected(TelemetryMessage::Observation(observation)) => {
+                TransmitError::Disconnected(observation)
+            }
+            _ => unreachable!("only single observations are transmitted fallibly"),
+        }
+    }
+}
+
+impl<L> fmt::Display for SendError<L> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SendError::Full(_) | SendError::Pending(_) => write!(f, "sending on a full channel"),
+            SendError::Disconnected(_) => write!(f, "sending on a disconnected channel"),
+        }
+    }
+}
+
+pub(crate) enum MessageSender<L> {
+    Unbounded(crossbeam::Sender<TelemetryMessage<L>>),
+    Bounded(Arc<BoundedQueue<L>>),
+}
+
+impl<L> MessageSender<L> {
+    /// Sends the message applying the `OverflowPolicy` of a bounded
+    /// channel.
+    ///
+    /// A message which can not be sent is handed back.
+    #[allow(clippy::result_large_err)]
+    pub fn send(&self, message: TelemetryMessage<L>) -> Result<(), SendError<L>> {
+        self.send_prioritized(message, Priority::default())
+    }
+
+    /// Sends the message like `send`. A full bounded channel which
+    /// drops observations drops those with the lowest `Priority` first.
+    #[allow(clippy::result_large_err)]
+    pub fn send_prioritized(
+        &self,
+        message: TelemetryMessage<L>,
+        priority: Priority,
+    ) -> Result<(), SendError<L>> {
+        match *self {
+            MessageSender::Unbounded(ref sender) => sender
+                .send(message)
+                .map_err(|crossbeam::SendError(message)| SendError::Disconnected(message)),
+            MessageSender::Bounded(ref queue) => {
+                queue.push(message, priority, OnFull::<fn() -> Waiter>::Wait)
+            }
+        }
+    }
+
+    /// Sends the message like `send` but never blocks.
+    ///
+    /// Where `send` would block the message is handed back with
+    /// `SendError::Pending` and the `Waiter` created by `waiter` is
+    /// woken once there is capacity.
+    #[allow(clippy::result_large_err)]
+    pub fn try_send<F>(&self, message: TelemetryMessage<L>, waiter: F) -> Result<(), SendError<L>>
+    where
+        F: FnOnce() -> Waiter,
+    {
+        match *self {
+            MessageSender::Unbounded(_) => self.send(message),
+            MessageSender::Bounded(ref queue) => {
+                queue.push(message, Priority::default(), OnFull::Register(waiter))
+            }
+        }
+    }
+}
+
+/// Sends the message with `try_send` as soon as the channel has
+/// capacity without blocking the thread.
+pub(crate) async fn send_async<L, S>(
+    message: TelemetryMessage<L>,
+    mut try_send: S,
+) -> Result<(), SendError<L>>
+where
+    S: FnMut(TelemetryMessage<L>, &Waker) -> Result<(), SendError<L>>,
+{
+    let mut pending = Some(message);
+    future::poll_fn(|cx| {
+        let message = pending.take().expect("polled after completion");
+        match try_send(message, cx.waker()) {
+            Err(SendError::Pending(message)) => {
+                pending = Some(message);
+                Poll::Pending
+            }
+            sent => Poll::Ready(sent),
+        }
+    })
+    .await
+}
+
+/// A task waiting for a bounded channel to have capacity
+pub(crate) enum Waiter {
+    /// A task of a `futures` executor
+    Task(futures::task::Task),
+    /// A task awaiting a `std::future::Future`
+    Waker(Waker),
+}
+
+impl Waiter {
+    fn wake(self) {
+        match self {
+            Waiter::Task(task) => task.notify(),
+            Waiter::Waker(waker) => waker.wake(),
+        }
+    }
+}
+
+/// What to do when a message has to wait for capacity
+enum OnFull<F> {
+    /// Block the current thread
+    Wait,
+    /// Return and register a `Waiter`
+    Register(F),
+}
+
+impl<L> Clone for MessageSender<L> {
+    fn clone(&self) -> Self {
+        match *self {
+            MessageSender::Unbounded(ref sender) => MessageSender::Unbounded(sender.clone()),
+            MessageSender::Bounded(ref queue) => {
+                queue.lock().senders += 1;
+                MessageSender::Bounded(Arc::clone(queue))
+            }
+        }
+    }
+}
+
+impl<L> Drop for MessageSender<L> {
+    fn drop(&mut self) {
+        if let MessageSender::Bounded(ref queue) = *self {
+            queue.lock().senders -= 1;
+        }
+    }
+}
+
+pub(crate) enum MessageReceiver<L> {
+    Unbounded(crossbeam::Receiver<TelemetryMessage<L>>),
+    Bounded(Arc<BoundedQueue<L>>),
+}
+
+impl<L> MessageReceiver<L> {
+    pub fn try_recv(&self) -> Result<TelemetryMessage<L>, TryRecvError> {
+        match *self {
+            MessageReceiver::Unbounded(ref receiver) => receiver.try_recv(),
+            MessageReceiver::Bounded(ref queue) => queue.pop(),
+        }
+    }
+
+    /// Returns the number of messages currently queued.
+    pub fn len(&self) -> usize {
+        match *self {
+            MessageReceiver::Unbounded(ref receiver) => receiver.len(),
+            MessageReceiver::Bounded(ref queue) => queue.lock().messages.len(),
+        }
+    }
+
+    /// Returns the number of observations dropped because the channel
+    /// was full since this was called the last time.
+    pub fn take_dropped(&self) -> u64 {
+        match *self {
+            MessageReceiver::Unbounded(_) => 0,
+            MessageReceiver::Bounded(ref queue) => std::mem::replace(&mut queue.lock().dropped, 0),
+        }
+    }
+}
+
+impl<L> Drop for MessageReceiver<L> {
+    fn drop(&mut self) {
+        if let MessageReceiver::Bounded(ref queue) = *self {
+            let waiters = {
+                let mut state = queue.lock();
+                state.receiver_alive = false;
+                std::mem::take(&mut state.waiters)
+            };
+            // Waiting senders have to fail now
+            queue.has_capacity.notify_all();
+            waiters.into_iter().for_each(Waiter::wake);
+        }
+    }
+}
+
+pub(crate) struct BoundedQueue<L> {
+    capacity: usize,
+    policy: OverflowPolicy,
+    state: Mutex<QueueState<L>>,
+    has_capacity: Condvar,
+}
+
+struct QueueState<L> {
+    messages: VecDeque<(TelemetryMessage<L>, Priority)>,
+    /// The number of queued messages containing observations.
+    /// Only these count towards the capacity.
+    queued_observations: usize,
+    dropped: u64,
+    senders: usize,
+    receiver_alive: bool,
+    waiters: Vec<Waiter>,
+}
+
+impl<L> BoundedQueue<L> {
+    fn lock(&self) -> MutexGuard<'_, QueueState<L>> {
+        // The state is consistent after each operation. A panic
+        // while it was locked did not corrupt it.
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn push<F>(
+        &self,
+        message: TelemetryMessage<L>,
+        priority: Priority,
+        on_full: OnFull<F>,
+    ) -> Result<(), SendError<L>>
+    where
+        F: FnOnce() -> Waiter,
+    {
+        let mut state = self.lock();
+        if !state.receiver_alive {
+            return Err(SendError::Disconnected(message));
+        }
+
+        let count = match message.observation_count() {
+            Some(count) => count,
+            None => {
+                state.messages.push_back((message, priority));
+                return Ok(());
+            }
+        };
+
+        if state.queued_observations >= self.capacity && self.policy == OverflowPolicy::Block {
+            if let OnFull::Register(waiter) = on_full {
+                state.waiters.push(waiter());
+                return Err(SendError::Pending(message));
+            }
+        }
+
+        while state.queued_observations >= self.capacity {
+            match self.policy {
+                OverflowPolicy::Block => {
+                    state = self
+                        .has_capacity
+                        .wait(state)
+                        .unwrap_or_else(PoisonError::into_inner);
+                    if !state.receiver_alive {
+                        return Err(SendError::Disconnected(message));
+                    }
+                }
+                OverflowPolicy::Reject => return Err(SendError::Full(message)),
+                OverflowPolicy::DropNewest | OverflowPolicy::DropOldest => {
+                    if !state.evict_observations(priority, self.policy) {
+                        state.dropped += count;
+                        return Ok(());
+                    }
+                }
+            }
+        }
+
+        state.queued_observations += 1;
+        state.messages.push_back((message, priority));
+        Ok(())
+    }
+
+    fn pop(&self) -> Result<TelemetryMessage<L>, TryRecvError> {
+        let mut state = self.lock();
+        match state.messages.pop_front() {
+            Some((message, _)) => {
+                if message.observation_count().is_some() {
+                    state.queued_observations -= 1;
+                    self.has_capacity.notify_one();
+                    let waiters = std::mem::take(&mut state.waiters);
+                    drop(state);
+                    waiters.into_iter().for_each(Waiter::wake);
+                }
+                Ok(message)
+            }
+            None if state.senders == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+}
+
+impl<L> QueueState<L> {
+    /// Makes room for observations with the given `Priority` by
+    /// removing a queued message containing observations with the
+    /// lowest priority. Among those `DropOldest` removes the oldest and
+    /// `DropNewest` the newest one.
+    ///
+    /// Only lower priority observations are removed to make room for
+    /// newer ones with `DropNewest`. Returns `false` if nothing was
+    /// removed and the new observations have to be dropped instead.
+    ///
+    /// Messages which add components are never removed.
+    fn evict_observations(&mut self, priority: Priority, policy: OverflowPolicy) -> bool {
+        let observations = self
+            .messages
+            .iter()
+            .enumerate()
+            .filter(|(_, (message, _))| message.observation_count().is_some())
+            .map(|(idx, &(_, queued_priority))| (idx, queued_priority));
+        let lowest = if policy == OverflowPolicy::DropOldest {
+            observations.min_by_key(|&(_, queued_priority)| queued_priority)
+        } else {
+            observations
+                .rev()
+                .min_by_key(|&(_, queued_priority)| queued_priority)
+        };
+
+        let idx = match lowest {
+            Some((idx, queued_priority))
+                if queued_priority < priority
+                    || (queued_priority == priority && policy == OverflowPolicy::DropOldest) =>
+            {
+                idx
+            }
+            _ => return false,
+        };
+
+        if let Some(count) = self
+            .messages
+            .remove(idx)
+            .and_then(|(message, _)| message.observation_count())
+        {
+            self.queued_observations -= 1;
+            self.dropped += count;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc::{self, RecvTimeoutError};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    use futures::executor::{self, Notify};
+    use futures::AsyncSink;
+
+    use crate::cockpit::Cockpit;
+    use crate::instruments::{Counter, Panel};
+    use crate::processor::{
+        OverflowPolicy, Priority, ProcessesTelemetryMessages, ProcessingOutcome,
+        ProcessingStrategy, TelemetryProcessor,
+    };
+    use crate::snapshot::{FindItem, ItemKind, Snapshot};
+    use crate::{
+        Observation, PutsSnapshot, TelemetryTransmitter, TransmitError, TransmitsTelemetryData,
+    };
+
+    /// Counts the observations of the labels 1 to 5 in separate panels
+    fn counting_cockpit() -> Cockpit<i32> {
+        let mut cockpit = Cockpit::without_name();
+        for label in 1..=5 {
+            cockpit.add_panel(
+                Panel::named(label, format!("panel_{}", label))
+                    .counter(Counter::new("count").for_label(label)),
+            );
+        }
+        cockpit
+    }
+
+    fn received_labels(processor: &TelemetryProcessor<i32>) -> Vec<i32> {
+        let mut snapshot = Snapshot::default();
+        processor.put_snapshot(&mut snapshot, false);
+        (1..=5)
+            .filter(|label| {
+                let path = format!("bounded/panel_{}/count", label);
+                snapshot.find(&path) == FindItem::Found(&ItemKind::UInt(1))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn a_bounded_channel_honors_the_overflow_policy() {
+        fn received(policy: OverflowPolicy) -> (Vec<i32>, ProcessingOutcome) {
+            let (tx, processor) = TelemetryProcessor::new_pair_bounded("bounded", 2, policy);
+            let mut processor = processor.cockpit(counting_cockpit());
+
+            for label in 1..=5 {
+                tx.observed_one_now(label);
+            }
+
+            let outcome = processor.process(100, ProcessingStrategy::ProcessAll);
+            (received_labels(&processor), outcome)
+        }
+
+        let (labels, outcome) = received(OverflowPolicy::DropNewest);
+        assert_eq!(labels, vec![1, 2]);
+        assert_eq!(outcome.processed, 2);
+        assert_eq!(outcome.dropped, 3);
+
+        let (labels, outcome) = received(OverflowPolicy::DropOldest);
+        assert_eq!(labels, vec![4, 5]);
+        assert_eq!(outcome.processed, 2);
+        assert_eq!(outcome.dropped, 3);
+    }
+
+    #[test]
+    fn a_full_bounded_channel_keeps_components_and_detects_disconnects() {
+        let (tx, mut processor): (TelemetryTransmitter<i32>, _) =
+            TelemetryProcessor::new_pair_bounded("bounded", 1, OverflowPolicy::DropOldest);
+
+        tx.observed_one_now(1)
+            .add_cockpit(counting_cockpit())
+            .observed_one_now(2);
+
+        let outcome = processor.process(100, ProcessingStrategy::ProcessAll);
+        assert_eq!(outcome.processed, 2);
+        assert_eq!(outcome.dropped, 1);
+        assert_eq!(received_labels(&processor), vec![2]);
+
+        drop(processor);
+        match tx.try_transmit(Observation::observed_one_now(3)) {
+            Err(TransmitError::Disconnected(observation)) => assert_eq!(*observation.label(), 3),
+            _ => panic!("the processor has been dropped"),
+        }
+        tx.observed_one_now(4);
+        assert_eq!(tx.failed_sends(), 2);
+    }
+
+    #[test]
+    fn a_rejecting_bounded_channel_hands_back_the_observation() {
+        let (tx, mut processor): (TelemetryTransmitter<i32>, _) =
+            TelemetryProcessor::new_pair_bounded("bounded", 1, OverflowPolicy::Reject);
+
+        assert!(tx.try_transmit(Observation::observed_one_now(1)).is_ok());
+        match tx.synced().try_transmit(Observation::observed_one_now(2)) {
+            Err(TransmitError::Full(observation)) => assert_eq!(*observation.label(), 2),
+            _ => panic!("the observation should have been rejected"),
+        }
+        tx.observed_one_now(3);
+        assert_eq!(tx.failed_sends(), 2);
+
+        let outcome = processor.process(100, ProcessingStrategy::ProcessAll);
+        assert_eq!(outcome.processed, 1);
+        assert_eq!(outcome.dropped, 0);
+    }
+
+    #[test]
+    fn a_full_bounded_sink_is_not_ready_until_there_is_capacity() {
+        struct Woken(AtomicBool);
+
+        impl Notify for Woken {
+            fn notify(&self, _id: usize) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let (tx, mut processor): (TelemetryTransmitter<i32>, _) =
+            TelemetryProcessor::new_pair_bounded("bounded", 1, OverflowPolicy::Block);
+        let woken = Arc::new(Woken(AtomicBool::new(false)));
+        let mut sink = executor::spawn(tx);
+
+        let sent = sink.start_send_notify(Observation::observed_one_now(1), &woken, 0);
+        assert!(sent.unwrap().is_ready());
+        match sink.start_send_notify(Observation::observed_one_now(2), &woken, 0) {
+            Ok(AsyncSink::NotReady(observation)) => assert_eq!(*observation.label(), 2),
+            _ => panic!("the sink should not be ready"),
+        }
+        assert!(!woken.0.load(Ordering::SeqCst));
+
+        let outcome = processor.process(100, ProcessingStrategy::ProcessAll);
+        assert_eq!(outcome.processed, 1);
+        assert!(woken.0.load(Ordering::SeqCst));
+
+        let sent = sink.start_send_notify(Observation::observed_one_now(2), &woken, 0);
+        assert!(sent.unwrap().is_ready());
+        assert_eq!(sink.get_ref().failed_sends(), 0);
+    }
+
+    #[test]
+    fn async_tasks_await_capacity_without_blocking_the_thread() {
+        use std::future::{self, Future};
+        use std::pin::Pin;
+        use std::task::{Context, Poll, Wake, Waker};
+
+        struct Unused;
+
+        impl Wake for Unused {
+            fn wake(self: Arc<Self>) {}
+        }
+
+        // A single threaded executor which polls every task in turn
+        // so it deadlocks if one of them blocks the thread
+        fn run_all(mut tasks: Vec<Pin<Box<dyn Future<Output = ()> + '_>>>) {
+            let waker = Waker::from(Arc::new(Unused));
+            let mut cx = Context::from_waker(&waker);
+            while !tasks.is_empty() {
+                tasks.retain_mut(|task| task.as_mut().poll(&mut cx).is_pending());
+            }
+        }
+
+        let (tx, mut processor): (TelemetryTransmitter<i32>, _) =
+            TelemetryProcessor::new_pair_bounded("bounded", 3, OverflowPolicy::Block);
+        processor.add_cockpit(
+            Cockpit::without_name()
+                .panel(Panel::named(1, "panel").counter(Counter::new("count").for_label(1))),
+        );
+
+        let mut tasks: Vec<Pin<Box<dyn Future<Output = ()> + '_>>> = (0..4)
+            .map(|_| {
+                let tx = tx.clone();
+                Box::pin(async move {
+                    for _ in 0..100 {
+                        tx.transmit_async(Observation::observed_one_now(1))
+                            .await
+                            .unwrap();
+                    }
+                }) as Pin<Box<dyn Future<Output = ()>>>
+            })
+            .collect();
+        let processing = &mut processor;
+        tasks.push(Box::pin(async move {
+            let mut processed = 0;
+            while processed < 400 {
+                processed += processing
+                    .process(2, ProcessingStrategy::ProcessAll)
+                    .processed;
+                let mut yielded = false;
+                future::poll_fn(|_| {
+                    if yielded {
+                        Poll::Ready(())
+                    } else {
+                        yielded = true;
+                        Poll::Pending
+                    }
+                })
+                .await;
+            }
+        }));
+        run_all(tasks);
+
+        let mut snapshot = Snapshot::default();
+        processor.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("bounded/panel/count"),
+            FindItem::Found(&ItemKind::UInt(400))
+        );
+        assert_eq!(tx.failed_sends(), 0);
+    }
+
+    #[tokio::test]
+    async fn tokio_tasks_await_capacity_while_the_processor_drains() {
+        let (tx, processor): (TelemetryTransmitter<i32>, _) =
+            TelemetryProcessor::new_pair_bounded("bounded", 3, OverflowPolicy::Block);
+        let mut processor = processor.cockpit(counting_cockpit());
+
+        let tasks: Vec<_> = (0..4)
+            .map(|_| {
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    for _ in 0..100 {
+                        tx.transmit_async(Observation::observed_one_now(1))
+                            .await
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        let draining = thread::spawn(move || {
+            let mut processed = 0;
+            while processed < 400 {
+                processed += processor
+                    .process(2, ProcessingStrategy::ProcessAll)
+                    .processed;
+                thread::yield_now();
+            }
+            processor
+        });
+
+        for task in tasks {
+            task.await.unwrap();
+        }
+        let processor = draining.join().unwrap();
+
+        let mut snapshot = Snapshot::default();
+        processor.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("bounded/panel_1/count"),
+            FindItem::Found(&ItemKind::UInt(400))
+        );
+        assert_eq!(tx.failed_sends(), 0);
+    }
+
+    #[test]
+    fn a_full_bounded_channel_drops_the_lowest_priority_first() {
+        fn received_labels(policy: OverflowPolicy) -> (Vec<i32>, usize) {
+            let (tx, processor) = TelemetryProcessor::new_pair_bounded("bounded", 3, policy);
+            let mut processor = processor.keep_recent_observations(10);
+
+            tx.transmit_prioritized(Observation::observed_one_now(1), Priority::High)
+                .transmit_prioritized(Observation::observed_one_now(2), Priority::Low)
+                .transmit_prioritized(Observation::observed_one_now(3), Priority::Low)
+                .observed_one_now(4)
+                .transmit_prioritized(Observation::observed_one_now(5), Priority::High)
+                .transmit_prioritized(Observation::observed_one_now(6), Priority::Low);
+
+            let outcome = processor.process(100, ProcessingStrategy::ProcessAll);
+            let labels = processor
+                .recent_observations()
+                .iter()
+                .map(|obs| *obs.label())
+                .collect();
+            (labels, outcome.dropped)
+        }
+
+        assert_eq!(
+            received_labels(OverflowPolicy::DropOldest),
+            (vec![1, 4, 5], 3)
+        );
+        assert_eq!(
+            received_labels(OverflowPolicy::DropNewest),
+            (vec![1, 4, 5], 3)
+        );
+    }
+
+    #[test]
+    fn a_bounded_channel_blocks_until_there_is_capacity() {
+        let (tx, processor): (TelemetryTransmitter<i32>, _) =
+            TelemetryProcessor::new_pair_bounded("bounded", 2, OverflowPolicy::Block);
+        let mut processor = processor.cockpit(counting_cockpit());
+
+        tx.observed_one_now(1).observed_one_now(2);
+
+        let (started_tx, started) = mpsc::channel();
+        let (sent_tx, sent) = mpsc::channel();
+        let blocked = tx.synced();
+        let sender = thread::spawn(move || {
+            started_tx.send(()).unwrap();
+            blocked.observed_one_now(3);
+            sent_tx.send(()).unwrap();
+        });
+
+        started.recv().unwrap();
+        assert_eq!(
+            sent.recv_timeout(Duration::from_millis(50)),
+            Err(RecvTimeoutError::Timeout)
+        );
+
+        let outcome = processor.process(1, ProcessingStrategy::ProcessAll);
+        assert_eq!(outcome.processed, 1);
+        sent.recv_timeout(Duration::from_secs(5))
+            .expect("the sender is still blocked");
+        sender.join().unwrap();
+
+        let outcome = processor.process(100, ProcessingStrategy::ProcessAll);
+        assert_eq!(outcome.processed, 2);
+        assert_eq!(outcome.dropped, 0);
+        assert_eq!(received_labels(&processor), vec![1, 2, 3]);
+    }
+}
@@ -4,7 +4,7 @@ use std::time::{Duration, Instant};
 use crate::instruments::*;
 use crate::snapshot::{ItemKind, Snapshot};
 use crate::util;
-use crate::{HandlesObservations, Observation, PutsSnapshot};
+use crate::{HandlesObservations, Observation, PutsSnapshot, TimeUnit};
 
 /// A cockpit groups panels.
 ///
@@ -21,6 +21,8 @@ pub struct Cockpit<L> {
     snapshooters: Vec<Box<dyn PutsSnapshot>>,
     last_activity_at: Instant,
     max_inactivity_duration: Option<Duration>,
+    activity_markers: util::ActivityMarkers,
+    default_time_unit: Option<TimeUnit>,
 }
 
 impl<L> Cockpit<L>
@@ -46,6 +48,40 @@ where
         Cockpit::default()
     }
 
+    /// Returns a copy of this `Cockpit` with the same structure and
+    /// configuration where all instruments start from scratch.
+    ///
+    /// This is useful to stamp out identical cockpits e.g. per tenant
+    /// which are then usually renamed.
+    ///
+    /// Fails for the same reasons as `Panel::template_clone` and if the
+    /// `Cockpit` itself has handlers or snapshooters.
+    pub fn template_clone(&self) -> Result<Cockpit<L>, TemplateError> {
+        if !self.handlers.is_empty() {
+            return Err(TemplateError::Handler);
+        }
+        if !self.snapshooters.is_empty() {
+            return Err(TemplateError::Snapshooter);
+        }
+
+        Ok(Cockpit {
+            name: self.name.clone(),
+            title: self.title.clone(),
+            description: self.description.clone(),
+            panels: self
+                .panels
+                .iter()
+                .map(Panel::template_clone)
+                .collect::<Result<_, _>>()?,
+            handlers: Vec::new(),
+            snapshooters: Vec::new(),
+            last_activity_at: Instant::now(),
+            max_inactivity_duration: self.max_inactivity_duration,
+            activity_markers: self.activity_markers.clone(),
+            default_time_unit: self.default_time_unit,
+        })
+    }
+
     /// Returns the name of this cockpit.
     ///
     /// If there is a name set, this will group the inner components in the
@@ -89,13 +125,60 @@ where
         self.max_inactivity_duration = Some(limit);
     }
 
+    /// Sets the keys of the fields marking whether this cockpit is
+    /// active if inactivity tracking is enabled.
+    ///
+    /// Default is `_active` and `_inactive`.
+    pub fn set_active_marker_names<A: Into<String>, I: Into<String>>(
+        &mut self,
+        active_key: A,
+        inactive_key: I,
+    ) {
+        self.activity_markers = util::ActivityMarkers::new(active_key, inactive_key);
+    }
+
+    /// Sets the time unit for the instruments of `Panel`s added
+    /// afterwards which do not have a time unit of their own.
+    ///
+    /// Applies to `Gauge`s and `Histogram`s set on a panel and to
+    /// instruments added to the panel later on. `Panel`s already added
+    /// keep their time units.
+    pub fn set_default_time_unit(&mut self, time_unit: TimeUnit) {
+        self.default_time_unit = Some(time_unit);
+    }
+
+    /// Sets the time unit for the instruments of `Panel`s added
+    /// afterwards which do not have a time unit of their own.
+    pub fn default_time_unit(mut self, time_unit: TimeUnit) -> Self {
+        self.set_default_time_unit(time_unit);
+        self
+    }
+
+    /// Returns the default time unit if one was set
+    pub fn get_default_time_unit(&self) -> Option<TimeUnit> {
+        self.default_time_unit
+    }
+
+    /// Checks that no two components put values on the same
+    /// path into the `Snapshot` where one would shadow the other.
+    ///
+    /// Returns the paths used more than once separated by `/`.
+    ///
+    /// See `Panel::validate`.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        util::validate_names(|names| self.put_names(names))
+    }
+
     /// Add a `Panel` to this cockpit.
     ///
     /// A `Panel` will receive only those `Observation`s where
     /// labels match.
     ///
     /// There can be multiple `Panel`s for the same label.
-    pub fn add_panel(&mut self, panel: Panel<L>) {
+    pub fn add_panel(&mut self, mut panel: Panel<L>) {
+        if let Some(time_unit) = self.default_time_unit {
+            panel.apply_default_time_unit(time_unit);
+        }
         self.panels.push(panel);
     }
 
@@ -120,6 +203,24 @@ where
         self.panels.iter_mut().collect()
     }
 
+    /// Returns the first `Panel` with the given name
+    pub fn get_panel_by_name(&self, name: &str) -> Option<&Panel<L>> {
+        self.panels.iter().find(|p| p.name() == Some(name))
+    }
+
+    /// Returns the first `Panel` with the given name mutable
+    pub fn get_panel_by_name_mut(&mut self, name: &str) -> Option<&mut Panel<L>> {
+        self.panels.iter_mut().find(|p| p.name() == Some(name))
+    }
+
+    /// Returns all `Panel`s accepting the given label
+    pub fn get_panels_by_label(&self, label: &L) -> Vec<&Panel<L>> {
+        self.panels
+            .iter()
+            .filter(|p| p.accepts_label(label))
+            .collect()
+    }
+
     /// Add a handler. This can be custom logic for
     /// metrics.
     ///
@@ -183,35 +284,46 @@ where
         self.snapshooters.iter().map(|p| &**p).collect()
     }
 
-    fn put_values_into_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
+    fn put_level(&self, into: &mut Snapshot, descriptive: bool, exported: bool) {
+        if let Some(ref name) = self.name {
+            let mut new_level = Snapshot::default();
+            self.put_values_into_snapshot(&mut new_level, descriptive, exported);
+            into.items
+                .push((name.clone(), ItemKind::Snapshot(new_level)));
+        } else {
+            self.put_values_into_snapshot(into, descriptive, exported);
+        }
+    }
+
+    fn put_component_names(&self, into: &mut Snapshot) {
+        self.panels.iter().for_each(|p| p.put_names(into));
+        self.handlers.iter().for_each(|h| h.put_names(into));
+        self.snapshooters.iter().for_each(|s| s.put_names(into));
+    }
+
+    fn put_values_into_snapshot(&self, into: &mut Snapshot, descriptive: bool, exported: bool) {
         util::put_default_descriptives(self, into, descriptive);
 
         if let Some(d) = self.max_inactivity_duration {
             if self.last_activity_at.elapsed() > d {
-                into.items
-                    .push(("_inactive".to_string(), ItemKind::Boolean(true)));
-                into.items
-                    .push(("_active".to_string(), ItemKind::Boolean(false)));
+                self.activity_markers.put(false, into);
                 return;
             } else {
-                into.items
-                    .push(("_inactive".to_string(), ItemKind::Boolean(false)));
-                into.items
-                    .push(("_active".to_string(), ItemKind::Boolean(true)));
+                self.activity_markers.put(true, into);
             }
         };
 
         self.panels
             .iter()
-            .for_each(|p| p.put_snapshot(into, descriptive));
+            .for_each(|p| util::put_snapshot_of(p, into, descriptive, exported));
 
         self.handlers
             .iter()
-            .for_each(|h| h.put_snapshot(into, descriptive));
+            .for_each(|h| util::put_snapshot_of(&**h, into, descriptive, exported));
 
         self.snapshooters
             .iter()
-            .for_each(|s| s.put_snapshot(into, descriptive));
+            .for_each(|s| util::put_snapshot_of(&**s, into, descriptive, exported));
     }
 }
 
@@ -220,13 +332,21 @@ where
     L: Clone + Eq + Send + 'static,
 {
     fn put_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
+        self.put_level(into, descriptive, false)
+    }
+
+    fn put_exported_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
+        self.put_level(into, descriptive, true)
+    }
+
+    fn put_names(&self, into: &mut Snapshot) {
         if let Some(ref name) = self.name {
             let mut new_level = Snapshot::default();
-            self.put_values_into_snapshot(&mut new_level, descriptive);
+            self.put_component_names(&mut new_level);
             into.items
                 .push((name.clone(), ItemKind::Snapshot(new_level)));
         } else {
-            self.put_values_into_snapshot(into, descriptive);
+            self.put_component_names(into);
         }
     }
 }
@@ -245,6 +365,8 @@ where
             snapshooters: Vec::new(),
             last_activity_at: Instant::now(),
             max_inactivity_duration: None,
+            activity_markers: util::ActivityMarkers::default(),
+            default_time_unit: None,
         }
     }
 }
@@ -270,6 +392,11 @@ where
 
         instruments_updated
     }
+
+    fn reset(&mut self) {
+        self.handlers.iter_mut().for_each(|h| h.reset());
+        self.panels.iter_mut().for_each(|p| p.reset());
+    }
 }
 
 impl<L> crate::Descriptive for Cockpit<L> {
@@ -281,3 +408,225 @@ impl<L> crate::Descriptive for Cockpit<L> {
         self.description.as_ref().map(|n| &**n)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::instruments::{Counter, Gauge, Histogram, Meter};
+    use crate::snapshot::{FindItem, ItemKind};
+
+    #[test]
+    fn reset_clears_all_instruments() {
+        let mut cockpit = Cockpit::new("cockpit").panel(
+            Panel::named(1, "panel")
+                .counter(Counter::new("counter"))
+                .gauge(Gauge::new("gauge"))
+                .meter(Meter::new("meter"))
+                .histogram(Histogram::new("histogram"))
+                .handler(Counter::new("nested_counter").for_label(1)),
+        );
+
+        for v in 1..=3 {
+            cockpit.handle_observation(&Observation::ObservedOneValue {
+                label: 1,
+                value: v.into(),
+                timestamp: Instant::now(),
+            });
+        }
+
+        let mut snapshot = Snapshot::default();
+        cockpit.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("cockpit/panel/counter"),
+            FindItem::Found(&ItemKind::UInt(3))
+        );
+        assert_eq!(
+            snapshot.find("cockpit/panel/gauge"),
+            FindItem::Found(&ItemKind::Int(3))
+        );
+
+        cockpit.reset();
+
+        let mut snapshot = Snapshot::default();
+        cockpit.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("cockpit/panel/counter"),
+            FindItem::Found(&ItemKind::UInt(0))
+        );
+        assert_eq!(
+            snapshot.find("cockpit/panel/nested_counter"),
+            FindItem::Found(&ItemKind::UInt(0))
+        );
+        assert_eq!(snapshot.find("cockpit/panel/gauge"), FindItem::NotFound);
+        assert_eq!(
+            snapshot.find("cockpit/panel/meter/count"),
+            FindItem::Found(&ItemKind::UInt(0))
+        );
+        assert_eq!(
+            snapshot.find("cockpit/panel/histogram/count"),
+            FindItem::Found(&ItemKind::UInt(0))
+        );
+    }
+
+    #[test]
+    fn panels_can_be_retrieved() {
+        let mut cockpit = Cockpit::new("cockpit")
+            .panel(Panel::named(1, "requests").counter(Counter::new("count")))
+            .panel(Panel::named(2, "errors").counter(Counter::new("count")));
+
+        cockpit.handle_observation(&Observation::ObservedOne {
+            label: 2,
+            timestamp: Instant::now(),
+        });
+
+        let errors = cockpit.get_panel_by_name("errors").unwrap();
+        assert_eq!(errors.name(), Some("errors"));
+        assert_eq!(errors.get_counter().unwrap().get(), 1);
+
+        assert_eq!(cockpit.get_panels().len(), 2);
+        assert_eq!(cockpit.get_panels_by_label(&1)[0].name(), Some("requests"));
+        assert!(cockpit.get_panel_by_name("unknown").is_none());
+    }
+
+    #[test]
+    fn the_default_time_unit_applies_to_panels_added_afterwards() {
+        let mut cockpit = Cockpit::new("cockpit");
+        cockpit.add_panel(Panel::named(1, "before").histogram(Histogram::new("histogram")));
+        cockpit.set_default_time_unit(TimeUnit::Milliseconds);
+        cockpit.add_panel(Panel::named(1, "after").histogram(Histogram::new("histogram")));
+        cockpit.add_panel(
+            Panel::named(1, "overridden")
+                .histogram(Histogram::new("histogram").display_time_unit(TimeUnit::Seconds)),
+        );
+
+        cockpit.handle_observation(&Observation::ObservedOneValue {
+            label: 1,
+            value: Duration::from_secs(2).into(),
+            timestamp: Instant::now(),
+        });
+
+        let mut snapshot = Snapshot::default();
+        cockpit.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("cockpit/before/histogram/max"),
+            FindItem::Found(&ItemKind::Int(2_000_000))
+        );
+        assert_eq!(
+            snapshot.find("cockpit/after/histogram/max"),
+            FindItem::Found(&ItemKind::Int(2_000))
+        );
+        assert_eq!(
+            snapshot.find("cockpit/overridden/histogram/max"),
+            FindItem::Found(&ItemKind::Int(2))
+        );
+    }
+
+    #[test]
+    fn validate_reports_same_named_instruments() {
+        let cockpit: Cockpit<i32> = Cockpit::new("cockpit")
+            .panel(
+                Panel::named(1, "panel")
+                    .counter(Counter::new("count"))
+                    .counter(Counter::new("count"))
+                    .gauge(Gauge::new("gauge"))
+                    .gauge(Gauge::new("gauge")),
+            )
+            .panel(Panel::named(2, "other").counter(Counter::new("count")));
+
+        // The gauges have no values yet
+        assert_eq!(
+            cockpit.validate(),
+            Err(vec![
+                "cockpit/panel/count".to_string(),
+                "cockpit/panel/gauge".to_string()
+            ])
+        );
+
+        let valid: Cockpit<i32> =
+            Cockpit::new("cockpit").panel(Panel::named(1, "panel").counter(Counter::new("count")));
+        assert_eq!(valid.validate(), Ok(()));
+    }
+
+    #[test]
+    fn a_template_clone_starts_from_scratch() {
+        let mut cockpit = Cockpit::new("tenant_a").panel(
+            Panel::named(1, "requests")
+                .counter(Counter::new("count"))
+                .histogram(Histogram::new_hdr("latency", 1_000, 2).buckets(&[10]))
+                .panel(
+                    Panel::named(1, "nested").gauge(Gauge::new("last").watermarks_enabled(true)),
+                ),
+        );
+        cockpit.handle_observation(&Observation::observed_one_value_now(1, 5));
+
+        let mut clone = cockpit.template_clone().unwrap();
+        clone.set_name("tenant_b");
+
+        let mut snapshot = Snapshot::default();
+        clone.put_snapshot(&mut snapshot, false);
+        let expect = |snapshot: &Snapshot, path: &str, item: ItemKind| {
+            assert_eq!(snapshot.find(path), FindItem::Found(&item), "{}", path);
+        };
+        expect(&snapshot, "tenant_b/requests/count", ItemKind::UInt(0));
+        expect(
+            &snapshot,
+            "tenant_b/requests/latency/count",
+            ItemKind::UInt(0),
+        );
+        expect(
+            &snapshot,
+            "tenant_b/requests/latency/bucket_10",
+            ItemKind::UInt(0),
+        );
+        assert_eq!(
+            snapshot.find("tenant_b/requests/nested/last"),
+            FindItem::NotFound
+        );
+
+        clone.handle_observation(&Observation::observed_one_value_now(1, 7));
+        clone.handle_observation(&Observation::observed_one_value_now(1, 7));
+
+        let mut original = Snapshot::default();
+        cockpit.put_snapshot(&mut original, false);
+        expect(&original, "tenant_a/requests/count", ItemKind::UInt(1));
+        expect(&original, "tenant_a/requests/nested/last", ItemKind::Int(5));
+
+        let mut snapshot = Snapshot::default();
+        clone.put_snapshot(&mut snapshot, false);
+        expect(&snapshot, "tenant_b/requests/count", ItemKind::UInt(2));
+        expect(
+            &snapshot,
+            "tenant_b/requests/latency/count",
+            ItemKind::UInt(2),
+        );
+        expect(&snapshot, "tenant_b/requests/nested/last", ItemKind::Int(7));
+        expect(
+            &snapshot,
+            "tenant_b/requests/nested/last_max",
+            ItemKind::Int(7),
+        );
+    }
+
+    #[test]
+    fn components_with_functions_can_not_be_template_cloned() {
+        let with_predicate: Cockpit<i32> =
+            Cockpit::without_name().panel(Panel::new(LabelFilter::predicate(|l: &i32| *l > 0)));
+        assert_eq!(
+            with_predicate.template_clone().err(),
+            Some(TemplateError::LabelPredicate)
+        );
+
+        let with_snapshooter: Cockpit<i32> = Cockpit::without_name().panel(
+            Panel::new(1).snapshooter(crate::instruments::polled::PolledValue::new(
+                "polled",
+                || Ok::<_, String>(crate::ObservedValue::from(1u64)),
+            )),
+        );
+        assert_eq!(
+            with_snapshooter.template_clone().err(),
+            Some(TemplateError::Snapshooter)
+        );
+    }
+}
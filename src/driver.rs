@@ -3,7 +3,7 @@ use std::fmt;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::thread;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use crossbeam_channel::{
     self, Receiver as CrossbeamReceiver, Sender as CrossbeamSender, TryRecvError,
@@ -36,12 +36,22 @@ pub struct DriverBuilder {
     pub description: Option<String>,
     /// Sets the `ProcessingStrategy`
     /// dropped. The default is **60 seconds**.
+    ///
+    /// Processors with a default strategy of their own are
+    /// processed with that one instead.
     pub processing_strategy: ProcessingStrategy,
     /// If true metrics for the `TelemetryDriver` will be added to the
     /// generated `Snapshot`
     ///
     /// Default is `true`
     pub with_driver_metrics: bool,
+    /// If true `_snapshot_at` (unix epoch millis) and
+    /// `_snapshot_duration_us` will be added to the top level of
+    /// each `Snapshot` taken from the `TelemetryDriver`. Both are
+    /// marked as gauges for exporters.
+    ///
+    /// Default is `false`
+    pub with_snapshot_metadata: bool,
 }
 
 impl DriverBuilder {
@@ -76,14 +86,23 @@ impl DriverBuilder {
         self
     }
 
+    pub fn set_snapshot_metadata(mut self, enabled: bool) -> Self {
+        self.with_snapshot_metadata = enabled;
+        self
+    }
+
     pub fn build(self) -> TelemetryDriver {
-        TelemetryDriver::new(
+        let driver = TelemetryDriver::new(
             self.name,
             self.title,
             self.description,
             self.processing_strategy,
             self.with_driver_metrics,
-        )
+        );
+        if self.with_snapshot_metadata {
+            driver.set_snapshot_metadata(true);
+        }
+        driver
     }
 }
 
@@ -95,6 +114,7 @@ impl Default for DriverBuilder {
             description: None,
             processing_strategy: ProcessingStrategy::default(),
             with_driver_metrics: true,
+            with_snapshot_metadata: false,
         }
     }
 }
@@ -220,6 +240,9 @@ impl TelemetryDriver {
     }
 
     /// Changes the `ProcessingStrategy`
+    ///
+    /// Unlike the strategy the driver was built with, this also
+    /// replaces the default strategies of the processors.
     pub fn change_processing_stragtegy(&self, strategy: ProcessingStrategy) {
         let _ = self
             .sender
@@ -236,12 +259,47 @@ impl TelemetryDriver {
         let _ = self.sender.send(DriverMessage::Resume);
     }
 
+    /// Enables or disables adding `_snapshot_at` (unix epoch millis) and
+    /// `_snapshot_duration_us` to the top level of each `Snapshot`
+    /// taken from this driver. Both are marked as gauges for exporters.
+    ///
+    /// If this driver is mounted into another driver the metadata is
+    /// not added to the snapshot of the other driver.
+    pub fn set_snapshot_metadata(&self, enabled: bool) {
+        let _ = self
+            .sender
+            .send(DriverMessage::SetSnapshotMetadata(enabled));
+    }
+
     pub fn snapshot(&self, descriptive: bool) -> Result<Snapshot, GetSnapshotError> {
+        self.get_snapshot(descriptive, true, false)
+    }
+
+    /// Returns a `Snapshot` which will be exported.
+    ///
+    /// Unlike `snapshot` this resets values which start over with each
+    /// exported `Snapshot`. See `PutsSnapshot::put_exported_snapshot`.
+    /// The `Snapshot`s passed to the callbacks registered with
+    /// `on_snapshot` are exported `Snapshot`s as well.
+    pub fn exported_snapshot(&self, descriptive: bool) -> Result<Snapshot, GetSnapshotError> {
+        self.get_snapshot(descriptive, true, true)
+    }
+
+    fn get_snapshot(
+        &self,
+        descriptive: bool,
+        root: bool,
+        exported: bool,
+    ) -> Result<Snapshot, GetSnapshotError> {
         let snapshot = Snapshot::default();
         let (tx, rx) = crossbeam_channel::unbounded();
-        let _ = self
-            .sender
-            .send(DriverMessage::GetSnapshotSync(snapshot, tx, descriptive));
+        let _ = self.sender.send(DriverMessage::GetSnapshotSync(
+            snapshot,
+            tx,
+            descriptive,
+            root,
+            exported,
+        ));
         rx.recv().map_err(|_err| GetSnapshotError)
     }
 
@@ -256,6 +314,38 @@ impl TelemetryDriver {
             .send(DriverMessage::GetSnapshotAsync(snapshot, tx, descriptive));
         rx.map_err(|_| GetSnapshotError)
     }
+
+    /// Registers a callback which will be called with a fresh `Snapshot`
+    /// every `interval`.
+    ///
+    /// The callback runs on the driver's thread so it will never run
+    /// concurrently with itself. If creating the `Snapshot` or the callback
+    /// takes longer than `interval` the missed invocations are skipped
+    /// instead of being caught up on. Since observations are not processed
+    /// while the callback runs it should return quickly.
+    ///
+    /// The `Snapshot`s are not descriptive. They are exported `Snapshot`s.
+    /// See `exported_snapshot`.
+    ///
+    /// # Panics
+    ///
+    /// If `interval` is zero.
+    pub fn on_snapshot<F>(&self, interval: Duration, f: F)
+    where
+        F: FnMut(Snapshot) + Send + 'static,
+    {
+        if interval == Duration::from_secs(0) {
+            panic!("the interval of a snapshot callback must not be zero");
+        }
+
+        let _ = self
+            .sender
+            .send(DriverMessage::AddSnapshotCallback(SnapshotCallback {
+                interval,
+                next_due: Instant::now() + interval,
+                callback: Box::new(f),
+            }));
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -282,11 +372,15 @@ impl ProcessesTelemetryMessages for TelemetryDriver {
     fn process(&mut self, _max: usize, _strategy: ProcessingStrategy) -> ProcessingOutcome {
         ProcessingOutcome::default()
     }
+
+    fn name(&self) -> Option<&str> {
+        self.descriptives.name.as_deref()
+    }
 }
 
-impl PutsSnapshot for TelemetryDriver {
-    fn put_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
-        if let Ok(snapshot) = self.snapshot(descriptive) {
+impl TelemetryDriver {
+    fn put_mounted_snapshot(&self, into: &mut Snapshot, descriptive: bool, exported: bool) {
+        if let Ok(snapshot) = self.get_snapshot(descriptive, false, exported) {
             snapshot
                 .items
                 .into_iter()
@@ -295,6 +389,16 @@ impl PutsSnapshot for TelemetryDriver {
     }
 }
 
+impl PutsSnapshot for TelemetryDriver {
+    fn put_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
+        self.put_mounted_snapshot(into, descriptive, false)
+    }
+
+    fn put_exported_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
+        self.put_mounted_snapshot(into, descriptive, true)
+    }
+}
+
 impl Default for TelemetryDriver {
     fn default() -> TelemetryDriver {
         TelemetryDriver::new(None, None, None, ProcessingStrategy::default(), true)
@@ -349,13 +453,36 @@ fn start_telemetry_loop(
 enum DriverMessage {
     AddProcessor(Box<dyn ProcessesTelemetryMessages>),
     AddSnapshooter(Box<dyn PutsSnapshot>),
-    GetSnapshotSync(Snapshot, CrossbeamSender<Snapshot>, bool),
+    AddSnapshotCallback(SnapshotCallback),
+    /// The last fields tell whether the snapshot is taken for the root
+    /// and whether it will be exported
+    GetSnapshotSync(Snapshot, CrossbeamSender<Snapshot>, bool, bool, bool),
     GetSnapshotAsync(Snapshot, oneshot::Sender<Snapshot>, bool),
     SetProcessingStrategy(ProcessingStrategy),
+    SetSnapshotMetadata(bool),
     Pause,
     Resume,
 }
 
+struct SnapshotCallback {
+    interval: Duration,
+    next_due: Instant,
+    callback: Box<dyn FnMut(Snapshot) + Send>,
+}
+
+impl SnapshotCallback {
+    /// Moves `next_due` to the first multiple of `interval` after `now`
+    /// so that missed invocations are skipped.
+    fn schedule_after(&mut self, now: Instant) {
+        if self.next_due > now {
+            return;
+        }
+        let interval = self.interval.as_nanos();
+        let into_interval = ((now - self.next_due).as_nanos() % interval) as u64;
+        self.next_due = now + self.interval - Duration::from_nanos(into_interval);
+    }
+}
+
 fn telemetry_loop(
     descriptives: Descriptives,
     is_running: &AtomicBool,
@@ -368,10 +495,14 @@ fn telemetry_loop(
 
     let mut processors: Vec<Box<dyn ProcessesTelemetryMessages>> = Vec::new();
     let mut snapshooters: Vec<Box<dyn PutsSnapshot>> = Vec::new();
+    let mut snapshot_callbacks: Vec<SnapshotCallback> = Vec::new();
 
-    let mut processing_stragtegy = processing_strategy;
+    // Set once the strategy is changed explicitly. It then takes
+    // precedence over the default strategies of the processors.
+    let mut strategy_override = None;
 
     let mut paused = false;
+    let mut snapshot_metadata = false;
 
     loop {
         if !is_running.load(Ordering::Relaxed) {
@@ -382,7 +513,14 @@ fn telemetry_loop(
             Ok(message) => match message {
                 DriverMessage::AddProcessor(processor) => processors.push(processor),
                 DriverMessage::AddSnapshooter(snapshooter) => snapshooters.push(snapshooter),
-                DriverMessage::GetSnapshotSync(mut snapshot, back_channel, descriptive) => {
+                DriverMessage::AddSnapshotCallback(callback) => snapshot_callbacks.push(callback),
+                DriverMessage::GetSnapshotSync(
+                    mut snapshot,
+                    back_channel,
+                    descriptive,
+                    root,
+                    exported,
+                ) => {
                     put_values_into_snapshot(
                         &mut snapshot,
                         &processors,
@@ -390,6 +528,8 @@ fn telemetry_loop(
                         driver_metrics.as_mut(),
                         &descriptives,
                         descriptive,
+                        exported,
+                        snapshot_metadata && root,
                     );
                     let _ = back_channel.send(snapshot);
                 }
@@ -401,13 +541,16 @@ fn telemetry_loop(
                         driver_metrics.as_mut(),
                         &descriptives,
                         descriptive,
+                        false,
+                        snapshot_metadata,
                     );
                     let _ = back_channel.send(snapshot);
                 }
                 DriverMessage::SetProcessingStrategy(strategy) => {
                     util::log_info(&format!("Processing strategy changed to {:?}", strategy));
-                    processing_stragtegy = strategy
+                    strategy_override = Some(strategy)
                 }
+                DriverMessage::SetSnapshotMetadata(enabled) => snapshot_metadata = enabled,
                 DriverMessage::Pause => {
                     util::log_info("pausing");
                     paused = true
@@ -434,7 +577,11 @@ fn telemetry_loop(
         }
 
         let started = Instant::now();
-        let outcome = do_a_run(&mut processors, 1_000, processing_stragtegy);
+        let strategy = StrategySelection {
+            driver_default: processing_strategy,
+            explicit: strategy_override,
+        };
+        let outcome = do_a_run(&mut processors, 1_000, strategy);
 
         dropped_since_last_logged += outcome.dropped;
 
@@ -448,6 +595,27 @@ fn telemetry_loop(
             driver_metrics.update_post_collection(&outcome, started);
         }
 
+        for scheduled in snapshot_callbacks.iter_mut() {
+            if scheduled.next_due > Instant::now() {
+                continue;
+            }
+
+            let mut snapshot = Snapshot::default();
+            put_values_into_snapshot(
+                &mut snapshot,
+                &processors,
+                &snapshooters,
+                driver_metrics.as_mut(),
+                &descriptives,
+                false,
+                true,
+                snapshot_metadata,
+            );
+            (scheduled.callback)(snapshot);
+
+            scheduled.schedule_after(Instant::now());
+        }
+
         if outcome.dropped > 0 || outcome.processed > 100 {
             continue;
         }
@@ -462,20 +630,39 @@ fn telemetry_loop(
     util::log_info("Metrix driver stopped");
 }
 
+/// Decides which `ProcessingStrategy` a processor is processed with
+#[derive(Clone, Copy)]
+struct StrategySelection {
+    driver_default: ProcessingStrategy,
+    explicit: Option<ProcessingStrategy>,
+}
+
+impl StrategySelection {
+    /// An explicitly changed strategy wins over the default strategy
+    /// of the processor which wins over the default of the driver.
+    fn for_processor(&self, processor: &dyn ProcessesTelemetryMessages) -> ProcessingStrategy {
+        self.explicit
+            .or_else(|| processor.get_default_strategy())
+            .unwrap_or(self.driver_default)
+    }
+}
+
 fn do_a_run(
     processors: &mut [Box<dyn ProcessesTelemetryMessages>],
     max: usize,
-    strategy: ProcessingStrategy,
+    strategy: StrategySelection,
 ) -> ProcessingOutcome {
     let mut outcome = ProcessingOutcome::default();
 
     for processor in processors.iter_mut() {
+        let strategy = strategy.for_processor(processor.as_ref());
         outcome.combine_with(&processor.process(max, strategy));
     }
 
     outcome
 }
 
+#[allow(clippy::too_many_arguments)]
 fn put_values_into_snapshot(
     into: &mut Snapshot,
     processors: &[Box<dyn ProcessesTelemetryMessages>],
@@ -483,8 +670,11 @@ fn put_values_into_snapshot(
     driver_metrics: Option<&mut DriverMetrics>,
     descriptives: &Descriptives,
     descriptive: bool,
+    exported: bool,
+    metadata: bool,
 ) {
     let started = Instant::now();
+    let started_at = SystemTime::now();
 
     if let Some(ref name) = descriptives.name {
         let mut new_level = Snapshot::default();
@@ -495,6 +685,7 @@ fn put_values_into_snapshot(
             driver_metrics,
             &descriptives,
             descriptive,
+            exported,
             started,
         );
         into.items
@@ -507,11 +698,28 @@ fn put_values_into_snapshot(
             driver_metrics,
             &descriptives,
             descriptive,
+            exported,
             started,
         );
     }
+
+    if metadata {
+        let millis = started_at
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        into.items
+            .push(("_snapshot_at".to_string(), ItemKind::UInt(millis)));
+        into.items.push((
+            "_snapshot_duration_us".to_string(),
+            ItemKind::UInt(started.elapsed().as_micros() as u64),
+        ));
+        util::put_gauge_type("_snapshot_at", into);
+        util::put_gauge_type("_snapshot_duration_us", into);
+    }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn add_snapshot_values(
     into: &mut Snapshot,
     processors: &[Box<dyn ProcessesTelemetryMessages>],
@@ -519,16 +727,17 @@ fn add_snapshot_values(
     driver_metrics: Option<&mut DriverMetrics>,
     descriptives: &Descriptives,
     descriptive: bool,
+    exported: bool,
     started: Instant,
 ) {
     util::put_default_descriptives(descriptives, into, descriptive);
     processors
         .iter()
-        .for_each(|p| p.put_snapshot(into, descriptive));
+        .for_each(|p| util::put_snapshot_of(&**p, into, descriptive, exported));
 
     snapshooters
         .iter()
-        .for_each(|s| s.put_snapshot(into, descriptive));
+        .for_each(|s| util::put_snapshot_of(&**s, into, descriptive, exported));
 
     if let Some(driver_metrics) = driver_metrics {
         driver_metrics.update_post_snapshot(started);
@@ -725,3 +934,210 @@ fn duration_to_micros(d: Duration) -> u64 {
     nanos / 1000
 }
 */
+
+#[cfg(test)]
+mod test {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::snapshot::FindItem;
+
+    /// Creates a callback which records when it was invoked
+    /// and then takes `duration` to return.
+    fn recording_callback(
+        duration: Duration,
+    ) -> (
+        Arc<Mutex<Vec<Instant>>>,
+        impl FnMut(Snapshot) + Send + 'static,
+    ) {
+        let invoked = Arc::new(Mutex::new(Vec::new()));
+        let invoked_in_callback = invoked.clone();
+        let callback = move |_snapshot: Snapshot| {
+            invoked_in_callback.lock().unwrap().push(Instant::now());
+            thread::sleep(duration);
+        };
+        (invoked, callback)
+    }
+
+    /// Waits until the callback was invoked `calls` times
+    /// but gives up after a few seconds.
+    fn wait_for_calls(invoked: &Mutex<Vec<Instant>>, calls: usize) -> Vec<Instant> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            let invoked = invoked.lock().unwrap().clone();
+            if invoked.len() >= calls || Instant::now() >= deadline {
+                return invoked;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    /// Returns once the driver has finished a run after all messages
+    /// and observations sent before.
+    ///
+    /// The driver handles one message per run before it processes the
+    /// observations. So a message sent after the answer to a snapshot
+    /// request is handled after the run which followed that request.
+    fn await_run(driver: &TelemetryDriver) {
+        driver.snapshot(false).unwrap();
+    }
+
+    #[test]
+    fn snapshot_callback_fires_periodically() {
+        let driver = DriverBuilder::default().build();
+        let (invoked, callback) = recording_callback(Duration::from_millis(0));
+        driver.on_snapshot(Duration::from_millis(20), callback);
+
+        let invoked = wait_for_calls(&invoked, 5);
+
+        assert!(invoked.len() >= 5, "calls: {}", invoked.len());
+    }
+
+    #[test]
+    fn slow_snapshot_callbacks_are_skipped() {
+        let driver = DriverBuilder::default().build();
+        let (invoked, callback) = recording_callback(Duration::from_millis(100));
+        driver.on_snapshot(Duration::from_millis(20), callback);
+
+        let invoked = wait_for_calls(&invoked, 3);
+
+        assert!(invoked.len() >= 3, "calls: {}", invoked.len());
+        // The callback never runs concurrently with itself. Which
+        // invocations are skipped is covered by
+        // `missed_snapshot_callbacks_keep_their_cadence`.
+        for calls in invoked.windows(2) {
+            assert!(calls[1] - calls[0] >= Duration::from_millis(100));
+        }
+    }
+
+    #[test]
+    fn missed_snapshot_callbacks_keep_their_cadence() {
+        let start = Instant::now();
+        let mut scheduled = SnapshotCallback {
+            interval: Duration::from_secs(10),
+            next_due: start,
+            callback: Box::new(|_| {}),
+        };
+
+        scheduled.schedule_after(start + Duration::from_secs(35));
+        assert_eq!(scheduled.next_due, start + Duration::from_secs(40));
+
+        scheduled.schedule_after(start + Duration::from_secs(40));
+        assert_eq!(scheduled.next_due, start + Duration::from_secs(50));
+
+        scheduled.schedule_after(start + Duration::from_secs(45));
+        assert_eq!(scheduled.next_due, start + Duration::from_secs(50));
+    }
+
+    #[test]
+    #[should_panic]
+    fn a_snapshot_callback_needs_an_interval() {
+        let driver = DriverBuilder::default().build();
+        driver.on_snapshot(Duration::from_secs(0), |_snapshot| {});
+    }
+
+    #[test]
+    fn processors_are_processed_with_their_default_strategy() {
+        use crate::instruments::{Cockpit, Counter, Panel};
+        use crate::processor::TelemetryProcessor;
+        use crate::{TelemetryTransmitter, TransmitsTelemetryData};
+
+        let mut driver = DriverBuilder::default().set_driver_metrics(false).build();
+        let (tx, processor): (TelemetryTransmitter<i32>, _) =
+            TelemetryProcessor::new_pair("processor");
+        let mut processor = processor.default_strategy(ProcessingStrategy::DropAll);
+        processor.add_cockpit(
+            Cockpit::without_name()
+                .panel(Panel::named(1, "panel").counter(Counter::new("count").for_label(1))),
+        );
+        driver.add_processor(processor);
+
+        tx.observed_one_now(1);
+        await_run(&driver);
+        let snapshot = driver.snapshot(false).unwrap();
+        assert_eq!(
+            snapshot.find("processor/panel/count"),
+            FindItem::Found(&ItemKind::UInt(0))
+        );
+
+        driver.change_processing_stragtegy(ProcessingStrategy::ProcessAll);
+        await_run(&driver);
+        tx.observed_one_now(1);
+        await_run(&driver);
+        let snapshot = driver.snapshot(false).unwrap();
+        assert_eq!(
+            snapshot.find("processor/panel/count"),
+            FindItem::Found(&ItemKind::UInt(1))
+        );
+    }
+
+    #[test]
+    fn only_exported_snapshots_reset_counters() {
+        use crate::instruments::{Cockpit, Counter, Panel};
+        use crate::processor::TelemetryProcessor;
+        use crate::{TelemetryTransmitter, TransmitsTelemetryData};
+
+        let mut driver = DriverBuilder::default().set_driver_metrics(false).build();
+        let (tx, mut processor): (TelemetryTransmitter<i32>, _) =
+            TelemetryProcessor::new_pair("processor");
+        processor.add_cockpit(Cockpit::without_name().panel(
+            Panel::named(1, "panel").counter(Counter::new("count").reset_on_snapshot(true)),
+        ));
+        driver.add_processor(processor);
+
+        tx.observed_one_now(1);
+        await_run(&driver);
+
+        for snapshot in &[
+            driver.snapshot(false).unwrap(),
+            driver.exported_snapshot(false).unwrap(),
+        ] {
+            assert_eq!(
+                snapshot.find("processor/panel/count"),
+                FindItem::Found(&ItemKind::UInt(1))
+            );
+        }
+        let snapshot = driver.snapshot(false).unwrap();
+        assert_eq!(
+            snapshot.find("processor/panel/count"),
+            FindItem::Found(&ItemKind::UInt(0))
+        );
+    }
+
+    #[test]
+    fn snapshot_metadata_is_only_added_to_the_root() {
+        let mut driver = DriverBuilder::new("outer")
+            .set_snapshot_metadata(true)
+            .build();
+        let inner = DriverBuilder::new("inner")
+            .set_snapshot_metadata(true)
+            .build();
+        driver.add_snapshooter(inner);
+
+        let before = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let snapshot = driver.snapshot(false).unwrap();
+        let after = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+
+        match snapshot.find("_snapshot_at") {
+            FindItem::Found(&ItemKind::UInt(at)) => assert!((before..=after).contains(&at)),
+            other => panic!("unexpected: {:?}", other),
+        }
+        match snapshot.find("_snapshot_duration_us") {
+            FindItem::Found(&ItemKind::UInt(us)) => assert!(us < 10_000_000),
+            other => panic!("unexpected: {:?}", other),
+        }
+        assert_eq!(
+            snapshot.find("_type__snapshot_duration_us"),
+            FindItem::Found(&ItemKind::Text("gauge".to_string()))
+        );
+        assert!(snapshot.find("outer/inner").opt().is_some());
+        assert!(snapshot.find("outer/inner/_snapshot_at").opt().is_none());
+        assert!(snapshot.find("outer/_snapshot_at").opt().is_none());
+    }
+}
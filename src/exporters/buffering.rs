@@ -0,0 +1,281 @@
+//! Retrying snapshots an `Exporter` failed to export
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use super::{ExportError, Exporter};
+use crate::snapshot::Snapshot;
+
+/// Wraps an `Exporter` and keeps the `Snapshot`s it failed to export
+/// to retry them later.
+///
+/// On each export the kept `Snapshot`s are exported first, oldest first,
+/// followed by the given `Snapshot`. Exporting stops at the first failure
+/// and the given `Snapshot` is kept as well. The error is returned so
+/// that the failure can still be logged.
+///
+/// Kept `Snapshot`s are exported with `Exporter::export_taken_at` and
+/// the time they were first exported. So they keep their timestamps.
+///
+/// At most `capacity` `Snapshot`s are kept. If there are more the oldest
+/// ones are dropped. A kept `Snapshot` is also dropped once retrying it
+/// failed `max_retries` times so that a `Snapshot` the backend never
+/// accepts does not hold back the ones after it.
+///
+/// # Example
+///
+/// ```
+/// use metrix::exporters::{BufferingExporter, ExportError, Exporter};
+/// use metrix::snapshot::Snapshot;
+///
+/// struct Unreachable;
+///
+/// impl Exporter for Unreachable {
+///     fn export(&self, _snapshot: &Snapshot) -> Result<(), ExportError> {
+///         Err(ExportError::Other("unreachable".to_string()))
+///     }
+/// }
+///
+/// let exporter = BufferingExporter::new(Unreachable, 2);
+///
+/// for _ in 0..3 {
+///     assert!(exporter.export(&Snapshot::default()).is_err());
+/// }
+///
+/// assert_eq!(exporter.buffered(), 2);
+/// assert_eq!(exporter.dropped(), 1);
+/// ```
+pub struct BufferingExporter<E> {
+    exporter: E,
+    capacity: usize,
+    max_retries: u32,
+    state: Mutex<BufferState>,
+}
+
+#[derive(Default)]
+struct BufferState {
+    snapshots: VecDeque<Buffered>,
+    dropped: u64,
+}
+
+struct Buffered {
+    snapshot: Snapshot,
+    taken_at: SystemTime,
+    retries: u32,
+}
+
+impl<E> BufferingExporter<E>
+where
+    E: Exporter,
+{
+    /// Creates a new `BufferingExporter` which keeps at most
+    /// `capacity` `Snapshot`s which could not be exported.
+    pub fn new(exporter: E, capacity: usize) -> BufferingExporter<E> {
+        BufferingExporter {
+            exporter,
+            capacity,
+            max_retries: 10,
+            state: Mutex::new(BufferState::default()),
+        }
+    }
+
+    /// Sets how often exporting a kept `Snapshot` is retried
+    /// before it is dropped.
+    ///
+    /// Default is 10.
+    pub fn set_max_retries(&mut self, max_retries: u32) {
+        self.max_retries = max_retries;
+    }
+
+    /// Sets how often exporting a kept `Snapshot` is retried
+    /// before it is dropped.
+    ///
+    /// Default is 10.
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.set_max_retries(max_retries);
+        self
+    }
+
+    /// Returns how often exporting a kept `Snapshot` is retried
+    /// before it is dropped.
+    pub fn get_max_retries(&self) -> u32 {
+        self.max_retries
+    }
+
+    /// Returns the wrapped `Exporter`
+    pub fn exporter(&self) -> &E {
+        &self.exporter
+    }
+
+    /// Returns the number of `Snapshot`s waiting to be exported
+    pub fn buffered(&self) -> usize {
+        self.lock().snapshots.len()
+    }
+
+    /// Returns the number of `Snapshot`s which were dropped because
+    /// the buffer was full or they were retried too often.
+    pub fn dropped(&self) -> u64 {
+        self.lock().dropped
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BufferState> {
+        // The state stays consistent even if a panic occurred while
+        // it was locked.
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<E> Exporter for BufferingExporter<E>
+where
+    E: Exporter,
+{
+    fn export(&self, snapshot: &Snapshot) -> Result<(), ExportError> {
+        self.export_taken_at(snapshot, SystemTime::now())
+    }
+
+    fn export_taken_at(
+        &self,
+        snapshot: &Snapshot,
+        taken_at: SystemTime,
+    ) -> Result<(), ExportError> {
+        let mut state = self.lock();
+
+        while let Some(buffered) = state.snapshots.front_mut() {
+            if let Err(err) = self
+                .exporter
+                .export_taken_at(&buffered.snapshot, buffered.taken_at)
+            {
+                buffered.retries += 1;
+                if buffered.retries >= self.max_retries {
+                    state.snapshots.pop_front();
+                    state.dropped += 1;
+                }
+                state.buffer(snapshot, taken_at, self.capacity);
+                return Err(err);
+            }
+            state.snapshots.pop_front();
+        }
+
+        let result = self.exporter.export_taken_at(snapshot, taken_at);
+        if result.is_err() {
+            state.buffer(snapshot, taken_at, self.capacity);
+        }
+        result
+    }
+}
+
+impl BufferState {
+    fn buffer(&mut self, snapshot: &Snapshot, taken_at: SystemTime, capacity: usize) {
+        if capacity == 0 {
+            self.dropped += 1;
+            return;
+        }
+        if self.snapshots.len() == capacity {
+            self.snapshots.pop_front();
+            self.dropped += 1;
+        }
+        self.snapshots.push_back(Buffered {
+            snapshot: snapshot.clone(),
+            taken_at,
+            retries: 0,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::{Cell, RefCell};
+
+    use super::*;
+    use crate::snapshot::ItemKind;
+
+    #[derive(Default)]
+    struct Sink {
+        failing: Cell<bool>,
+        received: RefCell<Vec<u64>>,
+        taken_at: RefCell<Vec<SystemTime>>,
+    }
+
+    impl Exporter for &Sink {
+        fn export(&self, snapshot: &Snapshot) -> Result<(), ExportError> {
+            self.export_taken_at(snapshot, SystemTime::now())
+        }
+
+        fn export_taken_at(
+            &self,
+            snapshot: &Snapshot,
+            taken_at: SystemTime,
+        ) -> Result<(), ExportError> {
+            if self.failing.get() {
+                return Err(ExportError::Other("sink is down".to_string()));
+            }
+            if let Some(n) = snapshot.find("n").opt().and_then(ItemKind::as_u64) {
+                self.received.borrow_mut().push(n);
+                self.taken_at.borrow_mut().push(taken_at);
+            }
+            Ok(())
+        }
+    }
+
+    fn snapshot(n: u64) -> Snapshot {
+        Snapshot {
+            items: vec![("n".to_string(), ItemKind::UInt(n))],
+        }
+    }
+
+    #[test]
+    fn buffered_snapshots_are_exported_in_order_once_the_sink_recovers() {
+        let sink = Sink::default();
+        let exporter = BufferingExporter::new(&sink, 3);
+
+        assert!(exporter.export(&snapshot(1)).is_ok());
+
+        sink.failing.set(true);
+        for n in 2..=5 {
+            assert!(exporter.export(&snapshot(n)).is_err());
+        }
+        assert_eq!(exporter.buffered(), 3);
+        assert_eq!(exporter.dropped(), 1);
+
+        sink.failing.set(false);
+        assert!(exporter.export(&snapshot(6)).is_ok());
+        assert_eq!(exporter.buffered(), 0);
+        assert_eq!(*sink.received.borrow(), vec![1, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn buffered_snapshots_keep_the_time_they_were_taken() {
+        let sink = Sink::default();
+        let exporter = BufferingExporter::new(&sink, 3);
+        let taken_at = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000);
+
+        sink.failing.set(true);
+        assert!(exporter.export_taken_at(&snapshot(1), taken_at).is_err());
+
+        sink.failing.set(false);
+        assert!(exporter.export(&snapshot(2)).is_ok());
+        assert_eq!(*sink.received.borrow(), vec![1, 2]);
+        assert_eq!(sink.taken_at.borrow()[0], taken_at);
+        assert!(sink.taken_at.borrow()[1] > taken_at);
+    }
+
+    #[test]
+    fn a_snapshot_is_dropped_after_the_last_retry() {
+        let sink = Sink::default();
+        let exporter = BufferingExporter::new(&sink, 10).max_retries(2);
+
+        sink.failing.set(true);
+        for n in 1..=3 {
+            assert!(exporter.export(&snapshot(n)).is_err());
+        }
+        // The first one has been retried twice
+        assert_eq!(exporter.buffered(), 2);
+        assert_eq!(exporter.dropped(), 1);
+
+        sink.failing.set(false);
+        assert!(exporter.export(&snapshot(4)).is_ok());
+        assert_eq!(*sink.received.borrow(), vec![2, 3, 4]);
+    }
+}
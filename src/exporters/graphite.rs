@@ -0,0 +1,386 @@
+//! The Graphite plaintext protocol
+use std::io::{self, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::prometheus::is_histogram;
+use super::{ExportError, Exporter};
+use crate::snapshot::{self, visit_snapshot, ItemKind, Snapshot, VisitsSnapshot};
+use crate::util;
+
+/// Renders a `Snapshot` in the Graphite plaintext protocol
+/// which is one `metric.path value timestamp` line per value.
+///
+/// The nested path of a value within the `Snapshot` becomes the
+/// metric path where the segments are joined with a dot. Characters
+/// that are not allowed within a segment are replaced by an underscore.
+///
+/// The quantiles of a `Histogram` are put directly below the path
+/// of the histogram, e.g. `latency.p95` instead of `latency.quantiles.p95`.
+///
+/// Booleans become `1` for `true` and `0` for `false`. Texts and
+/// floats which are not finite are skipped.
+///
+/// With an address set the `GraphiteExporter` is an `Exporter` which
+/// sends each `Snapshot` to that address. So it can be wrapped by a
+/// `BufferingExporter`.
+///
+/// # Example
+///
+/// ```
+/// use metrix::exporters::GraphiteExporter;
+/// use metrix::snapshot::*;
+///
+/// let inner = Snapshot {
+///     items: vec![("count".to_string(), ItemKind::UInt(42))],
+/// };
+///
+/// let snapshot = Snapshot {
+///     items: vec![("requests".to_string(), ItemKind::Snapshot(inner))],
+/// };
+///
+/// let exporter = GraphiteExporter::new().prefix("my_app");
+///
+/// assert_eq!(
+///     exporter.render(&snapshot, 1_500_000_000),
+///     "my_app.requests.count 42 1500000000\n"
+/// );
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct GraphiteExporter {
+    prefix: Option<String>,
+    address: Option<String>,
+}
+
+impl GraphiteExporter {
+    pub fn new() -> GraphiteExporter {
+        GraphiteExporter::default()
+    }
+
+    /// Sets a prefix which will be the first segment(s) of all metric paths.
+    ///
+    /// The prefix may contain dots to create multiple segments.
+    pub fn set_prefix<T: Into<String>>(&mut self, prefix: T) {
+        self.prefix = Some(prefix.into())
+    }
+
+    /// Sets a prefix which will be the first segment(s) of all metric paths.
+    ///
+    /// The prefix may contain dots to create multiple segments.
+    pub fn prefix<T: Into<String>>(mut self, prefix: T) -> Self {
+        self.set_prefix(prefix);
+        self
+    }
+
+    /// Returns the prefix if there is one
+    pub fn get_prefix(&self) -> Option<&str> {
+        self.prefix.as_deref()
+    }
+
+    /// Sets the address of the Graphite server (usually on port 2003)
+    /// the `Snapshot`s are sent to when used as an `Exporter`.
+    pub fn set_address<T: Into<String>>(&mut self, address: T) {
+        self.address = Some(address.into())
+    }
+
+    /// Sets the address of the Graphite server (usually on port 2003)
+    /// the `Snapshot`s are sent to when used as an `Exporter`.
+    pub fn address<T: Into<String>>(mut self, address: T) -> Self {
+        self.set_address(address);
+        self
+    }
+
+    /// Returns the address if there is one
+    pub fn get_address(&self) -> Option<&str> {
+        self.address.as_deref()
+    }
+
+    /// Creates the plaintext lines from the given `Snapshot` where
+    /// `timestamp` is the number of seconds since the unix epoch.
+    pub fn render(&self, snapshot: &Snapshot, timestamp: u64) -> String {
+        let prefix = self
+            .prefix
+            .as_ref()
+            .map(|p| {
+                p.split('.')
+                    .filter(|s| !s.is_empty())
+                    .map(sanitize_segment)
+                    .collect::<Vec<_>>()
+                    .join(".")
+            })
+            .unwrap_or_default();
+        let mut out = String::new();
+        put_snapshot(snapshot, &prefix, &timestamp.to_string(), &mut out);
+        out
+    }
+
+    /// Creates the plaintext lines from the given `Snapshot` with
+    /// the current time as the timestamp.
+    pub fn render_now(&self, snapshot: &Snapshot) -> String {
+        self.render(snapshot, unix_timestamp_now())
+    }
+
+    /// Writes the plaintext lines for the given `Snapshot` with
+    /// the current time as the timestamp to `out`.
+    pub fn write_to<W: Write>(&self, snapshot: &Snapshot, out: &mut W) -> io::Result<()> {
+        self.write_at(snapshot, unix_timestamp_now(), out)
+    }
+
+    /// Like `write_to` with `timestamp` in seconds since the unix epoch
+    fn write_at<W: Write>(
+        &self,
+        snapshot: &Snapshot,
+        timestamp: u64,
+        out: &mut W,
+    ) -> io::Result<()> {
+        out.write_all(self.render(snapshot, timestamp).as_bytes())?;
+        out.flush()
+    }
+
+    /// Connects to a Graphite server (usually on port 2003) and sends
+    /// the plaintext lines for the given `Snapshot` with the current time
+    /// as the timestamp.
+    pub fn send<A: ToSocketAddrs>(&self, snapshot: &Snapshot, address: A) -> io::Result<()> {
+        self.send_at(snapshot, address, unix_timestamp_now())
+    }
+
+    /// Like `send` with `timestamp` in seconds since the unix epoch
+    fn send_at<A: ToSocketAddrs>(
+        &self,
+        snapshot: &Snapshot,
+        address: A,
+        timestamp: u64,
+    ) -> io::Result<()> {
+        let mut stream = TcpStream::connect(address)?;
+        self.write_at(snapshot, timestamp, &mut stream)
+    }
+}
+
+impl Exporter for GraphiteExporter {
+    /// Sends the given `Snapshot` to the address with the current
+    /// time as the timestamp.
+    ///
+    /// Fails if no address is set.
+    fn export(&self, snapshot: &Snapshot) -> Result<(), ExportError> {
+        self.export_taken_at(snapshot, SystemTime::now())
+    }
+
+    /// Sends the given `Snapshot` to the address with `taken_at`
+    /// as the timestamp.
+    ///
+    /// Fails if no address is set.
+    fn export_taken_at(
+        &self,
+        snapshot: &Snapshot,
+        taken_at: SystemTime,
+    ) -> Result<(), ExportError> {
+        let address = self
+            .address
+            .as_ref()
+            .ok_or_else(|| ExportError::Other("no Graphite address set".to_string()))?;
+        Ok(self.send_at(snapshot, address.as_str(), unix_timestamp(taken_at))?)
+    }
+}
+
+fn put_snapshot(snapshot: &Snapshot, path: &str, timestamp: &str, out: &mut String) {
+    let mut lines = Lines {
+        paths: vec![path.to_string()],
+        timestamp,
+        out,
+    };
+    visit_snapshot(snapshot, &mut lines);
+}
+
+struct Lines<'b> {
+    /// The metric paths of the nested `Snapshot`s entered so far
+    paths: Vec<String>,
+    timestamp: &'b str,
+    out: &'b mut String,
+}
+
+impl<'b> Lines<'b> {
+    fn current_path(&self) -> &str {
+        self.paths.last().map(String::as_str).unwrap_or_default()
+    }
+}
+
+impl<'a, 'b> VisitsSnapshot<'a> for Lines<'b> {
+    fn enter(&mut self, path: &[&'a str], parent: &'a Snapshot, _snapshot: &'a Snapshot) -> bool {
+        let name = path[path.len() - 1];
+        // A `Histogram` puts its quantiles into a nested `Snapshot`
+        // named `quantiles` which is skipped.
+        let metric_path = if name == "quantiles" && is_histogram(parent) {
+            self.current_path().to_string()
+        } else {
+            join_path(self.current_path(), name)
+        };
+        self.paths.push(metric_path);
+        true
+    }
+
+    fn leave(&mut self, _path: &[&'a str]) {
+        self.paths.pop();
+    }
+
+    fn value(&mut self, path: &[&'a str], _parent: &'a Snapshot, item: &'a ItemKind) {
+        let metric_path = join_path(self.current_path(), path[path.len() - 1]);
+        put_value(item, &metric_path, self.timestamp, self.out)
+    }
+}
+
+fn put_value(item: &ItemKind, metric_path: &str, timestamp: &str, out: &mut String) {
+    let value = match *item {
+        ItemKind::UInt(v) => v.to_string(),
+        ItemKind::Int(v) => v.to_string(),
+        ItemKind::Float(v) if v.is_finite() => v.to_string(),
+        ItemKind::Boolean(v) => if v { "1" } else { "0" }.to_string(),
+        ItemKind::Float(_) | ItemKind::Text(_) | ItemKind::Snapshot(_) => return,
+    };
+
+    out.push_str(metric_path);
+    out.push(' ');
+    out.push_str(&value);
+    out.push(' ');
+    out.push_str(timestamp);
+    out.push('\n');
+}
+
+fn join_path(path: &str, name: &str) -> String {
+    snapshot::join_path(path, &sanitize_segment(name), ".")
+}
+
+/// Replaces all characters not in `[a-zA-Z0-9_-]` with an underscore
+/// since dots separate segments and whitespace separates the fields
+/// of a line.
+fn sanitize_segment(segment: &str) -> String {
+    util::sanitize_name(segment, &['-'])
+}
+
+fn unix_timestamp_now() -> u64 {
+    unix_timestamp(SystemTime::now())
+}
+
+fn unix_timestamp(at: SystemTime) -> u64 {
+    at.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sanitizes_segments() {
+        assert_eq!(sanitize_segment("a.b c"), "a_b_c");
+        assert_eq!(sanitize_segment("status-code_1"), "status-code_1");
+    }
+
+    #[test]
+    fn renders_a_small_tree() {
+        let quantiles = Snapshot {
+            items: vec![
+                ("p50".to_string(), ItemKind::Int(5)),
+                ("p95".to_string(), ItemKind::Int(9)),
+            ],
+        };
+        let histogram = Snapshot {
+            items: vec![
+                ("count".to_string(), ItemKind::UInt(10)),
+                ("mean".to_string(), ItemKind::Float(5.5)),
+                ("quantiles".to_string(), ItemKind::Snapshot(quantiles)),
+            ],
+        };
+        let http = Snapshot {
+            items: vec![
+                ("latency".to_string(), ItemKind::Snapshot(histogram)),
+                ("up".to_string(), ItemKind::Boolean(true)),
+                ("_title".to_string(), ItemKind::Text("skipped".to_string())),
+                ("ratio".to_string(), ItemKind::Float(f64::NAN)),
+            ],
+        };
+        let snapshot = Snapshot {
+            items: vec![
+                ("http".to_string(), ItemKind::Snapshot(http)),
+                ("in flight".to_string(), ItemKind::Int(-3)),
+            ],
+        };
+
+        let expected = "\
+servers.a.http.latency.count 10 1500000000
+servers.a.http.latency.mean 5.5 1500000000
+servers.a.http.latency.p50 5 1500000000
+servers.a.http.latency.p95 9 1500000000
+servers.a.http.up 1 1500000000
+servers.a.in_flight -3 1500000000
+";
+        let exporter = GraphiteExporter::new().prefix("servers.a");
+        assert_eq!(exporter.render(&snapshot, 1_500_000_000), expected);
+    }
+
+    #[test]
+    fn only_the_quantiles_of_a_histogram_are_flattened() {
+        let panel = Snapshot {
+            items: vec![("count".to_string(), ItemKind::UInt(3))],
+        };
+        let cockpit = Snapshot {
+            items: vec![("quantiles".to_string(), ItemKind::Snapshot(panel))],
+        };
+        let snapshot = Snapshot {
+            items: vec![("jobs".to_string(), ItemKind::Snapshot(cockpit))],
+        };
+
+        assert_eq!(
+            GraphiteExporter::new().render(&snapshot, 1_500_000_000),
+            "jobs.quantiles.count 3 1500000000\n"
+        );
+    }
+
+    #[test]
+    fn writes_to_a_writer() {
+        let snapshot = Snapshot {
+            items: vec![("count".to_string(), ItemKind::UInt(1))],
+        };
+
+        let mut out = Vec::new();
+        GraphiteExporter::new()
+            .write_to(&snapshot, &mut out)
+            .unwrap();
+
+        let line = String::from_utf8(out).unwrap();
+        let fields: Vec<&str> = line.trim_end().split(' ').collect();
+        assert_eq!(fields[0], "count");
+        assert_eq!(fields[1], "1");
+        assert!(fields[2].parse::<u64>().unwrap() > 1_500_000_000);
+    }
+
+    #[test]
+    fn exports_to_the_address_with_the_time_taken() {
+        use std::io::Read;
+        use std::net::TcpListener;
+        use std::time::Duration;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let exporter = GraphiteExporter::new().address(listener.local_addr().unwrap().to_string());
+
+        let snapshot = Snapshot {
+            items: vec![("count".to_string(), ItemKind::UInt(1))],
+        };
+        let taken_at = UNIX_EPOCH + Duration::from_secs(1_500_000_000);
+        exporter.export_taken_at(&snapshot, taken_at).unwrap();
+
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut received = String::new();
+        stream.read_to_string(&mut received).unwrap();
+        assert_eq!(received, "count 1 1500000000\n");
+    }
+
+    #[test]
+    fn export_fails_without_an_address() {
+        let snapshot = Snapshot {
+            items: vec![("count".to_string(), ItemKind::UInt(1))],
+        };
+
+        assert!(GraphiteExporter::new().export(&snapshot).is_err());
+    }
+}
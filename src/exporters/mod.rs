@@ -0,0 +1,124 @@
+//! Exporting `Snapshot`s to monitoring backends
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::time::SystemTime;
+
+use crate::snapshot::Snapshot;
+
+pub use self::buffering::BufferingExporter;
+pub use self::graphite::GraphiteExporter;
+pub use self::openmetrics::OpenMetricsExporter;
+pub use self::prometheus::PrometheusExporter;
+pub use crate::snapshot::{visit_snapshot, walk_snapshot, VisitsSnapshot};
+
+pub mod buffering;
+pub mod graphite;
+pub mod openmetrics;
+pub mod prometheus;
+#[cfg(feature = "remote-write")]
+pub mod remote_write;
+
+/// Sends `Snapshot`s to a monitoring backend
+pub trait Exporter {
+    /// Exports the given `Snapshot`
+    fn export(&self, snapshot: &Snapshot) -> Result<(), ExportError>;
+
+    /// Exports the given `Snapshot` which was taken at `taken_at`.
+    ///
+    /// This is used to export a `Snapshot` later than it was taken, e.g.
+    /// by a `BufferingExporter`. Exporters which put timestamps on the
+    /// values should use `taken_at` instead of the current time.
+    ///
+    /// The default calls `export`.
+    fn export_taken_at(
+        &self,
+        snapshot: &Snapshot,
+        taken_at: SystemTime,
+    ) -> Result<(), ExportError> {
+        let _ = taken_at;
+        self.export(snapshot)
+    }
+}
+
+/// The reason why a `Snapshot` could not be exported
+#[derive(Debug)]
+pub enum ExportError {
+    /// Writing to the backend failed
+    Io(io::Error),
+    /// Any other failure
+    Other(String),
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportError::Io(err) => write!(f, "export failed: {}", err),
+            ExportError::Other(message) => write!(f, "export failed: {}", message),
+        }
+    }
+}
+
+impl Error for ExportError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ExportError::Io(err) => Some(err),
+            ExportError::Other(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for ExportError {
+    fn from(err: io::Error) -> ExportError {
+        ExportError::Io(err)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::snapshot::ItemKind;
+
+    struct PathCollector {
+        paths: std::cell::RefCell<Vec<String>>,
+    }
+
+    impl Exporter for PathCollector {
+        fn export(&self, snapshot: &Snapshot) -> Result<(), ExportError> {
+            walk_snapshot(snapshot, |path, _| {
+                self.paths.borrow_mut().push(path.join("."))
+            });
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn an_exporter_collects_all_leaf_paths() {
+        let quantiles = Snapshot {
+            items: vec![("p50".to_string(), ItemKind::Int(5))],
+        };
+        let histogram = Snapshot {
+            items: vec![
+                ("count".to_string(), ItemKind::UInt(10)),
+                ("quantiles".to_string(), ItemKind::Snapshot(quantiles)),
+            ],
+        };
+        let snapshot = Snapshot {
+            items: vec![
+                ("latency".to_string(), ItemKind::Snapshot(histogram)),
+                ("empty".to_string(), ItemKind::Snapshot(Snapshot::default())),
+                ("_title".to_string(), ItemKind::Text("text".to_string())),
+            ],
+        };
+
+        let exporter = PathCollector {
+            paths: Default::default(),
+        };
+        exporter.export(&snapshot).unwrap();
+
+        assert_eq!(
+            exporter.paths.into_inner(),
+            vec!["latency.count", "latency.quantiles.p50", "_title"]
+        );
+    }
+}
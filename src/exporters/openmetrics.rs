@@ -0,0 +1,567 @@
+//! The OpenMetrics text format
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::snapshot::{self, visit_snapshot, ItemKind, Snapshot, VisitsSnapshot};
+use crate::util;
+
+use super::prometheus::{
+    format_float, has_buckets, is_histogram, join_name, metric_name, sample_value, sanitize_name,
+    walk_buckets, walk_summary, with_precision, HistogramLines,
+};
+
+/// Renders a `Snapshot` in the OpenMetrics text format.
+///
+/// The metric names and types are derived as described for the
+/// `PrometheusExporter` with the following additions:
+///
+/// * The samples of a `counter` get the suffix `_total`
+/// * The description of an instrument becomes a `# HELP` line
+/// * The time unit of an instrument becomes a `# UNIT` line and
+///   is appended to the metric name if it is not already the suffix
+/// * The output is terminated by `# EOF`
+///
+/// The buckets of a histogram can be annotated with `Exemplars`
+/// by rendering with `render_with_exemplars`.
+///
+/// Descriptions and time units are only part of a `Snapshot`
+/// if it was taken with `descriptive` set to `true`.
+///
+/// # Example
+///
+/// ```
+/// use metrix::exporters::OpenMetricsExporter;
+/// use metrix::snapshot::*;
+///
+/// let inner = Snapshot {
+///     items: vec![("count".to_string(), ItemKind::UInt(42))],
+/// };
+///
+/// let snapshot = Snapshot {
+///     items: vec![("requests".to_string(), ItemKind::Snapshot(inner))],
+/// };
+///
+/// let exporter = OpenMetricsExporter::new().prefix("my_app");
+///
+/// assert_eq!(
+///     exporter.render(&snapshot),
+///     "# TYPE my_app_requests_count counter\nmy_app_requests_count_total 42\n# EOF\n"
+/// );
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct OpenMetricsExporter {
+    prefix: Option<String>,
+    precision: Option<u32>,
+}
+
+impl OpenMetricsExporter {
+    pub fn new() -> OpenMetricsExporter {
+        OpenMetricsExporter::default()
+    }
+
+    /// Sets a prefix which will be the first segment of all metric names.
+    pub fn set_prefix<T: Into<String>>(&mut self, prefix: T) {
+        self.prefix = Some(prefix.into())
+    }
+
+    /// Sets a prefix which will be the first segment of all metric names.
+    pub fn prefix<T: Into<String>>(mut self, prefix: T) -> Self {
+        self.set_prefix(prefix);
+        self
+    }
+
+    /// Returns the prefix if there is one
+    pub fn get_prefix(&self) -> Option<&str> {
+        self.prefix.as_deref()
+    }
+
+    /// Rounds floats to the given number of decimal places.
+    ///
+    /// `NaN` and infinite values are not affected. Default is full precision.
+    pub fn set_precision(&mut self, decimal_places: u32) {
+        self.precision = Some(decimal_places)
+    }
+
+    /// Rounds floats to the given number of decimal places.
+    ///
+    /// `NaN` and infinite values are not affected. Default is full precision.
+    pub fn precision(mut self, decimal_places: u32) -> Self {
+        self.set_precision(decimal_places);
+        self
+    }
+
+    /// Returns the number of decimal places floats are rounded to if set
+    pub fn get_precision(&self) -> Option<u32> {
+        self.precision
+    }
+
+    /// Creates the OpenMetrics text format from the given `Snapshot`.
+    pub fn render(&self, snapshot: &Snapshot) -> String {
+        self.render_with_exemplars(snapshot, &Exemplars::default())
+    }
+
+    /// Creates the OpenMetrics text format from the given `Snapshot`
+    /// where the buckets of histograms are annotated with the matching
+    /// `Exemplars`.
+    pub fn render_with_exemplars(&self, snapshot: &Snapshot, exemplars: &Exemplars) -> String {
+        let prefix = self
+            .prefix
+            .as_ref()
+            .map(|p| sanitize_name(p))
+            .unwrap_or_default();
+        let snapshot = with_precision(snapshot, self.precision);
+        let mut out = String::new();
+        put_snapshot(&snapshot, &prefix, "", exemplars, &mut out);
+        out.push_str("# EOF\n");
+        out
+    }
+}
+
+impl Snapshot {
+    /// Output the OpenMetrics text format.
+    ///
+    /// All metric names will be prefixed with `prefix` if given.
+    ///
+    /// See `exporters::OpenMetricsExporter` for details.
+    pub fn to_openmetrics(&self, prefix: Option<&str>) -> String {
+        let mut exporter = OpenMetricsExporter::new();
+        if let Some(prefix) = prefix {
+            exporter.set_prefix(prefix);
+        }
+        exporter.render(self)
+    }
+}
+
+/// A value with labels, e.g. a trace id, which was counted
+/// by a bucket of a histogram.
+///
+/// It is rendered after the sample of the bucket, e.g.
+/// `latency_bucket{le="100"} 3 # {trace_id="abc"} 42`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exemplar {
+    labels: Vec<(String, String)>,
+    value: f64,
+    timestamp: Option<SystemTime>,
+}
+
+impl Exemplar {
+    pub fn new(value: f64) -> Exemplar {
+        Exemplar {
+            labels: Vec::new(),
+            value,
+            timestamp: None,
+        }
+    }
+
+    /// Adds a label like `trace_id`.
+    pub fn add_label<N: Into<String>, V: Into<String>>(&mut self, name: N, value: V) {
+        self.labels.push((name.into(), value.into()))
+    }
+
+    /// Adds a label like `trace_id`.
+    pub fn label<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+        self.add_label(name, value);
+        self
+    }
+
+    /// Sets the time the value was observed at.
+    pub fn set_timestamp(&mut self, timestamp: SystemTime) {
+        self.timestamp = Some(timestamp)
+    }
+
+    /// Sets the time the value was observed at.
+    pub fn timestamp(mut self, timestamp: SystemTime) -> Self {
+        self.set_timestamp(timestamp);
+        self
+    }
+
+    /// Returns the `# {labels} value [timestamp]` part of a line
+    fn render(&self) -> String {
+        let labels: Vec<String> = self
+            .labels
+            .iter()
+            .map(|(name, value)| format!("{}=\"{}\"", sanitize_name(name), escape_help(value)))
+            .collect();
+        let mut rendered = format!("# {{{}}} {}", labels.join(","), format_float(self.value));
+        if let Some(since_epoch) = self
+            .timestamp
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        {
+            rendered.push(' ');
+            rendered.push_str(&since_epoch.as_secs_f64().to_string());
+        }
+        rendered
+    }
+}
+
+/// The `Exemplar`s for the buckets of histograms.
+///
+/// A histogram is identified by its path within the `Snapshot`
+/// where the names are separated by `/` like for `Snapshot::find`.
+/// A bucket is identified by its upper bound as rendered, e.g. `10`
+/// or `+Inf`.
+///
+/// # Example
+///
+/// ```
+/// use metrix::exporters::openmetrics::{Exemplar, Exemplars};
+///
+/// let exemplars = Exemplars::new().exemplar(
+///     "http/latency",
+///     "100",
+///     Exemplar::new(42.0).label("trace_id", "abc"),
+/// );
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Exemplars {
+    exemplars: HashMap<(String, String), Exemplar>,
+}
+
+impl Exemplars {
+    pub fn new() -> Exemplars {
+        Exemplars::default()
+    }
+
+    /// Sets the `Exemplar` for the bucket with the given upper bound
+    /// of the histogram at `path`. A previous one is replaced.
+    pub fn add_exemplar<P: Into<String>, B: Into<String>>(
+        &mut self,
+        path: P,
+        upper_bound: B,
+        exemplar: Exemplar,
+    ) {
+        self.exemplars
+            .insert((path.into(), upper_bound.into()), exemplar);
+    }
+
+    /// Sets the `Exemplar` for the bucket with the given upper bound
+    /// of the histogram at `path`. A previous one is replaced.
+    pub fn exemplar<P: Into<String>, B: Into<String>>(
+        mut self,
+        path: P,
+        upper_bound: B,
+        exemplar: Exemplar,
+    ) -> Self {
+        self.add_exemplar(path, upper_bound, exemplar);
+        self
+    }
+
+    fn get(&self, path: &str, upper_bound: &str) -> Option<&Exemplar> {
+        self.exemplars
+            .get(&(path.to_string(), upper_bound.to_string()))
+    }
+}
+
+/// The descriptive parts of a metric family
+struct Metadata<'a> {
+    help: Option<&'a str>,
+    unit: Option<&'a str>,
+}
+
+impl<'a> Metadata<'a> {
+    fn lookup(snapshot: &'a Snapshot, name: &str) -> Metadata<'a> {
+        Metadata {
+            help: find_text(snapshot, &format!("_description_{}", name)),
+            unit: find_text(snapshot, &format!("_unit_{}", name)),
+        }
+    }
+
+    fn put(&self, family: &str, metric_type: &str, out: &mut String) {
+        put_line(&["# TYPE ", family, " ", metric_type], out);
+        if let Some(unit) = self.unit {
+            put_line(&["# UNIT ", family, " ", unit], out);
+        }
+        if let Some(help) = self.help {
+            put_line(&["# HELP ", family, " ", &escape_help(help)], out);
+        }
+    }
+
+    fn family_name(&self, name: &str) -> String {
+        match self.unit {
+            Some(unit) if !name.ends_with(&format!("_{}", unit)) => format!("{}_{}", name, unit),
+            _ => name.to_string(),
+        }
+    }
+}
+
+fn put_snapshot(
+    snapshot: &Snapshot,
+    base: &str,
+    base_path: &str,
+    exemplars: &Exemplars,
+    out: &mut String,
+) {
+    let mut families = Families {
+        base,
+        base_path,
+        exemplars,
+        out,
+    };
+    visit_snapshot(snapshot, &mut families);
+}
+
+struct Families<'b> {
+    /// The metric name the names of the path are appended to
+    base: &'b str,
+    /// The path of the visited `Snapshot` separated by `/`
+    base_path: &'b str,
+    exemplars: &'b Exemplars,
+    out: &'b mut String,
+}
+
+impl<'a, 'b> VisitsSnapshot<'a> for Families<'b> {
+    fn enter(&mut self, path: &[&'a str], parent: &'a Snapshot, snapshot: &'a Snapshot) -> bool {
+        let is_bucket_histogram = has_buckets(snapshot);
+        if !is_bucket_histogram && !is_histogram(snapshot) {
+            return true;
+        }
+
+        let metadata = Metadata::lookup(parent, path[path.len() - 1]);
+        let metric_name = metric_name(self.base, path);
+        let family = metadata.family_name(&metric_name);
+        let mut samples = Samples {
+            metric_name: &metric_name,
+            path: snapshot::join_path(self.base_path, &path.join("/"), "/"),
+            family,
+            metadata: &metadata,
+            exemplars: self.exemplars,
+            out: self.out,
+        };
+        if is_bucket_histogram {
+            metadata.put(&samples.family, "histogram", samples.out);
+            walk_buckets(snapshot, &mut samples);
+        } else {
+            metadata.put(&samples.family, "summary", samples.out);
+            walk_summary(snapshot, &mut samples);
+        }
+        false
+    }
+
+    fn value(&mut self, path: &[&'a str], parent: &'a Snapshot, item: &'a ItemKind) {
+        let name = path[path.len() - 1];
+        let metadata = Metadata::lookup(parent, name);
+        let is_gauge = util::is_gauge_type(parent, name);
+        put_single_value(
+            item,
+            &metric_name(self.base, path),
+            &metadata,
+            is_gauge,
+            self.out,
+        )
+    }
+}
+
+fn put_single_value(
+    item: &ItemKind,
+    metric_name: &str,
+    metadata: &Metadata,
+    is_gauge: bool,
+    out: &mut String,
+) {
+    match *item {
+        ItemKind::UInt(v) if !is_gauge => {
+            let family = metadata.family_name(metric_name.trim_end_matches("_total"));
+            metadata.put(&family, "counter", out);
+            put_line(&[&family, "_total ", &v.to_string()], out);
+        }
+        ItemKind::Text(_) | ItemKind::Snapshot(_) => {}
+        ref other => {
+            if let Some(value) = sample_value(other) {
+                let family = metadata.family_name(metric_name);
+                metadata.put(&family, "gauge", out);
+                put_line(&[&family, " ", &value], out);
+            }
+        }
+    }
+}
+
+struct Samples<'a> {
+    metric_name: &'a str,
+    /// The path of the histogram to look up its `Exemplars`
+    path: String,
+    family: String,
+    metadata: &'a Metadata<'a>,
+    exemplars: &'a Exemplars,
+    out: &'a mut String,
+}
+
+impl<'a> HistogramLines for Samples<'a> {
+    fn put_quantile(&mut self, quantile: &str, value: &str) {
+        put_line(
+            &[&self.family, "{quantile=\"", quantile, "\"} ", value],
+            self.out,
+        );
+    }
+
+    fn put_bucket(&mut self, upper_bound: &str, value: &str) {
+        let exemplar = self
+            .exemplars
+            .get(&self.path, upper_bound)
+            .map(|exemplar| format!(" {}", exemplar.render()))
+            .unwrap_or_default();
+        put_line(
+            &[
+                &self.family,
+                "_bucket{le=\"",
+                upper_bound,
+                "\"} ",
+                value,
+                &exemplar,
+            ],
+            self.out,
+        );
+    }
+
+    fn put_total(&mut self, name: &str, value: &str) {
+        put_line(&[&self.family, "_", name, " ", value], self.out);
+    }
+
+    fn put_other(&mut self, name: &str, item: &ItemKind) {
+        let metric_name = join_name(self.metric_name, name);
+        match item {
+            ItemKind::Snapshot(ref inner) => {
+                let path = snapshot::join_path(&self.path, name, "/");
+                put_snapshot(inner, &metric_name, &path, self.exemplars, self.out)
+            }
+            other => {
+                if let Some(value) = sample_value(other) {
+                    let sub_family = self.metadata.family_name(&metric_name);
+                    put_gauge_line(&sub_family, self.metadata.unit, &value, self.out);
+                }
+            }
+        }
+    }
+}
+
+/// The sub values of a histogram share the unit but not the description.
+fn put_gauge_line(family: &str, unit: Option<&str>, value: &str, out: &mut String) {
+    let metadata = Metadata { help: None, unit };
+    metadata.put(family, "gauge", out);
+    put_line(&[family, " ", value], out);
+}
+
+fn put_line(parts: &[&str], out: &mut String) {
+    parts.iter().for_each(|p| out.push_str(p));
+    out.push('\n');
+}
+
+fn find_text<'a>(snapshot: &'a Snapshot, name: &str) -> Option<&'a str> {
+    snapshot.items.iter().find_map(|(n, item)| match item {
+        ItemKind::Text(ref text) if n == name => Some(text.as_str()),
+        _ => None,
+    })
+}
+
+fn escape_help(help: &str) -> String {
+    help.replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cockpit::Cockpit;
+    use crate::instruments::{Counter, Histogram, Panel};
+    use crate::processor::{ProcessesTelemetryMessages, ProcessingStrategy, TelemetryProcessor};
+    use crate::{PutsSnapshot, TelemetryTransmitter, TimeUnit, TransmitsTelemetryData};
+
+    /// Every `# TYPE` must be followed by samples of its family
+    /// and the text must be terminated by `# EOF`.
+    fn assert_structurally_valid(text: &str) {
+        assert!(text.ends_with("# EOF\n"));
+        assert_eq!(text.matches("# EOF").count(), 1);
+
+        let lines: Vec<&str> = text.lines().collect();
+        for (idx, line) in lines.iter().enumerate() {
+            if let Some(declaration) = line.strip_prefix("# TYPE ") {
+                let family = declaration.split(' ').next().unwrap();
+                let has_sample = lines[idx + 1..]
+                    .iter()
+                    .skip_while(|l| l.starts_with("# UNIT ") || l.starts_with("# HELP "))
+                    .take_while(|l| !l.starts_with('#'))
+                    .any(|l| l.starts_with(family));
+                assert!(has_sample, "no sample for '{}'", family);
+            }
+        }
+    }
+
+    #[test]
+    fn renders_units_help_and_counters() {
+        let (tx, mut processor): (TelemetryTransmitter<i32>, _) =
+            TelemetryProcessor::new_pair("service");
+        processor.add_cockpit(
+            Cockpit::new("http").panel(
+                Panel::named(1, "requests")
+                    .counter(Counter::new("count").description("All \"requests\""))
+                    .histogram(
+                        Histogram::new("latency")
+                            .display_time_unit(TimeUnit::Milliseconds)
+                            .quantiles(&[0.5])
+                            .description("Time to respond"),
+                    ),
+            ),
+        );
+
+        tx.observed_one_value_now(1, 5).observed_one_value_now(1, 5);
+        processor.process(100, ProcessingStrategy::ProcessAll);
+
+        let mut snapshot = Snapshot::default();
+        processor.put_snapshot(&mut snapshot, true);
+        let text = OpenMetricsExporter::new().render(&snapshot);
+
+        assert_structurally_valid(&text);
+
+        let expected = "\
+# TYPE service_http_requests_count counter
+# HELP service_http_requests_count All \\\"requests\\\"
+service_http_requests_count_total 2
+# TYPE service_http_requests_latency_milliseconds summary
+# UNIT service_http_requests_latency_milliseconds milliseconds
+# HELP service_http_requests_latency_milliseconds Time to respond
+service_http_requests_latency_milliseconds{quantile=\"0.5\"} 5
+service_http_requests_latency_milliseconds_count 2
+";
+        assert!(text.starts_with(expected), "{}", text);
+        assert!(text.contains(
+            "# TYPE service_http_requests_latency_max_milliseconds gauge\n\
+             # UNIT service_http_requests_latency_max_milliseconds milliseconds\n\
+             service_http_requests_latency_max_milliseconds 5\n"
+        ));
+    }
+
+    #[test]
+    fn renders_histogram_buckets_with_exemplars() {
+        let (tx, mut processor): (TelemetryTransmitter<i32>, _) =
+            TelemetryProcessor::new_pair("service");
+        processor.add_cockpit(Cockpit::new("http").panel(
+            Panel::named(1, "requests").histogram(Histogram::new("latency").buckets(&[10, 100])),
+        ));
+
+        tx.observed_one_value_now(1, 5)
+            .observed_one_value_now(1, 50);
+        processor.process(100, ProcessingStrategy::ProcessAll);
+
+        let mut snapshot = Snapshot::default();
+        processor.put_snapshot(&mut snapshot, false);
+        let exemplars = Exemplars::new().exemplar(
+            "service/http/requests/latency",
+            "100",
+            Exemplar::new(50.0).label("trace_id", "abc"),
+        );
+        let text = OpenMetricsExporter::new().render_with_exemplars(&snapshot, &exemplars);
+
+        assert_structurally_valid(&text);
+
+        let expected = "\
+# TYPE service_http_requests_latency histogram
+service_http_requests_latency_bucket{le=\"10\"} 1
+service_http_requests_latency_bucket{le=\"100\"} 2 # {trace_id=\"abc\"} 50
+service_http_requests_latency_bucket{le=\"+Inf\"} 2
+service_http_requests_latency_count 2
+service_http_requests_latency_sum 55
+";
+        assert!(text.starts_with(expected), "{}", text);
+        assert!(!text.contains("quantile"));
+    }
+}
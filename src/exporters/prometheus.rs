@@ -0,0 +1,638 @@
+//! The Prometheus text exposition format
+use std::borrow::Cow;
+
+use crate::snapshot::{self, visit_snapshot, ItemKind, Snapshot, VisitsSnapshot};
+use crate::util;
+
+/// Renders a `Snapshot` in the Prometheus text exposition format.
+///
+/// The nested path of a value within the `Snapshot` becomes the
+/// metric name where the segments are joined with an underscore.
+/// Characters that are not allowed within a metric name are replaced
+/// by an underscore.
+///
+/// Since a `Snapshot` does not know which instrument created a value
+/// the types are derived from the values:
+///
+/// * Unsigned integers (as written by a `Counter`) become a `counter`
+///   unless there is a text `_type_[name]` with the value `gauge` next to
+///   them which marks values that can go down
+/// * Signed integers, floats and booleans become a `gauge`. Booleans are
+///   `1` for `true` and `0` for `false`.
+/// * A nested `Snapshot` containing `quantiles` (as written by a `Histogram`)
+///   becomes a `summary`
+/// * A nested `Snapshot` containing `bucket_inf` (as written by a `Histogram`
+///   with buckets) becomes a `histogram`. The quantiles are omitted in this
+///   case since a metric can only have one type.
+/// * Texts are skipped
+///
+/// Floats are rendered with full precision unless a precision
+/// is set with `set_precision`.
+///
+/// # Example
+///
+/// ```
+/// use metrix::exporters::PrometheusExporter;
+/// use metrix::snapshot::*;
+///
+/// let inner = Snapshot {
+///     items: vec![("count".to_string(), ItemKind::UInt(42))],
+/// };
+///
+/// let snapshot = Snapshot {
+///     items: vec![("requests".to_string(), ItemKind::Snapshot(inner))],
+/// };
+///
+/// let exporter = PrometheusExporter::new().prefix("my_app");
+///
+/// assert_eq!(
+///     exporter.render(&snapshot),
+///     "# TYPE my_app_requests_count counter\nmy_app_requests_count 42\n"
+/// );
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PrometheusExporter {
+    prefix: Option<String>,
+    precision: Option<u32>,
+}
+
+impl PrometheusExporter {
+    pub fn new() -> PrometheusExporter {
+        PrometheusExporter::default()
+    }
+
+    /// Sets a prefix which will be the first segment of all metric names.
+    pub fn set_prefix<T: Into<String>>(&mut self, prefix: T) {
+        self.prefix = Some(prefix.into())
+    }
+
+    /// Sets a prefix which will be the first segment of all metric names.
+    pub fn prefix<T: Into<String>>(mut self, prefix: T) -> Self {
+        self.set_prefix(prefix);
+        self
+    }
+
+    /// Returns the prefix if there is one
+    pub fn get_prefix(&self) -> Option<&str> {
+        self.prefix.as_deref()
+    }
+
+    /// Rounds floats to the given number of decimal places.
+    ///
+    /// `NaN` and infinite values are not affected. Default is full precision.
+    pub fn set_precision(&mut self, decimal_places: u32) {
+        self.precision = Some(decimal_places)
+    }
+
+    /// Rounds floats to the given number of decimal places.
+    ///
+    /// `NaN` and infinite values are not affected. Default is full precision.
+    pub fn precision(mut self, decimal_places: u32) -> Self {
+        self.set_precision(decimal_places);
+        self
+    }
+
+    /// Returns the number of decimal places floats are rounded to if set
+    pub fn get_precision(&self) -> Option<u32> {
+        self.precision
+    }
+
+    /// Creates the text exposition format from the given `Snapshot`.
+    pub fn render(&self, snapshot: &Snapshot) -> String {
+        let prefix = self
+            .prefix
+            .as_ref()
+            .map(|p| sanitize_name(p))
+            .unwrap_or_default();
+        let snapshot = with_precision(snapshot, self.precision);
+        let mut out = String::new();
+        put_snapshot(&snapshot, &prefix, &mut out);
+        out
+    }
+}
+
+impl Snapshot {
+    /// Output the Prometheus text exposition format.
+    ///
+    /// All metric names will be prefixed with `prefix` if given.
+    ///
+    /// See `exporters::PrometheusExporter` for details.
+    pub fn to_prometheus(&self, prefix: Option<&str>) -> String {
+        let mut exporter = PrometheusExporter::new();
+        if let Some(prefix) = prefix {
+            exporter.set_prefix(prefix);
+        }
+        exporter.render(self)
+    }
+}
+
+fn put_snapshot(snapshot: &Snapshot, base: &str, out: &mut String) {
+    visit_snapshot(snapshot, &mut Metrics { base, out });
+}
+
+struct Metrics<'b> {
+    base: &'b str,
+    out: &'b mut String,
+}
+
+impl<'a, 'b> VisitsSnapshot<'a> for Metrics<'b> {
+    fn enter(&mut self, path: &[&'a str], _parent: &'a Snapshot, snapshot: &'a Snapshot) -> bool {
+        if has_buckets(snapshot) {
+            put_histogram(snapshot, &metric_name(self.base, path), self.out);
+            false
+        } else if is_histogram(snapshot) {
+            put_summary(snapshot, &metric_name(self.base, path), self.out);
+            false
+        } else {
+            true
+        }
+    }
+
+    fn value(&mut self, path: &[&'a str], parent: &'a Snapshot, item: &'a ItemKind) {
+        let is_gauge = util::is_gauge_type(parent, path[path.len() - 1]);
+        put_single_value(item, &metric_name(self.base, path), is_gauge, self.out)
+    }
+}
+
+fn put_single_value(item: &ItemKind, metric_name: &str, is_gauge: bool, out: &mut String) {
+    let (metric_type, value) = match *item {
+        ItemKind::UInt(v) if is_gauge => ("gauge", v.to_string()),
+        ItemKind::UInt(v) => ("counter", v.to_string()),
+        ItemKind::Int(v) => ("gauge", v.to_string()),
+        ItemKind::Float(v) => ("gauge", format_float(v)),
+        ItemKind::Boolean(v) => ("gauge", if v { "1" } else { "0" }.to_string()),
+        ItemKind::Text(_) | ItemKind::Snapshot(_) => return,
+    };
+
+    put_type(metric_name, metric_type, out);
+    put_sample(metric_name, "", &value, out);
+}
+
+/// Writes the lines of a summary or a histogram while `walk_summary`
+/// or `walk_buckets` walks through a histogram
+pub(crate) trait HistogramLines {
+    /// A quantile, e.g. `0.99`, with its value
+    fn put_quantile(&mut self, quantile: &str, value: &str);
+    /// A bucket with its upper bound, e.g. `10` or `+Inf`, and
+    /// the cumulative count of values
+    fn put_bucket(&mut self, upper_bound: &str, value: &str);
+    /// The `count` or the `sum` of the histogram
+    fn put_total(&mut self, name: &str, value: &str);
+    /// Any other item of the histogram, e.g. `max`
+    fn put_other(&mut self, name: &str, item: &ItemKind);
+}
+
+/// Walks through a histogram putting the quantiles first followed
+/// by the `count` and `sum` and then everything else.
+pub(crate) fn walk_summary(histogram: &Snapshot, lines: &mut dyn HistogramLines) {
+    for (name, item) in &histogram.items {
+        if let ItemKind::Snapshot(ref quantiles) = item {
+            if name == "quantiles" {
+                for (q_name, q_value) in &quantiles.items {
+                    if let (Some(quantile), Some(value)) =
+                        (quantile_from_name(q_name), sample_value(q_value))
+                    {
+                        lines.put_quantile(&quantile, &value);
+                    }
+                }
+            }
+        }
+    }
+
+    for (name, item) in &histogram.items {
+        match name.as_str() {
+            "count" | "sum" => {
+                if let Some(value) = sample_value(item) {
+                    lines.put_total(name, &value);
+                }
+            }
+            "quantiles" => {}
+            _ => lines.put_other(name, item),
+        }
+    }
+}
+
+/// Walks through a histogram with buckets putting the buckets first
+/// followed by the `count` and `sum` of the bucketed values and then
+/// everything else except for the quantiles.
+pub(crate) fn walk_buckets(histogram: &Snapshot, lines: &mut dyn HistogramLines) {
+    for (name, item) in &histogram.items {
+        if name == "bucket_sum_total" {
+            continue;
+        }
+        if let (Some(bound), Some(value)) = (name.strip_prefix("bucket_"), sample_value(item)) {
+            let bound = if bound == "inf" { "+Inf" } else { bound };
+            lines.put_bucket(bound, &value);
+        }
+    }
+
+    // The buckets count all values since creation. So the count and sum
+    // have to as well which the ones of a windowed histogram do not.
+    for (name, total) in &[("bucket_inf", "count"), ("bucket_sum_total", "sum")] {
+        if let Some(value) = histogram
+            .items
+            .iter()
+            .find(|(n, _)| n == name)
+            .and_then(|(_, item)| sample_value(item))
+        {
+            lines.put_total(total, &value);
+        }
+    }
+
+    for (name, item) in &histogram.items {
+        match name.as_str() {
+            "count" | "sum" | "quantiles" => {}
+            _ if name.starts_with("bucket_") => {}
+            _ => lines.put_other(name, item),
+        }
+    }
+}
+
+struct Samples<'a> {
+    metric_name: &'a str,
+    out: &'a mut String,
+}
+
+impl<'a> HistogramLines for Samples<'a> {
+    fn put_quantile(&mut self, quantile: &str, value: &str) {
+        let labels = format!("{{quantile=\"{}\"}}", quantile);
+        put_sample(self.metric_name, &labels, value, self.out);
+    }
+
+    fn put_bucket(&mut self, upper_bound: &str, value: &str) {
+        let labels = format!("{{le=\"{}\"}}", upper_bound);
+        put_sample(
+            &join_name(self.metric_name, "bucket"),
+            &labels,
+            value,
+            self.out,
+        );
+    }
+
+    fn put_total(&mut self, name: &str, value: &str) {
+        put_sample(&join_name(self.metric_name, name), "", value, self.out);
+    }
+
+    fn put_other(&mut self, name: &str, item: &ItemKind) {
+        let metric_name = join_name(self.metric_name, name);
+        match item {
+            ItemKind::Snapshot(ref inner) => put_snapshot(inner, &metric_name, self.out),
+            other => put_gauge_value(other, &metric_name, self.out),
+        }
+    }
+}
+
+fn put_summary(histogram: &Snapshot, metric_name: &str, out: &mut String) {
+    put_type(metric_name, "summary", out);
+    walk_summary(histogram, &mut Samples { metric_name, out });
+}
+
+fn put_histogram(histogram: &Snapshot, metric_name: &str, out: &mut String) {
+    put_type(metric_name, "histogram", out);
+    walk_buckets(histogram, &mut Samples { metric_name, out });
+}
+
+fn put_gauge_value(item: &ItemKind, metric_name: &str, out: &mut String) {
+    if let Some(value) = sample_value(item) {
+        put_type(metric_name, "gauge", out);
+        put_sample(metric_name, "", &value, out);
+    }
+}
+
+fn put_type(metric_name: &str, metric_type: &str, out: &mut String) {
+    out.push_str("# TYPE ");
+    out.push_str(metric_name);
+    out.push(' ');
+    out.push_str(metric_type);
+    out.push('\n');
+}
+
+fn put_sample(metric_name: &str, labels: &str, value: &str, out: &mut String) {
+    out.push_str(metric_name);
+    out.push_str(labels);
+    out.push(' ');
+    out.push_str(value);
+    out.push('\n');
+}
+
+pub(crate) fn sample_value(item: &ItemKind) -> Option<String> {
+    match *item {
+        ItemKind::UInt(v) => Some(v.to_string()),
+        ItemKind::Int(v) => Some(v.to_string()),
+        ItemKind::Float(v) => Some(format_float(v)),
+        ItemKind::Boolean(v) => Some(if v { "1" } else { "0" }.to_string()),
+        ItemKind::Text(_) | ItemKind::Snapshot(_) => None,
+    }
+}
+
+pub(crate) fn format_float(v: f64) -> String {
+    if v.is_nan() {
+        "NaN".to_string()
+    } else if v.is_infinite() && v > 0.0 {
+        "+Inf".to_string()
+    } else if v.is_infinite() {
+        "-Inf".to_string()
+    } else {
+        v.to_string()
+    }
+}
+
+/// Returns the `Snapshot` with all floats rounded to the given
+/// number of decimal places.
+pub(crate) fn with_precision(snapshot: &Snapshot, precision: Option<u32>) -> Cow<'_, Snapshot> {
+    match precision {
+        Some(decimal_places) => Cow::Owned(round_floats(snapshot, decimal_places)),
+        None => Cow::Borrowed(snapshot),
+    }
+}
+
+fn round_floats(snapshot: &Snapshot, decimal_places: u32) -> Snapshot {
+    let items = snapshot
+        .items
+        .iter()
+        .map(|(name, item)| {
+            let item = match *item {
+                ItemKind::Float(v) => ItemKind::Float(round_float(v, decimal_places)),
+                ItemKind::Snapshot(ref inner) => {
+                    ItemKind::Snapshot(round_floats(inner, decimal_places))
+                }
+                ref other => other.clone(),
+            };
+            (name.clone(), item)
+        })
+        .collect();
+    Snapshot { items }
+}
+
+fn round_float(v: f64, decimal_places: u32) -> f64 {
+    let factor = 10f64.powi(decimal_places.min(i32::MAX as u32) as i32);
+    let rounded = (v * factor).round() / factor;
+    if rounded.is_finite() {
+        rounded
+    } else {
+        v
+    }
+}
+
+/// A `Histogram` puts its count next to its quantiles which are
+/// values in a nested `Snapshot` named `quantiles`.
+pub(crate) fn is_histogram(snapshot: &Snapshot) -> bool {
+    let has_count = snapshot
+        .items
+        .iter()
+        .any(|(name, item)| name == "count" && !matches!(item, ItemKind::Snapshot(_)));
+    let has_quantiles = snapshot.items.iter().any(|(name, item)| match item {
+        ItemKind::Snapshot(quantiles) if name == "quantiles" => quantiles
+            .items
+            .iter()
+            .all(|(_, item)| !matches!(item, ItemKind::Snapshot(_))),
+        _ => false,
+    });
+    has_count && has_quantiles
+}
+
+/// A `Histogram` with buckets puts the count of all
+/// values as `bucket_inf`.
+pub(crate) fn has_buckets(snapshot: &Snapshot) -> bool {
+    snapshot.items.iter().any(|(name, _)| name == "bucket_inf")
+}
+
+/// Quantiles are named like `p50` or `p999` which
+/// are the digits after the decimal point. `p100` is the maximum.
+pub(crate) fn quantile_from_name(name: &str) -> Option<String> {
+    let digits = name.strip_prefix('p')?;
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    if digits == "100" {
+        return Some("1".to_string());
+    }
+    let digits = digits.trim_end_matches('0');
+    if digits.is_empty() {
+        Some("0".to_string())
+    } else {
+        Some(format!("0.{}", digits))
+    }
+}
+
+/// Joins the sanitized segments of `path` to the metric name `base`
+pub(crate) fn metric_name(base: &str, path: &[&str]) -> String {
+    path.iter()
+        .fold(base.to_string(), |name, segment| join_name(&name, segment))
+}
+
+pub(crate) fn join_name(path: &str, name: &str) -> String {
+    snapshot::join_path(path, &sanitize_name(name), "_")
+}
+
+/// Replaces all characters not in `[a-zA-Z0-9_]` with an underscore.
+///
+/// A name must not start with a digit so an underscore is prepended
+/// in that case.
+pub(crate) fn sanitize_name(name: &str) -> String {
+    let sanitized = util::sanitize_name(name, &[]);
+
+    if sanitized.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", sanitized)
+    } else {
+        sanitized
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::cockpit::Cockpit;
+    use crate::instruments::{Counter, Gauge, Histogram, Panel, Update, Updates};
+    use crate::processor::{ProcessesTelemetryMessages, ProcessingStrategy, TelemetryProcessor};
+    use crate::{PutsSnapshot, TelemetryTransmitter, TransmitsTelemetryData};
+
+    #[derive(Clone, PartialEq, Eq)]
+    enum Label {
+        Requests,
+        Healthy,
+    }
+
+    #[test]
+    fn sanitizes_names() {
+        assert_eq!(sanitize_name("a-b.c d"), "a_b_c_d");
+        assert_eq!(sanitize_name("1xx"), "_1xx");
+        assert_eq!(sanitize_name("ok_123"), "ok_123");
+    }
+
+    #[test]
+    fn quantile_names() {
+        assert_eq!(quantile_from_name("p50"), Some("0.5".to_string()));
+        assert_eq!(quantile_from_name("p999"), Some("0.999".to_string()));
+        assert_eq!(quantile_from_name("p75"), Some("0.75".to_string()));
+        assert_eq!(quantile_from_name("p05"), Some("0.05".to_string()));
+        assert_eq!(quantile_from_name("p100"), Some("1".to_string()));
+        assert_eq!(quantile_from_name("max"), None);
+    }
+
+    #[test]
+    fn renders_a_processor_snapshot() {
+        let (tx, mut processor): (TelemetryTransmitter<Label>, _) =
+            TelemetryProcessor::new_pair("service");
+
+        let cockpit = Cockpit::new("http")
+            .panel(Panel::named(Label::Requests, "requests").counter(Counter::new("count")))
+            .panel(
+                Panel::named(Label::Healthy, "health")
+                    .gauge(Gauge::new("status-code"))
+                    .handler(crate::instruments::Flag::new("up").for_label(Label::Healthy)),
+            );
+        processor.add_cockpit(cockpit);
+
+        tx.observed_one_now(Label::Requests)
+            .observed_one_now(Label::Requests)
+            .observed_one_value_now(Label::Healthy, 1);
+        processor.process(100, ProcessingStrategy::ProcessAll);
+
+        let mut snapshot = Snapshot::default();
+        processor.put_snapshot(&mut snapshot, false);
+
+        let expected = "\
+# TYPE app_service_http_requests_count counter
+app_service_http_requests_count 2
+# TYPE app_service_http_health_status_code gauge
+app_service_http_health_status_code 1
+# TYPE app_service_http_health_up gauge
+app_service_http_health_up 1
+";
+        assert_eq!(snapshot.to_prometheus(Some("app")), expected);
+    }
+
+    #[test]
+    fn renders_a_decremented_counter_as_gauge() {
+        let mut counter = Counter::new("in_flight");
+        counter.inc_by(3);
+
+        let mut snapshot = Snapshot::default();
+        counter.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            PrometheusExporter::new().render(&snapshot),
+            "# TYPE in_flight counter\nin_flight 3\n"
+        );
+
+        counter.dec();
+        let mut snapshot = Snapshot::default();
+        counter.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            PrometheusExporter::new().render(&snapshot),
+            "# TYPE in_flight gauge\nin_flight 2\n"
+        );
+    }
+
+    #[test]
+    fn renders_counts_reset_on_snapshot_as_gauges() {
+        let counter = Counter::new("requests").reset_on_snapshot(true);
+        counter.inc_by_shared(3);
+
+        let mut snapshot = Snapshot::default();
+        counter.put_exported_snapshot(&mut snapshot, false);
+
+        assert_eq!(
+            PrometheusExporter::new().render(&snapshot),
+            "# TYPE requests gauge\nrequests 3\n"
+        );
+    }
+
+    #[test]
+    fn renders_a_histogram_as_summary() {
+        let quantiles = Snapshot {
+            items: vec![
+                ("p50".to_string(), ItemKind::Int(5)),
+                ("p999".to_string(), ItemKind::Int(9)),
+            ],
+        };
+        let histogram = Snapshot {
+            items: vec![
+                ("count".to_string(), ItemKind::UInt(10)),
+                ("max".to_string(), ItemKind::Int(9)),
+                ("mean".to_string(), ItemKind::Float(5.5)),
+                ("quantiles".to_string(), ItemKind::Snapshot(quantiles)),
+            ],
+        };
+        let snapshot = Snapshot {
+            items: vec![
+                ("latency".to_string(), ItemKind::Snapshot(histogram)),
+                ("_title".to_string(), ItemKind::Text("skipped".to_string())),
+                ("ratio".to_string(), ItemKind::Float(f64::NAN)),
+            ],
+        };
+
+        let expected = "\
+# TYPE latency summary
+latency{quantile=\"0.5\"} 5
+latency{quantile=\"0.999\"} 9
+latency_count 10
+# TYPE latency_max gauge
+latency_max 9
+# TYPE latency_mean gauge
+latency_mean 5.5
+# TYPE ratio gauge
+ratio NaN
+";
+        assert_eq!(PrometheusExporter::new().render(&snapshot), expected);
+    }
+
+    #[test]
+    fn renders_a_histogram_with_buckets() {
+        let mut histogram = Histogram::new("latency").buckets(&[10, 100]);
+        for v in &[1, 20, 30, 500] {
+            histogram.update(&Update::ObservationWithValue((*v).into(), Instant::now()));
+        }
+        let mut snapshot = Snapshot::default();
+        histogram.put_snapshot(&mut snapshot, false);
+
+        let rendered = PrometheusExporter::new().render(&snapshot);
+
+        let expected = "\
+# TYPE latency histogram
+latency_bucket{le=\"10\"} 1
+latency_bucket{le=\"100\"} 3
+latency_bucket{le=\"+Inf\"} 4
+latency_count 4
+latency_sum 551
+# TYPE latency_max gauge
+";
+        assert!(rendered.starts_with(expected), "{}", rendered);
+        assert!(!rendered.contains("quantile"));
+    }
+
+    #[test]
+    fn rounds_floats_to_the_precision() {
+        let snapshot = Snapshot {
+            items: vec![
+                ("ratio".to_string(), ItemKind::Float(0.123_456_789)),
+                ("nan".to_string(), ItemKind::Float(f64::NAN)),
+                ("inf".to_string(), ItemKind::Float(f64::INFINITY)),
+                ("neg_inf".to_string(), ItemKind::Float(f64::NEG_INFINITY)),
+                ("count".to_string(), ItemKind::UInt(7)),
+            ],
+        };
+
+        let expected = "\
+# TYPE ratio gauge
+ratio 0.123
+# TYPE nan gauge
+nan NaN
+# TYPE inf gauge
+inf +Inf
+# TYPE neg_inf gauge
+neg_inf -Inf
+# TYPE count counter
+count 7
+";
+        let exporter = PrometheusExporter::new().precision(3);
+        assert_eq!(exporter.render(&snapshot), expected);
+        assert!(PrometheusExporter::new()
+            .render(&snapshot)
+            .contains("ratio 0.123456789\n"));
+        // Rounding would overflow
+        assert_eq!(round_float(1e307, 3).to_bits(), 1e307f64.to_bits());
+    }
+}
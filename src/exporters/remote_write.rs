@@ -0,0 +1,713 @@
+//! The Prometheus remote write protocol
+//!
+//! A `WriteRequest` is encoded with protobuf and framed as a snappy
+//! block. Since this crate has no dependencies for protobuf, snappy or
+//! HTTP, all three are implemented here as far as they are needed:
+//! The snappy block only contains literals which is valid snappy but
+//! not compressed, and requests are sent via plain HTTP/1.1 without TLS.
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::prometheus::{
+    has_buckets, is_histogram, join_name, metric_name, quantile_from_name, sanitize_name,
+};
+use super::{ExportError, Exporter};
+use crate::snapshot::{visit_snapshot, ItemKind, Snapshot, VisitsSnapshot};
+
+/// Converts a `Snapshot` into a remote write `WriteRequest` and
+/// sends it to a Prometheus remote write endpoint.
+///
+/// The nested path of a value within the `Snapshot` becomes the
+/// `__name__` label of a time series where the segments are joined
+/// with an underscore just like with the `PrometheusExporter`.
+///
+/// The quantiles of a `Histogram` become time series named like the
+/// histogram with a `quantile` label. The buckets of a `Histogram` with
+/// buckets become time series named like the histogram postfixed with
+/// `_bucket` with an `le` label followed by `_count` and `_sum` as with
+/// the `PrometheusExporter`. The quantiles are omitted in this case.
+/// Booleans become `1` for `true` and `0` for `false`. Texts are skipped.
+///
+/// # Example
+///
+/// ```
+/// use metrix::exporters::remote_write::RemoteWriteExporter;
+/// use metrix::snapshot::*;
+///
+/// let inner = Snapshot {
+///     items: vec![("count".to_string(), ItemKind::UInt(42))],
+/// };
+///
+/// let snapshot = Snapshot {
+///     items: vec![("requests".to_string(), ItemKind::Snapshot(inner))],
+/// };
+///
+/// let exporter = RemoteWriteExporter::new("http://localhost:9090/api/v1/write").prefix("my_app");
+///
+/// let request = exporter.write_request(&snapshot, 1_500_000_000_000);
+///
+/// assert_eq!(request.timeseries[0].name(), Some("my_app_requests_count"));
+/// assert_eq!(request.timeseries[0].samples[0].value, 42.0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct RemoteWriteExporter {
+    url: String,
+    prefix: Option<String>,
+    timeout: Duration,
+}
+
+impl RemoteWriteExporter {
+    /// Creates an exporter sending to the given URL which must be
+    /// like `http://host[:port]/path`.
+    pub fn new<T: Into<String>>(url: T) -> RemoteWriteExporter {
+        RemoteWriteExporter {
+            url: url.into(),
+            prefix: None,
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// Returns the URL requests are sent to
+    pub fn get_url(&self) -> &str {
+        &self.url
+    }
+
+    /// Sets a prefix which will be the first segment of all metric names.
+    pub fn set_prefix<T: Into<String>>(&mut self, prefix: T) {
+        self.prefix = Some(prefix.into())
+    }
+
+    /// Sets a prefix which will be the first segment of all metric names.
+    pub fn prefix<T: Into<String>>(mut self, prefix: T) -> Self {
+        self.set_prefix(prefix);
+        self
+    }
+
+    /// Returns the prefix if there is one
+    pub fn get_prefix(&self) -> Option<&str> {
+        self.prefix.as_deref()
+    }
+
+    /// Sets the timeout for connecting as well as for each
+    /// read and write when sending a request.
+    ///
+    /// Default is 10 seconds.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Sets the timeout for connecting as well as for each
+    /// read and write when sending a request.
+    ///
+    /// Default is 10 seconds.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.set_timeout(timeout);
+        self
+    }
+
+    /// Returns the timeout for sending a request
+    pub fn get_timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Creates the `WriteRequest` for the given `Snapshot` where all samples
+    /// have the `timestamp` in milliseconds since the unix epoch.
+    pub fn write_request(&self, snapshot: &Snapshot, timestamp: i64) -> WriteRequest {
+        let prefix = self
+            .prefix
+            .as_ref()
+            .map(|p| sanitize_name(p))
+            .unwrap_or_default();
+        let mut timeseries = Vec::new();
+        put_snapshot(snapshot, &prefix, timestamp, &mut timeseries);
+        WriteRequest { timeseries }
+    }
+
+    /// Creates the request body which is the snappy framed
+    /// protobuf encoding of the `WriteRequest` for the given `Snapshot`
+    /// with the current time as the timestamp.
+    pub fn render_now(&self, snapshot: &Snapshot) -> Vec<u8> {
+        snappy_block(&self.write_request(snapshot, unix_millis_now()).encode())
+    }
+
+    /// Writes the HTTP request for the given `Snapshot` to `stream`
+    /// and reads the response.
+    ///
+    /// Fails if the status of the response is not `2xx`.
+    pub fn post_to<S: Read + Write>(&self, snapshot: &Snapshot, stream: &mut S) -> io::Result<()> {
+        self.post_at(snapshot, stream, unix_millis_now())
+    }
+
+    /// Like `post_to` with `timestamp` in milliseconds since the unix epoch
+    fn post_at<S: Read + Write>(
+        &self,
+        snapshot: &Snapshot,
+        stream: &mut S,
+        timestamp: i64,
+    ) -> io::Result<()> {
+        let (host, path) = split_url(&self.url)?;
+        let body = snappy_block(&self.write_request(snapshot, timestamp).encode());
+
+        let head = format!(
+            "POST {} HTTP/1.1\r\n\
+             Host: {}\r\n\
+             Content-Encoding: snappy\r\n\
+             Content-Type: application/x-protobuf\r\n\
+             X-Prometheus-Remote-Write-Version: 0.1.0\r\n\
+             Content-Length: {}\r\n\
+             Connection: close\r\n\r\n",
+            path,
+            host,
+            body.len()
+        );
+        stream.write_all(head.as_bytes())?;
+        stream.write_all(&body)?;
+        stream.flush()?;
+
+        let mut status_line = String::new();
+        BufReader::new(stream).read_line(&mut status_line)?;
+        match status_line.split_whitespace().nth(1) {
+            Some(status) if status.starts_with('2') => Ok(()),
+            _ => Err(io::Error::other(format!(
+                "remote write failed: {}",
+                status_line.trim_end()
+            ))),
+        }
+    }
+
+    /// Connects to the configured URL and sends the given `Snapshot`
+    /// with the current time as the timestamp.
+    pub fn send(&self, snapshot: &Snapshot) -> io::Result<()> {
+        self.send_at(snapshot, unix_millis_now())
+    }
+
+    /// Like `send` with `timestamp` in milliseconds since the unix epoch
+    fn send_at(&self, snapshot: &Snapshot, timestamp: i64) -> io::Result<()> {
+        let (host, _) = split_url(&self.url)?;
+        let address = if host.contains(':') {
+            host.to_string()
+        } else {
+            format!("{}:80", host)
+        };
+        let mut stream = self.connect(&address)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        self.post_at(snapshot, &mut stream, timestamp)
+    }
+
+    /// Connects to the first of the addresses `address` resolves to
+    /// which accepts the connection within the timeout.
+    fn connect(&self, address: &str) -> io::Result<TcpStream> {
+        let mut last_err = None;
+        for address in address.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&address, self.timeout) {
+                Ok(stream) => return Ok(stream),
+                Err(err) => last_err = Some(err),
+            }
+        }
+        Err(last_err.unwrap_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("could not resolve {}", address),
+            )
+        }))
+    }
+}
+
+impl Exporter for RemoteWriteExporter {
+    /// Sends the given `Snapshot` with the current time as the timestamp.
+    ///
+    /// See `send`.
+    fn export(&self, snapshot: &Snapshot) -> Result<(), ExportError> {
+        Ok(self.send(snapshot)?)
+    }
+
+    /// Sends the given `Snapshot` with `taken_at` as the timestamp.
+    fn export_taken_at(
+        &self,
+        snapshot: &Snapshot,
+        taken_at: SystemTime,
+    ) -> Result<(), ExportError> {
+        Ok(self.send_at(snapshot, unix_millis(taken_at))?)
+    }
+}
+
+/// The protobuf message sent to a remote write endpoint
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct WriteRequest {
+    pub timeseries: Vec<TimeSeries>,
+}
+
+impl WriteRequest {
+    /// Encodes this `WriteRequest` with protobuf
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        for series in &self.timeseries {
+            put_message(1, &series.encode(), &mut out);
+        }
+        out
+    }
+}
+
+/// A time series identified by its labels
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TimeSeries {
+    /// The labels sorted by name
+    pub labels: Vec<Label>,
+    pub samples: Vec<Sample>,
+}
+
+impl TimeSeries {
+    /// Returns the value of the `__name__` label
+    pub fn name(&self) -> Option<&str> {
+        self.labels
+            .iter()
+            .find(|l| l.name == "__name__")
+            .map(|l| l.value.as_str())
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        for label in &self.labels {
+            let mut encoded = Vec::new();
+            put_message(1, label.name.as_bytes(), &mut encoded);
+            put_message(2, label.value.as_bytes(), &mut encoded);
+            put_message(1, &encoded, &mut out);
+        }
+        for sample in &self.samples {
+            let mut encoded = Vec::new();
+            put_varint(1 << 3 | 1, &mut encoded);
+            encoded.extend_from_slice(&sample.value.to_le_bytes());
+            put_varint(2 << 3, &mut encoded);
+            put_varint(sample.timestamp as u64, &mut encoded);
+            put_message(2, &encoded, &mut out);
+        }
+        out
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Label {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Sample {
+    pub value: f64,
+    /// Milliseconds since the unix epoch
+    pub timestamp: i64,
+}
+
+fn put_snapshot(snapshot: &Snapshot, base: &str, timestamp: i64, out: &mut Vec<TimeSeries>) {
+    let mut series = Series {
+        base,
+        timestamp,
+        out,
+    };
+    visit_snapshot(snapshot, &mut series);
+}
+
+struct Series<'b> {
+    base: &'b str,
+    timestamp: i64,
+    out: &'b mut Vec<TimeSeries>,
+}
+
+impl<'a, 'b> VisitsSnapshot<'a> for Series<'b> {
+    fn enter(&mut self, path: &[&'a str], _parent: &'a Snapshot, snapshot: &'a Snapshot) -> bool {
+        if has_buckets(snapshot) {
+            let metric_name = metric_name(self.base, path);
+            put_bucket_histogram(snapshot, &metric_name, self.timestamp, self.out);
+            false
+        } else if is_histogram(snapshot) {
+            let metric_name = metric_name(self.base, path);
+            put_histogram(snapshot, &metric_name, self.timestamp, self.out);
+            false
+        } else {
+            true
+        }
+    }
+
+    fn value(&mut self, path: &[&'a str], _parent: &'a Snapshot, item: &'a ItemKind) {
+        let metric_name = metric_name(self.base, path);
+        put_value(item, &metric_name, None, self.timestamp, self.out)
+    }
+}
+
+fn put_histogram(
+    histogram: &Snapshot,
+    metric_name: &str,
+    timestamp: i64,
+    out: &mut Vec<TimeSeries>,
+) {
+    for (name, item) in &histogram.items {
+        match item {
+            ItemKind::Snapshot(ref quantiles) if name == "quantiles" => {
+                for (q_name, q_value) in &quantiles.items {
+                    if let Some(quantile) = quantile_from_name(q_name) {
+                        let label = ("quantile", quantile);
+                        put_value(q_value, metric_name, Some(label), timestamp, out);
+                    }
+                }
+            }
+            ItemKind::Snapshot(ref inner) => {
+                put_snapshot(inner, &join_name(metric_name, name), timestamp, out)
+            }
+            other => put_value(other, &join_name(metric_name, name), None, timestamp, out),
+        }
+    }
+}
+
+fn put_bucket_histogram(
+    histogram: &Snapshot,
+    metric_name: &str,
+    timestamp: i64,
+    out: &mut Vec<TimeSeries>,
+) {
+    let bucket_name = join_name(metric_name, "bucket");
+    for (name, item) in &histogram.items {
+        if name == "bucket_sum_total" {
+            continue;
+        }
+        if let Some(bound) = name.strip_prefix("bucket_") {
+            let bound = if bound == "inf" { "+Inf" } else { bound };
+            let label = ("le", bound.to_string());
+            put_value(item, &bucket_name, Some(label), timestamp, out);
+        }
+    }
+
+    // The count and sum have to cover all values since
+    // creation like the buckets do.
+    for (name, postfix) in &[("bucket_inf", "count"), ("bucket_sum_total", "sum")] {
+        if let Some((_, item)) = histogram.items.iter().find(|(n, _)| n == name) {
+            put_value(item, &join_name(metric_name, postfix), None, timestamp, out);
+        }
+    }
+
+    for (name, item) in &histogram.items {
+        match name.as_str() {
+            "count" | "sum" | "quantiles" => {}
+            _ if name.starts_with("bucket_") => {}
+            _ => match item {
+                ItemKind::Snapshot(ref inner) => {
+                    put_snapshot(inner, &join_name(metric_name, name), timestamp, out)
+                }
+                other => put_value(other, &join_name(metric_name, name), None, timestamp, out),
+            },
+        }
+    }
+}
+
+/// Puts a time series named `metric_name` with an optional
+/// additional label given as its name and value.
+fn put_value(
+    item: &ItemKind,
+    metric_name: &str,
+    label: Option<(&str, String)>,
+    timestamp: i64,
+    out: &mut Vec<TimeSeries>,
+) {
+    let value = match *item {
+        ItemKind::UInt(v) => v as f64,
+        ItemKind::Int(v) => v as f64,
+        ItemKind::Float(v) => v,
+        ItemKind::Boolean(v) => {
+            if v {
+                1.0
+            } else {
+                0.0
+            }
+        }
+        ItemKind::Text(_) | ItemKind::Snapshot(_) => return,
+    };
+
+    let mut labels = vec![Label {
+        name: "__name__".to_string(),
+        value: metric_name.to_string(),
+    }];
+    if let Some((name, value)) = label {
+        labels.push(Label {
+            name: name.to_string(),
+            value,
+        });
+    }
+    labels.sort_by(|a, b| a.name.cmp(&b.name));
+
+    out.push(TimeSeries {
+        labels,
+        samples: vec![Sample { value, timestamp }],
+    });
+}
+
+fn put_varint(mut v: u64, out: &mut Vec<u8>) {
+    while v >= 0x80 {
+        out.push((v as u8) | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+
+/// Puts a length delimited field
+fn put_message(field: u64, bytes: &[u8], out: &mut Vec<u8>) {
+    put_varint(field << 3 | 2, out);
+    put_varint(bytes.len() as u64, out);
+    out.extend_from_slice(bytes);
+}
+
+/// Frames the bytes as a snappy block consisting of literals only
+fn snappy_block(bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len() + bytes.len() / 65_536 * 3 + 8);
+    put_varint(bytes.len() as u64, &mut out);
+    for chunk in bytes.chunks(65_536) {
+        let n = chunk.len() - 1;
+        if n < 60 {
+            out.push((n as u8) << 2);
+        } else if n < 256 {
+            out.push(60 << 2);
+            out.push(n as u8);
+        } else {
+            out.push(61 << 2);
+            out.extend_from_slice(&(n as u16).to_le_bytes());
+        }
+        out.extend_from_slice(chunk);
+    }
+    out
+}
+
+/// Splits `http://host[:port]/path` into the host with
+/// the port and the path
+fn split_url(url: &str) -> io::Result<(&str, &str)> {
+    let rest = url.strip_prefix("http://").ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("only http URLs are supported: {}", url),
+        )
+    })?;
+    Ok(match rest.find('/') {
+        Some(idx) => (&rest[..idx], &rest[idx..]),
+        None => (rest, "/"),
+    })
+}
+
+fn unix_millis_now() -> i64 {
+    unix_millis(SystemTime::now())
+}
+
+fn unix_millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn snapshot() -> Snapshot {
+        let mut quantiles = Snapshot::default();
+        quantiles.push("p50", ItemKind::Int(7));
+        let mut latency = Snapshot::default();
+        latency.push("count", ItemKind::UInt(3));
+        latency.push("quantiles", ItemKind::Snapshot(quantiles));
+
+        let mut snapshot = Snapshot::default();
+        snapshot.push("up", ItemKind::Boolean(true));
+        snapshot.push("version", ItemKind::Text("1.0".to_string()));
+        snapshot.push("latency", ItemKind::Snapshot(latency));
+        snapshot
+    }
+
+    fn read_varint(bytes: &[u8], pos: &mut usize) -> u64 {
+        let mut v = 0;
+        let mut shift = 0;
+        loop {
+            let b = bytes[*pos];
+            *pos += 1;
+            v |= u64::from(b & 0x7F) << shift;
+            if b & 0x80 == 0 {
+                return v;
+            }
+            shift += 7;
+        }
+    }
+
+    #[test]
+    fn builds_the_write_request() {
+        let request = RemoteWriteExporter::new("http://localhost/write")
+            .prefix("app")
+            .write_request(&snapshot(), 1_000);
+
+        let series: Vec<(Option<&str>, Vec<&Label>, f64)> = request
+            .timeseries
+            .iter()
+            .map(|s| {
+                (
+                    s.name(),
+                    s.labels.iter().filter(|l| l.name != "__name__").collect(),
+                    s.samples[0].value,
+                )
+            })
+            .collect();
+        let quantile = Label {
+            name: "quantile".to_string(),
+            value: "0.5".to_string(),
+        };
+        assert_eq!(
+            series,
+            vec![
+                (Some("app_up"), vec![], 1.0),
+                (Some("app_latency_count"), vec![], 3.0),
+                (Some("app_latency"), vec![&quantile], 7.0),
+            ]
+        );
+        assert!(request
+            .timeseries
+            .iter()
+            .all(|s| s.samples[0].timestamp == 1_000));
+    }
+
+    #[test]
+    fn puts_the_buckets_of_a_histogram() {
+        use crate::instruments::{Histogram, Update, Updates};
+        use crate::PutsSnapshot;
+        use std::time::Instant;
+
+        let mut histogram = Histogram::new("latency").buckets(&[10, 100]);
+        for v in &[1, 20, 30, 500] {
+            histogram.update(&Update::ObservationWithValue((*v).into(), Instant::now()));
+        }
+        let mut snapshot = Snapshot::default();
+        histogram.put_snapshot(&mut snapshot, false);
+
+        let request =
+            RemoteWriteExporter::new("http://localhost/write").write_request(&snapshot, 1);
+
+        let series: Vec<(Option<&str>, Option<&str>, f64)> = request
+            .timeseries
+            .iter()
+            .map(|s| {
+                (
+                    s.name(),
+                    s.labels
+                        .iter()
+                        .find(|l| l.name == "le")
+                        .map(|l| l.value.as_str()),
+                    s.samples[0].value,
+                )
+            })
+            .collect();
+        assert_eq!(
+            &series[..5],
+            &[
+                (Some("latency_bucket"), Some("10"), 1.0),
+                (Some("latency_bucket"), Some("100"), 3.0),
+                (Some("latency_bucket"), Some("+Inf"), 4.0),
+                (Some("latency_count"), None, 4.0),
+                (Some("latency_sum"), None, 551.0),
+            ]
+        );
+        assert!(request
+            .timeseries
+            .iter()
+            .all(|s| s.labels.iter().all(|l| l.name != "quantile")));
+    }
+
+    #[test]
+    fn encodes_a_series() {
+        let request = WriteRequest {
+            timeseries: vec![TimeSeries {
+                labels: vec![Label {
+                    name: "__name__".to_string(),
+                    value: "up".to_string(),
+                }],
+                samples: vec![Sample {
+                    value: 1.0,
+                    timestamp: 300,
+                }],
+            }],
+        };
+
+        let mut expected = vec![0x0A, 30, 0x0A, 14, 0x0A, 8];
+        expected.extend_from_slice(b"__name__");
+        expected.extend_from_slice(&[0x12, 2]);
+        expected.extend_from_slice(b"up");
+        expected.extend_from_slice(&[0x12, 12, 0x09]);
+        expected.extend_from_slice(&1.0f64.to_le_bytes());
+        expected.extend_from_slice(&[0x10, 0xAC, 0x02]);
+        assert_eq!(request.encode(), expected);
+    }
+
+    #[test]
+    fn snappy_block_contains_the_literals() {
+        let bytes: Vec<u8> = (0..100_000u32).map(|v| v as u8).collect();
+
+        let block = snappy_block(&bytes);
+
+        let mut pos = 0;
+        assert_eq!(read_varint(&block, &mut pos), 100_000);
+        let mut decoded = Vec::new();
+        while pos < block.len() {
+            let len = match block[pos] >> 2 {
+                60 => {
+                    pos += 2;
+                    block[pos - 1] as usize + 1
+                }
+                61 => {
+                    pos += 3;
+                    u16::from_le_bytes([block[pos - 2], block[pos - 1]]) as usize + 1
+                }
+                n => {
+                    pos += 1;
+                    n as usize + 1
+                }
+            };
+            decoded.extend_from_slice(&block[pos..pos + len]);
+            pos += len;
+        }
+        assert_eq!(decoded, bytes);
+    }
+
+    struct MockStream {
+        written: Vec<u8>,
+        response: io::Cursor<Vec<u8>>,
+    }
+
+    impl Read for MockStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.response.read(buf)
+        }
+    }
+
+    impl Write for MockStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.written.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn posts_the_request() {
+        let exporter = RemoteWriteExporter::new("http://localhost:9090/api/v1/write");
+        let mut stream = MockStream {
+            written: Vec::new(),
+            response: io::Cursor::new(b"HTTP/1.1 204 No Content\r\n\r\n".to_vec()),
+        };
+
+        exporter.post_to(&snapshot(), &mut stream).unwrap();
+
+        let written = String::from_utf8_lossy(&stream.written);
+        assert!(written.starts_with("POST /api/v1/write HTTP/1.1\r\nHost: localhost:9090\r\n"));
+        assert!(written.contains("Content-Encoding: snappy\r\n"));
+
+        let mut failing = MockStream {
+            written: Vec::new(),
+            response: io::Cursor::new(b"HTTP/1.1 400 Bad Request\r\n\r\n".to_vec()),
+        };
+        assert!(exporter.post_to(&snapshot(), &mut failing).is_err());
+    }
+}
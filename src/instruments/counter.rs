@@ -1,17 +1,46 @@
+use std::cmp::Ordering as CmpOrdering;
+use std::fmt;
+use std::ops::AddAssign;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::instruments::fundamentals::metrics_meter::{Meter as MMeter, StdMeter};
+use crate::instruments::fundamentals::{Clock, SharedClock};
 use crate::instruments::{
     AcceptAllLabels, Instrument, InstrumentAdapter, LabelFilter, LabelPredicate, Update, Updates,
 };
 use crate::snapshot::Snapshot;
 use crate::util;
+use crate::ObservedValue;
 use crate::{Descriptive, PutsSnapshot};
 
-/// A simple ever increasing counter
+/// A simple counter
 ///
 /// Reacts to the following `Observation`s:
 ///
 /// * `Observation::Observed`(Update::Observations)
 /// * `Obervation::ObservedOne`(Update::Observation)
 /// * `Obervation::ObservedOneValue`(Update::ObservationWithValue)
+/// * `Obervation::ObservedValues`(Update::ObservationsWithValue)
+///
+/// An observed value of `ObservedValue::ChangedBy` changes the counter
+/// by the given delta. Other negative values decrement the counter by one
+/// while all other values increment it by one. For many observations
+/// with a value this happens once per observation.
+///
+/// The counter never goes below zero. Once it has been decremented
+/// it is marked with a text `_type_[name]` so that exporters treat it
+/// as a gauge since a counter is expected to only go up.
+///
+/// The increments per second can be limited to keep a runaway
+/// loop from corrupting the counter. See `set_max_inc_per_sec`.
+///
+/// The count is stored in an atomic so reading it never locks and
+/// a counter shared between threads can be incremented
+/// with `inc_by_shared`.
+///
+/// For direct use a counter can be incremented with `+=` and compared
+/// with a `u64`.
 ///
 /// # Example
 ///
@@ -29,7 +58,13 @@ pub struct Counter {
     name: String,
     title: Option<String>,
     description: Option<String>,
-    count: u64,
+    count: AtomicU64,
+    rate: Option<StdMeter>,
+    update_tracker: util::UpdateTracker,
+    inc_limit: Option<IncLimit>,
+    clock: SharedClock,
+    reset_on_snapshot: bool,
+    decremented: AtomicBool,
 }
 
 impl Counter {
@@ -38,7 +73,13 @@ impl Counter {
             name: name.into(),
             title: None,
             description: None,
-            count: 0,
+            count: AtomicU64::new(0),
+            rate: None,
+            update_tracker: util::UpdateTracker::default(),
+            inc_limit: None,
+            clock: SharedClock::default(),
+            reset_on_snapshot: false,
+            decremented: AtomicBool::new(false),
         }
     }
     pub fn new_with_defaults<T: Into<String>>(name: T) -> Counter {
@@ -76,19 +117,268 @@ impl Counter {
         self
     }
 
+    /// Enables tracking when this counter was updated last.
+    ///
+    /// The number of seconds since the last update will be put into
+    /// the `Snapshot` as `_updated_secs_ago_[counter_name]`. Nothing is added before the first update.
+    ///
+    /// Default is disabled.
+    pub fn set_updated_secs_ago_enabled(&mut self, enabled: bool) {
+        self.update_tracker.set_enabled(enabled);
+    }
+
+    /// Enables tracking when this counter was updated last.
+    ///
+    /// The number of seconds since the last update will be put into
+    /// the `Snapshot` as `_updated_secs_ago_[counter_name]`. Nothing is added before the first update.
+    ///
+    /// Default is disabled.
+    pub fn updated_secs_ago_enabled(mut self, enabled: bool) -> Self {
+        self.set_updated_secs_ago_enabled(enabled);
+        self
+    }
+
+    /// Enables tracking the rate of the increments so that a separate
+    /// `Meter` for the same observations is not needed.
+    ///
+    /// The one minute rate per second will be put into the `Snapshot`
+    /// as `[counter_name]_per_second`. Decrements do not lower the rate.
+    ///
+    /// Default is disabled.
+    pub fn set_rate_enabled(&mut self, enabled: bool) {
+        if enabled {
+            if self.rate.is_none() {
+                self.rate = Some(StdMeter::with_clock(self.clock.clone()));
+            }
+        } else {
+            self.rate = None;
+        }
+    }
+
+    /// Enables tracking the rate of the increments.
+    ///
+    /// See `set_rate_enabled`.
+    ///
+    /// Default is disabled.
+    pub fn rate_enabled(mut self, enabled: bool) -> Self {
+        self.set_rate_enabled(enabled);
+        self
+    }
+
+    /// If enabled the count is set to zero whenever it is put into an
+    /// exported `Snapshot` so that each exported `Snapshot` contains only
+    /// the increments since the previous one. Other `Snapshot`s like the
+    /// ones taken by `TelemetryDriver::snapshot` do not reset the count.
+    /// See `PutsSnapshot::put_exported_snapshot`.
+    ///
+    /// Since the count then goes up and down it is marked with a text
+    /// `_type_[name]` so that exporters treat it as a gauge.
+    ///
+    /// Reading and clearing the count is a single atomic operation.
+    /// Increments made concurrently with `inc_by_shared` are
+    /// therefore reported in exactly one `Snapshot`.
+    ///
+    /// Default is disabled.
+    pub fn set_reset_on_snapshot(&mut self, enabled: bool) {
+        self.reset_on_snapshot = enabled;
+    }
+
+    /// If enabled the count is set to zero whenever it is put into an
+    /// exported `Snapshot`.
+    ///
+    /// See `set_reset_on_snapshot`.
+    ///
+    /// Default is disabled.
+    pub fn reset_on_snapshot(mut self, enabled: bool) -> Self {
+        self.set_reset_on_snapshot(enabled);
+        self
+    }
+
+    /// Returns true if the count is set to zero whenever it is put
+    /// into an exported `Snapshot`.
+    pub fn get_reset_on_snapshot(&self) -> bool {
+        self.reset_on_snapshot
+    }
+
+    /// Limits the sum of the increments within each second to
+    /// `max_inc_per_sec`.
+    ///
+    /// Increments exceeding the limit are not applied but counted
+    /// instead. This count is put into the `Snapshot` as
+    /// `[counter_name]_rate_limited_count`. The limit applies to
+    /// seconds starting with the first increment so that the full
+    /// budget is available again at each second boundary.
+    /// Decrements are not limited.
+    ///
+    /// Default is no limit.
+    ///
+    /// # Panics
+    ///
+    /// If `max_inc_per_sec` is zero.
+    pub fn set_max_inc_per_sec(&mut self, max_inc_per_sec: u64) {
+        assert!(max_inc_per_sec > 0, "max_inc_per_sec must be positive");
+        self.inc_limit = Some(IncLimit::new(max_inc_per_sec));
+    }
+
+    /// Limits the sum of the increments within each second to
+    /// `max_inc_per_sec`.
+    ///
+    /// See `set_max_inc_per_sec`.
+    ///
+    /// # Panics
+    ///
+    /// If `max_inc_per_sec` is zero.
+    pub fn max_inc_per_sec(mut self, max_inc_per_sec: u64) -> Self {
+        self.set_max_inc_per_sec(max_inc_per_sec);
+        self
+    }
+
+    /// Returns the sum of the increments which were not applied
+    /// because they exceeded the limit.
+    ///
+    /// Always 0 if no limit was set.
+    pub fn rate_limited_count(&self) -> u64 {
+        self.inc_limit.as_ref().map(|l| l.limited).unwrap_or(0)
+    }
+
+    /// Sets the `Clock` used for the limit of the increments,
+    /// for the rate and for the time since the last update.
+    ///
+    /// The rate and the time of the last update are reset. Mostly useful for testing with
+    /// a `ManualOffsetClock`.
+    ///
+    /// Default is the `WallClock`.
+    pub fn set_clock<C: Clock + Send + Sync + 'static>(&mut self, clock: C) {
+        self.clock = SharedClock::new(clock);
+        self.update_tracker.set_clock(self.clock.clone());
+        if self.rate.is_some() {
+            self.rate = Some(StdMeter::with_clock(self.clock.clone()));
+        }
+    }
+
+    /// Returns a counter with the same configuration which
+    /// has not observed anything yet.
+    pub(crate) fn template_clone(&self) -> Counter {
+        Counter {
+            name: self.name.clone(),
+            title: self.title.clone(),
+            description: self.description.clone(),
+            count: AtomicU64::new(0),
+            rate: self
+                .rate
+                .as_ref()
+                .map(|_| StdMeter::with_clock(self.clock.clone())),
+            update_tracker: self.update_tracker.template(),
+            inc_limit: self
+                .inc_limit
+                .as_ref()
+                .map(|limit| IncLimit::new(limit.max_per_sec)),
+            clock: self.clock.clone(),
+            reset_on_snapshot: self.reset_on_snapshot,
+            decremented: AtomicBool::new(false),
+        }
+    }
+
     /// Increase the stored value by one.
     pub fn inc(&mut self) {
-        self.count += 1;
+        self.inc_by(1);
     }
 
     /// Increase the stored value by `n`
     pub fn inc_by(&mut self, n: u64) {
-        self.count += n;
+        let n = match self.inc_limit {
+            Some(ref mut limit) => limit.take(self.clock.now(), n),
+            None => n,
+        };
+        if n == 0 {
+            return;
+        }
+        let count = self.count.get_mut();
+        *count = count.saturating_add(n);
+        if let Some(ref rate) = self.rate {
+            rate.mark(n as i64);
+        }
+    }
+
+    /// Increase the stored value by `n` through a shared reference,
+    /// e.g. from several threads.
+    ///
+    /// The count is updated without locking. A limit set with
+    /// `set_max_inc_per_sec` is not applied.
+    pub fn inc_by_shared(&self, n: u64) {
+        if n == 0 {
+            return;
+        }
+        let _ = self
+            .count
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+                Some(count.saturating_add(n))
+            });
+        if let Some(ref rate) = self.rate {
+            rate.mark(n as i64);
+        }
+    }
+
+    /// Decrease the stored value by one.
+    ///
+    /// The value will not go below zero.
+    pub fn dec(&mut self) {
+        self.dec_by(1);
+    }
+
+    /// Decrease the stored value by `n`
+    ///
+    /// The value will not go below zero.
+    pub fn dec_by(&mut self, n: u64) {
+        if n == 0 {
+            return;
+        }
+        *self.decremented.get_mut() = true;
+        let count = self.count.get_mut();
+        *count = count.saturating_sub(n);
+    }
+
+    /// Decrease the stored value by `n` through a shared reference,
+    /// e.g. from several threads.
+    ///
+    /// The value will not go below zero.
+    pub fn dec_by_shared(&self, n: u64) {
+        if n == 0 {
+            return;
+        }
+        self.decremented.store(true, Ordering::Relaxed);
+        let _ = self
+            .count
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+                Some(count.saturating_sub(n))
+            });
+    }
+
+    /// Increase the stored value by `delta` if positive or
+    /// decrease it if negative.
+    ///
+    /// The value will not go below zero.
+    pub fn inc_by_signed(&mut self, delta: i64) {
+        if delta < 0 {
+            self.dec_by(delta.unsigned_abs());
+        } else {
+            self.inc_by(delta as u64);
+        }
     }
 
     /// Get the current value
     pub fn get(&self) -> u64 {
-        self.count
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// Sets the stored value to zero through a shared reference
+    pub(crate) fn clear_shared(&self) {
+        self.count.store(0, Ordering::Relaxed);
+    }
+
+    /// Returns true if the value has ever been decreased
+    pub(crate) fn was_decremented(&self) -> bool {
+        self.decremented.load(Ordering::Relaxed)
     }
 
     pub fn accept<L: Eq + Send + 'static, F: Into<LabelFilter<L>>>(
@@ -135,34 +425,143 @@ impl Counter {
     }
 }
 
-impl Instrument for Counter {}
+impl Instrument for Counter {
+    fn name(&self) -> Option<&str> {
+        Some(self.get_name())
+    }
+
+    fn reset(&mut self) {
+        *self.count.get_mut() = 0;
+        if self.rate.is_some() {
+            self.rate = Some(StdMeter::with_clock(self.clock.clone()));
+        }
+        if let Some(ref mut limit) = self.inc_limit {
+            *limit = IncLimit::new(limit.max_per_sec);
+        }
+        self.update_tracker.reset();
+    }
+}
+
+impl Counter {
+    fn put_values(&self, into: &mut Snapshot, descriptive: bool, exported: bool) {
+        util::put_postfixed_descriptives(self, &self.name, into, descriptive);
+        if self.reset_on_snapshot {
+            let count = if exported {
+                self.count.swap(0, Ordering::Relaxed)
+            } else {
+                self.get()
+            };
+            into.items.push((self.name.clone(), count.into()));
+        } else {
+            into.items.push((self.name.clone(), self.get().into()));
+        }
+        if self.reset_on_snapshot || self.was_decremented() {
+            util::put_gauge_type(&self.name, into);
+        }
+        if let Some(ref rate) = self.rate {
+            into.items.push((
+                format!("{}_per_second", self.name),
+                rate.snapshot().rates[0].into(),
+            ));
+        }
+        if let Some(ref limit) = self.inc_limit {
+            into.items.push((
+                format!("{}_rate_limited_count", self.name),
+                limit.limited.into(),
+            ));
+        }
+        self.update_tracker.put_postfixed(&self.name, into);
+    }
+}
 
 impl PutsSnapshot for Counter {
     fn put_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
-        util::put_postfixed_descriptives(self, &self.name, into, descriptive);
-        into.items.push((self.name.clone(), self.count.into()));
+        self.put_values(into, descriptive, false)
+    }
+
+    fn put_exported_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
+        self.put_values(into, descriptive, true)
+    }
+
+    fn put_names(&self, into: &mut Snapshot) {
+        util::put_name(&self.name, into)
     }
 }
 
 impl Updates for Counter {
     fn update(&mut self, with: &Update) -> usize {
-        match *with {
-            Update::Observation(_) => {
-                self.inc();
-                1
-            }
-            Update::Observations(n, _) => {
-                self.inc_by(n);
-                1
-            }
-            Update::ObservationWithValue(_, _) => {
-                self.inc();
-                1
+        self.update_tracker.updated();
+        match CountChange::of(with) {
+            CountChange::Inc(n) => self.inc_by(n),
+            CountChange::Dec(n) => self.dec_by(n),
+        }
+        1
+    }
+}
+
+/// How an `Update` changes the count of a `Counter`
+///
+/// An observed value of `ObservedValue::ChangedBy` changes the count
+/// by the given delta. Other negative values decrement it by one while
+/// all other values increment it by one. For many observations with
+/// a value this happens once per observation.
+pub(crate) enum CountChange {
+    Inc(u64),
+    Dec(u64),
+}
+
+impl CountChange {
+    pub(crate) fn of(update: &Update) -> CountChange {
+        match *update {
+            Update::Observation(_) => CountChange::Inc(1),
+            Update::Observations(n, _) => CountChange::Inc(n),
+            Update::ObservationWithValue(value, _) => CountChange::of_value(value, 1),
+            Update::ObservationsWithValue(value, n, _) => CountChange::of_value(value, n),
+        }
+    }
+
+    fn of_value(value: ObservedValue, n: u64) -> CountChange {
+        match value {
+            ObservedValue::ChangedBy(delta) => {
+                let delta = delta.saturating_mul(n.min(i64::MAX as u64) as i64);
+                if delta < 0 {
+                    CountChange::Dec(delta.unsigned_abs())
+                } else {
+                    CountChange::Inc(delta as u64)
+                }
             }
+            ObservedValue::SignedInteger(v) if v < 0 => CountChange::Dec(n),
+            ObservedValue::Float(v) if v < 0.0 => CountChange::Dec(n),
+            _ => CountChange::Inc(n),
         }
     }
 }
 
+/// Same as `inc_by`
+impl AddAssign<u64> for Counter {
+    fn add_assign(&mut self, n: u64) {
+        self.inc_by(n);
+    }
+}
+
+impl PartialEq<u64> for Counter {
+    fn eq(&self, other: &u64) -> bool {
+        self.get() == *other
+    }
+}
+
+impl PartialOrd<u64> for Counter {
+    fn partial_cmp(&self, other: &u64) -> Option<CmpOrdering> {
+        self.get().partial_cmp(other)
+    }
+}
+
+impl fmt::Display for Counter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.get())
+    }
+}
+
 impl Descriptive for Counter {
     fn title(&self) -> Option<&str> {
         self.title.as_ref().map(|n| &**n)
@@ -173,6 +572,45 @@ impl Descriptive for Counter {
     }
 }
 
+/// The budget for increments within the current second
+struct IncLimit {
+    max_per_sec: u64,
+    second_started: Option<Instant>,
+    used: u64,
+    limited: u64,
+}
+
+impl IncLimit {
+    fn new(max_per_sec: u64) -> IncLimit {
+        IncLimit {
+            max_per_sec,
+            second_started: None,
+            used: 0,
+            limited: 0,
+        }
+    }
+
+    /// Returns the part of `n` which is within the budget
+    fn take(&mut self, now: Instant, n: u64) -> u64 {
+        match self.second_started {
+            Some(started) if now >= started => {
+                let elapsed_secs = (now - started).as_secs();
+                if elapsed_secs > 0 {
+                    self.second_started = Some(started + Duration::from_secs(elapsed_secs));
+                    self.used = 0;
+                }
+            }
+            Some(_) => {}
+            None => self.second_started = Some(now),
+        }
+
+        let allowed = n.min(self.max_per_sec - self.used);
+        self.used += allowed;
+        self.limited = self.limited.saturating_add(n - allowed);
+        allowed
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::time::Instant;
@@ -196,5 +634,223 @@ mod test {
 
         counter.update(&Update::ObservationWithValue(33.into(), Instant::now()));
         assert_eq!(counter.get(), 6);
+
+        counter.update(&Update::ObservationWithValue((-33).into(), Instant::now()));
+        assert_eq!(counter.get(), 5);
+
+        counter.update(&Update::ObservationWithValue(
+            ObservedValue::ChangedBy(-3),
+            Instant::now(),
+        ));
+        assert_eq!(counter.get(), 2);
+
+        counter.update(&Update::ObservationWithValue(
+            ObservedValue::ChangedBy(4),
+            Instant::now(),
+        ));
+        assert_eq!(counter.get(), 6);
+    }
+
+    #[test]
+    fn decrement_saturates_at_zero() {
+        let mut counter = Counter::new("");
+
+        counter.inc_by(2);
+        counter.dec();
+        assert_eq!(counter.get(), 1);
+
+        counter.dec_by(5);
+        assert_eq!(counter.get(), 0);
+
+        counter.dec();
+        assert_eq!(counter.get(), 0);
+
+        counter.inc_by_signed(-10);
+        assert_eq!(counter.get(), 0);
+
+        counter.update(&Update::ObservationWithValue(
+            ObservedValue::ChangedBy(-1),
+            Instant::now(),
+        ));
+        assert_eq!(counter.get(), 0);
+    }
+
+    #[test]
+    fn mixed_increments_and_decrements() {
+        let mut counter = Counter::new("");
+
+        counter.inc();
+        counter.inc_by(4);
+        counter.dec();
+        counter.inc_by_signed(3);
+        counter.inc_by_signed(-2);
+        counter.dec_by(1);
+
+        assert_eq!(counter.get(), 4);
+    }
+
+    #[test]
+    fn the_rate_follows_a_steady_cadence() {
+        use crate::instruments::{Clock, ManualOffsetClock};
+        use crate::snapshot::{FindItem, ItemKind};
+
+        let clock = ManualOffsetClock::default();
+        let mut counter = Counter::new("requests").rate_enabled(true);
+        counter.set_clock(clock.clone());
+
+        for _ in 0..60 {
+            counter.update(&Update::Observations(10, clock.now()));
+            clock.advance_n_seconds(1);
+        }
+
+        let mut snapshot = Snapshot::default();
+        counter.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("requests"),
+            FindItem::Found(&ItemKind::UInt(600))
+        );
+        match snapshot.find("requests_per_second") {
+            FindItem::Found(ItemKind::Float(rate)) => {
+                assert!((rate - 10.0).abs() < 0.5, "rate: {}", rate)
+            }
+            other => panic!("no rate found: {}", other),
+        }
+    }
+
+    #[test]
+    fn the_time_since_the_last_update_is_taken_from_the_clock() {
+        use crate::instruments::ManualOffsetClock;
+        use crate::snapshot::{FindItem, ItemKind};
+
+        let clock = ManualOffsetClock::default();
+        let mut counter = Counter::new("requests").updated_secs_ago_enabled(true);
+        counter.set_clock(clock.clone());
+
+        counter.update(&Update::Observation(clock.now()));
+        clock.advance_n_seconds(3);
+
+        let mut snapshot = Snapshot::default();
+        counter.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("_updated_secs_ago_requests"),
+            FindItem::Found(&ItemKind::Float(3.0))
+        );
+    }
+
+    #[test]
+    fn increments_beyond_the_limit_are_counted_as_rate_limited() {
+        use crate::instruments::ManualOffsetClock;
+        use crate::snapshot::{FindItem, ItemKind};
+
+        let clock = ManualOffsetClock::default();
+        let mut counter = Counter::new("requests").max_inc_per_sec(100);
+        counter.set_clock(clock.clone());
+
+        for _ in 0..1_000 {
+            counter.update(&Update::Observation(clock.now()));
+        }
+        assert_eq!(counter.get(), 100);
+        assert_eq!(counter.rate_limited_count(), 900);
+
+        clock.advance_millis(999);
+        counter.inc_by(50);
+        assert_eq!(counter.get(), 100);
+        assert_eq!(counter.rate_limited_count(), 950);
+
+        clock.advance_millis(1);
+        counter.inc_by(150);
+        assert_eq!(counter.get(), 200);
+        assert_eq!(counter.rate_limited_count(), 1_000);
+
+        counter.dec_by(10);
+        assert_eq!(counter.get(), 190);
+
+        let mut snapshot = Snapshot::default();
+        counter.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("requests_rate_limited_count"),
+            FindItem::Found(&ItemKind::UInt(1_000))
+        );
+    }
+
+    #[test]
+    fn concurrent_increments_are_not_lost() {
+        use std::sync::Arc;
+        use std::thread;
+
+        let counter = Arc::new(Counter::new("").rate_enabled(true));
+
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let counter = Arc::clone(&counter);
+                thread::spawn(move || {
+                    for _ in 0..10_000 {
+                        counter.inc_by_shared(1);
+                    }
+                    counter.inc_by_shared(5);
+                })
+            })
+            .collect();
+        threads.into_iter().for_each(|t| t.join().unwrap());
+
+        assert_eq!(counter.get(), 8 * 10_005);
+    }
+
+    #[test]
+    fn a_counter_can_be_used_like_a_number() {
+        let mut counter = Counter::new("");
+
+        counter += 5;
+        assert!(counter == 5);
+        assert!(counter != 4);
+
+        counter += 2;
+        assert!(counter > 6);
+        assert!(counter <= 7);
+        assert_eq!(counter.to_string(), "7");
+    }
+
+    #[test]
+    fn each_snapshot_reports_only_its_interval_when_reset_on_snapshot() {
+        use crate::snapshot::{FindItem, ItemKind};
+
+        let counter = Counter::new("count").reset_on_snapshot(true);
+
+        counter.inc_by_shared(3);
+        let mut snapshot = Snapshot::default();
+        counter.put_exported_snapshot(&mut snapshot, false);
+        assert_eq!(snapshot.find("count"), FindItem::Found(&ItemKind::UInt(3)));
+        assert_eq!(
+            snapshot.find("_type_count"),
+            FindItem::Found(&ItemKind::Text("gauge".to_string()))
+        );
+        assert_eq!(counter.get(), 0);
+
+        counter.inc_by_shared(2);
+        let mut snapshot = Snapshot::default();
+        counter.put_exported_snapshot(&mut snapshot, false);
+        assert_eq!(snapshot.find("count"), FindItem::Found(&ItemKind::UInt(2)));
+
+        let mut snapshot = Snapshot::default();
+        counter.put_exported_snapshot(&mut snapshot, false);
+        assert_eq!(snapshot.find("count"), FindItem::Found(&ItemKind::UInt(0)));
+    }
+
+    #[test]
+    fn only_exported_snapshots_reset_the_count() {
+        use crate::snapshot::{FindItem, ItemKind};
+
+        let counter = Counter::new("count").reset_on_snapshot(true);
+        counter.inc_by_shared(3);
+
+        let mut snapshot = Snapshot::default();
+        counter.put_snapshot(&mut snapshot, false);
+        assert_eq!(snapshot.find("count"), FindItem::Found(&ItemKind::UInt(3)));
+        assert_eq!(counter.get(), 3);
+
+        let mut snapshot = Snapshot::default();
+        counter.put_exported_snapshot(&mut snapshot, false);
+        assert_eq!(snapshot.find("count"), FindItem::Found(&ItemKind::UInt(3)));
+        assert_eq!(counter.get(), 0);
     }
 }
@@ -0,0 +1,368 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+
+use crate::instruments::fundamentals::hyper_log_log::HyperLogLog;
+use crate::instruments::fundamentals::{Clock, SharedClock};
+use crate::instruments::{
+    AcceptAllLabels, Instrument, InstrumentAdapter, LabelFilter, LabelPredicate, Update, Updates,
+};
+use crate::snapshot::{ItemKind, Snapshot};
+use crate::util;
+use crate::{Descriptive, ObservedValue, PutsSnapshot, TimeUnit};
+
+const DEFAULT_PRECISION: u8 = 12;
+
+/// Estimates how many distinct values were observed. E.g. the
+/// number of users making requests.
+///
+/// Reacts to the following `Observation`s:
+///
+/// * `Obervation::ObservedOneValue`(Update::ObservationWithValue)
+///
+/// The values are not stored. Instead a HyperLogLog sketch with
+/// `2^precision` registers is kept which has a relative error of about
+/// `1.04 / sqrt(2^precision)`. The default precision is 12 which
+/// needs 4KB and gives a relative error of about 1.6%.
+///
+/// Integers are distinct by their value regardless of whether they
+/// were observed signed or unsigned and durations by their length.
+/// `ObservedValue::ChangedBy` is ignored.
+///
+/// The estimate is put into a nested `Snapshot` as `estimated_distinct`.
+/// With a window it goes down again so it is then marked with a text
+/// `_type_estimated_distinct` which makes exporters treat it as a gauge.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Instant;
+/// use metrix::instruments::*;
+///
+/// let mut distinct = DistinctCounter::new("users");
+/// for user_id in &[1, 2, 1, 3, 2] {
+///     distinct.update(&Update::ObservationWithValue((*user_id).into(), Instant::now()));
+/// }
+///
+/// assert_eq!(distinct.estimate(), 3);
+/// ```
+pub struct DistinctCounter {
+    name: String,
+    title: Option<String>,
+    description: Option<String>,
+    sketch: HyperLogLog,
+    window: Option<Duration>,
+    window_started: Instant,
+    clock: SharedClock,
+}
+
+impl DistinctCounter {
+    pub fn new<T: Into<String>>(name: T) -> DistinctCounter {
+        Self::with_precision(name, DEFAULT_PRECISION)
+    }
+
+    /// Creates a `DistinctCounter` using `2^precision` registers.
+    ///
+    /// Each additional bit of precision doubles the memory and divides
+    /// the relative error by `sqrt(2)`.
+    ///
+    /// # Panics
+    ///
+    /// If `precision` is not within `4..=16`.
+    pub fn with_precision<T: Into<String>>(name: T, precision: u8) -> DistinctCounter {
+        let clock = SharedClock::default();
+        DistinctCounter {
+            name: name.into(),
+            title: None,
+            description: None,
+            sketch: HyperLogLog::new(precision),
+            window: None,
+            window_started: clock.now(),
+            clock,
+        }
+    }
+
+    pub fn new_with_defaults<T: Into<String>>(name: T) -> DistinctCounter {
+        Self::new(name)
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_name<T: Into<String>>(&mut self, name: T) {
+        self.name = name.into();
+    }
+
+    pub fn name<T: Into<String>>(mut self, name: T) -> Self {
+        self.set_name(name);
+        self
+    }
+
+    pub fn set_title<T: Into<String>>(&mut self, title: T) {
+        self.title = Some(title.into())
+    }
+
+    pub fn title<T: Into<String>>(mut self, title: T) -> Self {
+        self.set_title(title);
+        self
+    }
+
+    pub fn set_description<T: Into<String>>(&mut self, description: T) {
+        self.description = Some(description.into())
+    }
+
+    pub fn description<T: Into<String>>(mut self, description: T) -> Self {
+        self.set_description(description);
+        self
+    }
+
+    /// Only count the values observed within consecutive windows
+    /// of the given length.
+    ///
+    /// Once a window elapsed all values are forgotten and a
+    /// new window starts with the next observation.
+    ///
+    /// Default is to count all values since creation or the last reset.
+    pub fn set_window(&mut self, window: Duration) {
+        self.window = Some(window);
+        self.window_started = self.clock.now();
+    }
+
+    /// Only count the values observed within consecutive windows
+    /// of the given length.
+    ///
+    /// Default is to count all values since creation or the last reset.
+    pub fn window(mut self, window: Duration) -> Self {
+        self.set_window(window);
+        self
+    }
+
+    /// Sets the `Clock` used for the windows.
+    ///
+    /// All values observed so far are discarded so this should be done
+    /// right after creation. Mostly useful for testing with
+    /// a `ManualOffsetClock`.
+    ///
+    /// Default is the `WallClock`.
+    pub fn set_clock<C: Clock + Send + Sync + 'static>(&mut self, clock: C) {
+        self.clock = SharedClock::new(clock);
+        self.reset();
+    }
+
+    /// Sets the `Clock` used for the windows.
+    ///
+    /// Default is the `WallClock`.
+    pub fn clock<C: Clock + Send + Sync + 'static>(mut self, clock: C) -> Self {
+        self.set_clock(clock);
+        self
+    }
+
+    /// Returns the precision in bits
+    pub fn get_precision(&self) -> u8 {
+        self.sketch.precision()
+    }
+
+    /// Returns the expected relative error of the estimate
+    pub fn relative_error(&self) -> f64 {
+        self.sketch.relative_error()
+    }
+
+    /// Returns the estimated number of distinct values
+    pub fn estimate(&self) -> u64 {
+        if self.window_elapsed() {
+            0
+        } else {
+            self.sketch.estimate()
+        }
+    }
+
+    pub fn accept<L: Eq + Send + 'static, F: Into<LabelFilter<L>>>(
+        self,
+        accept: F,
+    ) -> InstrumentAdapter<L, Self> {
+        InstrumentAdapter::accept(accept, self)
+    }
+
+    /// Creates an `InstrumentAdapter` that makes this instrument
+    /// react on observations on the given label.
+    pub fn for_label<L: Eq + Send + 'static>(self, label: L) -> InstrumentAdapter<L, Self> {
+        self.accept(label)
+    }
+
+    /// Creates an `InstrumentAdapter` that makes this instrument
+    /// react on observations with the given labels.
+    ///
+    /// If `labels` is empty the instrument will not react to any observations
+    pub fn for_labels<L: Eq + Send + 'static>(self, labels: Vec<L>) -> InstrumentAdapter<L, Self> {
+        self.accept(labels)
+    }
+
+    /// Creates an `InstrumentAdapter` that makes this instrument react on
+    /// all observations.
+    pub fn for_all_labels<L: Eq + Send + 'static>(self) -> InstrumentAdapter<L, Self> {
+        self.accept(AcceptAllLabels)
+    }
+
+    /// Creates an `InstrumentAdapter` that makes this instrument react on
+    /// observations with labels specified by the predicate.
+    pub fn for_labels_by_predicate<L, P>(self, label_predicate: P) -> InstrumentAdapter<L, Self>
+    where
+        L: Eq + Send + 'static,
+        P: Fn(&L) -> bool + Send + 'static,
+    {
+        self.accept(LabelPredicate(label_predicate))
+    }
+
+    /// Creates an `InstrumentAdapter` that makes this instrument to no
+    /// observations.
+    pub fn adapter<L: Eq + Send + 'static>(self) -> InstrumentAdapter<L, Self> {
+        InstrumentAdapter::deaf(self)
+    }
+
+    fn window_elapsed(&self) -> bool {
+        self.window
+            .map(|window| self.clock.now() - self.window_started >= window)
+            .unwrap_or(false)
+    }
+}
+
+impl Instrument for DistinctCounter {
+    fn name(&self) -> Option<&str> {
+        Some(self.get_name())
+    }
+
+    fn reset(&mut self) {
+        self.sketch.clear();
+        self.window_started = self.clock.now();
+    }
+}
+
+impl PutsSnapshot for DistinctCounter {
+    fn put_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
+        util::put_postfixed_descriptives(self, &self.name, into, descriptive);
+        let mut new_level = Snapshot::default();
+        new_level.push("estimated_distinct", ItemKind::UInt(self.estimate()));
+        if self.window.is_some() {
+            util::put_gauge_type("estimated_distinct", &mut new_level);
+        }
+        into.push(self.name.clone(), ItemKind::Snapshot(new_level));
+    }
+}
+
+impl Updates for DistinctCounter {
+    fn update(&mut self, with: &Update) -> usize {
+        match *with {
+            Update::ObservationWithValue(ref value, _)
+            | Update::ObservationsWithValue(ref value, _, _) => {
+                if let Some(hash) = hash_value(value) {
+                    if self.window_elapsed() {
+                        self.reset();
+                    }
+                    self.sketch.update(hash);
+                    1
+                } else {
+                    0
+                }
+            }
+            _ => 0,
+        }
+    }
+}
+
+impl Descriptive for DistinctCounter {
+    fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+}
+
+fn hash_value(value: &ObservedValue) -> Option<u64> {
+    let mut hasher = DefaultHasher::new();
+    match *value {
+        ObservedValue::SignedInteger(v) => i128::from(v).hash(&mut hasher),
+        ObservedValue::UnsignedInteger(v) => i128::from(v).hash(&mut hasher),
+        ObservedValue::Float(v) => v.to_bits().hash(&mut hasher),
+        ObservedValue::Bool(v) => v.hash(&mut hasher),
+        ObservedValue::Duration(time, unit) => {
+            super::duration_to_display_value(time, unit, TimeUnit::Nanoseconds).hash(&mut hasher)
+        }
+        ObservedValue::ChangedBy(_) => return None,
+    }
+    Some(hasher.finish())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::instruments::ManualOffsetClock;
+    use crate::snapshot::FindItem;
+
+    use super::*;
+
+    #[test]
+    fn estimate_is_within_the_error_bound() {
+        let mut distinct = DistinctCounter::new("users");
+
+        for _ in 0..3 {
+            for user_id in 0..20_000u64 {
+                distinct.update(&Update::ObservationWithValue(
+                    user_id.into(),
+                    Instant::now(),
+                ));
+            }
+        }
+
+        let expected = 20_000.0;
+        let error = (distinct.estimate() as f64 - expected).abs() / expected;
+        assert!(
+            error < 3.0 * distinct.relative_error(),
+            "estimate {} is off by {}",
+            distinct.estimate(),
+            error
+        );
+
+        let mut snapshot = Snapshot::default();
+        distinct.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("users/estimated_distinct"),
+            FindItem::Found(&ItemKind::UInt(distinct.estimate()))
+        );
+    }
+
+    #[test]
+    fn signed_and_unsigned_integers_are_the_same() {
+        let mut distinct = DistinctCounter::with_precision("", 8);
+
+        distinct.update(&Update::ObservationWithValue(5i64.into(), Instant::now()));
+        distinct.update(&Update::ObservationWithValue(5u64.into(), Instant::now()));
+        distinct.update(&Update::ObservationWithValue(6u64.into(), Instant::now()));
+
+        assert_eq!(distinct.estimate(), 2);
+
+        distinct.reset();
+
+        assert_eq!(distinct.estimate(), 0);
+    }
+
+    #[test]
+    fn windowed_counter_forgets_old_values() {
+        let clock = ManualOffsetClock::default();
+        let mut distinct = DistinctCounter::new("")
+            .clock(clock.clone())
+            .window(Duration::from_secs(5));
+
+        distinct.update(&Update::ObservationWithValue(1.into(), clock.now()));
+        distinct.update(&Update::ObservationWithValue(2.into(), clock.now()));
+        clock.advance_n_seconds(4);
+        assert_eq!(distinct.estimate(), 2);
+
+        clock.advance_a_second();
+        assert_eq!(distinct.estimate(), 0);
+
+        distinct.update(&Update::ObservationWithValue(3.into(), clock.now()));
+        assert_eq!(distinct.estimate(), 1);
+    }
+}
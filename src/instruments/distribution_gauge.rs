@@ -0,0 +1,369 @@
+use std::collections::VecDeque;
+
+use crate::instruments::{
+    AcceptAllLabels, Instrument, InstrumentAdapter, LabelFilter, LabelPredicate, Update, Updates,
+};
+use crate::snapshot::{ItemKind, Snapshot};
+use crate::util;
+use crate::{Descriptive, ObservedValue, PutsSnapshot, TimeUnit};
+
+const DEFAULT_CAPACITY: usize = 256;
+
+/// A gauge which keeps the most recent values to show their distribution
+///
+/// Unlike a `Gauge` which only shows the last value this is meant for
+/// slowly changing levels like the length of a queue where the spread
+/// of the values is of interest. Unlike a `Histogram` which records
+/// events, e.g. latencies, the values are the states of the level.
+///
+/// Keeps the last `capacity` values. The default capacity is 256.
+///
+/// Reacts to `Observation::ObservedOneValue`(Update::ObservationWithValue)
+/// with all values that can be converted to an `i64`. Durations are
+/// converted to the `display_time_unit`. `ObservedValue::ChangedBy`
+/// changes the last value. An `Observation::ObservedValues` is kept
+/// once like a single observation.
+///
+/// The `Snapshot` contains the number of values kept as `count`. Once a
+/// value was observed it also contains `min`, `max`, `mean`, `p50` and `p95`.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Instant;
+/// use metrix::instruments::*;
+///
+/// let mut gauge = DistributionGauge::new("queue_length");
+/// for v in 1..=100 {
+///     gauge.update(&Update::ObservationWithValue(v.into(), Instant::now()));
+/// }
+///
+/// assert_eq!(Some(50), gauge.quantile(0.5));
+/// assert_eq!(Some(95), gauge.quantile(0.95));
+/// ```
+pub struct DistributionGauge {
+    name: String,
+    title: Option<String>,
+    description: Option<String>,
+    capacity: usize,
+    values: VecDeque<i64>,
+    display_time_unit: Option<TimeUnit>,
+}
+
+impl DistributionGauge {
+    pub fn new<T: Into<String>>(name: T) -> DistributionGauge {
+        Self::with_capacity(name, DEFAULT_CAPACITY)
+    }
+
+    /// Creates a `DistributionGauge` keeping the last `capacity` values.
+    ///
+    /// # Panics
+    ///
+    /// If `capacity` is 0.
+    pub fn with_capacity<T: Into<String>>(name: T, capacity: usize) -> DistributionGauge {
+        if capacity == 0 {
+            panic!("the capacity must be at least 1");
+        }
+
+        DistributionGauge {
+            name: name.into(),
+            title: None,
+            description: None,
+            capacity,
+            values: VecDeque::with_capacity(capacity),
+            display_time_unit: None,
+        }
+    }
+
+    pub fn new_with_defaults<T: Into<String>>(name: T) -> DistributionGauge {
+        Self::new(name)
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_name<T: Into<String>>(&mut self, name: T) {
+        self.name = name.into();
+    }
+
+    pub fn name<T: Into<String>>(mut self, name: T) -> Self {
+        self.set_name(name);
+        self
+    }
+
+    pub fn set_title<T: Into<String>>(&mut self, title: T) {
+        self.title = Some(title.into())
+    }
+
+    pub fn title<T: Into<String>>(mut self, title: T) -> Self {
+        self.set_title(title);
+        self
+    }
+
+    pub fn set_description<T: Into<String>>(&mut self, description: T) {
+        self.description = Some(description.into())
+    }
+
+    pub fn description<T: Into<String>>(mut self, description: T) -> Self {
+        self.set_description(description);
+        self
+    }
+
+    /// Sets the unit durations will be converted to.
+    ///
+    /// If set, the unit will be part of a descriptive `Snapshot`
+    /// as `_unit_[name]`.
+    ///
+    /// Default is `TimeUnit::Microseconds`.
+    pub fn set_display_time_unit(&mut self, display_time_unit: TimeUnit) {
+        self.display_time_unit = Some(display_time_unit)
+    }
+
+    /// Sets the unit durations will be converted to.
+    ///
+    /// If set, the unit will be part of a descriptive `Snapshot`
+    /// as `_unit_[name]`.
+    ///
+    /// Default is `TimeUnit::Microseconds`.
+    pub fn display_time_unit(mut self, display_time_unit: TimeUnit) -> Self {
+        self.set_display_time_unit(display_time_unit);
+        self
+    }
+
+    /// Returns the number of values kept at most
+    pub fn get_capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Sets the value dropping the oldest value if the capacity is reached
+    pub fn set(&mut self, value: i64) {
+        if self.values.len() == self.capacity {
+            self.values.pop_front();
+        }
+        self.values.push_back(value);
+    }
+
+    /// The last value
+    pub fn get(&self) -> Option<i64> {
+        self.values.back().copied()
+    }
+
+    /// The number of values kept
+    pub fn count(&self) -> usize {
+        self.values.len()
+    }
+
+    /// The smallest of the values kept
+    pub fn min(&self) -> Option<i64> {
+        self.values.iter().min().copied()
+    }
+
+    /// The biggest of the values kept
+    pub fn max(&self) -> Option<i64> {
+        self.values.iter().max().copied()
+    }
+
+    /// The average of the values kept
+    pub fn mean(&self) -> Option<f64> {
+        if self.values.is_empty() {
+            None
+        } else {
+            let sum: f64 = self.values.iter().map(|&v| v as f64).sum();
+            Some(sum / self.values.len() as f64)
+        }
+    }
+
+    /// The smallest of the values kept so that at least the
+    /// given share of the values is not bigger (nearest rank).
+    ///
+    /// `quantile` is clamped to `0.0..=1.0`.
+    pub fn quantile(&self, quantile: f64) -> Option<i64> {
+        let mut sorted: Vec<i64> = self.values.iter().copied().collect();
+        sorted.sort_unstable();
+        quantile_of_sorted(&sorted, quantile)
+    }
+
+    pub fn accept<L: Eq + Send + 'static, F: Into<LabelFilter<L>>>(
+        self,
+        accept: F,
+    ) -> InstrumentAdapter<L, Self> {
+        InstrumentAdapter::accept(accept, self)
+    }
+
+    /// Creates an `InstrumentAdapter` that makes this instrument
+    /// react on observations on the given label.
+    pub fn for_label<L: Eq + Send + 'static>(self, label: L) -> InstrumentAdapter<L, Self> {
+        self.accept(label)
+    }
+
+    /// Creates an `InstrumentAdapter` that makes this instrument
+    /// react on observations with the given labels.
+    ///
+    /// If `labels` is empty the instrument will not react to any observations
+    pub fn for_labels<L: Eq + Send + 'static>(self, labels: Vec<L>) -> InstrumentAdapter<L, Self> {
+        self.accept(labels)
+    }
+
+    /// Creates an `InstrumentAdapter` that makes this instrument react on
+    /// all observations.
+    pub fn for_all_labels<L: Eq + Send + 'static>(self) -> InstrumentAdapter<L, Self> {
+        self.accept(AcceptAllLabels)
+    }
+
+    /// Creates an `InstrumentAdapter` that makes this instrument react on
+    /// observations with labels specified by the predicate.
+    pub fn for_labels_by_predicate<L, P>(self, label_predicate: P) -> InstrumentAdapter<L, Self>
+    where
+        L: Eq + Send + 'static,
+        P: Fn(&L) -> bool + Send + 'static,
+    {
+        self.accept(LabelPredicate(label_predicate))
+    }
+
+    /// Creates an `InstrumentAdapter` that makes this instrument to no
+    /// observations.
+    pub fn adapter<L: Eq + Send + 'static>(self) -> InstrumentAdapter<L, Self> {
+        InstrumentAdapter::deaf(self)
+    }
+}
+
+fn quantile_of_sorted(sorted: &[i64], quantile: f64) -> Option<i64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let quantile = quantile.clamp(0.0, 1.0);
+    let rank = (quantile * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.max(1) - 1])
+}
+
+impl Instrument for DistributionGauge {
+    fn name(&self) -> Option<&str> {
+        Some(self.get_name())
+    }
+
+    fn reset(&mut self) {
+        self.values.clear();
+    }
+}
+
+impl PutsSnapshot for DistributionGauge {
+    fn put_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
+        util::put_postfixed_descriptives(self, &self.name, into, descriptive);
+        util::put_postfixed_time_unit(self.display_time_unit, &self.name, into, descriptive);
+        let mut new_level = Snapshot::default();
+        new_level.push("count", self.values.len().into());
+        let mut sorted: Vec<i64> = self.values.iter().copied().collect();
+        sorted.sort_unstable();
+        if let (Some(&min), Some(&max), Some(mean)) = (sorted.first(), sorted.last(), self.mean()) {
+            new_level.push("min", min.into());
+            new_level.push("max", max.into());
+            new_level.push("mean", mean.into());
+        }
+        if let Some(p50) = quantile_of_sorted(&sorted, 0.5) {
+            new_level.push("p50", p50.into());
+        }
+        if let Some(p95) = quantile_of_sorted(&sorted, 0.95) {
+            new_level.push("p95", p95.into());
+        }
+        into.push(self.name.clone(), ItemKind::Snapshot(new_level));
+    }
+}
+
+impl Updates for DistributionGauge {
+    fn update(&mut self, with: &Update) -> usize {
+        match *with {
+            Update::ObservationWithValue(ObservedValue::Duration(time, time_unit), _) => {
+                let d = super::duration_to_display_value(
+                    time,
+                    time_unit,
+                    self.display_time_unit.unwrap_or_default(),
+                );
+                self.set(d as i64);
+                1
+            }
+            Update::ObservationWithValue(ObservedValue::ChangedBy(delta), _) => {
+                let value = self.get().unwrap_or(0).saturating_add(delta);
+                self.set(value);
+                1
+            }
+            Update::ObservationWithValue(v, _) => {
+                if let Some(v) = v.convert_to_i64() {
+                    self.set(v);
+                    1
+                } else {
+                    0
+                }
+            }
+            Update::ObservationsWithValue(v, n, timestamp) => self.update(
+                &Update::ObservationWithValue(super::gauge::repeated(v, n), timestamp),
+            ),
+            _ => 0,
+        }
+    }
+}
+
+impl Descriptive for DistributionGauge {
+    fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::snapshot::FindItem;
+
+    #[test]
+    fn empty() {
+        let gauge = DistributionGauge::new("gauge");
+
+        assert_eq!(gauge.get(), None);
+        assert_eq!(gauge.quantile(0.5), None);
+
+        let mut snapshot = Snapshot::default();
+        gauge.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("gauge/count"),
+            FindItem::Found(&ItemKind::UInt(0))
+        );
+        assert_eq!(snapshot.find("gauge/p50"), FindItem::NotFound);
+    }
+
+    #[test]
+    fn reports_the_distribution_of_recent_values() {
+        let mut gauge = DistributionGauge::with_capacity("gauge", 100);
+
+        for v in 1_000..1_050 {
+            gauge.update(&Update::ObservationWithValue(v.into(), Instant::now()));
+        }
+        for v in (1..=100).rev() {
+            gauge.update(&Update::ObservationWithValue(v.into(), Instant::now()));
+        }
+
+        let mut snapshot = Snapshot::default();
+        gauge.put_snapshot(&mut snapshot, false);
+        let expect = |path: &str, item: ItemKind| {
+            assert_eq!(snapshot.find(path), FindItem::Found(&item), "{}", path);
+        };
+        expect("gauge/count", ItemKind::UInt(100));
+        expect("gauge/min", ItemKind::Int(1));
+        expect("gauge/max", ItemKind::Int(100));
+        expect("gauge/mean", ItemKind::Float(50.5));
+        expect("gauge/p50", ItemKind::Int(50));
+        expect("gauge/p95", ItemKind::Int(95));
+
+        gauge.update(&Update::ObservationWithValue(
+            ObservedValue::ChangedBy(-1),
+            Instant::now(),
+        ));
+        assert_eq!(gauge.get(), Some(0));
+        assert_eq!(gauge.min(), Some(0));
+    }
+}
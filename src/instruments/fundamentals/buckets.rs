@@ -12,15 +12,6 @@ pub struct SecondsBuckets<T, C = WallClock> {
     current_idx: usize,
 }
 
-impl<T> SecondsBuckets<T, WallClock>
-where
-    T: Default,
-{
-    pub fn new(for_seconds: usize) -> Self {
-        Self::with_clock(for_seconds, WallClock)
-    }
-}
-
 impl<T, C> SecondsBuckets<T, C>
 where
     T: Default,
@@ -47,6 +38,13 @@ where
         }
     }
 
+    /// Replaces all buckets with their default
+    pub fn reset(&mut self) {
+        self.buckets.iter_mut().for_each(|b| *b = T::default());
+        self.current_idx = self.buckets.len() - 1;
+        self.current_time = self.clock.now();
+    }
+
     pub fn current_mut(&mut self) -> &mut T {
         self.tick();
         &mut self.buckets[self.current_idx]
@@ -1,9 +1,16 @@
+use std::fmt;
+use std::sync::Arc;
 use std::time::Instant;
 
+/// A source of the current time
+///
+/// Components that measure time can be given a `Clock`
+/// so that tests can control the passing of time.
 pub trait Clock {
     fn now(&self) -> Instant;
 }
 
+/// The monotonic system clock. This is the default.
 #[derive(Debug, Clone, Copy)]
 pub struct WallClock;
 
@@ -14,14 +21,42 @@ impl Clock for WallClock {
     }
 }
 
-#[cfg(test)]
+/// A `Clock` that can be shared between components
+#[derive(Clone)]
+pub(crate) struct SharedClock(Arc<dyn Clock + Send + Sync>);
+
+impl SharedClock {
+    pub fn new<C: Clock + Send + Sync + 'static>(clock: C) -> Self {
+        SharedClock(Arc::new(clock))
+    }
+}
+
+impl Clock for SharedClock {
+    #[inline]
+    fn now(&self) -> Instant {
+        self.0.now()
+    }
+}
+
+impl Default for SharedClock {
+    fn default() -> Self {
+        SharedClock::new(WallClock)
+    }
+}
+
+impl fmt::Debug for SharedClock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("SharedClock")
+    }
+}
+
 pub mod manual_clock {
     use std::sync::{Arc, Mutex};
     use std::time::{Duration, Instant};
 
     use super::Clock;
 
-    /// A clock for testing
+    /// A clock for testing which only advances when told to
     ///
     /// Clones will share the offset
     #[derive(Debug, Clone)]
@@ -0,0 +1,306 @@
+//! A reservoir sample favouring recent values
+//!
+//! Values are sampled with Vitter's algorithm for weighted reservoirs
+//! where the weight grows exponentially with the time since a landmark
+//! (forward decay). A value is kept if its priority `weight / u` with
+//! `u` uniform in `(0, 1]` is among the `size` highest priorities. Since
+//! the weights would overflow eventually the landmark is moved forward
+//! every hour and all weights are rescaled.
+use std::cmp::{Ordering, Reverse};
+use std::collections::hash_map::RandomState;
+use std::collections::BinaryHeap;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, Instant};
+
+use super::{Clock, WallClock};
+
+const RESCALE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Keeps a fixed size sample of the observed values where
+/// recent values are more likely to be kept.
+pub struct DecayingReservoir<C = WallClock> {
+    size: usize,
+    alpha: f64,
+    count: u64,
+    samples: BinaryHeap<Reverse<Sample>>,
+    landmark: Instant,
+    next_rescale: Instant,
+    clock: C,
+    random: XorShift,
+}
+
+impl<C> DecayingReservoir<C>
+where
+    C: Clock,
+{
+    /// Creates a reservoir keeping `size` samples where the weight of a value
+    /// grows by `e^alpha` per second.
+    ///
+    /// # Panics
+    ///
+    /// If `size` is 0 or `alpha` is not positive.
+    pub fn with_clock(size: usize, alpha: f64, clock: C) -> Self {
+        if size == 0 {
+            panic!("the reservoir size must be at least 1");
+        }
+        if alpha <= 0.0 || !alpha.is_finite() {
+            panic!("alpha must be positive");
+        }
+
+        let now = clock.now();
+        DecayingReservoir {
+            size,
+            alpha,
+            count: 0,
+            samples: BinaryHeap::with_capacity(size),
+            landmark: now,
+            next_rescale: now + RESCALE_INTERVAL,
+            clock,
+            random: XorShift::new(),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn update(&mut self, value: i64) {
+        self.update_n(value, 1)
+    }
+
+    pub fn update_n(&mut self, value: i64, n: u64) {
+        let now = self.clock.now();
+        self.update_n_at(now, value, n)
+    }
+
+    /// Records `value` as observed `n` times at `timestamp`.
+    ///
+    /// All `n` occurrences are counted but at most `size` of them are
+    /// sampled since more copies could not be kept anyway.
+    pub fn update_n_at(&mut self, timestamp: Instant, value: i64, n: u64) {
+        self.rescale_if_needed();
+        self.count = self.count.saturating_add(n);
+        let copies = n.min(self.size as u64);
+        (0..copies).for_each(|_| self.sample(timestamp, value));
+    }
+
+    fn sample(&mut self, timestamp: Instant, value: i64) {
+        let age = timestamp
+            .saturating_duration_since(self.landmark)
+            .as_secs_f64();
+        let weight = (self.alpha * age).exp();
+        let priority = weight / self.random.next_f64();
+        let sample = Sample {
+            priority,
+            weight,
+            value,
+        };
+
+        if self.samples.len() < self.size {
+            self.samples.push(Reverse(sample));
+        } else if let Some(mut lowest) = self.samples.peek_mut() {
+            if lowest.0.priority < priority {
+                *lowest = Reverse(sample);
+            }
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn alpha(&self) -> f64 {
+        self.alpha
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+        self.count = 0;
+        self.landmark = self.clock.now();
+        self.next_rescale = self.landmark + RESCALE_INTERVAL;
+    }
+
+    /// Creates a snapshot of the sampled values
+    pub fn snapshot(&self) -> ReservoirSnapshot {
+        let total_weight: f64 = self.samples.iter().map(|s| s.0.weight).sum();
+        let mut values: Vec<(i64, f64)> = self
+            .samples
+            .iter()
+            .map(|s| (s.0.value, s.0.weight / total_weight))
+            .collect();
+        values.sort_unstable_by_key(|&(value, _)| value);
+        ReservoirSnapshot {
+            values,
+            count: self.count,
+        }
+    }
+
+    fn rescale_if_needed(&mut self) {
+        let now = self.clock.now();
+        if now < self.next_rescale {
+            return;
+        }
+
+        let factor = (-self.alpha * (now - self.landmark).as_secs_f64()).exp();
+        self.samples = self
+            .samples
+            .drain()
+            .map(|Reverse(sample)| {
+                Reverse(Sample {
+                    priority: sample.priority * factor,
+                    weight: sample.weight * factor,
+                    value: sample.value,
+                })
+            })
+            .collect();
+        self.landmark = now;
+        self.next_rescale = now + RESCALE_INTERVAL;
+    }
+}
+
+struct Sample {
+    priority: f64,
+    weight: f64,
+    value: i64,
+}
+
+impl PartialEq for Sample {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Sample {}
+
+impl PartialOrd for Sample {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Sample {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority.total_cmp(&other.priority)
+    }
+}
+
+/// The sampled values of a `DecayingReservoir` sorted
+/// by value with their normalized weights
+pub struct ReservoirSnapshot {
+    values: Vec<(i64, f64)>,
+    count: u64,
+}
+
+impl ReservoirSnapshot {
+    /// The number of all values observed, not only the sampled ones
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the value at the given quantile of the weighted samples.
+    pub fn value(&self, quantile: f64) -> i64 {
+        let mut seen = 0.0;
+        for &(value, weight) in &self.values {
+            seen += weight;
+            if seen >= quantile {
+                return value;
+            }
+        }
+        self.max()
+    }
+
+    pub fn min(&self) -> i64 {
+        self.values.first().map(|&(v, _)| v).unwrap_or(0)
+    }
+
+    pub fn max(&self) -> i64 {
+        self.values.last().map(|&(v, _)| v).unwrap_or(0)
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.values.iter().map(|&(v, w)| v as f64 * w).sum()
+    }
+
+    pub fn stddev(&self) -> f64 {
+        if self.values.len() < 2 {
+            return 0.0;
+        }
+        let mean = self.mean();
+        let variance: f64 = self
+            .values
+            .iter()
+            .map(|&(v, w)| w * (v as f64 - mean) * (v as f64 - mean))
+            .sum();
+        variance.sqrt()
+    }
+}
+
+/// A xorshift64* generator which is good enough for sampling
+struct XorShift(u64);
+
+impl XorShift {
+    fn new() -> Self {
+        let seed = RandomState::new().build_hasher().finish();
+        // The state must never be zero
+        XorShift(seed | 1)
+    }
+
+    /// Returns a number within `(0, 1]`
+    fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        let bits = self.0.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 11;
+        (bits + 1) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::instruments::fundamentals::ManualOffsetClock;
+
+    use super::*;
+
+    #[test]
+    fn empty() {
+        let reservoir = DecayingReservoir::with_clock(10, 0.015, WallClock);
+
+        let snapshot = reservoir.snapshot();
+        assert_eq!(snapshot.count(), 0);
+        assert_eq!(snapshot.value(0.5), 0);
+        assert_eq!(snapshot.max(), 0);
+    }
+
+    #[test]
+    fn the_median_follows_a_distribution_shift() {
+        let clock = ManualOffsetClock::default();
+        let mut reservoir = DecayingReservoir::with_clock(100, 0.015, clock.clone());
+
+        for _ in 0..1_000 {
+            reservoir.update(10);
+        }
+        assert_eq!(reservoir.snapshot().value(0.5), 10);
+
+        clock.advance_n_seconds(300);
+        for _ in 0..200 {
+            reservoir.update(1_000);
+        }
+
+        let snapshot = reservoir.snapshot();
+        assert_eq!(snapshot.count(), 1_200);
+        assert_eq!(snapshot.value(0.5), 1_000);
+    }
+
+    #[test]
+    fn rescaling_keeps_the_sample() {
+        let clock = ManualOffsetClock::default();
+        let mut reservoir = DecayingReservoir::with_clock(10, 0.015, clock.clone());
+
+        for v in 1..=10 {
+            reservoir.update(v);
+        }
+        clock.advance_n_seconds(2 * 60 * 60);
+        reservoir.update(11);
+
+        let snapshot = reservoir.snapshot();
+        assert_eq!(snapshot.max(), 11);
+        assert!(snapshot.values.iter().all(|&(_, w)| w.is_finite()));
+    }
+}
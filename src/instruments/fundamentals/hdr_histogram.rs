@@ -0,0 +1,264 @@
+//! A histogram with logarithmic buckets as known from HdrHistogram
+//!
+//! Values are recorded into buckets whose width doubles with each
+//! power of two while every bucket is split into enough sub buckets
+//! to keep the relative error within the given number of
+//! significant decimal digits.
+
+/// Records non negative values up to a maximum trackable value
+/// with a fixed relative precision.
+#[derive(Clone)]
+pub struct HdrHistogram {
+    max_value: u64,
+    significant_digits: u8,
+    sub_bucket_half_count_magnitude: u32,
+    sub_bucket_half_count: usize,
+    sub_bucket_mask: u64,
+    counts: Vec<u64>,
+    total_count: u64,
+    min: u64,
+    max: u64,
+    sum: f64,
+    sum_of_squares: f64,
+}
+
+impl HdrHistogram {
+    /// Creates a new `HdrHistogram` tracking values from 0 up to `max_value`
+    /// with `significant_digits` decimal digits of precision.
+    ///
+    /// # Panics
+    ///
+    /// If `significant_digits` is not within `1..=5` or `max_value` is
+    /// smaller than 2.
+    pub fn new(max_value: u64, significant_digits: u8) -> Self {
+        if !(1..=5).contains(&significant_digits) {
+            panic!("significant digits must be within 1..=5");
+        }
+        if max_value < 2 {
+            panic!("max value must be at least 2");
+        }
+
+        let largest_value_with_single_unit_resolution = 2 * 10u64.pow(significant_digits.into());
+        let sub_bucket_count_magnitude =
+            64 - (largest_value_with_single_unit_resolution - 1).leading_zeros();
+        let sub_bucket_half_count_magnitude = sub_bucket_count_magnitude - 1;
+        let sub_bucket_count = 1u64 << sub_bucket_count_magnitude;
+        let sub_bucket_half_count = (sub_bucket_count / 2) as usize;
+
+        let mut smallest_untrackable_value = sub_bucket_count;
+        let mut bucket_count = 1;
+        while smallest_untrackable_value <= max_value {
+            if smallest_untrackable_value > u64::MAX / 2 {
+                bucket_count += 1;
+                break;
+            }
+            smallest_untrackable_value <<= 1;
+            bucket_count += 1;
+        }
+
+        HdrHistogram {
+            max_value,
+            significant_digits,
+            sub_bucket_half_count_magnitude,
+            sub_bucket_half_count,
+            sub_bucket_mask: sub_bucket_count - 1,
+            counts: vec![0; (bucket_count + 1) * sub_bucket_half_count],
+            total_count: 0,
+            min: u64::MAX,
+            max: 0,
+            sum: 0.0,
+            sum_of_squares: 0.0,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn update(&mut self, value: i64) {
+        self.update_n(value, 1)
+    }
+
+    /// Records a value `n` times.
+    ///
+    /// Negative values are recorded as 0 and values
+    /// greater than the maximum trackable value as the
+    /// maximum trackable value.
+    pub fn update_n(&mut self, value: i64, n: u64) {
+        if n == 0 {
+            return;
+        }
+        let value = if value < 0 {
+            0
+        } else {
+            std::cmp::min(value as u64, self.max_value)
+        };
+
+        let idx = self.counts_index(value);
+        self.counts[idx] += n;
+        self.total_count += n;
+        self.min = std::cmp::min(self.min, value);
+        self.max = std::cmp::max(self.max, value);
+        self.sum += value as f64 * n as f64;
+        self.sum_of_squares += (value as f64) * (value as f64) * n as f64;
+    }
+
+    /// Removes all recorded values
+    pub fn clear(&mut self) {
+        self.counts.iter_mut().for_each(|c| *c = 0);
+        self.total_count = 0;
+        self.min = u64::MAX;
+        self.max = 0;
+        self.sum = 0.0;
+        self.sum_of_squares = 0.0;
+    }
+
+    pub fn max_value(&self) -> u64 {
+        self.max_value
+    }
+
+    pub fn significant_digits(&self) -> u8 {
+        self.significant_digits
+    }
+
+    pub fn count(&self) -> u64 {
+        self.total_count
+    }
+
+    pub fn min(&self) -> i64 {
+        if self.total_count == 0 {
+            0
+        } else {
+            self.min as i64
+        }
+    }
+
+    pub fn max(&self) -> i64 {
+        self.max as i64
+    }
+
+    pub fn mean(&self) -> f64 {
+        if self.total_count == 0 {
+            0.0
+        } else {
+            self.sum / self.total_count as f64
+        }
+    }
+
+    pub fn stddev(&self) -> f64 {
+        if self.total_count == 0 {
+            return 0.0;
+        }
+        let mean = self.mean();
+        let variance = self.sum_of_squares / self.total_count as f64 - mean * mean;
+        if variance > 0.0 {
+            variance.sqrt()
+        } else {
+            0.0
+        }
+    }
+
+    /// Returns the value at the given quantile which must be within `0.0..=1.0`.
+    ///
+    /// The returned value is the highest value that is equivalent to the
+    /// recorded values within the precision of this histogram but never
+    /// greater than the maximum value recorded.
+    pub fn value(&self, quantile: f64) -> i64 {
+        if self.total_count == 0 {
+            return 0;
+        }
+
+        let count_at_quantile =
+            std::cmp::max(1, (quantile * self.total_count as f64).ceil() as u64);
+
+        let mut seen = 0;
+        for (idx, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= count_at_quantile {
+                let value = self.highest_equivalent_value(self.value_from_index(idx));
+                return std::cmp::min(value, self.max) as i64;
+            }
+        }
+
+        self.max as i64
+    }
+
+    fn bucket_index(&self, value: u64) -> u32 {
+        let pow2_ceiling = 64 - (value | self.sub_bucket_mask).leading_zeros();
+        pow2_ceiling - (self.sub_bucket_half_count_magnitude + 1)
+    }
+
+    fn counts_index(&self, value: u64) -> usize {
+        let bucket_index = self.bucket_index(value);
+        let sub_bucket_index = (value >> bucket_index) as usize;
+        let bucket_base_index = (bucket_index as usize + 1) << self.sub_bucket_half_count_magnitude;
+        bucket_base_index + sub_bucket_index - self.sub_bucket_half_count
+    }
+
+    fn value_from_index(&self, index: usize) -> u64 {
+        let mut bucket_index = (index >> self.sub_bucket_half_count_magnitude) as i64 - 1;
+        let mut sub_bucket_index =
+            (index & (self.sub_bucket_half_count - 1)) + self.sub_bucket_half_count;
+        if bucket_index < 0 {
+            sub_bucket_index -= self.sub_bucket_half_count;
+            bucket_index = 0;
+        }
+        (sub_bucket_index as u64) << bucket_index
+    }
+
+    fn highest_equivalent_value(&self, value: u64) -> u64 {
+        let bucket_index = self.bucket_index(value);
+        let lowest_equivalent_value = (value >> bucket_index) << bucket_index;
+        lowest_equivalent_value + (1u64 << bucket_index) - 1
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn empty() {
+        let histogram = HdrHistogram::new(1_000, 3);
+
+        assert_eq!(histogram.count(), 0);
+        assert_eq!(histogram.value(0.5), 0);
+        assert_eq!(histogram.min(), 0);
+        assert_eq!(histogram.max(), 0);
+    }
+
+    #[test]
+    fn small_values_are_exact() {
+        let mut histogram = HdrHistogram::new(1_000_000, 3);
+
+        for v in 0..=100 {
+            histogram.update(v);
+        }
+
+        assert_eq!(histogram.count(), 101);
+        assert_eq!(histogram.min(), 0);
+        assert_eq!(histogram.max(), 100);
+        assert_eq!(histogram.value(0.5), 50);
+        assert_eq!(histogram.value(1.0), 100);
+        assert!((histogram.mean() - 50.0).abs() < 0.0001);
+    }
+
+    #[test]
+    fn values_out_of_range_are_clamped() {
+        let mut histogram = HdrHistogram::new(1_000, 2);
+
+        histogram.update(-5);
+        histogram.update(5_000);
+
+        assert_eq!(histogram.min(), 0);
+        assert_eq!(histogram.max(), 1_000);
+    }
+
+    #[test]
+    fn clear() {
+        let mut histogram = HdrHistogram::new(1_000, 2);
+        histogram.update(5);
+
+        histogram.clear();
+
+        assert_eq!(histogram.count(), 0);
+        assert_eq!(histogram.max(), 0);
+    }
+}
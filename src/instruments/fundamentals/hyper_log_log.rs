@@ -0,0 +1,124 @@
+//! Estimates the number of distinct values with HyperLogLog
+//!
+//! Every value is hashed and the hash selects one of `2^precision`
+//! registers by its first bits. A register keeps the highest position
+//! of the first set bit seen in the remaining bits. The harmonic mean
+//! of the registers gives an estimate of the number of distinct values
+//! with a relative error of about `1.04 / sqrt(2^precision)`.
+
+/// Counts distinct hashes approximately with fixed memory.
+pub struct HyperLogLog {
+    precision: u8,
+    registers: Vec<u8>,
+}
+
+impl HyperLogLog {
+    /// Creates a new `HyperLogLog` with `2^precision` registers.
+    ///
+    /// # Panics
+    ///
+    /// If `precision` is not within `4..=16`.
+    pub fn new(precision: u8) -> Self {
+        if !(4..=16).contains(&precision) {
+            panic!("precision must be within 4..=16");
+        }
+
+        HyperLogLog {
+            precision,
+            registers: vec![0; 1 << precision],
+        }
+    }
+
+    pub fn precision(&self) -> u8 {
+        self.precision
+    }
+
+    /// The expected relative standard error of an estimate
+    pub fn relative_error(&self) -> f64 {
+        1.04 / (self.registers.len() as f64).sqrt()
+    }
+
+    /// Adds a value given by its 64 bit hash
+    pub fn update(&mut self, hash: u64) {
+        let idx = (hash >> (64 - self.precision)) as usize;
+        // The marker bit limits the rank if all remaining bits are zero
+        let remaining = (hash << self.precision) | (1 << (self.precision - 1));
+        let rank = remaining.leading_zeros() as u8 + 1;
+        if rank > self.registers[idx] {
+            self.registers[idx] = rank;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.registers.iter_mut().for_each(|r| *r = 0);
+    }
+
+    /// Returns the estimated number of distinct values added
+    pub fn estimate(&self) -> u64 {
+        let m = self.registers.len() as f64;
+        let alpha = match self.registers.len() {
+            16 => 0.673,
+            32 => 0.697,
+            64 => 0.709,
+            _ => 0.7213 / (1.0 + 1.079 / m),
+        };
+
+        let sum: f64 = self
+            .registers
+            .iter()
+            .map(|&r| 1.0 / (1u64 << r) as f64)
+            .sum();
+        let estimate = alpha * m * m / sum;
+
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        if estimate <= 2.5 * m && zeros > 0 {
+            // Linear counting is more precise for small cardinalities
+            (m * (m / zeros as f64).ln()).round() as u64
+        } else {
+            estimate.round() as u64
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    use super::*;
+
+    fn hash(v: u64) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        v.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn empty() {
+        let hll = HyperLogLog::new(10);
+
+        assert_eq!(hll.estimate(), 0);
+    }
+
+    #[test]
+    fn small_cardinalities_are_almost_exact() {
+        let mut hll = HyperLogLog::new(12);
+
+        for v in 0..20 {
+            hll.update(hash(v));
+            hll.update(hash(v));
+        }
+
+        assert_eq!(hll.estimate(), 20);
+
+        hll.clear();
+
+        assert_eq!(hll.estimate(), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn precision_out_of_range() {
+        HyperLogLog::new(17);
+    }
+}
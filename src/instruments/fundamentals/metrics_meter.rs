@@ -10,6 +10,8 @@ use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
+use super::{Clock, SharedClock};
+
 const NANOS_PER_SEC: u64 = 1_000_000_000;
 const WINDOW: [f64; 3] = [1.0, 5.0, 15.0];
 
@@ -18,6 +20,7 @@ const WINDOW: [f64; 3] = [1.0, 5.0, 15.0];
 pub struct MeterSnapshot {
     pub count: i64,
     pub rates: [f64; 3],
+    pub custom_rate: Option<f64>,
     pub mean: f64,
 }
 
@@ -25,6 +28,7 @@ pub struct MeterSnapshot {
 struct StdMeterData {
     count: i64,
     ewma: [EWMA; 3],
+    custom: Option<EWMA>,
     next_tick: Instant,
 }
 
@@ -33,6 +37,8 @@ struct StdMeterData {
 pub struct StdMeter {
     data: Mutex<StdMeterData>,
     start: Instant,
+    tick_interval: Duration,
+    clock: SharedClock,
 }
 
 // A Meter trait
@@ -58,6 +64,7 @@ impl Meter for StdMeter {
         MeterSnapshot {
             count: s.count,
             rates: [s.ewma[0].rate(), s.ewma[1].rate(), s.ewma[2].rate()],
+            custom_rate: s.custom.as_ref().map(EWMA::rate),
             mean: self.mean_inner(&s),
         }
     }
@@ -71,6 +78,9 @@ impl Meter for StdMeter {
         for i in 0..WINDOW.len() {
             s.ewma[i].update(n as usize);
         }
+        if let Some(ref custom) = s.custom {
+            custom.update(n as usize);
+        }
     }
 
     fn tick(&self) {
@@ -113,38 +123,65 @@ impl StdMeter {
         if s.count == 0 {
             0.
         } else {
-            let dur = self.start.elapsed();
+            let dur = self.clock.now() - self.start;
             let nanos = dur.as_secs() * NANOS_PER_SEC + dur.subsec_nanos() as u64;
             s.count as f64 / nanos as f64 * NANOS_PER_SEC as f64
         }
     }
 
     fn tick_inner(&self, s: &mut StdMeterData) {
-        let now = Instant::now();
+        let now = self.clock.now();
 
         while s.next_tick <= now {
             for ewma in &mut s.ewma {
                 ewma.tick();
             }
-            s.next_tick += Duration::from_secs(TICK_RATE_SECS);
+            if let Some(ref mut custom) = s.custom {
+                custom.tick();
+            }
+            s.next_tick += self.tick_interval;
         }
     }
 }
 
-impl Default for StdMeter {
-    fn default() -> Self {
-        let now = Instant::now();
+impl StdMeter {
+    pub(crate) fn with_clock(clock: SharedClock) -> Self {
+        Self::with_config(clock, Duration::from_secs(TICK_RATE_SECS), None)
+    }
+
+    /// Creates a meter ticking every `tick_interval` with an additional
+    /// rate decaying over `custom_window` if given.
+    ///
+    /// The 1, 5 and 15 minute rates keep their windows.
+    pub(crate) fn with_config(
+        clock: SharedClock,
+        tick_interval: Duration,
+        custom_window: Option<Duration>,
+    ) -> Self {
+        let now = clock.now();
+        let ewma = |minutes: f64| {
+            EWMA::with_window(Duration::from_secs(60) * minutes as u32, tick_interval)
+        };
         StdMeter {
             data: Mutex::new(StdMeterData {
                 count: 0,
-                ewma: [EWMA::new(1.0), EWMA::new(5.0), EWMA::new(15.0)],
-                next_tick: now + Duration::from_secs(TICK_RATE_SECS),
+                ewma: [ewma(1.0), ewma(5.0), ewma(15.0)],
+                custom: custom_window.map(|window| EWMA::with_window(window, tick_interval)),
+                next_tick: now + tick_interval,
             }),
             start: now,
+            tick_interval,
+            clock,
         }
     }
 }
 
+impl Default for StdMeter {
+    fn default() -> Self {
+        Self::with_clock(SharedClock::default())
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -201,6 +238,7 @@ pub const TICK_RATE_SECS: u64 = 5;
 pub struct EWMA {
     uncounted: AtomicUsize, // This tracks uncounted events
     alpha: f64,
+    tick_nanos: u64,
     rate: f64,
     init: bool,
 }
@@ -213,7 +251,7 @@ impl EWMA {
 
     pub fn tick(&mut self) {
         let counter = self.uncounted.swap(0, Ordering::SeqCst);
-        let i_rate = (counter as f64) / (TICK_RATE_SECS * NANOS_PER_SEC) as f64;
+        let i_rate = (counter as f64) / self.tick_nanos as f64;
 
         if self.init {
             self.rate += self.alpha * (i_rate - self.rate);
@@ -232,16 +270,27 @@ impl EWMA {
         EWMA {
             uncounted: AtomicUsize::new(0),
             alpha,
+            tick_nanos: TICK_RATE_SECS * NANOS_PER_SEC,
             rate: 0.0,
             init: false,
         }
     }
 
     /// constructs a new EWMA for a n-minute moving average.
+    #[cfg(test)]
     pub fn new(n: f64) -> Self {
         let i = -(TICK_RATE_SECS as f64) / 60.0 / n;
         EWMA::new_by_alpha(1.0 - i.exp())
     }
+
+    /// constructs a new EWMA averaging over `window` which
+    /// is ticked every `tick_interval`.
+    pub fn with_window(window: Duration, tick_interval: Duration) -> Self {
+        let i = -tick_interval.as_secs_f64() / window.as_secs_f64();
+        let mut ewma = EWMA::new_by_alpha(1.0 - i.exp());
+        ewma.tick_nanos = tick_interval.as_nanos() as u64;
+        ewma
+    }
 }
 
 #[cfg(test)]
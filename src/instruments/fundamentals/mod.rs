@@ -1,8 +1,12 @@
 pub mod buckets;
 mod clock;
+pub(crate) mod decaying_reservoir;
+pub(crate) mod hdr_histogram;
+pub(crate) mod hyper_log_log;
 pub(crate) mod metrics_meter;
+pub(crate) mod sliding_window;
 
-#[cfg(test)]
 pub use clock::manual_clock::ManualOffsetClock;
+pub(crate) use clock::SharedClock;
 
 pub use clock::{Clock, WallClock};
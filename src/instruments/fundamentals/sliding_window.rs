@@ -0,0 +1,224 @@
+//! Keeps values observed within a trailing time window
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use super::{Clock, WallClock};
+
+/// Stores all values with their timestamps and forgets
+/// those which are older than the window.
+///
+/// A value observed several times at once is stored once
+/// with the number of times it was observed.
+pub struct SlidingWindow<C = WallClock> {
+    window: Duration,
+    values: VecDeque<(Instant, i64, u64)>,
+    clock: C,
+}
+
+impl<C> SlidingWindow<C>
+where
+    C: Clock,
+{
+    pub fn with_clock(window: Duration, clock: C) -> Self {
+        SlidingWindow {
+            window,
+            values: VecDeque::new(),
+            clock,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn update(&mut self, value: i64) {
+        self.update_n(value, 1)
+    }
+
+    pub fn update_n(&mut self, value: i64, n: u64) {
+        let now = self.clock.now();
+        self.update_n_at(now, value, n)
+    }
+
+    /// Records `value` as observed `n` times at `timestamp`.
+    ///
+    /// Values are expected to arrive roughly in the order of their
+    /// timestamps since outdated values are only removed from the front.
+    pub fn update_n_at(&mut self, timestamp: Instant, value: i64, n: u64) {
+        if n > 0 {
+            self.values.push_back((timestamp, value, n));
+        }
+        self.remove_outdated();
+    }
+
+    pub fn clear(&mut self) {
+        self.values.clear();
+    }
+
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Creates a snapshot of all values within the window
+    pub fn snapshot(&self) -> WindowSnapshot {
+        let now = self.clock.now();
+        let mut values: Vec<(i64, u64)> = self
+            .values
+            .iter()
+            .filter(|(timestamp, _, _)| !self.is_outdated(*timestamp, now))
+            .map(|&(_, value, n)| (value, n))
+            .collect();
+        values.sort_unstable();
+        let count = values
+            .iter()
+            .fold(0u64, |sum, &(_, n)| sum.saturating_add(n));
+        WindowSnapshot { values, count }
+    }
+
+    fn remove_outdated(&mut self) {
+        let now = self.clock.now();
+        while let Some(&(timestamp, _, _)) = self.values.front() {
+            if self.is_outdated(timestamp, now) {
+                self.values.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn is_outdated(&self, timestamp: Instant, now: Instant) -> bool {
+        timestamp + self.window < now
+    }
+}
+
+/// The sorted values of a `SlidingWindow` with the number
+/// of times they were observed
+pub struct WindowSnapshot {
+    values: Vec<(i64, u64)>,
+    count: u64,
+}
+
+impl WindowSnapshot {
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the value at the given quantile using the nearest rank.
+    pub fn value(&self, quantile: f64) -> i64 {
+        if self.count == 0 {
+            return 0;
+        }
+        let rank = ((quantile * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0u64;
+        for &(value, n) in &self.values {
+            seen = seen.saturating_add(n);
+            if seen >= rank {
+                return value;
+            }
+        }
+        self.max()
+    }
+
+    pub fn min(&self) -> i64 {
+        self.values.first().map(|&(v, _)| v).unwrap_or(0)
+    }
+
+    pub fn max(&self) -> i64 {
+        self.values.last().map(|&(v, _)| v).unwrap_or(0)
+    }
+
+    pub fn sum(&self) -> i64 {
+        self.values.iter().fold(0i64, |sum, &(v, n)| {
+            sum.saturating_add(v.saturating_mul(n.min(i64::MAX as u64) as i64))
+        })
+    }
+
+    pub fn mean(&self) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        self.values
+            .iter()
+            .map(|&(v, n)| v as f64 * n as f64)
+            .sum::<f64>()
+            / self.count as f64
+    }
+
+    pub fn stddev(&self) -> f64 {
+        if self.count < 2 {
+            return 0.0;
+        }
+        let mean = self.mean();
+        let sum_of_squares: f64 = self
+            .values
+            .iter()
+            .map(|&(v, n)| (v as f64 - mean) * (v as f64 - mean) * n as f64)
+            .sum();
+        (sum_of_squares / (self.count - 1) as f64).sqrt()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::instruments::fundamentals::ManualOffsetClock;
+
+    use super::*;
+
+    #[test]
+    fn old_values_age_out() {
+        let clock = ManualOffsetClock::default();
+        let mut window = SlidingWindow::with_clock(Duration::from_secs(10), clock.clone());
+
+        window.update(100);
+        clock.advance_n_seconds(5);
+        window.update(1);
+        window.update(3);
+
+        let snapshot = window.snapshot();
+        assert_eq!(snapshot.count(), 3);
+        assert_eq!(snapshot.min(), 1);
+        assert_eq!(snapshot.max(), 100);
+
+        clock.advance_n_seconds(6);
+
+        let snapshot = window.snapshot();
+        assert_eq!(snapshot.count(), 2);
+        assert_eq!(snapshot.min(), 1);
+        assert_eq!(snapshot.max(), 3);
+        assert_eq!(snapshot.value(0.5), 1);
+        assert!((snapshot.mean() - 2.0).abs() < 0.0001);
+
+        window.update(2);
+        assert_eq!(window.values.len(), 3);
+    }
+
+    #[test]
+    fn values_observed_many_times_are_stored_once() {
+        let clock = ManualOffsetClock::default();
+        let mut window = SlidingWindow::with_clock(Duration::from_secs(10), clock.clone());
+
+        window.update_n(1, 3);
+        window.update_n(10, 1_000_000);
+        assert_eq!(window.values.len(), 2);
+
+        let snapshot = window.snapshot();
+        assert_eq!(snapshot.count(), 1_000_003);
+        assert_eq!(snapshot.sum(), 10_000_003);
+        assert_eq!(snapshot.value(0.0), 1);
+        assert_eq!(snapshot.value(0.5), 10);
+        assert_eq!(snapshot.min(), 1);
+        assert_eq!(snapshot.max(), 10);
+    }
+
+    #[test]
+    fn idle_window_is_empty() {
+        let clock = ManualOffsetClock::default();
+        let mut window = SlidingWindow::with_clock(Duration::from_secs(10), clock.clone());
+
+        window.update(1);
+        window.update(2);
+
+        clock.advance_n_seconds(60);
+
+        let snapshot = window.snapshot();
+        assert_eq!(snapshot.count(), 0);
+        assert_eq!(snapshot.max(), 0);
+    }
+}
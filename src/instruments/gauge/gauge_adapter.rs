@@ -1,6 +1,6 @@
 use crate::instruments::{
-    AcceptAllLabels, BorrowedLabelAndUpdate, LabelFilter, LabelPredicate, Update, UpdateModifier,
-    Updates,
+    AcceptAllLabels, BorrowedLabelAndUpdate, LabelFilter, LabelPredicate, TemplateError, Update,
+    UpdateModifier, Updates,
 };
 use crate::snapshot::Snapshot;
 use crate::{HandlesObservations, Observation, ObservedValue, PutsSnapshot};
@@ -11,6 +11,7 @@ pub struct GaugeAdapter<L> {
     strategy: GaugeUpdateStrategy<L>,
     gauge: Gauge,
     modify_update: UpdateModifier<L>,
+    enabled: bool,
 }
 
 impl<L> GaugeAdapter<L>
@@ -28,6 +29,7 @@ where
             gauge,
             strategy: GaugeUpdateStrategy::Filter(accept.into()),
             modify_update: UpdateModifier::KeepAsIs,
+            enabled: true,
         }
     }
 
@@ -59,6 +61,7 @@ where
             gauge,
             strategy: GaugeUpdateStrategy::DeltasOnly(accept.into()),
             modify_update: UpdateModifier::KeepAsIs,
+            enabled: true,
         }
     }
 
@@ -109,6 +112,7 @@ where
             gauge,
             strategy: GaugeUpdateStrategy::IncDecOnLabels(accept_incr.into(), accept_decr.into()),
             modify_update: UpdateModifier::KeepAsIs,
+            enabled: true,
         }
     }
 
@@ -150,9 +154,26 @@ where
             gauge,
             strategy: GaugeUpdateStrategy::Filter(LabelFilter::accept_none()),
             modify_update: UpdateModifier::KeepAsIs,
+            enabled: true,
         }
     }
 
+    /// Enables or disables the `Gauge`.
+    ///
+    /// A disabled `Gauge` ignores all observations but is still
+    /// part of the `Snapshot` with an additional field
+    /// `_disabled_[gauge_name]` set to `true`.
+    ///
+    /// Default is enabled.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Returns `true` if the `Gauge` reacts on observations.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
     pub fn gauge(&self) -> &Gauge {
         &self.gauge
     }
@@ -164,6 +185,30 @@ where
     pub fn into_inner(self) -> Gauge {
         self.gauge
     }
+
+    /// Returns a copy of this adapter with a fresh `Gauge`
+    pub(crate) fn template_clone(&self) -> Result<Self, TemplateError>
+    where
+        L: Clone,
+    {
+        let strategy = match self.strategy {
+            GaugeUpdateStrategy::Filter(ref filter) => {
+                GaugeUpdateStrategy::Filter(filter.try_clone()?)
+            }
+            GaugeUpdateStrategy::DeltasOnly(ref filter) => {
+                GaugeUpdateStrategy::DeltasOnly(filter.try_clone()?)
+            }
+            GaugeUpdateStrategy::IncDecOnLabels(ref inc, ref dec) => {
+                GaugeUpdateStrategy::IncDecOnLabels(inc.try_clone()?, dec.try_clone()?)
+            }
+        };
+        Ok(Self {
+            strategy,
+            gauge: self.gauge.template_clone(),
+            modify_update: self.modify_update.try_clone()?,
+            enabled: self.enabled,
+        })
+    }
 }
 
 impl<L> HandlesObservations for GaugeAdapter<L>
@@ -173,6 +218,10 @@ where
     type Label = L;
 
     fn handle_observation(&mut self, observation: &Observation<Self::Label>) -> usize {
+        if !self.enabled {
+            return 0;
+        }
+
         let BorrowedLabelAndUpdate(label, update) = observation.into();
 
         match self.strategy {
@@ -190,7 +239,8 @@ where
                 let update = self.modify_update.modify(label, update);
 
                 match update {
-                    Update::ObservationWithValue(ObservedValue::ChangedBy(_), _) => {
+                    Update::ObservationWithValue(ObservedValue::ChangedBy(_), _)
+                    | Update::ObservationsWithValue(ObservedValue::ChangedBy(_), _, _) => {
                         self.gauge.update(&update)
                     }
                     _ => 0,
@@ -214,6 +264,25 @@ where
             }
         }
     }
+
+    fn reset(&mut self) {
+        self.gauge.reset()
+    }
+}
+
+impl<L> GaugeAdapter<L>
+where
+    L: Send + 'static,
+{
+    fn put_values(&self, into: &mut Snapshot, descriptive: bool, exported: bool) {
+        util::put_snapshot_of(&self.gauge, into, descriptive, exported);
+        if !self.enabled {
+            crate::instruments::instrument_adapter::put_disabled_marker(
+                self.gauge.get_name(),
+                into,
+            );
+        }
+    }
 }
 
 impl<L> PutsSnapshot for GaugeAdapter<L>
@@ -221,7 +290,15 @@ where
     L: Send + 'static,
 {
     fn put_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
-        self.gauge.put_snapshot(into, descriptive)
+        self.put_values(into, descriptive, false)
+    }
+
+    fn put_exported_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
+        self.put_values(into, descriptive, true)
+    }
+
+    fn put_names(&self, into: &mut Snapshot) {
+        self.gauge.put_names(into)
     }
 }
 
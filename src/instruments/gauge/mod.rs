@@ -1,11 +1,11 @@
-use std::cell::RefCell;
-use std::time::Duration;
+use std::cell::{Cell, RefCell};
+use std::time::{Duration, Instant};
 
 use crate::instruments::{
-    fundamentals::buckets::SecondsBuckets, AcceptAllLabels, Instrument, LabelFilter,
-    LabelPredicate, Update, Updates,
+    fundamentals::{buckets::SecondsBuckets, Clock, SharedClock},
+    AcceptAllLabels, Instrument, LabelFilter, LabelPredicate, Update, Updates,
 };
-use crate::snapshot::Snapshot;
+use crate::snapshot::{ItemKind, Snapshot};
 use crate::util;
 use crate::{Descriptive, ObservedValue, PutsSnapshot, TimeUnit, DECR, INCR};
 pub use gauge_adapter::*;
@@ -14,6 +14,34 @@ use tracking::*;
 mod gauge_adapter;
 mod tracking;
 
+/// Defines what a `Gauge` puts into a `Snapshot`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GaugeMode {
+    /// The value observed last. This is the default.
+    #[default]
+    Value,
+    /// The difference between the value observed last and the value
+    /// of the previous snapshot. Use this if the observed values are
+    /// cumulative like the bytes sent as reported by some other system.
+    Delta,
+    /// Same as `Delta` but divided by the seconds elapsed since the
+    /// previous snapshot which is a float.
+    DeltaPerSecond,
+}
+
+/// Defines how a `Gauge` interprets observed values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GaugeInputMode {
+    /// An observed value replaces the current value. This is the default.
+    #[default]
+    Absolute,
+    /// An observed value changes the current value by the observed
+    /// amount like an `ObservedValue::ChangedBy` would. Use this if
+    /// the observations are events like "+1 checked out" and
+    /// "-1 returned". Durations still replace the value.
+    Relative,
+}
+
 /// Simply returns the value that has been observed last.
 ///
 /// Reacts  `Observation::Observation::ObservedOneValue`
@@ -22,6 +50,14 @@ mod tracking;
 /// * All `ObservedValue`s tha can be converted to an `i64` which
 /// directly set the value
 ///
+/// An `Observation::ObservedValues` sets the value once while an
+/// `ObservedValue::ChangedBy` is applied as often as it was observed.
+///
+/// An observed `ObservedValue::Float` is kept as it is and put into
+/// the `Snapshot` as a float. See `get_f64`. Clamping, smoothing,
+/// watermarks and changes by `ObservedValue::ChangedBy` keep the
+/// fraction while tracking and `GaugeMode::Delta` use the rounded value.
+///
 /// # Examples
 ///
 /// ```
@@ -61,9 +97,23 @@ pub struct Gauge {
     name: String,
     title: Option<String>,
     description: Option<String>,
-    value: Option<i64>,
-    tracking: Option<RefCell<SecondsBuckets<Bucket>>>,
-    display_time_unit: TimeUnit,
+    value: Option<Level>,
+    tracking: Option<RefCell<SecondsBuckets<Bucket, SharedClock>>>,
+    watermarks: Option<Cell<Option<(Level, Level)>>>,
+    reset_watermarks_on_snapshot: bool,
+    display_time_unit: Option<TimeUnit>,
+    mode: GaugeMode,
+    input_mode: GaugeInputMode,
+    delta_baseline: Cell<Option<(i64, Instant)>>,
+    smoothing_alpha: Option<f64>,
+    smoothed: Cell<Option<f64>>,
+    auto_scale_durations: bool,
+    clamp: Option<(i64, i64)>,
+    clamped_count: u64,
+    update_tracker: util::UpdateTracker,
+    clock: SharedClock,
+    expire_after: Option<Duration>,
+    last_set_at: Option<Instant>,
 }
 
 impl Gauge {
@@ -74,7 +124,21 @@ impl Gauge {
             description: None,
             value: None,
             tracking: None,
-            display_time_unit: TimeUnit::default(),
+            watermarks: None,
+            reset_watermarks_on_snapshot: false,
+            display_time_unit: None,
+            mode: GaugeMode::default(),
+            input_mode: GaugeInputMode::default(),
+            delta_baseline: Cell::new(None),
+            smoothing_alpha: None,
+            smoothed: Cell::new(None),
+            auto_scale_durations: false,
+            clamp: None,
+            clamped_count: 0,
+            update_tracker: util::UpdateTracker::default(),
+            clock: SharedClock::default(),
+            expire_after: None,
+            last_set_at: None,
         }
     }
 
@@ -82,6 +146,38 @@ impl Gauge {
         Self::new(name)
     }
 
+    /// Returns a gauge with the same configuration which
+    /// has not observed anything yet.
+    pub(crate) fn template_clone(&self) -> Gauge {
+        Gauge {
+            name: self.name.clone(),
+            title: self.title.clone(),
+            description: self.description.clone(),
+            value: None,
+            tracking: self.tracking.as_ref().map(|tracking| {
+                RefCell::new(SecondsBuckets::with_clock(
+                    tracking.borrow().len(),
+                    self.clock.clone(),
+                ))
+            }),
+            watermarks: self.watermarks.as_ref().map(|_| Cell::new(None)),
+            reset_watermarks_on_snapshot: self.reset_watermarks_on_snapshot,
+            display_time_unit: self.display_time_unit,
+            mode: self.mode,
+            input_mode: self.input_mode,
+            delta_baseline: Cell::new(None),
+            smoothing_alpha: self.smoothing_alpha,
+            smoothed: Cell::new(None),
+            auto_scale_durations: self.auto_scale_durations,
+            clamp: self.clamp,
+            clamped_count: 0,
+            update_tracker: self.update_tracker.template(),
+            clock: self.clock.clone(),
+            expire_after: self.expire_after,
+            last_set_at: None,
+        }
+    }
+
     pub fn get_name(&self) -> &str {
         &self.name
     }
@@ -159,19 +255,303 @@ impl Gauge {
     /// * `[gauge_name]_avg`: The average of all values for all records
     pub fn set_tracking(&mut self, for_seconds: usize) {
         if for_seconds != 0 {
-            self.tracking = Some(RefCell::new(SecondsBuckets::new(for_seconds)))
+            self.tracking = Some(RefCell::new(SecondsBuckets::with_clock(
+                for_seconds,
+                self.clock.clone(),
+            )))
+        }
+    }
+
+    /// Sets the `Clock` used for tracking, for the rate of
+    /// `GaugeMode::DeltaPerSecond` and for the time since the last update.
+    ///
+    /// Tracked values are discarded. Mostly useful for testing with
+    /// a `ManualOffsetClock`.
+    ///
+    /// Default is the `WallClock`.
+    pub fn set_clock<C: Clock + Send + Sync + 'static>(&mut self, clock: C) {
+        self.clock = SharedClock::new(clock);
+        self.update_tracker.set_clock(self.clock.clone());
+        if let Some(tracking) = self.tracking.take() {
+            self.set_tracking(tracking.into_inner().len());
         }
+        self.delta_baseline.set(None);
     }
 
+    /// Sets the `Clock` used for tracking, for the rate of
+    /// `GaugeMode::DeltaPerSecond` and for the time since the last update.
+    ///
+    /// Default is the `WallClock`.
+    pub fn clock<C: Clock + Send + Sync + 'static>(mut self, clock: C) -> Self {
+        self.set_clock(clock);
+        self
+    }
+
+    /// Enables tracking of the smallest and biggest values observed.
+    ///
+    /// If enabled, the following fields will be added:
+    ///
+    /// * `[gauge_name]_min`: The smallest value observed
+    /// * `[gauge_name]_max`: The biggest value observed
+    /// * `[gauge_name]_peak`: The value with the biggest magnitude
+    ///   which is either `min` or `max`. This field is omitted
+    ///   if tracking is also enabled since tracking has its own `peak`.
+    ///
+    /// The first observed value is both `min` and `max`.
+    ///
+    /// Default is disabled.
+    pub fn set_watermarks_enabled(&mut self, enabled: bool) {
+        if enabled {
+            if self.watermarks.is_none() {
+                self.watermarks = Some(Cell::new(None));
+            }
+        } else {
+            self.watermarks = None;
+        }
+    }
+
+    /// Enables tracking of the smallest and biggest values observed.
+    ///
+    /// If enabled, the following fields will be added:
+    ///
+    /// * `[gauge_name]_min`: The smallest value observed
+    /// * `[gauge_name]_max`: The biggest value observed
+    /// * `[gauge_name]_peak`: The value with the biggest magnitude
+    ///   which is either `min` or `max`. This field is omitted
+    ///   if tracking is also enabled since tracking has its own `peak`.
+    ///
+    /// The first observed value is both `min` and `max`.
+    ///
+    /// Default is disabled.
+    pub fn watermarks_enabled(mut self, enabled: bool) -> Self {
+        self.set_watermarks_enabled(enabled);
+        self
+    }
+
+    /// If `true` the watermarks will start over after each exported
+    /// snapshot so that they only contain values observed since the
+    /// last one. See `PutsSnapshot::put_exported_snapshot`.
+    ///
+    /// Only has an effect if watermarks are enabled. Default is `false`.
+    pub fn set_reset_watermarks_on_snapshot(&mut self, reset: bool) {
+        self.reset_watermarks_on_snapshot = reset;
+    }
+
+    /// If `true` the watermarks will start over after each exported
+    /// snapshot so that they only contain values observed since the
+    /// last one. See `PutsSnapshot::put_exported_snapshot`.
+    ///
+    /// Only has an effect if watermarks are enabled. Default is `false`.
+    pub fn reset_watermarks_on_snapshot(mut self, reset: bool) -> Self {
+        self.set_reset_watermarks_on_snapshot(reset);
+        self
+    }
+
+    /// Sets the unit durations will be converted to.
+    ///
+    /// If set, the unit will be part of a descriptive `Snapshot`
+    /// as `_unit_[name]`.
+    ///
+    /// Default is `TimeUnit::Microseconds`.
     pub fn set_display_time_unit(&mut self, display_time_unit: TimeUnit) {
-        self.display_time_unit = display_time_unit
+        self.display_time_unit = Some(display_time_unit)
     }
 
+    /// Sets the unit durations will be converted to.
+    ///
+    /// If set, the unit will be part of a descriptive `Snapshot`
+    /// as `_unit_[name]`.
+    ///
+    /// Default is `TimeUnit::Microseconds`.
     pub fn display_time_unit(mut self, display_time_unit: TimeUnit) -> Self {
         self.set_display_time_unit(display_time_unit);
         self
     }
 
+    /// Sets the display time unit unless one was already set
+    pub(crate) fn set_default_display_time_unit(&mut self, display_time_unit: TimeUnit) {
+        if self.display_time_unit.is_none() {
+            self.display_time_unit = Some(display_time_unit)
+        }
+    }
+
+    /// If enabled the value is treated as a duration and displayed
+    /// as a float in the unit which keeps it readable, e.g. `1.5` seconds
+    /// instead of `1500000` microseconds. The unit is added as a text
+    /// `[gauge_name]_unit`. See `TimeUnit::auto_scale`.
+    ///
+    /// Observed durations are kept in nanoseconds so the display time unit
+    /// is ignored. Only has an effect with `GaugeMode::Value`.
+    ///
+    /// Default is disabled.
+    pub fn set_auto_scale_durations(&mut self, enabled: bool) {
+        self.auto_scale_durations = enabled;
+    }
+
+    /// If enabled the value is treated as a duration and displayed
+    /// in the unit which keeps it readable.
+    ///
+    /// See `set_auto_scale_durations`.
+    ///
+    /// Default is disabled.
+    pub fn auto_scale_durations(mut self, enabled: bool) -> Self {
+        self.set_auto_scale_durations(enabled);
+        self
+    }
+
+    /// Sets what will be put into a `Snapshot`.
+    ///
+    /// With `GaugeMode::Delta` or `GaugeMode::DeltaPerSecond` the value
+    /// at the time of an exported snapshot is the baseline for the next
+    /// snapshot (see `PutsSnapshot::put_exported_snapshot`):
+    ///
+    /// * The first snapshot after a value has been observed has no
+    ///   baseline and therefore does not contain the gauge.
+    /// * If the value went down (e.g. the observed counter was reset)
+    ///   the new value is taken as the difference instead of
+    ///   reporting a negative spike.
+    ///
+    /// Tracking and watermarks always refer to the observed values.
+    ///
+    /// Default is `GaugeMode::Value`.
+    pub fn set_mode(&mut self, mode: GaugeMode) {
+        self.mode = mode;
+        self.delta_baseline.set(None);
+    }
+
+    /// Sets what will be put into a `Snapshot`.
+    ///
+    /// See `set_mode` for details.
+    ///
+    /// Default is `GaugeMode::Value`.
+    pub fn mode(mut self, mode: GaugeMode) -> Self {
+        self.set_mode(mode);
+        self
+    }
+
+    /// Sets how observed values change the value.
+    ///
+    /// With `GaugeInputMode::Relative` each observed value is added to
+    /// the current value. If there is no value yet the observed value
+    /// is added to zero.
+    ///
+    /// Default is `GaugeInputMode::Absolute`.
+    pub fn set_input_mode(&mut self, input_mode: GaugeInputMode) {
+        self.input_mode = input_mode;
+    }
+
+    /// Sets how observed values change the value.
+    ///
+    /// See `set_input_mode` for details.
+    ///
+    /// Default is `GaugeInputMode::Absolute`.
+    pub fn input_mode(mut self, input_mode: GaugeInputMode) -> Self {
+        self.set_input_mode(input_mode);
+        self
+    }
+
+    /// Enables tracking when this gauge was updated last.
+    ///
+    /// The number of seconds since the last update will be put into
+    /// the `Snapshot` as `_updated_secs_ago_[gauge_name]`. Nothing is added before the first update.
+    ///
+    /// Default is disabled.
+    pub fn set_updated_secs_ago_enabled(&mut self, enabled: bool) {
+        self.update_tracker.set_enabled(enabled);
+    }
+
+    /// Enables tracking when this gauge was updated last.
+    ///
+    /// The number of seconds since the last update will be put into
+    /// the `Snapshot` as `_updated_secs_ago_[gauge_name]`. Nothing is added before the first update.
+    ///
+    /// Default is disabled.
+    pub fn updated_secs_ago_enabled(mut self, enabled: bool) -> Self {
+        self.set_updated_secs_ago_enabled(enabled);
+        self
+    }
+
+    /// Enables an exponentially weighted moving average of the
+    /// observed values which will be added as `[gauge_name]_smoothed`.
+    ///
+    /// Each observed value updates the average with
+    /// `smoothed = alpha * value + (1 - alpha) * smoothed`.
+    /// The first observed value initializes the average.
+    ///
+    /// # Panics
+    ///
+    /// If `alpha` is not within `0.0..=1.0`.
+    pub fn set_smoothing(&mut self, alpha: f64) {
+        if !(0.0..=1.0).contains(&alpha) {
+            panic!("alpha {} is not within 0.0..=1.0", alpha);
+        }
+        self.smoothing_alpha = Some(alpha);
+        self.smoothed.set(None);
+    }
+
+    /// Enables an exponentially weighted moving average of the
+    /// observed values which will be added as `[gauge_name]_smoothed`.
+    ///
+    /// See `set_smoothing`.
+    ///
+    /// # Panics
+    ///
+    /// If `alpha` is not within `0.0..=1.0`.
+    pub fn smoothing(mut self, alpha: f64) -> Self {
+        self.set_smoothing(alpha);
+        self
+    }
+
+    /// Clamps the values of the gauge to the range `min..=max`, e.g.
+    /// percentages slightly out of range due to sampling artifacts.
+    ///
+    /// How often a value had to be clamped will be added as
+    /// `[gauge_name]_clamped_count`.
+    ///
+    /// # Panics
+    ///
+    /// If `min` is greater than `max`.
+    pub fn set_clamp(&mut self, min: i64, max: i64) {
+        if min > max {
+            panic!("min {} is greater than max {}", min, max);
+        }
+        self.clamp = Some((min, max));
+        self.clamped_count = 0;
+    }
+
+    /// Clamps the values of the gauge to the range `min..=max`.
+    ///
+    /// See `set_clamp`.
+    ///
+    /// # Panics
+    ///
+    /// If `min` is greater than `max`.
+    pub fn clamp(mut self, min: i64, max: i64) -> Self {
+        self.set_clamp(min, max);
+        self
+    }
+
+    /// Lets the value expire if no value has been observed for the
+    /// given duration. An expired gauge returns `None` on `get` and
+    /// puts no value into a `Snapshot` instead of a stale one.
+    ///
+    /// The next observed value re-activates the gauge. Increments and
+    /// decrements then start from zero.
+    ///
+    /// Default is to never expire.
+    pub fn set_expire_after(&mut self, expire_after: Duration) {
+        self.expire_after = Some(expire_after);
+    }
+
+    /// Lets the value expire if no value has been observed for the
+    /// given duration.
+    ///
+    /// See `set_expire_after`.
+    pub fn expire_after(mut self, expire_after: Duration) -> Self {
+        self.set_expire_after(expire_after);
+        self
+    }
+
     pub fn accept<L: Eq + Send + 'static, F: Into<LabelFilter<L>>>(
         self,
         accept: F,
@@ -317,58 +697,229 @@ impl Gauge {
         } else {
             observed
         };
+        let observed = match (self.input_mode, observed.convert_to_i64()) {
+            (GaugeInputMode::Relative, Some(delta)) => ObservedValue::ChangedBy(delta),
+            _ => observed,
+        };
 
-        if let Some(value) = self.value.take() {
-            let next_value = if let Some(next_value) = self.next_value(Some(value), observed) {
-                if let Some(ref buckets) = self.tracking {
-                    match buckets.try_borrow_mut() {
-                        Ok(mut borrowed) => borrowed.current_mut().update(next_value),
-                        Err(_err) => crate::util::log_error("borrow mut in gauge::set failed!"),
-                    }
+        if self.is_expired() {
+            self.value = None;
+            self.smoothed.set(None);
+        }
+
+        let current = self.value.take();
+        let next_value = self.next_value(current, observed);
+        if let Some(next_value) = next_value {
+            if let Some(ref buckets) = self.tracking {
+                match buckets.try_borrow_mut() {
+                    Ok(mut borrowed) => borrowed.current_mut().update(next_value.as_i64()),
+                    Err(_err) => crate::util::log_error("borrow mut in gauge::set failed!"),
                 }
-                next_value
+            }
+            self.update_watermarks(next_value);
+            self.update_smoothed(next_value.as_f64());
+        }
+        self.value = next_value.or(current);
+
+        if self.value.is_some() {
+            self.last_set_at = Some(self.clock.now());
+        }
+    }
+
+    pub fn get(&self) -> Option<i64> {
+        self.get_level().map(Level::as_i64)
+    }
+
+    /// Returns the value without rounding if the last observed
+    /// value was a float.
+    pub fn get_f64(&self) -> Option<f64> {
+        self.get_level().map(Level::as_f64)
+    }
+
+    fn get_level(&self) -> Option<Level> {
+        if self.is_expired() {
+            None
+        } else {
+            self.value
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        match (self.expire_after, self.last_set_at) {
+            (Some(expire_after), Some(last_set_at)) => {
+                self.clock.now().saturating_duration_since(last_set_at) > expire_after
+            }
+            _ => false,
+        }
+    }
+
+    fn update_watermarks(&self, value: Level) {
+        if let Some(ref watermarks) = self.watermarks {
+            let next = match watermarks.get() {
+                Some((min, max)) => (
+                    if value.as_f64() < min.as_f64() {
+                        value
+                    } else {
+                        min
+                    },
+                    if value.as_f64() > max.as_f64() {
+                        value
+                    } else {
+                        max
+                    },
+                ),
+                None => (value, value),
+            };
+            watermarks.set(Some(next));
+        }
+    }
+
+    fn update_smoothed(&self, value: f64) {
+        if let Some(alpha) = self.smoothing_alpha {
+            let next = match self.smoothed.get() {
+                Some(smoothed) => alpha * value + (1.0 - alpha) * smoothed,
+                None => value,
+            };
+            self.smoothed.set(Some(next));
+        }
+    }
+
+    fn put_watermarks(&self, into: &mut Snapshot, exported: bool) {
+        if let Some(ref watermarks) = self.watermarks {
+            let current = if self.reset_watermarks_on_snapshot && exported {
+                watermarks.take()
             } else {
-                value
+                watermarks.get()
             };
 
-            self.value = Some(next_value);
-        } else {
-            self.value = self.next_value(None, observed).map(|next_value| {
-                if let Some(ref buckets) = self.tracking {
-                    match buckets.try_borrow_mut() {
-                        Ok(mut borrowed) => borrowed.current_mut().update(next_value),
-                        Err(_err) => crate::util::log_error("borrow mut in gauge::set failed!"),
-                    }
+            if let Some((min, max)) = current {
+                into.items.push((format!("{}_min", self.name), min.into()));
+                into.items.push((format!("{}_max", self.name), max.into()));
+                if self.tracking.is_none() {
+                    let peak = if min.as_f64().abs() > max.as_f64().abs() {
+                        min
+                    } else {
+                        max
+                    };
+                    into.items
+                        .push((format!("{}_peak", self.name), peak.into()));
                 }
+            }
+        }
+    }
 
-                next_value
-            });
+    fn put_value(&self, level: Level, into: &mut Snapshot, exported: bool) {
+        let value = level.as_i64();
+        if self.mode == GaugeMode::Value {
+            if self.auto_scale_durations {
+                let (scaled, unit) = TimeUnit::auto_scale(std::cmp::max(value, 0) as u64);
+                into.items.push((self.name.clone(), scaled.into()));
+                into.items
+                    .push((format!("{}_unit", self.name), unit.name().into()));
+            } else {
+                into.items.push((self.name.clone(), level.into()));
+            }
+            return;
+        }
+
+        let now = self.clock.now();
+        let baseline = if exported {
+            self.delta_baseline.replace(Some((value, now)))
+        } else {
+            self.delta_baseline.get()
+        };
+
+        if let Some((previous, previous_at)) = baseline {
+            let delta = if value >= previous {
+                value - previous
+            } else {
+                std::cmp::max(value, 0)
+            };
+
+            if self.mode == GaugeMode::Delta {
+                into.items.push((self.name.clone(), delta.into()));
+            } else {
+                let elapsed = (now - previous_at).as_secs_f64();
+                let per_second = if elapsed > 0.0 {
+                    delta as f64 / elapsed
+                } else {
+                    0.0
+                };
+                into.items.push((self.name.clone(), per_second.into()));
+            }
         }
     }
 
-    pub fn get(&self) -> Option<i64> {
-        self.value
+    fn next_value(&mut self, current: Option<Level>, observed: ObservedValue) -> Option<Level> {
+        let next_value = self.unclamped_next_value(current, observed)?;
+        match self
+            .clamp
+            .and_then(|(min, max)| next_value.clamped(min, max))
+        {
+            Some(clamped) => {
+                self.clamped_count += 1;
+                Some(clamped)
+            }
+            None => Some(next_value),
+        }
     }
 
-    fn next_value(&self, current: Option<i64>, observed: ObservedValue) -> Option<i64> {
+    fn unclamped_next_value(
+        &self,
+        current: Option<Level>,
+        observed: ObservedValue,
+    ) -> Option<Level> {
         match observed {
-            ObservedValue::ChangedBy(d) => current.map(|c| c + d).or_else(|| Some(d)),
+            ObservedValue::ChangedBy(d) => Some(match current {
+                Some(Level::Int(c)) => Level::Int(c + d),
+                Some(Level::Float(c)) => Level::Float(c + d as f64),
+                None => Level::Int(d),
+            }),
             ObservedValue::Duration(time, unit) => {
-                let value = super::duration_to_display_value(time, unit, self.display_time_unit);
-                Some(value as i64)
+                let target_unit = if self.auto_scale_durations {
+                    TimeUnit::Nanoseconds
+                } else {
+                    self.display_time_unit.unwrap_or_default()
+                };
+                let value = super::duration_to_display_value(time, unit, target_unit);
+                Some(Level::Int(value as i64))
             }
-            x => x.convert_to_i64().or_else(|| current),
+            ObservedValue::Float(v) if observed.convert_to_i64().is_some() => Some(Level::Float(v)),
+            x => x.convert_to_i64().map(Level::Int).or(current),
         }
     }
 }
 
-impl Instrument for Gauge {}
+impl Instrument for Gauge {
+    fn name(&self) -> Option<&str> {
+        Some(self.get_name())
+    }
 
-impl PutsSnapshot for Gauge {
-    fn put_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
+    fn reset(&mut self) {
+        self.value = None;
+        self.last_set_at = None;
+        self.delta_baseline.set(None);
+        self.smoothed.set(None);
+        self.clamped_count = 0;
+        self.update_tracker.reset();
+        if let Some(ref watermarks) = self.watermarks {
+            watermarks.set(None);
+        }
+        if let Some(ref buckets) = self.tracking {
+            match buckets.try_borrow_mut() {
+                Ok(mut borrowed) => borrowed.reset(),
+                Err(_err) => crate::util::log_error("borrow mut in gauge::reset failed!"),
+            }
+        }
+    }
+}
+
+impl Gauge {
+    fn put_values(&self, into: &mut Snapshot, descriptive: bool, exported: bool) {
         util::put_postfixed_descriptives(self, &self.name, into, descriptive);
-        if let Some(value) = self.value {
-            into.items.push((self.name.clone(), value.into()));
+        util::put_postfixed_time_unit(self.display_time_unit, &self.name, into, descriptive);
+        if let Some(level) = self.get_level() {
+            self.put_value(level, into, exported);
             if let Some(ref buckets) = self.tracking {
                 match buckets.try_borrow_mut() {
                     Ok(mut borrowed) => BucketsStats::from_buckets(&mut *borrowed)
@@ -379,7 +930,33 @@ impl PutsSnapshot for Gauge {
                     }
                 }
             }
+            self.put_watermarks(into, exported);
+            if let Some(smoothed) = self.smoothed.get() {
+                into.items
+                    .push((format!("{}_smoothed", self.name), smoothed.into()));
+            }
+        }
+        if self.clamp.is_some() {
+            into.items.push((
+                format!("{}_clamped_count", self.name),
+                self.clamped_count.into(),
+            ));
         }
+        self.update_tracker.put_postfixed(&self.name, into);
+    }
+}
+
+impl PutsSnapshot for Gauge {
+    fn put_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
+        self.put_values(into, descriptive, false)
+    }
+
+    fn put_exported_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
+        self.put_values(into, descriptive, true)
+    }
+
+    fn put_names(&self, into: &mut Snapshot) {
+        util::put_name(&self.name, into)
     }
 }
 
@@ -387,14 +964,76 @@ impl Updates for Gauge {
     fn update(&mut self, with: &Update) -> usize {
         match *with {
             Update::ObservationWithValue(v, _) => {
+                self.update_tracker.updated();
                 self.set(v);
                 1
             }
+            Update::ObservationsWithValue(v, n, _) => {
+                self.update_tracker.updated();
+                self.set(repeated(v, n));
+                1
+            }
             _ => 0,
         }
     }
 }
 
+/// The level of a `Gauge`
+///
+/// An observed float stays a float, also when it is changed
+/// or clamped.
+#[derive(Debug, Clone, Copy)]
+enum Level {
+    Int(i64),
+    Float(f64),
+}
+
+impl Level {
+    /// Returns the level clamped to `min..=max` if it is out of range.
+    fn clamped(self, min: i64, max: i64) -> Option<Level> {
+        match self {
+            Level::Int(v) if v < min || v > max => Some(Level::Int(v.clamp(min, max))),
+            Level::Float(v) if v < min as f64 || v > max as f64 => {
+                Some(Level::Float(v.clamp(min as f64, max as f64)))
+            }
+            _ => None,
+        }
+    }
+
+    fn as_i64(self) -> i64 {
+        match self {
+            Level::Int(value) => value,
+            Level::Float(value) => value.round() as i64,
+        }
+    }
+
+    fn as_f64(self) -> f64 {
+        match self {
+            Level::Int(value) => value as f64,
+            Level::Float(value) => value,
+        }
+    }
+}
+
+impl From<Level> for ItemKind {
+    fn from(level: Level) -> ItemKind {
+        match level {
+            Level::Int(value) => value.into(),
+            Level::Float(value) => value.into(),
+        }
+    }
+}
+
+/// A value observed `n` times is set once. Changes are applied `n` times.
+pub(crate) fn repeated(value: ObservedValue, n: u64) -> ObservedValue {
+    match value {
+        ObservedValue::ChangedBy(delta) => {
+            ObservedValue::ChangedBy(delta.saturating_mul(n.min(i64::MAX as u64) as i64))
+        }
+        value => value,
+    }
+}
+
 impl Descriptive for Gauge {
     fn title(&self) -> Option<&str> {
         self.title.as_ref().map(|n| &**n)
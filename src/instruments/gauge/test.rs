@@ -1,5 +1,6 @@
 use std::time::Instant;
 
+use crate::snapshot::ItemKind;
 use crate::{Decrement, DecrementBy, HandlesObservations, Increment, IncrementBy, Observation};
 
 use super::*;
@@ -332,3 +333,345 @@ fn gauge_inc_dec_on_many() {
     ));
     assert_eq!(gauge_adapter.gauge().get(), Some(0));
 }
+
+#[test]
+fn gauge_watermarks() {
+    let mut gauge = Gauge::new("gauge").watermarks_enabled(true);
+
+    gauge.set(5.into());
+
+    let mut snapshot = Snapshot::default();
+    gauge.put_snapshot(&mut snapshot, false);
+    assert_eq!(snapshot.find("gauge_min").opt(), Some(&ItemKind::Int(5)));
+    assert_eq!(snapshot.find("gauge_max").opt(), Some(&ItemKind::Int(5)));
+    assert_eq!(snapshot.find("gauge_peak").opt(), Some(&ItemKind::Int(5)));
+
+    gauge.set((-7).into());
+    gauge.set(3.into());
+    gauge.set(ObservedValue::ChangedBy(2));
+
+    let mut snapshot = Snapshot::default();
+    gauge.put_snapshot(&mut snapshot, false);
+    assert_eq!(snapshot.find("gauge").opt(), Some(&ItemKind::Int(5)));
+    assert_eq!(snapshot.find("gauge_min").opt(), Some(&ItemKind::Int(-7)));
+    assert_eq!(snapshot.find("gauge_max").opt(), Some(&ItemKind::Int(5)));
+    assert_eq!(snapshot.find("gauge_peak").opt(), Some(&ItemKind::Int(-7)));
+}
+
+#[test]
+fn gauge_watermarks_reset_on_snapshot() {
+    let mut gauge = Gauge::new("gauge")
+        .watermarks_enabled(true)
+        .reset_watermarks_on_snapshot(true);
+
+    gauge.set((-3).into());
+    gauge.set((-1).into());
+
+    let mut snapshot = Snapshot::default();
+    gauge.put_snapshot(&mut snapshot, false);
+    assert_eq!(snapshot.find("gauge_min").opt(), Some(&ItemKind::Int(-3)));
+
+    let mut snapshot = Snapshot::default();
+    gauge.put_exported_snapshot(&mut snapshot, false);
+    assert_eq!(snapshot.find("gauge_min").opt(), Some(&ItemKind::Int(-3)));
+    assert_eq!(snapshot.find("gauge_max").opt(), Some(&ItemKind::Int(-1)));
+
+    let mut snapshot = Snapshot::default();
+    gauge.put_exported_snapshot(&mut snapshot, false);
+    assert_eq!(snapshot.find("gauge").opt(), Some(&ItemKind::Int(-1)));
+    assert_eq!(snapshot.find("gauge_min").opt(), None);
+    assert_eq!(snapshot.find("gauge_max").opt(), None);
+
+    gauge.set(10.into());
+
+    let mut snapshot = Snapshot::default();
+    gauge.put_exported_snapshot(&mut snapshot, false);
+    assert_eq!(snapshot.find("gauge_min").opt(), Some(&ItemKind::Int(10)));
+    assert_eq!(snapshot.find("gauge_max").opt(), Some(&ItemKind::Int(10)));
+}
+
+fn snapshot_value(gauge: &Gauge) -> Option<ItemKind> {
+    let mut snapshot = Snapshot::default();
+    gauge.put_exported_snapshot(&mut snapshot, false);
+    snapshot
+        .items
+        .into_iter()
+        .find(|(name, _)| name == "bytes")
+        .map(|(_, item)| item)
+}
+
+#[test]
+fn delta_mode_reports_differences_of_a_monotonic_sequence() {
+    let mut gauge = Gauge::new("bytes").mode(GaugeMode::Delta);
+
+    assert_eq!(snapshot_value(&gauge), None);
+
+    gauge.set(100.into());
+    assert_eq!(snapshot_value(&gauge), None);
+
+    gauge.set(150.into());
+    let mut snapshot = Snapshot::default();
+    gauge.put_snapshot(&mut snapshot, false);
+    assert_eq!(snapshot.find("bytes").opt(), Some(&ItemKind::Int(50)));
+    gauge.set(180.into());
+    assert_eq!(snapshot_value(&gauge), Some(ItemKind::Int(80)));
+
+    assert_eq!(snapshot_value(&gauge), Some(ItemKind::Int(0)));
+
+    gauge.set(200.into());
+    assert_eq!(snapshot_value(&gauge), Some(ItemKind::Int(20)));
+    assert_eq!(gauge.get(), Some(200));
+}
+
+#[test]
+fn delta_mode_does_not_report_negative_spikes_on_resets() {
+    let mut gauge = Gauge::new("bytes").mode(GaugeMode::Delta);
+
+    gauge.set(1_000.into());
+    assert_eq!(snapshot_value(&gauge), None);
+
+    gauge.set(1_500.into());
+    assert_eq!(snapshot_value(&gauge), Some(ItemKind::Int(500)));
+
+    gauge.set(30.into());
+    assert_eq!(snapshot_value(&gauge), Some(ItemKind::Int(30)));
+
+    gauge.set(70.into());
+    assert_eq!(snapshot_value(&gauge), Some(ItemKind::Int(40)));
+}
+
+#[test]
+fn delta_per_second_mode_reports_a_rate() {
+    use crate::instruments::fundamentals::ManualOffsetClock;
+
+    let clock = ManualOffsetClock::default();
+    let mut gauge = Gauge::new("bytes")
+        .mode(GaugeMode::DeltaPerSecond)
+        .clock(clock.clone());
+
+    gauge.set(0.into());
+    assert_eq!(snapshot_value(&gauge), None);
+
+    clock.advance_n_seconds(2);
+    gauge.set(1_000.into());
+
+    assert_eq!(snapshot_value(&gauge), Some(ItemKind::Float(500.0)));
+}
+
+#[test]
+fn smoothing_approaches_a_new_level_exponentially() {
+    let mut gauge = Gauge::new("gauge").smoothing(0.5);
+
+    gauge.set(0.into());
+    let mut expected_distance = 100.0;
+    for _ in 0..10 {
+        gauge.set(100.into());
+        expected_distance /= 2.0;
+
+        let mut snapshot = Snapshot::default();
+        gauge.put_snapshot(&mut snapshot, false);
+        match snapshot.find("gauge_smoothed") {
+            crate::snapshot::FindItem::Found(ItemKind::Float(smoothed)) => {
+                assert!(((100.0 - smoothed) - expected_distance).abs() < 0.0001)
+            }
+            other => panic!("no smoothed value: {}", other),
+        }
+    }
+
+    let mut snapshot = Snapshot::default();
+    gauge.put_snapshot(&mut snapshot, false);
+    assert_eq!(
+        snapshot.find("gauge"),
+        crate::snapshot::FindItem::Found(&ItemKind::Int(100))
+    );
+}
+
+#[test]
+fn first_value_initializes_smoothing() {
+    let mut gauge = Gauge::new("gauge").smoothing(0.1);
+
+    gauge.set(42.into());
+
+    let mut snapshot = Snapshot::default();
+    gauge.put_snapshot(&mut snapshot, false);
+    assert_eq!(
+        snapshot.find("gauge_smoothed"),
+        crate::snapshot::FindItem::Found(&ItemKind::Float(42.0))
+    );
+}
+
+#[test]
+#[should_panic]
+fn smoothing_alpha_must_be_valid() {
+    let _ = Gauge::new("gauge").smoothing(1.5);
+}
+
+#[test]
+fn auto_scaled_durations_are_readable() {
+    let mut gauge = Gauge::new("latency").auto_scale_durations(true);
+
+    gauge.set(ObservedValue::Duration(1_500, TimeUnit::Milliseconds));
+
+    let mut snapshot = Snapshot::default();
+    gauge.put_snapshot(&mut snapshot, false);
+    assert_eq!(
+        snapshot.find("latency"),
+        crate::snapshot::FindItem::Found(&ItemKind::Float(1.5))
+    );
+    assert_eq!(
+        snapshot.find("latency_unit"),
+        crate::snapshot::FindItem::Found(&ItemKind::Text("seconds".to_string()))
+    );
+}
+
+#[test]
+fn out_of_range_values_are_clamped_and_counted() {
+    let mut gauge = Gauge::new("cpu").clamp(0, 100);
+
+    gauge.set(50.into());
+    assert_eq!(gauge.get(), Some(50));
+    gauge.set(101.into());
+    assert_eq!(gauge.get(), Some(100));
+    gauge.set((-1).into());
+    assert_eq!(gauge.get(), Some(0));
+    gauge.set(crate::ChangeBy(-5).into());
+    assert_eq!(gauge.get(), Some(0));
+
+    let mut snapshot = Snapshot::default();
+    gauge.put_snapshot(&mut snapshot, false);
+    assert_eq!(
+        snapshot.find("cpu"),
+        crate::snapshot::FindItem::Found(&ItemKind::Int(0))
+    );
+    assert_eq!(
+        snapshot.find("cpu_clamped_count"),
+        crate::snapshot::FindItem::Found(&ItemKind::UInt(3))
+    );
+}
+
+#[test]
+fn a_clamp_range_with_a_single_value_pins_the_gauge() {
+    let mut gauge = Gauge::new("").clamp(7, 7);
+
+    gauge.set(7.into());
+    gauge.set(3.into());
+    gauge.set(12.into());
+
+    assert_eq!(gauge.get(), Some(7));
+    assert_eq!(gauge.clamped_count, 2);
+}
+
+#[test]
+fn a_stale_value_expires() {
+    use crate::instruments::fundamentals::ManualOffsetClock;
+    use std::time::Duration;
+
+    let clock = ManualOffsetClock::default();
+    let mut gauge = Gauge::new("temperature")
+        .clock(clock.clone())
+        .expire_after(Duration::from_secs(10));
+
+    gauge.set(21.into());
+    clock.advance_n_seconds(10);
+    assert_eq!(gauge.get(), Some(21));
+
+    clock.advance_a_second();
+    assert_eq!(gauge.get(), None);
+    let mut snapshot = Snapshot::default();
+    gauge.put_snapshot(&mut snapshot, false);
+    assert_eq!(
+        snapshot.find("temperature"),
+        crate::snapshot::FindItem::NotFound
+    );
+
+    gauge.set(crate::ChangeBy(2).into());
+    assert_eq!(gauge.get(), Some(2));
+    gauge.set(22.into());
+    assert_eq!(gauge.get(), Some(22));
+}
+
+#[test]
+fn relative_input_adjusts_the_level() {
+    let mut gauge = Gauge::new("pool_size").input_mode(GaugeInputMode::Relative);
+
+    gauge.set(3.into());
+    assert_eq!(gauge.get(), Some(3));
+    gauge.set((-1).into());
+    gauge.set(2.into());
+    assert_eq!(gauge.get(), Some(4));
+}
+
+#[test]
+fn fractional_values_are_not_rounded() {
+    let mut gauge = Gauge::new("ratio");
+    let snapshot_value = |gauge: &Gauge| {
+        let mut snapshot = Snapshot::default();
+        gauge.put_snapshot(&mut snapshot, false);
+        snapshot.find("ratio").opt().cloned()
+    };
+
+    gauge.set(0.5.into());
+    assert_eq!(gauge.get_f64(), Some(0.5));
+    assert_eq!(snapshot_value(&gauge), Some(ItemKind::Float(0.5)));
+
+    gauge.set(1.5.into());
+    assert_eq!(gauge.get_f64(), Some(1.5));
+    assert_eq!(snapshot_value(&gauge), Some(ItemKind::Float(1.5)));
+
+    gauge.set(3.into());
+    assert_eq!(gauge.get_f64(), Some(3.0));
+    assert_eq!(snapshot_value(&gauge), Some(ItemKind::Int(3)));
+}
+
+#[test]
+fn fractional_values_out_of_range_are_clamped_and_counted() {
+    let mut gauge = Gauge::new("cpu").clamp(0, 100);
+    let snapshot_value = |gauge: &Gauge| {
+        let mut snapshot = Snapshot::default();
+        gauge.put_snapshot(&mut snapshot, false);
+        snapshot.find("cpu").opt().cloned()
+    };
+
+    gauge.set(100.4.into());
+    assert_eq!(gauge.get_f64(), Some(100.0));
+    assert_eq!(snapshot_value(&gauge), Some(ItemKind::Float(100.0)));
+    assert_eq!(gauge.clamped_count, 1);
+
+    gauge.set((-0.3).into());
+    assert_eq!(gauge.get_f64(), Some(0.0));
+    assert_eq!(snapshot_value(&gauge), Some(ItemKind::Float(0.0)));
+    assert_eq!(gauge.clamped_count, 2);
+
+    gauge.set(99.6.into());
+    assert_eq!(gauge.get_f64(), Some(99.6));
+    assert_eq!(gauge.clamped_count, 2);
+}
+
+#[test]
+fn fractional_values_are_smoothed_and_tracked_as_watermarks() {
+    let mut gauge = Gauge::new("ratio").smoothing(0.5).watermarks_enabled(true);
+
+    gauge.set(0.25.into());
+    gauge.set(0.75.into());
+    gauge.set(crate::ChangeBy(-1).into());
+
+    let mut snapshot = Snapshot::default();
+    gauge.put_snapshot(&mut snapshot, false);
+    assert_eq!(snapshot.find("ratio").opt(), Some(&ItemKind::Float(-0.25)));
+    assert_eq!(
+        snapshot.find("ratio_smoothed").opt(),
+        Some(&ItemKind::Float(0.125))
+    );
+    assert_eq!(
+        snapshot.find("ratio_min").opt(),
+        Some(&ItemKind::Float(-0.25))
+    );
+    assert_eq!(
+        snapshot.find("ratio_max").opt(),
+        Some(&ItemKind::Float(0.75))
+    );
+    assert_eq!(
+        snapshot.find("ratio_peak").opt(),
+        Some(&ItemKind::Float(0.75))
+    );
+}
@@ -1,38 +1,174 @@
+use std::cell::Cell;
 use std::time::{Duration, Instant};
 
 use exponential_decay_histogram::ExponentialDecayHistogram;
 
+use crate::instruments::fundamentals::decaying_reservoir::{DecayingReservoir, ReservoirSnapshot};
+use crate::instruments::fundamentals::hdr_histogram::HdrHistogram;
+use crate::instruments::fundamentals::sliding_window::{SlidingWindow, WindowSnapshot};
+use crate::instruments::fundamentals::SharedClock;
 use crate::instruments::{
-    AcceptAllLabels, Instrument, InstrumentAdapter, LabelFilter, LabelPredicate, Update, Updates,
+    AcceptAllLabels, Clock, Instrument, InstrumentAdapter, LabelFilter, LabelPredicate, Update,
+    Updates,
 };
 use crate::snapshot::{ItemKind, Snapshot};
 use crate::util;
 use crate::{Descriptive, ObservedValue, PutsSnapshot, TimeUnit};
 
+const DEFAULT_QUANTILES: &[f64] = &[0.5, 0.75, 0.95, 0.98, 0.99, 0.999];
+
 /// For tracking values. E.g. request latencies
+///
+/// The quantiles are put into a nested `Snapshot` named `quantiles`
+/// where the name of a quantile is made of the digits after the
+/// decimal point with at least two digits. E.g. `p50` for 0.5 and
+/// `p999` for 0.999.
+///
+/// By default the values are kept in an exponentially decaying reservoir
+/// which favours recent values. A histogram created with `new_hdr` uses
+/// logarithmic buckets instead which keep the precision for values
+/// spanning several orders of magnitude but do not decay.
+/// A histogram created with `with_window` only considers the
+/// values observed within a trailing time window. A histogram created
+/// with `with_decaying_reservoir` keeps a sample of configurable size
+/// where recent values dominate.
+///
+/// The snapshot always contains the `count` and the `sum` of the values,
+/// even if nothing was observed yet. For a histogram created with
+/// `with_window` both only cover the values within the window. Otherwise
+/// they cover all values since creation or the last reset. Durations are
+/// summed up in the display time unit.
+///
+/// A count of all values since creation which is neither limited to
+/// the window nor cleared can be added. See `set_total_count_enabled`.
+///
+/// An `Observation::ObservedValues` records its value as often as
+/// given by its count. The default histogram keeps a reservoir of
+/// 1028 samples and therefore records at most 1028 copies of a value
+/// at once. Its `count` and `sum` still include all of them. All
+/// other histograms record the count without copying the value.
+///
+/// Values are recorded as integers. Unless fraction digits are set the
+/// histogram switches to 3 fraction digits when it observes the first
+/// float with a fraction so that floats are not rounded.
+/// See `set_fraction_digits`.
+///
+/// Additionally cumulative counts for buckets with fixed upper bounds
+/// can be tracked. See `set_buckets`.
+///
+/// For non-overlapping windows aligned to the reporting interval the
+/// values can be cleared whenever a `Snapshot` is exported.
+/// See `set_clear_on_snapshot`.
 pub struct Histogram {
     name: String,
     title: Option<String>,
     description: Option<String>,
-    inner_histogram: ExponentialDecayHistogram,
+    inner_histogram: Backend,
     last_update: Instant,
     max_inactivity_duration: Option<Duration>,
     reset_after_inactivity: bool,
-    display_time_unit: TimeUnit,
+    display_time_unit: Option<TimeUnit>,
+    quantiles: Vec<f64>,
+    buckets: Option<Buckets>,
+    count: u64,
+    sum: i64,
+    update_tracker: util::UpdateTracker,
+    clear_on_snapshot: bool,
+    cleared_by_snapshot: Cell<bool>,
+    trim_fraction: Option<f64>,
+    total_count: u64,
+    total_count_enabled: bool,
+    scale: i64,
+    auto_fraction_digits: bool,
+    clock: SharedClock,
 }
 
 impl Histogram {
     pub fn new<T: Into<String>>(name: T) -> Histogram {
-        let inner_histogram = ExponentialDecayHistogram::new();
+        Self::with_backend(name, Backend::Decaying(ExponentialDecayHistogram::new()))
+    }
+
+    /// Creates a `Histogram` that records values with HdrHistogram style
+    /// logarithmic buckets.
+    ///
+    /// Values from 0 up to `max_value` are tracked with a relative error
+    /// given by `significant_digits`. E.g. with 3 significant digits
+    /// a reported quantile is off by at most 0.1%. Negative values are
+    /// recorded as 0 and values above `max_value` as `max_value`.
+    ///
+    /// `max_value` is given in the unit of the observed values. It is
+    /// scaled along with the values when fraction digits are set.
+    ///
+    /// # Panics
+    ///
+    /// If `significant_digits` is not within `1..=5` or `max_value`
+    /// is smaller than 2.
+    pub fn new_hdr<T: Into<String>>(name: T, max_value: u64, significant_digits: u8) -> Histogram {
+        Self::with_backend(
+            name,
+            Backend::Hdr(HdrHistogram::new(max_value, significant_digits)),
+        )
+    }
+
+    /// Creates a `Histogram` that only considers values observed within
+    /// the last `window`.
+    ///
+    /// All values within the window are kept so this should not be used
+    /// for long windows with many observations.
+    pub fn with_window<T: Into<String>>(name: T, window: Duration) -> Histogram {
+        Self::with_backend(
+            name,
+            Backend::Windowed(SlidingWindow::with_clock(window, SharedClock::default())),
+        )
+    }
+
+    /// Creates a `Histogram` that keeps a sample of `size` values where
+    /// more recent values are more likely to be kept and weigh more.
+    ///
+    /// The weight of a value grows by `e^alpha` per second. So with an
+    /// `alpha` of 0.015 a value weighs about 2.5 times as much as a value
+    /// observed a minute earlier. Unlike the default histogram this
+    /// reflects a shift of the distribution after a bounded number of
+    /// observations.
+    ///
+    /// # Panics
+    ///
+    /// If `size` is 0 or `alpha` is not positive.
+    pub fn with_decaying_reservoir<T: Into<String>>(name: T, size: usize, alpha: f64) -> Histogram {
+        Self::with_backend(
+            name,
+            Backend::Reservoir(DecayingReservoir::with_clock(
+                size,
+                alpha,
+                SharedClock::default(),
+            )),
+        )
+    }
+
+    fn with_backend<T: Into<String>>(name: T, inner_histogram: Backend) -> Histogram {
+        let clock = SharedClock::default();
         Histogram {
             name: name.into(),
             title: None,
             description: None,
             inner_histogram,
-            last_update: Instant::now(),
+            last_update: clock.now(),
             max_inactivity_duration: None,
             reset_after_inactivity: true,
-            display_time_unit: TimeUnit::default(),
+            display_time_unit: None,
+            quantiles: DEFAULT_QUANTILES.to_vec(),
+            buckets: None,
+            count: 0,
+            sum: 0,
+            update_tracker: util::UpdateTracker::default(),
+            clear_on_snapshot: false,
+            cleared_by_snapshot: Cell::new(false),
+            trim_fraction: None,
+            total_count: 0,
+            total_count_enabled: false,
+            scale: 1,
+            auto_fraction_digits: true,
+            clock,
         }
     }
 
@@ -40,6 +176,37 @@ impl Histogram {
         Self::new(name)
     }
 
+    /// Returns a histogram with the same configuration which
+    /// has not observed anything yet.
+    pub(crate) fn template_clone(&self) -> Histogram {
+        Histogram {
+            name: self.name.clone(),
+            title: self.title.clone(),
+            description: self.description.clone(),
+            inner_histogram: self.inner_histogram.template_clone(&self.clock),
+            last_update: self.clock.now(),
+            max_inactivity_duration: self.max_inactivity_duration,
+            reset_after_inactivity: self.reset_after_inactivity,
+            display_time_unit: self.display_time_unit,
+            quantiles: self.quantiles.clone(),
+            buckets: self
+                .buckets
+                .as_ref()
+                .map(|buckets| Buckets::new(&buckets.bounds)),
+            count: 0,
+            sum: 0,
+            update_tracker: self.update_tracker.template(),
+            clear_on_snapshot: self.clear_on_snapshot,
+            cleared_by_snapshot: Cell::new(false),
+            trim_fraction: self.trim_fraction,
+            total_count: 0,
+            total_count_enabled: self.total_count_enabled,
+            scale: self.scale,
+            auto_fraction_digits: self.auto_fraction_digits,
+            clock: self.clock.clone(),
+        }
+    }
+
     pub fn get_name(&self) -> &str {
         &self.name
     }
@@ -107,14 +274,265 @@ impl Histogram {
         self
     }
 
+    /// Sets the `Clock` used for the window, the decaying reservoir,
+    /// inactivity tracking and the time since the last update.
+    ///
+    /// All values observed so far are discarded so this should be done
+    /// right after creation. Mostly useful for testing with
+    /// a `ManualOffsetClock`.
+    ///
+    /// Default is the `WallClock`.
+    pub fn set_clock<C: Clock + Send + Sync + 'static>(&mut self, clock: C) {
+        self.clock = SharedClock::new(clock);
+        self.update_tracker.set_clock(self.clock.clone());
+        self.inner_histogram = self.inner_histogram.template_clone(&self.clock);
+        self.reset();
+    }
+
+    /// Sets the `Clock` used for the window, the decaying reservoir,
+    /// inactivity tracking and the time since the last update.
+    ///
+    /// Default is the `WallClock`.
+    pub fn clock<C: Clock + Send + Sync + 'static>(mut self, clock: C) -> Self {
+        self.set_clock(clock);
+        self
+    }
+
+    /// Enables tracking when this histogram was updated last.
+    ///
+    /// The number of seconds since the last update will be put into
+    /// the `Snapshot` as `_updated_secs_ago`. Nothing is added before the first update.
+    ///
+    /// Default is disabled.
+    pub fn set_updated_secs_ago_enabled(&mut self, enabled: bool) {
+        self.update_tracker.set_enabled(enabled);
+    }
+
+    /// Enables tracking when this histogram was updated last.
+    ///
+    /// The number of seconds since the last update will be put into
+    /// the `Snapshot` as `_updated_secs_ago`. Nothing is added before the first update.
+    ///
+    /// Default is disabled.
+    pub fn updated_secs_ago_enabled(mut self, enabled: bool) -> Self {
+        self.set_updated_secs_ago_enabled(enabled);
+        self
+    }
+
+    /// Sets the unit durations will be converted to.
+    ///
+    /// If set, the unit will be part of a descriptive `Snapshot`
+    /// as `_unit_[name]`.
+    ///
+    /// Default is `TimeUnit::Microseconds`.
     pub fn set_display_time_unit(&mut self, display_time_unit: TimeUnit) {
-        self.display_time_unit = display_time_unit
+        self.display_time_unit = Some(display_time_unit)
     }
+    /// Sets the unit durations will be converted to.
+    ///
+    /// If set, the unit will be part of a descriptive `Snapshot`
+    /// as `_unit_[name]`.
+    ///
+    /// Default is `TimeUnit::Microseconds`.
     pub fn display_time_unit(mut self, display_time_unit: TimeUnit) -> Self {
         self.set_display_time_unit(display_time_unit);
         self
     }
 
+    /// Sets the display time unit unless one was already set
+    pub(crate) fn set_default_display_time_unit(&mut self, display_time_unit: TimeUnit) {
+        if self.display_time_unit.is_none() {
+            self.display_time_unit = Some(display_time_unit)
+        }
+    }
+
+    /// Sets the quantiles to be put into a `Snapshot`.
+    ///
+    /// The default is `[0.5, 0.75, 0.95, 0.98, 0.99, 0.999]`.
+    ///
+    /// # Panics
+    ///
+    /// If a quantile is not within `0.0..=1.0`.
+    pub fn set_quantiles(&mut self, quantiles: &[f64]) {
+        for q in quantiles {
+            if !(0.0..=1.0).contains(q) {
+                panic!("quantile {} is not within 0.0..=1.0", q);
+            }
+        }
+        self.quantiles = quantiles.to_vec();
+    }
+
+    /// Sets the quantiles to be put into a `Snapshot`.
+    ///
+    /// The default is `[0.5, 0.75, 0.95, 0.98, 0.99, 0.999]`.
+    ///
+    /// # Panics
+    ///
+    /// If a quantile is not within `0.0..=1.0`.
+    pub fn quantiles(mut self, quantiles: &[f64]) -> Self {
+        self.set_quantiles(quantiles);
+        self
+    }
+
+    /// Enables counting values in buckets with the given upper bounds
+    /// as known from Prometheus histograms.
+    ///
+    /// The `Snapshot` will contain the cumulative count of all values
+    /// less than or equal to a bound as `bucket_[bound]` and the
+    /// count of all values as `bucket_inf`. Negative values are counted
+    /// in the first bucket. The sum of all counted values is put as
+    /// `bucket_sum_total`.
+    ///
+    /// The bounds will be sorted and duplicates removed. Unlike the
+    /// quantiles the buckets count all values since creation or
+    /// the last reset of the histogram. So `bucket_sum_total` can differ
+    /// from `sum` which only covers the window of a windowed histogram.
+    pub fn set_buckets(&mut self, bounds: &[u64]) {
+        self.buckets = Some(Buckets::new(bounds));
+    }
+
+    /// Enables counting values in buckets with the given upper bounds
+    /// as known from Prometheus histograms.
+    ///
+    /// See `set_buckets`.
+    pub fn buckets(mut self, bounds: &[u64]) -> Self {
+        self.set_buckets(bounds);
+        self
+    }
+
+    /// If enabled all values are cleared right after they have been put
+    /// into an exported `Snapshot`. The next `Snapshot` then only contains
+    /// the values observed in between. This applies to descriptive and
+    /// non-descriptive snapshots alike. See
+    /// `PutsSnapshot::put_exported_snapshot`.
+    ///
+    /// The count and sum as well as the bucket counts then also only cover
+    /// the values since the last exported `Snapshot`.
+    ///
+    /// Default is disabled.
+    pub fn set_clear_on_snapshot(&mut self, enabled: bool) {
+        self.clear_on_snapshot = enabled;
+    }
+
+    /// If enabled all values are cleared right after they have been put
+    /// into an exported `Snapshot`.
+    ///
+    /// Default is disabled.
+    pub fn clear_on_snapshot(mut self, enabled: bool) -> Self {
+        self.set_clear_on_snapshot(enabled);
+        self
+    }
+
+    /// Enables a trimmed mean which discards the given fraction of the
+    /// lowest and the highest values. It is put into the `Snapshot`
+    /// as `trimmed_mean`.
+    ///
+    /// The trimmed mean is approximated from the quantiles of the
+    /// histogram. A fraction of `0.0` reports the regular mean. If less
+    /// than one value would remain after trimming the median is reported.
+    ///
+    /// Default is disabled.
+    ///
+    /// # Panics
+    ///
+    /// If the fraction is not within `0.0..0.5`.
+    pub fn set_trimmed_mean(&mut self, fraction: f64) {
+        if !(0.0..0.5).contains(&fraction) {
+            panic!("trim fraction {} is not within 0.0..0.5", fraction);
+        }
+        self.trim_fraction = Some(fraction);
+    }
+
+    /// Enables a trimmed mean which discards the given fraction of the
+    /// lowest and the highest values.
+    ///
+    /// See `set_trimmed_mean`.
+    ///
+    /// # Panics
+    ///
+    /// If the fraction is not within `0.0..0.5`.
+    pub fn trimmed_mean(mut self, fraction: f64) -> Self {
+        self.set_trimmed_mean(fraction);
+        self
+    }
+
+    /// Keeps the given number of digits after the decimal point of
+    /// the observed values instead of rounding them to integers.
+    ///
+    /// The values are recorded multiplied by `10^digits`. The `sum`,
+    /// `max`, `min`, `mean`, `stddev` and the quantiles are then put into
+    /// the `Snapshot` as floats. The values observed so far are cleared.
+    ///
+    /// Default is 0 until the first float with a fraction is observed
+    /// which switches to 3 digits and clears the values observed before.
+    /// Setting 0 explicitly keeps rounding floats.
+    ///
+    /// # Panics
+    ///
+    /// If `digits` is greater than 9.
+    pub fn set_fraction_digits(&mut self, digits: u32) {
+        self.auto_fraction_digits = false;
+        self.apply_fraction_digits(digits);
+    }
+
+    fn apply_fraction_digits(&mut self, digits: u32) {
+        assert!(digits <= 9, "at most 9 fraction digits are supported");
+        let scale = 10i64.pow(digits);
+        if let Backend::Hdr(ref mut histogram) = self.inner_histogram {
+            let max_value =
+                (histogram.max_value() / self.scale as u64).saturating_mul(scale as u64);
+            *histogram = HdrHistogram::new(max_value, histogram.significant_digits());
+        }
+        self.scale = scale;
+        self.reset_values();
+    }
+
+    /// Keeps the given number of digits after the decimal point of
+    /// the observed values instead of rounding them to integers.
+    ///
+    /// See `set_fraction_digits`.
+    ///
+    /// # Panics
+    ///
+    /// If `digits` is greater than 9.
+    pub fn fraction_digits(mut self, digits: u32) -> Self {
+        self.set_fraction_digits(digits);
+        self
+    }
+
+    /// Enables putting the number of values observed since creation
+    /// into the `Snapshot` as `total_count`.
+    ///
+    /// Unlike the `count` the total count is neither limited to the
+    /// window nor cleared on a snapshot or after inactivity. Only
+    /// `reset` sets it back to zero. This gives a monotonic count next
+    /// to windowed quantiles.
+    ///
+    /// Default is disabled.
+    pub fn set_total_count_enabled(&mut self, enabled: bool) {
+        self.total_count_enabled = enabled;
+    }
+
+    /// Enables putting the number of values observed since creation
+    /// into the `Snapshot` as `total_count`.
+    ///
+    /// See `set_total_count_enabled`.
+    ///
+    /// Default is disabled.
+    pub fn total_count_enabled(mut self, enabled: bool) -> Self {
+        self.set_total_count_enabled(enabled);
+        self
+    }
+
+    /// Returns the number of values observed since creation
+    /// or the last reset.
+    ///
+    /// Unlike the `count` in the `Snapshot` this is neither limited
+    /// to a window nor cleared on a snapshot.
+    pub fn total_count(&self) -> u64 {
+        self.total_count
+    }
+
     pub fn accept<L: Eq + Send + 'static, F: Into<LabelFilter<L>>>(
         self,
         accept: F,
@@ -158,9 +576,13 @@ impl Histogram {
         InstrumentAdapter::deaf(self)
     }
 
+    fn inactive_for(&self) -> Duration {
+        self.clock.now().saturating_duration_since(self.last_update)
+    }
+
     fn put_values_into_snapshot(&self, into: &mut Snapshot) {
         if let Some(d) = self.max_inactivity_duration {
-            if self.last_update.elapsed() > d {
+            if self.inactive_for() > d {
                 into.items
                     .push(("_inactive".to_string(), ItemKind::Boolean(true)));
                 into.items
@@ -174,76 +596,206 @@ impl Histogram {
             }
         };
 
-        let snapshot = self.inner_histogram.snapshot();
+        if self.total_count_enabled {
+            into.items
+                .push(("total_count".to_string(), self.total_count.into()));
+        }
+
+        // Taking a snapshot does not allow to clear the values right away.
+        // So they are skipped here and actually cleared on the next update.
+        if self.cleared_by_snapshot.get() {
+            HistogramSnapshot::default().put_snapshot(into, self.scale);
+            if let Some(ref buckets) = self.buckets {
+                buckets.put_empty_snapshot(into, self.scale);
+            }
+            return;
+        }
 
-        let histo_snapshot = if snapshot.count() > 0 {
-            let quantiles = vec![
-                (50u16, snapshot.value(0.5)),
-                (75u16, snapshot.value(0.75)),
-                (95u16, snapshot.value(0.95)),
-                (98u16, snapshot.value(0.98)),
-                (99u16, snapshot.value(0.99)),
-                (999u16, snapshot.value(0.999)),
-            ];
+        let distribution = self.inner_histogram.distribution();
+        // A backend which keeps only a sample of the values
+        // does not know how many values there were.
+        let (count, sum) = match distribution.sum() {
+            Some(sum) => (distribution.count(), sum),
+            None => (self.count, self.sum),
+        };
+        let histo_snapshot = if distribution.count() > 0 {
+            let quantiles = self
+                .quantiles
+                .iter()
+                .map(|&q| (quantile_name(q), distribution.value(q)))
+                .collect();
 
             HistogramSnapshot {
-                min: Some(snapshot.min()),
-                max: Some(snapshot.max()),
-                mean: Some(snapshot.mean()),
-                stddev: Some(snapshot.stddev()),
-                count: snapshot.count(),
+                min: Some(distribution.min()),
+                max: Some(distribution.max()),
+                mean: Some(distribution.mean()),
+                trimmed_mean: self.trim_fraction.map(|fraction| {
+                    trimmed_mean(fraction, distribution.count(), distribution.mean(), |q| {
+                        distribution.value(q)
+                    })
+                }),
+                stddev: Some(distribution.stddev()),
+                count,
+                sum,
                 quantiles,
             }
         } else {
             HistogramSnapshot::default()
         };
 
-        histo_snapshot.put_snapshot(into);
+        histo_snapshot.put_snapshot(into, self.scale);
+
+        if let Some(ref buckets) = self.buckets {
+            buckets.put_snapshot(into, self.scale);
+        }
+    }
+
+    fn reset_values(&mut self) {
+        self.cleared_by_snapshot.set(false);
+        self.inner_histogram.reset();
+        self.count = 0;
+        self.sum = 0;
+        if let Some(ref mut buckets) = self.buckets {
+            buckets.reset();
+        }
+    }
+
+    fn scaled(&self, value: ObservedValue) -> Option<i64> {
+        match value {
+            ObservedValue::Float(v) if self.scale != 1 => {
+                let scaled = v * self.scale as f64;
+                if scaled.is_finite() && scaled.abs() <= i64::MAX as f64 {
+                    Some(scaled.round() as i64)
+                } else {
+                    None
+                }
+            }
+            value => value.convert_to_i64().map(|v| v.saturating_mul(self.scale)),
+        }
+    }
+
+    fn record(&mut self, timestamp: Instant, value: i64) {
+        self.record_n(timestamp, value, 1)
+    }
+
+    fn record_n(&mut self, timestamp: Instant, value: i64, n: u64) {
+        if timestamp > self.last_update {
+            self.inner_histogram.update_n_at(timestamp, value, n);
+            self.last_update = timestamp
+        } else {
+            self.inner_histogram.update_n(value, n);
+            self.last_update = self.clock.now();
+        }
+        if let Some(ref mut buckets) = self.buckets {
+            buckets.update_n(value, n, self.scale);
+        }
+        self.total_count = self.total_count.saturating_add(n);
+        self.count = self.count.saturating_add(n);
+        self.sum = self
+            .sum
+            .saturating_add(value.saturating_mul(n.min(i64::MAX as u64) as i64));
     }
 }
 
-impl Instrument for Histogram {}
+impl Instrument for Histogram {
+    fn name(&self) -> Option<&str> {
+        Some(self.get_name())
+    }
 
-impl PutsSnapshot for Histogram {
-    fn put_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
+    fn reset(&mut self) {
+        self.reset_values();
+        self.total_count = 0;
+        self.last_update = self.clock.now();
+        self.update_tracker.reset();
+    }
+}
+
+impl Histogram {
+    fn put_values(&self, into: &mut Snapshot, descriptive: bool, exported: bool) {
         util::put_postfixed_descriptives(self, &self.name, into, descriptive);
+        util::put_postfixed_time_unit(self.display_time_unit, &self.name, into, descriptive);
         let mut new_level = Snapshot::default();
         self.put_values_into_snapshot(&mut new_level);
+        self.update_tracker.put(&mut new_level);
         into.push(self.name.clone(), ItemKind::Snapshot(new_level));
+        if self.clear_on_snapshot && exported {
+            self.cleared_by_snapshot.set(true);
+        }
+    }
+}
+
+impl PutsSnapshot for Histogram {
+    fn put_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
+        self.put_values(into, descriptive, false)
+    }
+
+    fn put_exported_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
+        self.put_values(into, descriptive, true)
+    }
+
+    fn put_names(&self, into: &mut Snapshot) {
+        util::put_name(&self.name, into)
     }
 }
 
 impl Updates for Histogram {
     fn update(&mut self, with: &Update) -> usize {
         if let Some(d) = self.max_inactivity_duration {
-            if self.reset_after_inactivity && self.last_update.elapsed() > d {
-                self.inner_histogram = ExponentialDecayHistogram::new()
+            if self.reset_after_inactivity && self.inactive_for() > d {
+                self.reset_values()
             }
         };
 
-        self.last_update = Instant::now();
+        if self.cleared_by_snapshot.get() {
+            self.reset_values();
+        }
+
+        self.last_update = self.clock.now();
+        self.update_tracker.updated();
+
+        if let Update::ObservationWithValue(ObservedValue::Float(v), _)
+        | Update::ObservationsWithValue(ObservedValue::Float(v), _, _) = *with
+        {
+            if self.auto_fraction_digits && v.is_finite() && v.fract() != 0.0 {
+                self.auto_fraction_digits = false;
+                self.apply_fraction_digits(AUTO_FRACTION_DIGITS);
+            }
+        }
 
         match *with {
             Update::ObservationWithValue(ObservedValue::Duration(time, time_unit), timestamp) => {
-                let d = super::duration_to_display_value(time, time_unit, self.display_time_unit);
-                if timestamp > self.last_update {
-                    self.inner_histogram.update_at(timestamp, d as i64);
-                    self.last_update = timestamp
+                let d = super::duration_to_display_value(
+                    time,
+                    time_unit,
+                    self.display_time_unit.unwrap_or_default(),
+                );
+                self.record(timestamp, (d as i64).saturating_mul(self.scale));
+                1
+            }
+            Update::ObservationWithValue(v, timestamp) => {
+                if let Some(v) = self.scaled(v) {
+                    self.record(timestamp, v);
+                    1
                 } else {
-                    self.inner_histogram.update(d as i64);
-                    self.last_update = Instant::now();
+                    0
                 }
+            }
+            Update::ObservationsWithValue(
+                ObservedValue::Duration(time, time_unit),
+                n,
+                timestamp,
+            ) => {
+                let d = super::duration_to_display_value(
+                    time,
+                    time_unit,
+                    self.display_time_unit.unwrap_or_default(),
+                );
+                self.record_n(timestamp, (d as i64).saturating_mul(self.scale), n);
                 1
             }
-            Update::ObservationWithValue(v, timestamp) => {
-                if let Some(v) = v.convert_to_i64() {
-                    if timestamp > self.last_update {
-                        self.inner_histogram.update_at(timestamp, v);
-                        self.last_update = timestamp
-                    } else {
-                        self.inner_histogram.update(v);
-                        self.last_update = Instant::now();
-                    }
+            Update::ObservationsWithValue(v, n, timestamp) => {
+                if let Some(v) = self.scaled(v) {
+                    self.record_n(timestamp, v, n);
                     1
                 } else {
                     0
@@ -265,13 +817,255 @@ impl Descriptive for Histogram {
     }
 }
 
+/// The statistics of the values recorded by a `Backend`
+trait Distribution {
+    fn count(&self) -> u64;
+    fn min(&self) -> i64;
+    fn max(&self) -> i64;
+    fn mean(&self) -> f64;
+    fn stddev(&self) -> f64;
+    fn value(&self, quantile: f64) -> i64;
+
+    /// The sum of the recorded values if it only covers the values
+    /// kept by the backend, e.g. those within a window. The `count`
+    /// then also only covers these values.
+    ///
+    /// Otherwise the count and sum of all values are reported.
+    fn sum(&self) -> Option<i64>;
+}
+
+macro_rules! impl_distribution {
+    ($type:ty) => {
+        impl_distribution!($type, |_| None);
+    };
+    ($type:ty, $sum:expr) => {
+        impl Distribution for $type {
+            fn count(&self) -> u64 {
+                <$type>::count(self)
+            }
+
+            fn min(&self) -> i64 {
+                <$type>::min(self)
+            }
+
+            fn max(&self) -> i64 {
+                <$type>::max(self)
+            }
+
+            fn mean(&self) -> f64 {
+                <$type>::mean(self)
+            }
+
+            fn stddev(&self) -> f64 {
+                <$type>::stddev(self)
+            }
+
+            fn value(&self, quantile: f64) -> i64 {
+                <$type>::value(self, quantile)
+            }
+
+            fn sum(&self) -> Option<i64> {
+                $sum(self)
+            }
+        }
+    };
+}
+
+impl_distribution!(exponential_decay_histogram::Snapshot);
+impl_distribution!(ReservoirSnapshot);
+impl_distribution!(HdrHistogram);
+impl_distribution!(WindowSnapshot, |window: &WindowSnapshot| Some(window.sum()));
+
+impl<D: Distribution> Distribution for &D {
+    fn count(&self) -> u64 {
+        (**self).count()
+    }
+
+    fn min(&self) -> i64 {
+        (**self).min()
+    }
+
+    fn max(&self) -> i64 {
+        (**self).max()
+    }
+
+    fn mean(&self) -> f64 {
+        (**self).mean()
+    }
+
+    fn stddev(&self) -> f64 {
+        (**self).stddev()
+    }
+
+    fn value(&self, quantile: f64) -> i64 {
+        (**self).value(quantile)
+    }
+
+    fn sum(&self) -> Option<i64> {
+        (**self).sum()
+    }
+}
+
+/// The fraction digits a `Histogram` switches to when it observes
+/// a float with a fraction and no fraction digits were set.
+const AUTO_FRACTION_DIGITS: u32 = 3;
+
+/// The most copies of a value the default backend records for a
+/// single `Observation::ObservedValues`.
+///
+/// This is the size of its reservoir so more copies would only
+/// replace each other. The `Histogram` counts all copies itself.
+const MAX_DECAYING_COPIES: u64 = 1028;
+
+enum Backend {
+    Decaying(ExponentialDecayHistogram),
+    Windowed(SlidingWindow<SharedClock>),
+    Reservoir(DecayingReservoir<SharedClock>),
+    Hdr(HdrHistogram),
+}
+
+impl Backend {
+    /// Returns an empty backend with the same configuration
+    /// which uses `clock`
+    fn template_clone(&self, clock: &SharedClock) -> Backend {
+        match self {
+            Backend::Decaying(_) => Backend::Decaying(ExponentialDecayHistogram::new()),
+            Backend::Windowed(window) => {
+                Backend::Windowed(SlidingWindow::with_clock(window.window(), clock.clone()))
+            }
+            Backend::Reservoir(reservoir) => Backend::Reservoir(DecayingReservoir::with_clock(
+                reservoir.size(),
+                reservoir.alpha(),
+                clock.clone(),
+            )),
+            Backend::Hdr(histogram) => {
+                let mut histogram = histogram.clone();
+                histogram.clear();
+                Backend::Hdr(histogram)
+            }
+        }
+    }
+
+    fn distribution(&self) -> Box<dyn Distribution + '_> {
+        match self {
+            Backend::Decaying(histogram) => Box::new(histogram.snapshot()),
+            Backend::Windowed(window) => Box::new(window.snapshot()),
+            Backend::Reservoir(reservoir) => Box::new(reservoir.snapshot()),
+            Backend::Hdr(histogram) => Box::new(histogram),
+        }
+    }
+
+    fn update_n(&mut self, value: i64, n: u64) {
+        match self {
+            Backend::Decaying(histogram) => {
+                (0..n.min(MAX_DECAYING_COPIES)).for_each(|_| histogram.update(value))
+            }
+            Backend::Windowed(window) => window.update_n(value, n),
+            Backend::Reservoir(reservoir) => reservoir.update_n(value, n),
+            Backend::Hdr(histogram) => histogram.update_n(value, n),
+        }
+    }
+
+    fn update_n_at(&mut self, timestamp: Instant, value: i64, n: u64) {
+        match self {
+            Backend::Decaying(histogram) => {
+                (0..n.min(MAX_DECAYING_COPIES)).for_each(|_| histogram.update_at(timestamp, value))
+            }
+            Backend::Windowed(window) => window.update_n_at(timestamp, value, n),
+            Backend::Reservoir(reservoir) => reservoir.update_n_at(timestamp, value, n),
+            Backend::Hdr(histogram) => histogram.update_n(value, n),
+        }
+    }
+
+    fn reset(&mut self) {
+        match self {
+            Backend::Decaying(histogram) => *histogram = ExponentialDecayHistogram::new(),
+            Backend::Windowed(window) => window.clear(),
+            Backend::Reservoir(reservoir) => reservoir.clear(),
+            Backend::Hdr(histogram) => histogram.clear(),
+        }
+    }
+}
+
+/// Cumulative counts of values below fixed upper bounds
+struct Buckets {
+    bounds: Vec<u64>,
+    counts: Vec<u64>,
+    count: u64,
+    /// The sum of the counted values multiplied by the scale
+    sum: i64,
+}
+
+impl Buckets {
+    fn new(bounds: &[u64]) -> Buckets {
+        let mut bounds = bounds.to_vec();
+        bounds.sort_unstable();
+        bounds.dedup();
+        Buckets {
+            counts: vec![0; bounds.len()],
+            bounds,
+            count: 0,
+            sum: 0,
+        }
+    }
+
+    /// Counts a value multiplied by `scale` `n` times
+    fn update_n(&mut self, scaled_value: i64, n: u64, scale: i64) {
+        let value = unscale_rounding_up(scaled_value, scale);
+        let first_matching = if value < 0 {
+            0
+        } else {
+            let value = value as u64;
+            self.bounds
+                .iter()
+                .position(|&bound| value <= bound)
+                .unwrap_or(self.bounds.len())
+        };
+        self.counts[first_matching..]
+            .iter_mut()
+            .for_each(|c| *c += n);
+        self.count += n;
+        self.sum = self
+            .sum
+            .saturating_add(scaled_value.saturating_mul(n.min(i64::MAX as u64) as i64));
+    }
+
+    fn reset(&mut self) {
+        self.counts.iter_mut().for_each(|c| *c = 0);
+        self.count = 0;
+        self.sum = 0;
+    }
+
+    fn put_snapshot(&self, into: &mut Snapshot, scale: i64) {
+        for (bound, count) in self.bounds.iter().zip(self.counts.iter()) {
+            into.items
+                .push((format!("bucket_{}", bound), (*count).into()));
+        }
+        into.items
+            .push(("bucket_inf".to_string(), self.count.into()));
+        into.items
+            .push(("bucket_sum_total".to_string(), scaled_item(self.sum, scale)));
+    }
+
+    fn put_empty_snapshot(&self, into: &mut Snapshot, scale: i64) {
+        for bound in &self.bounds {
+            into.items.push((format!("bucket_{}", bound), 0u64.into()));
+        }
+        into.items.push(("bucket_inf".to_string(), 0u64.into()));
+        into.items
+            .push(("bucket_sum_total".to_string(), scaled_item(0, scale)));
+    }
+}
+
 struct HistogramSnapshot {
     pub max: Option<i64>,
     pub min: Option<i64>,
     pub mean: Option<f64>,
+    pub trimmed_mean: Option<f64>,
     pub stddev: Option<f64>,
     pub count: u64,
-    pub quantiles: Vec<(u16, i64)>,
+    pub sum: i64,
+    pub quantiles: Vec<(String, i64)>,
 }
 
 impl Default for HistogramSnapshot {
@@ -280,35 +1074,46 @@ impl Default for HistogramSnapshot {
             max: None,
             min: None,
             mean: None,
+            trimmed_mean: None,
             stddev: None,
             count: 0,
+            sum: 0,
             quantiles: Vec::new(),
         }
     }
 }
 
 impl HistogramSnapshot {
-    pub fn put_snapshot(&self, into: &mut Snapshot) {
+    /// Puts the values divided by `scale` into the `Snapshot`.
+    /// Integers stay integers if `scale` is 1.
+    pub fn put_snapshot(&self, into: &mut Snapshot, scale: i64) {
+        let int = |v: i64| scaled_item(v, scale);
+        let float = |v: f64| -> ItemKind { (v / scale as f64).into() };
+
         into.items.push(("count".to_string(), self.count.into()));
+        into.items.push(("sum".to_string(), int(self.sum)));
 
         if let Some(x) = self.max {
-            into.items.push(("max".to_string(), x.into()));
+            into.items.push(("max".to_string(), int(x)));
         }
         if let Some(x) = self.min {
-            into.items.push(("min".to_string(), x.into()));
+            into.items.push(("min".to_string(), int(x)));
         }
         if let Some(x) = self.mean {
-            into.items.push(("mean".to_string(), x.into()));
+            into.items.push(("mean".to_string(), float(x)));
+        }
+        if let Some(x) = self.trimmed_mean {
+            into.items.push(("trimmed_mean".to_string(), float(x)));
         }
         if let Some(x) = self.stddev {
-            into.items.push(("stddev".to_string(), x.into()));
+            into.items.push(("stddev".to_string(), float(x)));
         }
 
         if !self.quantiles.is_empty() {
             let mut quantiles = Snapshot::default();
 
-            for &(ref q, ref v) in &self.quantiles {
-                quantiles.items.push((format!("p{}", q), ItemKind::Int(*v)));
+            for &(ref q, v) in &self.quantiles {
+                quantiles.items.push((q.clone(), int(v)));
             }
 
             into.items
@@ -316,3 +1121,615 @@ impl HistogramSnapshot {
         }
     }
 }
+
+/// Divides the value by `scale`.
+/// Integers stay integers if `scale` is 1.
+fn scaled_item(value: i64, scale: i64) -> ItemKind {
+    if scale == 1 {
+        value.into()
+    } else {
+        (value as f64 / scale as f64).into()
+    }
+}
+
+/// Divides a value recorded with `scale` rounding up so that
+/// it can be compared with integer bucket bounds.
+fn unscale_rounding_up(value: i64, scale: i64) -> i64 {
+    let unscaled = value.div_euclid(scale);
+    if value.rem_euclid(scale) > 0 {
+        unscaled + 1
+    } else {
+        unscaled
+    }
+}
+
+/// Approximates the mean of the values between the quantiles
+/// `fraction` and `1.0 - fraction` by sampling quantiles evenly
+/// within that range.
+fn trimmed_mean<F: Fn(f64) -> i64>(fraction: f64, count: u64, mean: f64, value: F) -> f64 {
+    if fraction <= 0.0 {
+        return mean;
+    }
+
+    let span = 1.0 - 2.0 * fraction;
+    if (count as f64) * span < 1.0 {
+        return value(0.5) as f64;
+    }
+
+    let samples = count.min(MAX_TRIMMED_MEAN_SAMPLES);
+    let sum: f64 = (0..samples)
+        .map(|i| value(fraction + span * (i as f64 + 0.5) / samples as f64) as f64)
+        .sum();
+    sum / samples as f64
+}
+
+const MAX_TRIMMED_MEAN_SAMPLES: u64 = 1_000;
+
+/// Creates names like `p50` for 0.5 or `p999` for 0.999
+fn quantile_name(quantile: f64) -> String {
+    // Quantiles close to 1 are rounded up to 1 by the formatting
+    let formatted = format!("{:.6}", quantile);
+    let fraction = match formatted.strip_prefix("0.") {
+        Some(fraction) => fraction,
+        None => return "p100".to_string(),
+    };
+    let mut digits = fraction.trim_end_matches('0').to_string();
+    while digits.len() < 2 {
+        digits.push('0');
+    }
+    format!("p{}", digits)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn find_quantile(snapshot: &Snapshot, name: &str) -> i64 {
+        match snapshot.find(&format!("histogram/quantiles/{}", name)) {
+            crate::snapshot::FindItem::Found(&ItemKind::Int(v)) => v,
+            _ => panic!("quantile {} not found", name),
+        }
+    }
+
+    #[test]
+    fn quantile_names() {
+        assert_eq!(quantile_name(0.0), "p00");
+        assert_eq!(quantile_name(0.05), "p05");
+        assert_eq!(quantile_name(0.5), "p50");
+        assert_eq!(quantile_name(0.75), "p75");
+        assert_eq!(quantile_name(0.999), "p999");
+        assert_eq!(quantile_name(1.0), "p100");
+        assert_eq!(quantile_name(0.9999999), "p100");
+        assert_eq!(quantile_name(0.0000001), "p00");
+    }
+
+    #[test]
+    fn default_quantiles_are_kept() {
+        let mut histogram = Histogram::new("histogram");
+        histogram.update(&Update::ObservationWithValue(1.into(), Instant::now()));
+
+        let mut snapshot = Snapshot::default();
+        histogram.put_snapshot(&mut snapshot, false);
+
+        for name in &["p50", "p75", "p95", "p98", "p99", "p999"] {
+            assert_eq!(find_quantile(&snapshot, name), 1);
+        }
+    }
+
+    #[test]
+    fn configured_quantiles_are_computed() {
+        let mut histogram = Histogram::new("histogram").quantiles(&[0.5, 0.95, 0.999]);
+        for v in 1..=1000 {
+            histogram.update(&Update::ObservationWithValue(
+                (v as i64).into(),
+                Instant::now(),
+            ));
+        }
+
+        let mut snapshot = Snapshot::default();
+        histogram.put_snapshot(&mut snapshot, false);
+
+        assert!((find_quantile(&snapshot, "p50") - 500).abs() <= 5);
+        assert!((find_quantile(&snapshot, "p95") - 950).abs() <= 5);
+        assert!((find_quantile(&snapshot, "p999") - 999).abs() <= 2);
+        assert_eq!(
+            snapshot.find("histogram/quantiles/p75"),
+            crate::snapshot::FindItem::NotFound
+        );
+    }
+
+    #[test]
+    fn hdr_quantiles_are_within_error_bound() {
+        let mut histogram = Histogram::new_hdr("histogram", 3_600_000_000, 3)
+            .quantiles(&[0.1, 0.5, 0.9, 0.99, 0.999]);
+
+        // From 1µs up to 1000s
+        let mut values = Vec::new();
+        for exp in 0..7 {
+            for i in 1..=1_000i64 {
+                values.push(i * 10i64.pow(exp));
+            }
+        }
+        for v in &values {
+            histogram.update(&Update::ObservationWithValue((*v).into(), Instant::now()));
+        }
+        values.sort();
+
+        let mut snapshot = Snapshot::default();
+        histogram.put_snapshot(&mut snapshot, false);
+
+        for &(name, q) in &[
+            ("p10", 0.1),
+            ("p50", 0.5),
+            ("p90", 0.9),
+            ("p99", 0.99),
+            ("p999", 0.999),
+        ] {
+            let expected = values[((q * values.len() as f64).ceil() as usize) - 1] as f64;
+            let reported = find_quantile(&snapshot, name) as f64;
+            assert!(
+                (reported - expected).abs() <= expected * 0.001,
+                "{}: expected {} but got {}",
+                name,
+                expected,
+                reported
+            );
+        }
+        assert_eq!(
+            snapshot.find("histogram/max"),
+            crate::snapshot::FindItem::Found(&ItemKind::Int(1_000_000_000))
+        );
+    }
+
+    #[test]
+    fn the_hdr_max_value_is_scaled_by_the_fraction_digits() {
+        let mut histogram = Histogram::new_hdr("histogram", 100, 3).fraction_digits(2);
+        histogram.update(&Update::ObservationWithValue(99.5.into(), Instant::now()));
+        histogram.update(&Update::ObservationWithValue(250.0.into(), Instant::now()));
+
+        let mut snapshot = Snapshot::default();
+        histogram.put_snapshot(&mut snapshot, false);
+        match snapshot.find("histogram/min") {
+            crate::snapshot::FindItem::Found(ItemKind::Float(min)) => {
+                assert!((min - 99.5).abs() <= 0.1, "min: {}", min)
+            }
+            other => panic!("no min found: {}", other),
+        }
+        match snapshot.find("histogram/max") {
+            crate::snapshot::FindItem::Found(ItemKind::Float(max)) => {
+                assert!((max - 100.0).abs() <= 0.1, "max: {}", max)
+            }
+            other => panic!("no max found: {}", other),
+        }
+    }
+
+    #[test]
+    fn windowed_histogram_forgets_old_values() {
+        use crate::instruments::ManualOffsetClock;
+
+        let clock = ManualOffsetClock::default();
+        let mut histogram =
+            Histogram::with_window("histogram", Duration::from_secs(10)).clock(clock.clone());
+
+        histogram.update(&Update::ObservationWithValue(1000.into(), clock.now()));
+        clock.advance_n_seconds(11);
+        histogram.update(&Update::ObservationWithValue(1.into(), clock.now()));
+
+        let mut snapshot = Snapshot::default();
+        histogram.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("histogram/count"),
+            crate::snapshot::FindItem::Found(&ItemKind::UInt(1))
+        );
+        assert_eq!(
+            snapshot.find("histogram/max"),
+            crate::snapshot::FindItem::Found(&ItemKind::Int(1))
+        );
+
+        clock.advance_n_seconds(11);
+
+        let mut snapshot = Snapshot::default();
+        histogram.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("histogram/count"),
+            crate::snapshot::FindItem::Found(&ItemKind::UInt(0))
+        );
+        assert_eq!(
+            snapshot.find("histogram/max"),
+            crate::snapshot::FindItem::NotFound
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn invalid_quantiles_are_rejected() {
+        Histogram::new("histogram").set_quantiles(&[0.5, 1.5]);
+    }
+
+    #[test]
+    fn buckets_are_cumulative() {
+        let mut histogram = Histogram::new("histogram").buckets(&[100, 10, 50, 10]);
+
+        for v in &[-1, 5, 10, 11, 50, 99, 1_000] {
+            histogram.update(&Update::ObservationWithValue((*v).into(), Instant::now()));
+        }
+
+        let mut snapshot = Snapshot::default();
+        histogram.put_snapshot(&mut snapshot, false);
+
+        let expected = [
+            ("bucket_10", 3),
+            ("bucket_50", 5),
+            ("bucket_100", 6),
+            ("bucket_inf", 7),
+        ];
+        for (name, count) in &expected {
+            assert_eq!(
+                snapshot.find(&format!("histogram/{}", name)),
+                crate::snapshot::FindItem::Found(&ItemKind::UInt(*count)),
+                "{}",
+                name
+            );
+        }
+        assert_eq!(
+            snapshot.find("histogram/sum"),
+            crate::snapshot::FindItem::Found(&ItemKind::Int(1_174))
+        );
+        assert_eq!(
+            snapshot.find("histogram/bucket_sum_total"),
+            crate::snapshot::FindItem::Found(&ItemKind::Int(1_174))
+        );
+
+        histogram.reset();
+        let mut snapshot = Snapshot::default();
+        histogram.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("histogram/bucket_inf"),
+            crate::snapshot::FindItem::Found(&ItemKind::UInt(0))
+        );
+    }
+
+    #[test]
+    fn the_bucket_sum_is_cumulative_for_windowed_histograms() {
+        use crate::instruments::ManualOffsetClock;
+
+        let clock = ManualOffsetClock::default();
+        let mut histogram = Histogram::with_window("histogram", Duration::from_secs(1))
+            .buckets(&[10])
+            .clock(clock.clone());
+        for v in &[5, 20] {
+            histogram.update(&Update::ObservationWithValue((*v).into(), clock.now()));
+        }
+        clock.advance_n_seconds(2);
+
+        let mut snapshot = Snapshot::default();
+        histogram.put_snapshot(&mut snapshot, false);
+
+        assert_eq!(
+            snapshot.find("histogram/sum"),
+            crate::snapshot::FindItem::Found(&ItemKind::Int(0))
+        );
+        assert_eq!(
+            snapshot.find("histogram/bucket_inf"),
+            crate::snapshot::FindItem::Found(&ItemKind::UInt(2))
+        );
+        assert_eq!(
+            snapshot.find("histogram/bucket_sum_total"),
+            crate::snapshot::FindItem::Found(&ItemKind::Int(25))
+        );
+    }
+
+    #[test]
+    fn decaying_reservoir_keeps_the_snapshot_keys() {
+        let mut histogram = Histogram::with_decaying_reservoir("histogram", 100, 0.015);
+
+        for v in 1..=1_000 {
+            histogram.update(&Update::ObservationWithValue(v.into(), Instant::now()));
+        }
+
+        let mut snapshot = Snapshot::default();
+        histogram.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("histogram/count"),
+            crate::snapshot::FindItem::Found(&ItemKind::UInt(1_000))
+        );
+        assert!(snapshot.find("histogram/quantiles/p50").opt().is_some());
+        assert!(snapshot.find("histogram/min").opt().is_some());
+    }
+
+    #[test]
+    fn count_and_sum_are_always_present() {
+        let mut histogram = Histogram::new("histogram").display_time_unit(TimeUnit::Milliseconds);
+
+        let mut snapshot = Snapshot::default();
+        histogram.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("histogram/count"),
+            crate::snapshot::FindItem::Found(&ItemKind::UInt(0))
+        );
+        assert_eq!(
+            snapshot.find("histogram/sum"),
+            crate::snapshot::FindItem::Found(&ItemKind::Int(0))
+        );
+
+        for v in &[3, 5, 7] {
+            histogram.update(&Update::ObservationWithValue((*v).into(), Instant::now()));
+        }
+        histogram.update(&Update::ObservationWithValue(
+            Duration::from_millis(10).into(),
+            Instant::now(),
+        ));
+
+        let mut snapshot = Snapshot::default();
+        histogram.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("histogram/count"),
+            crate::snapshot::FindItem::Found(&ItemKind::UInt(4))
+        );
+        assert_eq!(
+            snapshot.find("histogram/sum"),
+            crate::snapshot::FindItem::Found(&ItemKind::Int(25))
+        );
+    }
+
+    #[test]
+    fn clear_on_snapshot_starts_each_snapshot_empty() {
+        for &descriptive in &[false, true] {
+            let mut histogram = Histogram::new("histogram")
+                .buckets(&[10])
+                .clear_on_snapshot(true);
+            for v in 1..=5 {
+                histogram.update(&Update::ObservationWithValue(v.into(), Instant::now()));
+            }
+
+            let mut first = Snapshot::default();
+            histogram.put_snapshot(&mut first, descriptive);
+            assert_eq!(
+                first.find("histogram/count"),
+                crate::snapshot::FindItem::Found(&ItemKind::UInt(5))
+            );
+
+            let mut first = Snapshot::default();
+            histogram.put_exported_snapshot(&mut first, descriptive);
+            assert_eq!(
+                first.find("histogram/count"),
+                crate::snapshot::FindItem::Found(&ItemKind::UInt(5))
+            );
+            assert_eq!(
+                first.find("histogram/sum"),
+                crate::snapshot::FindItem::Found(&ItemKind::Int(15))
+            );
+
+            let mut second = Snapshot::default();
+            histogram.put_exported_snapshot(&mut second, descriptive);
+            assert_eq!(
+                second.find("histogram/count"),
+                crate::snapshot::FindItem::Found(&ItemKind::UInt(0))
+            );
+            assert_eq!(
+                second.find("histogram/sum"),
+                crate::snapshot::FindItem::Found(&ItemKind::Int(0))
+            );
+            assert_eq!(
+                second.find("histogram/bucket_10"),
+                crate::snapshot::FindItem::Found(&ItemKind::UInt(0))
+            );
+            assert_eq!(
+                second.find("histogram/max"),
+                crate::snapshot::FindItem::NotFound
+            );
+
+            histogram.update(&Update::ObservationWithValue(7.into(), Instant::now()));
+            let mut third = Snapshot::default();
+            histogram.put_exported_snapshot(&mut third, descriptive);
+            assert_eq!(
+                third.find("histogram/count"),
+                crate::snapshot::FindItem::Found(&ItemKind::UInt(1))
+            );
+            assert_eq!(
+                third.find("histogram/max"),
+                crate::snapshot::FindItem::Found(&ItemKind::Int(7))
+            );
+        }
+    }
+
+    #[test]
+    fn the_total_count_survives_clearing_the_window() {
+        let mut histogram = Histogram::with_window("histogram", Duration::from_secs(60))
+            .clear_on_snapshot(true)
+            .total_count_enabled(true);
+
+        for expected_total in &[3u64, 6, 9] {
+            for v in 1..=3 {
+                histogram.update(&Update::ObservationWithValue(v.into(), Instant::now()));
+            }
+            let mut snapshot = Snapshot::default();
+            histogram.put_exported_snapshot(&mut snapshot, false);
+            assert_eq!(
+                snapshot.find("histogram/count"),
+                crate::snapshot::FindItem::Found(&ItemKind::UInt(3))
+            );
+            assert_eq!(
+                snapshot.find("histogram/total_count"),
+                crate::snapshot::FindItem::Found(&ItemKind::UInt(*expected_total))
+            );
+        }
+
+        let mut snapshot = Snapshot::default();
+        histogram.put_exported_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("histogram/count"),
+            crate::snapshot::FindItem::Found(&ItemKind::UInt(0))
+        );
+        assert_eq!(histogram.total_count(), 9);
+
+        histogram.reset();
+        assert_eq!(histogram.total_count(), 0);
+    }
+
+    #[test]
+    fn large_counts_are_recorded_without_copying_the_value() {
+        let mut histogram = Histogram::with_window("histogram", Duration::from_secs(60));
+        histogram.update(&Update::ObservationsWithValue(
+            7.into(),
+            1_000_000_000,
+            Instant::now(),
+        ));
+        histogram.update(&Update::ObservationWithValue(1.into(), Instant::now()));
+
+        let mut snapshot = Snapshot::default();
+        histogram.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("histogram/count"),
+            crate::snapshot::FindItem::Found(&ItemKind::UInt(1_000_000_001))
+        );
+        assert_eq!(find_quantile(&snapshot, "p50"), 7);
+    }
+
+    #[test]
+    fn the_count_and_sum_include_copies_beyond_the_reservoir() {
+        let mut histogram = Histogram::new("histogram");
+        histogram.update(&Update::ObservationsWithValue(
+            7.into(),
+            MAX_DECAYING_COPIES * 2,
+            Instant::now(),
+        ));
+
+        let mut snapshot = Snapshot::default();
+        histogram.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("histogram/count"),
+            crate::snapshot::FindItem::Found(&ItemKind::UInt(MAX_DECAYING_COPIES * 2))
+        );
+        assert_eq!(
+            snapshot.find("histogram/sum"),
+            crate::snapshot::FindItem::Found(&ItemKind::Int(7 * MAX_DECAYING_COPIES as i64 * 2))
+        );
+        assert_eq!(
+            snapshot.find("histogram/mean"),
+            crate::snapshot::FindItem::Found(&ItemKind::Float(7.0))
+        );
+    }
+
+    #[test]
+    fn fractional_values_are_kept_by_default() {
+        let mut histogram = Histogram::new("histogram");
+        histogram.update(&Update::ObservationWithValue(0.5.into(), Instant::now()));
+        histogram.update(&Update::ObservationWithValue(1.5.into(), Instant::now()));
+        histogram.update(&Update::ObservationWithValue(2.into(), Instant::now()));
+
+        let mut snapshot = Snapshot::default();
+        histogram.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("histogram/sum"),
+            crate::snapshot::FindItem::Found(&ItemKind::Float(4.0))
+        );
+        assert_eq!(
+            snapshot.find("histogram/min"),
+            crate::snapshot::FindItem::Found(&ItemKind::Float(0.5))
+        );
+        assert_eq!(
+            snapshot.find("histogram/max"),
+            crate::snapshot::FindItem::Found(&ItemKind::Float(2.0))
+        );
+    }
+
+    #[test]
+    fn floats_are_rounded_with_zero_fraction_digits() {
+        let mut histogram = Histogram::new("histogram").fraction_digits(0);
+        histogram.update(&Update::ObservationWithValue(1.5.into(), Instant::now()));
+
+        let mut snapshot = Snapshot::default();
+        histogram.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("histogram/max"),
+            crate::snapshot::FindItem::Found(&ItemKind::Int(2))
+        );
+    }
+
+    #[test]
+    fn fractional_values_are_kept_with_fraction_digits() {
+        let mut histogram = Histogram::with_window("histogram", Duration::from_secs(60))
+            .quantiles(&[0.5])
+            .buckets(&[1])
+            .fraction_digits(2);
+        for &v in &[0.25, 0.5, 0.75, 1.25] {
+            histogram.update(&Update::ObservationWithValue(v.into(), Instant::now()));
+        }
+
+        let mut snapshot = Snapshot::default();
+        histogram.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("histogram/sum"),
+            crate::snapshot::FindItem::Found(&ItemKind::Float(2.75))
+        );
+        assert_eq!(
+            snapshot.find("histogram/max"),
+            crate::snapshot::FindItem::Found(&ItemKind::Float(1.25))
+        );
+        assert_eq!(
+            snapshot.find("histogram/min"),
+            crate::snapshot::FindItem::Found(&ItemKind::Float(0.25))
+        );
+        match snapshot.find("histogram/quantiles/p50") {
+            crate::snapshot::FindItem::Found(ItemKind::Float(p50)) => {
+                assert!(*p50 >= 0.5 && *p50 <= 0.75, "p50: {}", p50)
+            }
+            other => panic!("unexpected p50: {:?}", other),
+        }
+        assert_eq!(
+            snapshot.find("histogram/bucket_1"),
+            crate::snapshot::FindItem::Found(&ItemKind::UInt(3))
+        );
+    }
+
+    #[test]
+    fn the_trimmed_mean_ignores_outliers() {
+        let mut histogram = Histogram::with_window("histogram", Duration::from_secs(60))
+            .quantiles(&[])
+            .trimmed_mean(0.05);
+        for v in 0..95 {
+            histogram.update(&Update::ObservationWithValue(
+                (100 + v % 10).into(),
+                Instant::now(),
+            ));
+        }
+        for _ in 0..5 {
+            histogram.update(&Update::ObservationWithValue(
+                1_000_000u64.into(),
+                Instant::now(),
+            ));
+        }
+
+        let mut snapshot = Snapshot::default();
+        histogram.put_snapshot(&mut snapshot, false);
+        let mean = match snapshot.find("histogram/mean") {
+            crate::snapshot::FindItem::Found(&ItemKind::Float(v)) => v,
+            _ => panic!("mean not found"),
+        };
+        let trimmed_mean = match snapshot.find("histogram/trimmed_mean") {
+            crate::snapshot::FindItem::Found(&ItemKind::Float(v)) => v,
+            _ => panic!("trimmed mean not found"),
+        };
+        assert!(mean > 50_000.0);
+        assert!(
+            (100.0..110.0).contains(&trimmed_mean),
+            "trimmed mean: {}",
+            trimmed_mean
+        );
+    }
+
+    #[test]
+    fn trimmed_mean_edge_cases() {
+        let values = [1, 2, 3, 4, 100];
+        let value = |q: f64| {
+            let rank = (q * values.len() as f64).ceil() as usize;
+            values[rank.saturating_sub(1).min(values.len() - 1)]
+        };
+
+        assert_eq!(trimmed_mean(0.0, 5, 22.0, value), 22.0);
+        assert_eq!(trimmed_mean(0.2, 5, 22.0, value), 3.0);
+        // Only the median remains
+        assert_eq!(trimmed_mean(0.45, 5, 22.0, value), 3.0);
+    }
+}
@@ -1,4 +1,8 @@
-use crate::snapshot::Snapshot;
+use std::time::{Duration, Instant};
+
+use crate::instruments::fundamentals::{Clock, SharedClock};
+use crate::snapshot::{ItemKind, Snapshot};
+use crate::util;
 use crate::{HandlesObservations, Observation, PutsSnapshot};
 
 use super::*;
@@ -15,12 +19,25 @@ impl<L> UpdateModifier<L> {
             UpdateModifier::Modify(ref f) => f(label, update),
         }
     }
+
+    pub fn try_clone(&self) -> Result<Self, TemplateError> {
+        match self {
+            UpdateModifier::KeepAsIs => Ok(UpdateModifier::KeepAsIs),
+            UpdateModifier::Modify(_) => Err(TemplateError::UpdateModifier),
+        }
+    }
 }
 
 pub struct InstrumentAdapter<L, I> {
     label_filter: LabelFilter<L>,
     instrument: I,
     modify_update: UpdateModifier<L>,
+    scale_factor: f64,
+    enabled: bool,
+    descriptive_override: Option<bool>,
+    max_inactivity_duration: Option<Duration>,
+    last_update: Instant,
+    clock: SharedClock,
 }
 
 impl<L, I> InstrumentAdapter<L, I>
@@ -33,6 +50,12 @@ where
             instrument,
             label_filter: LabelFilter::accept_all(),
             modify_update: UpdateModifier::KeepAsIs,
+            scale_factor: 1.0,
+            enabled: true,
+            descriptive_override: None,
+            max_inactivity_duration: None,
+            last_update: Instant::now(),
+            clock: SharedClock::default(),
         }
     }
 
@@ -41,6 +64,12 @@ where
             instrument,
             label_filter: accept.into(),
             modify_update: UpdateModifier::KeepAsIs,
+            scale_factor: 1.0,
+            enabled: true,
+            descriptive_override: None,
+            max_inactivity_duration: None,
+            last_update: Instant::now(),
+            clock: SharedClock::default(),
         }
     }
 
@@ -64,6 +93,12 @@ where
             instrument,
             label_filter: LabelFilter::accept_none(),
             modify_update: UpdateModifier::KeepAsIs,
+            scale_factor: 1.0,
+            enabled: true,
+            descriptive_override: None,
+            max_inactivity_duration: None,
+            last_update: Instant::now(),
+            clock: SharedClock::default(),
         }
     }
 
@@ -85,9 +120,137 @@ where
         self
     }
 
+    /// Multiplies observed values by `factor` before they reach
+    /// the instrument, e.g. 0.001 to turn milliseconds into seconds.
+    ///
+    /// Observations without a value are not scaled. Neither are values
+    /// which can not be multiplied like booleans and changes. Integers
+    /// become floats unless the factor is 1.0. The scaling happens before
+    /// a modification set with `modify_with`.
+    ///
+    /// Default is 1.0.
+    pub fn set_scale_factor(&mut self, factor: f64) {
+        self.scale_factor = factor;
+    }
+
+    /// Multiplies observed values by `factor` before they reach
+    /// the instrument.
+    ///
+    /// See `set_scale_factor`.
+    ///
+    /// Default is 1.0.
+    pub fn scale_factor(mut self, factor: f64) -> Self {
+        self.set_scale_factor(factor);
+        self
+    }
+
+    /// Enables or disables the instrument.
+    ///
+    /// A disabled instrument ignores all observations but is still
+    /// part of the `Snapshot` with an additional field
+    /// `_disabled_[instrument_name]` set to `true`.
+    ///
+    /// Default is enabled.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Returns `true` if the instrument reacts on observations.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Puts the title and the description of the instrument into
+    /// the `Snapshot` if `descriptive` is `true` or leaves them out
+    /// if it is `false` regardless of whether a descriptive
+    /// `Snapshot` was requested.
+    ///
+    /// Default is to follow the request.
+    pub fn set_descriptive_override(&mut self, descriptive: bool) {
+        self.descriptive_override = Some(descriptive);
+    }
+
+    /// Forces the title and the description of the instrument to be
+    /// part of the `Snapshot` or not.
+    ///
+    /// See `set_descriptive_override`.
+    pub fn descriptive_override(mut self, descriptive: bool) -> Self {
+        self.set_descriptive_override(descriptive);
+        self
+    }
+
+    /// Sets the maximum amount of time the instrument may be
+    /// inactive until its values are left out of the `Snapshot`.
+    ///
+    /// Instead `_inactive` and `_active` are put into the `Snapshot` of
+    /// the instrument. An instrument without its own `Snapshot`,
+    /// e.g. a `Counter`, gets `_inactive_[instrument_name]` and
+    /// `_active_[instrument_name]` next to it. The limit is independent
+    /// of the limit of a `Panel` containing the instrument.
+    ///
+    /// Default is no inactivity tracking.
+    pub fn set_inactivity_limit(&mut self, limit: Duration) {
+        self.max_inactivity_duration = Some(limit);
+    }
+
+    /// Sets the maximum amount of time the instrument may be
+    /// inactive until its values are left out of the `Snapshot`.
+    ///
+    /// See `set_inactivity_limit`.
+    pub fn inactivity_limit(mut self, limit: Duration) -> Self {
+        self.set_inactivity_limit(limit);
+        self
+    }
+
+    /// Sets the `Clock` used for inactivity tracking.
+    ///
+    /// Mostly useful for testing with a `ManualOffsetClock`.
+    ///
+    /// Default is the `WallClock`.
+    pub fn set_clock<C: Clock + Send + Sync + 'static>(&mut self, clock: C) {
+        self.clock = SharedClock::new(clock);
+        self.last_update = self.clock.now();
+    }
+
+    /// Sets the `Clock` used for inactivity tracking.
+    ///
+    /// Default is the `WallClock`.
+    pub fn clock<C: Clock + Send + Sync + 'static>(mut self, clock: C) -> Self {
+        self.set_clock(clock);
+        self
+    }
+
     pub fn instrument(&self) -> &I {
         &self.instrument
     }
+
+    pub fn instrument_mut(&mut self) -> &mut I {
+        &mut self.instrument
+    }
+
+    pub fn into_inner(self) -> I {
+        self.instrument
+    }
+
+    /// Returns a copy of this adapter where the instrument is
+    /// created by `clone_instrument`.
+    pub(crate) fn template_clone_with<F>(&self, clone_instrument: F) -> Result<Self, TemplateError>
+    where
+        L: Clone,
+        F: FnOnce(&I) -> I,
+    {
+        Ok(Self {
+            label_filter: self.label_filter.try_clone()?,
+            instrument: clone_instrument(&self.instrument),
+            modify_update: self.modify_update.try_clone()?,
+            scale_factor: self.scale_factor,
+            enabled: self.enabled,
+            descriptive_override: self.descriptive_override,
+            max_inactivity_duration: self.max_inactivity_duration,
+            last_update: self.clock.now(),
+            clock: self.clock.clone(),
+        })
+    }
 }
 
 impl<L, I> HandlesObservations for InstrumentAdapter<L, I>
@@ -98,15 +261,58 @@ where
     type Label = L;
 
     fn handle_observation(&mut self, observation: &Observation<Self::Label>) -> usize {
-        if !self.label_filter.accepts(observation.label()) {
+        if !self.enabled || !self.label_filter.accepts(observation.label()) {
             return 0;
         }
 
         let BorrowedLabelAndUpdate(label, update) = observation.into();
 
+        let update = scale_update(update, self.scale_factor);
         let update = self.modify_update.modify(label, update);
 
-        self.instrument.update(&update)
+        let instruments_updated = self.instrument.update(&update);
+        if instruments_updated > 0 {
+            self.last_update = self.clock.now();
+        }
+        instruments_updated
+    }
+
+    fn reset(&mut self) {
+        self.instrument.reset()
+    }
+}
+
+impl<L, I> InstrumentAdapter<L, I>
+where
+    L: Send + 'static,
+    I: Instrument,
+{
+    fn put_values(&self, into: &mut Snapshot, descriptive: bool, exported: bool) {
+        let is_active = self
+            .max_inactivity_duration
+            .map(|d| self.clock.now() - self.last_update <= d);
+
+        // The values of an inactive instrument are left out. So they
+        // must not be reset as if they had been exported.
+        let mut values = Snapshot::default();
+        util::put_snapshot_of(
+            &self.instrument,
+            &mut values,
+            self.descriptive_override.unwrap_or(descriptive),
+            exported && is_active != Some(false),
+        );
+
+        if let Some(name) = self.instrument.name() {
+            if let Some(is_active) = is_active {
+                put_activity_markers(is_active, name, &mut values);
+            }
+
+            if !self.enabled {
+                put_disabled_marker(name, &mut values);
+            }
+        }
+
+        into.items.extend(values.items);
     }
 }
 
@@ -116,10 +322,67 @@ where
     I: Instrument,
 {
     fn put_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
-        self.instrument.put_snapshot(into, descriptive)
+        self.put_values(into, descriptive, false)
+    }
+
+    fn put_exported_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
+        self.put_values(into, descriptive, true)
+    }
+
+    fn put_names(&self, into: &mut Snapshot) {
+        self.instrument.put_names(into)
     }
 }
 
+#[allow(clippy::float_cmp)]
+fn scale_update(update: Update, factor: f64) -> Update {
+    if factor == 1.0 {
+        return update;
+    }
+
+    let scale = |value: ObservedValue| (value * ObservedValue::Float(factor)).unwrap_or(value);
+    match update {
+        Update::ObservationWithValue(value, timestamp) => {
+            Update::ObservationWithValue(scale(value), timestamp)
+        }
+        Update::ObservationsWithValue(value, n, timestamp) => {
+            Update::ObservationsWithValue(scale(value), n, timestamp)
+        }
+        update => update,
+    }
+}
+
+/// Puts the activity markers into the own `Snapshot` of the instrument
+/// named `name` or next to its values. All values of an inactive
+/// instrument are removed. They must not have been reset by
+/// putting them into an exported `Snapshot`.
+fn put_activity_markers(is_active: bool, name: &str, values: &mut Snapshot) {
+    let markers = util::ActivityMarkers::default();
+    if !is_active {
+        let has_own_snapshot = values
+            .items
+            .iter()
+            .any(|(n, item)| n == name && matches!(item, ItemKind::Snapshot(_)));
+        values.items.clear();
+        if has_own_snapshot {
+            values
+                .items
+                .push((name.to_string(), ItemKind::Snapshot(Snapshot::default())));
+        }
+    }
+    match values.items.iter_mut().find(|(n, _)| n == name) {
+        Some((_, ItemKind::Snapshot(ref mut inner))) => markers.put(is_active, inner),
+        _ => markers.put_postfixed(is_active, name, values),
+    }
+}
+
+pub(crate) fn put_disabled_marker(instrument_name: &str, into: &mut Snapshot) {
+    into.items.push((
+        format!("_disabled_{}", instrument_name),
+        ItemKind::Boolean(true),
+    ));
+}
+
 impl<L, I> From<I> for InstrumentAdapter<L, I>
 where
     L: Clone + Eq + Send + 'static,
@@ -129,3 +392,152 @@ where
         InstrumentAdapter::new(instrument)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::time::Instant;
+
+    use super::*;
+
+    #[test]
+    fn values_are_scaled_before_reaching_the_instrument() {
+        let mut adapter = InstrumentAdapter::for_label(1, Gauge::new("gauge")).scale_factor(0.001);
+
+        adapter.handle_observation(&Observation::observed_one_value_now(1, 12_345));
+        assert_eq!(adapter.instrument().get(), Some(12));
+
+        let mut counter =
+            InstrumentAdapter::for_label(1, Counter::new("counter")).scale_factor(0.001);
+        counter.handle_observation(&Observation::observed(1, 5, Instant::now()));
+        assert_eq!(counter.instrument().get(), 5);
+    }
+
+    #[test]
+    fn markers_are_put_for_names_with_a_leading_underscore() {
+        use crate::instruments::ManualOffsetClock;
+        use crate::snapshot::FindItem;
+
+        let clock = ManualOffsetClock::default();
+        let mut adapter = InstrumentAdapter::for_label(1, Counter::new("_internal"))
+            .inactivity_limit(Duration::from_secs(10))
+            .clock(clock.clone());
+        adapter.set_enabled(false);
+
+        let mut snapshot = Snapshot::default();
+        adapter.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("_internal"),
+            FindItem::Found(&ItemKind::UInt(0))
+        );
+        assert_eq!(
+            snapshot.find("_active__internal"),
+            FindItem::Found(&ItemKind::Boolean(true))
+        );
+        assert_eq!(
+            snapshot.find("_disabled__internal"),
+            FindItem::Found(&ItemKind::Boolean(true))
+        );
+
+        clock.advance_n_seconds(11);
+
+        let mut snapshot = Snapshot::default();
+        adapter.put_snapshot(&mut snapshot, false);
+        assert_eq!(snapshot.find("_internal"), FindItem::NotFound);
+        assert_eq!(
+            snapshot.find("_inactive__internal"),
+            FindItem::Found(&ItemKind::Boolean(true))
+        );
+    }
+
+    #[test]
+    fn an_instrument_becomes_inactive_on_its_own() {
+        use crate::instruments::ManualOffsetClock;
+        use crate::snapshot::FindItem;
+
+        let clock = ManualOffsetClock::default();
+        let mut panel = Panel::named(vec![1, 2], "panel")
+            .inactivity_limit(Duration::from_secs(60))
+            .clock(clock.clone())
+            .handler(
+                Counter::new("quiet")
+                    .for_label(2)
+                    .inactivity_limit(Duration::from_secs(10))
+                    .clock(clock.clone()),
+            )
+            .handler(
+                Meter::new("quiet_rate")
+                    .for_label(2)
+                    .inactivity_limit(Duration::from_secs(10))
+                    .clock(clock.clone()),
+            )
+            .handler(Counter::new("busy").for_label(1));
+
+        panel.handle_observation(&Observation::observed_one_now(2));
+        clock.advance_n_seconds(5);
+        panel.handle_observation(&Observation::observed_one_now(1));
+
+        let mut snapshot = Snapshot::default();
+        panel.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("panel/quiet"),
+            FindItem::Found(&ItemKind::UInt(1))
+        );
+        assert_eq!(
+            snapshot.find("panel/_active_quiet"),
+            FindItem::Found(&ItemKind::Boolean(true))
+        );
+        assert_eq!(
+            snapshot.find("panel/quiet_rate/_active"),
+            FindItem::Found(&ItemKind::Boolean(true))
+        );
+
+        clock.advance_n_seconds(6);
+        panel.handle_observation(&Observation::observed_one_now(1));
+
+        let mut snapshot = Snapshot::default();
+        panel.put_snapshot(&mut snapshot, false);
+        assert_eq!(snapshot.find("panel/quiet"), FindItem::NotFound);
+        assert_eq!(
+            snapshot.find("panel/_inactive_quiet"),
+            FindItem::Found(&ItemKind::Boolean(true))
+        );
+        assert_eq!(
+            snapshot.find("panel/quiet_rate/_inactive"),
+            FindItem::Found(&ItemKind::Boolean(true))
+        );
+        assert_eq!(snapshot.find("panel/quiet_rate/count"), FindItem::NotFound);
+        assert_eq!(
+            snapshot.find("panel/busy"),
+            FindItem::Found(&ItemKind::UInt(2))
+        );
+        assert_eq!(
+            snapshot.find("panel/_active"),
+            FindItem::Found(&ItemKind::Boolean(true))
+        );
+    }
+
+    #[test]
+    fn an_inactive_instrument_keeps_its_values_for_the_next_export() {
+        use crate::instruments::ManualOffsetClock;
+        use crate::snapshot::FindItem;
+
+        let clock = ManualOffsetClock::default();
+        let mut adapter =
+            InstrumentAdapter::for_label(1, Counter::new("count").reset_on_snapshot(true))
+                .inactivity_limit(Duration::from_secs(10))
+                .clock(clock.clone());
+
+        adapter.handle_observation(&Observation::observed_one_now(1));
+        clock.advance_n_seconds(11);
+
+        let mut snapshot = Snapshot::default();
+        adapter.put_exported_snapshot(&mut snapshot, false);
+        assert_eq!(snapshot.find("count"), FindItem::NotFound);
+
+        adapter.handle_observation(&Observation::observed_one_now(1));
+
+        let mut snapshot = Snapshot::default();
+        adapter.put_exported_snapshot(&mut snapshot, false);
+        assert_eq!(snapshot.find("count"), FindItem::Found(&ItemKind::UInt(2)));
+    }
+}
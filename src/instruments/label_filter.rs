@@ -1,3 +1,5 @@
+use super::TemplateError;
+
 pub struct LabelFilter<L> {
     internal: LabelFilterInternal<L>,
 }
@@ -21,6 +23,31 @@ where
         }
     }
 
+    /// Accepts labels where the projected field equals `value`.
+    ///
+    /// This is useful for labels with multiple dimensions like tuples
+    /// or structs where only one dimension should be matched while
+    /// all others are ignored.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use metrix::instruments::LabelFilter;
+    ///
+    /// let filter = LabelFilter::match_field(|&(endpoint, _status)| endpoint, "/users");
+    ///
+    /// assert!(filter.accepts(&("/users", 200)));
+    /// assert!(filter.accepts(&("/users", 404)));
+    /// assert!(!filter.accepts(&("/orders", 200)));
+    /// ```
+    pub fn match_field<F, V>(project: F, value: V) -> Self
+    where
+        F: Fn(&L) -> V + Send + 'static,
+        V: PartialEq + Send + 'static,
+    {
+        Self::predicate(move |label| project(label) == value)
+    }
+
     pub fn accept_all() -> Self {
         Self {
             internal: LabelFilterInternal::AcceptAll,
@@ -44,6 +71,28 @@ where
     fn create(internal: LabelFilterInternal<L>) -> Self {
         Self { internal }
     }
+
+    /// Returns a copy unless the filter is based on a predicate
+    pub(crate) fn try_clone(&self) -> Result<Self, TemplateError>
+    where
+        L: Clone,
+    {
+        use LabelFilterInternal::*;
+        let internal = match self.internal {
+            AcceptNone => AcceptNone,
+            AcceptAll => AcceptAll,
+            One(ref a) => One(a.clone()),
+            Two(ref a, ref b) => Two(a.clone(), b.clone()),
+            Three(ref a, ref b, ref c) => Three(a.clone(), b.clone(), c.clone()),
+            Four(ref a, ref b, ref c, ref d) => Four(a.clone(), b.clone(), c.clone(), d.clone()),
+            Five(ref a, ref b, ref c, ref d, ref e) => {
+                Five(a.clone(), b.clone(), c.clone(), d.clone(), e.clone())
+            }
+            Many(ref many) => Many(many.clone()),
+            Predicate(_) => return Err(TemplateError::LabelPredicate),
+        };
+        Ok(Self::create(internal))
+    }
 }
 
 pub struct AcceptNoLabel;
@@ -356,6 +405,31 @@ mod test_label_filter {
         assert!(!filter.accepts(&6));
     }
 
+    #[test]
+    fn match_field_on_a_composite_label() {
+        #[derive(Clone, Copy, PartialEq, Eq)]
+        enum Endpoint {
+            Users,
+            Orders,
+        }
+
+        #[derive(Clone, Copy, PartialEq, Eq)]
+        enum Status {
+            Ok,
+            NotFound,
+        }
+
+        let filter = LabelFilter::match_field(
+            |&(endpoint, _): &(Endpoint, Status)| endpoint,
+            Endpoint::Users,
+        );
+
+        assert!(filter.accepts(&(Endpoint::Users, Status::Ok)));
+        assert!(filter.accepts(&(Endpoint::Users, Status::NotFound)));
+        assert!(!filter.accepts(&(Endpoint::Orders, Status::Ok)));
+        assert!(!filter.accepts(&(Endpoint::Orders, Status::NotFound)));
+    }
+
     #[test]
     fn many_filters() {
         let max = 20;
@@ -0,0 +1,270 @@
+use std::collections::BTreeMap;
+
+use crate::snapshot::{ItemKind, Snapshot};
+use crate::util;
+use crate::{Descriptive, HandlesObservations, Observation, PutsSnapshot};
+
+const DEFAULT_MAX_LABELS: usize = 100;
+
+/// Counts the observations per label
+///
+/// Each observed label is turned into a key by the function given on
+/// creation. The count of each key is put into a nested `Snapshot`
+/// next to the `total` of all observations.
+///
+/// To keep the `Snapshot` from growing without bounds only a limited
+/// number of keys is tracked. Observations with labels resulting in
+/// further keys are counted as `_other`. The default limit is 100.
+///
+/// An `Observation::Observed` and an `Observation::ObservedValues`
+/// increment by their count. All other `Observation`s increment by one.
+///
+/// Since this needs the labels it is not an `Instrument` but
+/// a handler which can be added to a `Panel` via `add_handler`.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Instant;
+/// use metrix::instruments::*;
+/// use metrix::{HandlesObservations, Observation};
+///
+/// let mut counter = LabeledCounter::new("requests", |label: &&str| label.to_string());
+///
+/// counter.handle_observation(&Observation::ObservedOne {
+///     label: "get",
+///     timestamp: Instant::now(),
+/// });
+///
+/// assert_eq!(counter.get("get"), Some(1));
+/// assert_eq!(counter.total(), 1);
+/// ```
+pub struct LabeledCounter<L> {
+    name: String,
+    title: Option<String>,
+    description: Option<String>,
+    key_for_label: Box<dyn Fn(&L) -> String + Send>,
+    max_labels: usize,
+    counts: BTreeMap<String, u64>,
+    other: u64,
+    total: u64,
+}
+
+impl<L> LabeledCounter<L>
+where
+    L: Send + 'static,
+{
+    /// Creates a new `LabeledCounter` where `key_for_label`
+    /// determines the key of the count for a label.
+    pub fn new<T, F>(name: T, key_for_label: F) -> LabeledCounter<L>
+    where
+        T: Into<String>,
+        F: Fn(&L) -> String + Send + 'static,
+    {
+        LabeledCounter {
+            name: name.into(),
+            title: None,
+            description: None,
+            key_for_label: Box::new(key_for_label),
+            max_labels: DEFAULT_MAX_LABELS,
+            counts: BTreeMap::new(),
+            other: 0,
+            total: 0,
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_name<T: Into<String>>(&mut self, name: T) {
+        self.name = name.into();
+    }
+
+    pub fn name<T: Into<String>>(mut self, name: T) -> Self {
+        self.set_name(name);
+        self
+    }
+
+    pub fn set_title<T: Into<String>>(&mut self, title: T) {
+        self.title = Some(title.into())
+    }
+
+    pub fn title<T: Into<String>>(mut self, title: T) -> Self {
+        self.set_title(title);
+        self
+    }
+
+    pub fn set_description<T: Into<String>>(&mut self, description: T) {
+        self.description = Some(description.into())
+    }
+
+    pub fn description<T: Into<String>>(mut self, description: T) -> Self {
+        self.set_description(description);
+        self
+    }
+
+    /// Sets the maximum number of keys to track.
+    ///
+    /// Observations with labels resulting in further keys
+    /// are counted as `_other`.
+    ///
+    /// Default is 100.
+    pub fn set_max_labels(&mut self, max_labels: usize) {
+        self.max_labels = max_labels;
+    }
+
+    /// Sets the maximum number of keys to track.
+    ///
+    /// Default is 100.
+    pub fn max_labels(mut self, max_labels: usize) -> Self {
+        self.set_max_labels(max_labels);
+        self
+    }
+
+    /// Returns the count for the given key if it is tracked
+    pub fn get(&self, key: &str) -> Option<u64> {
+        self.counts.get(key).copied()
+    }
+
+    /// Returns the count of the observations with labels
+    /// which exceeded the maximum number of keys
+    pub fn get_other(&self) -> u64 {
+        self.other
+    }
+
+    /// Returns the count of all observations
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    fn count(&mut self, label: &L, n: u64) {
+        let key = (self.key_for_label)(label);
+        if let Some(count) = self.counts.get_mut(&key) {
+            *count = count.saturating_add(n);
+        } else if self.counts.len() < self.max_labels {
+            self.counts.insert(key, n);
+        } else {
+            self.other = self.other.saturating_add(n);
+        }
+        self.total = self.total.saturating_add(n);
+    }
+}
+
+impl<L> HandlesObservations for LabeledCounter<L>
+where
+    L: Send + 'static,
+{
+    type Label = L;
+
+    fn handle_observation(&mut self, observation: &Observation<Self::Label>) -> usize {
+        let n = match *observation {
+            Observation::Observed { count, .. } | Observation::ObservedValues { count, .. } => {
+                count
+            }
+            _ => 1,
+        };
+        self.count(observation.label(), n);
+        1
+    }
+
+    fn reset(&mut self) {
+        self.counts.clear();
+        self.other = 0;
+        self.total = 0;
+    }
+}
+
+impl<L> PutsSnapshot for LabeledCounter<L>
+where
+    L: Send + 'static,
+{
+    fn put_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
+        util::put_postfixed_descriptives(self, &self.name, into, descriptive);
+        let mut new_level = Snapshot::default();
+        new_level.push("total", self.total.into());
+        for (key, count) in &self.counts {
+            new_level.push(key.clone(), (*count).into());
+        }
+        if self.other > 0 {
+            new_level.push("_other", self.other.into());
+        }
+        into.push(self.name.clone(), ItemKind::Snapshot(new_level));
+    }
+}
+
+impl<L> Descriptive for LabeledCounter<L> {
+    fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::instruments::Panel;
+    use crate::snapshot::FindItem;
+
+    #[test]
+    fn counts_per_label_within_a_panel() {
+        let mut panel = Panel::named(vec![1, 2, 3], "panel")
+            .handler(LabeledCounter::new("requests", |label: &i32| {
+                format!("label_{}", label)
+            }));
+
+        for &label in &[1, 2, 2, 3, 3, 3] {
+            panel.handle_observation(&Observation::ObservedOne {
+                label,
+                timestamp: Instant::now(),
+            });
+        }
+        panel.handle_observation(&Observation::Observed {
+            label: 1,
+            count: 4,
+            timestamp: Instant::now(),
+        });
+
+        let mut snapshot = Snapshot::default();
+        panel.put_snapshot(&mut snapshot, false);
+        let expect = |path: &str, count: u64| {
+            assert_eq!(
+                snapshot.find(path),
+                FindItem::Found(&ItemKind::UInt(count)),
+                "{}",
+                path
+            );
+        };
+        expect("panel/requests/label_1", 5);
+        expect("panel/requests/label_2", 2);
+        expect("panel/requests/label_3", 3);
+        expect("panel/requests/total", 10);
+        assert_eq!(snapshot.find("panel/requests/_other"), FindItem::NotFound);
+    }
+
+    #[test]
+    fn labels_beyond_the_limit_are_counted_as_other() {
+        let mut counter = LabeledCounter::new("", |label: &i32| label.to_string()).max_labels(2);
+
+        for label in 0..5 {
+            counter.handle_observation(&Observation::ObservedOne {
+                label,
+                timestamp: Instant::now(),
+            });
+        }
+
+        assert_eq!(counter.get("0"), Some(1));
+        assert_eq!(counter.get("2"), None);
+        assert_eq!(counter.get_other(), 3);
+        assert_eq!(counter.total(), 5);
+
+        counter.reset();
+        assert_eq!(counter.total(), 0);
+        assert_eq!(counter.get("0"), None);
+    }
+}
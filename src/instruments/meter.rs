@@ -1,7 +1,9 @@
 use std::cell::Cell;
+use std::collections::VecDeque;
 use std::time::{Duration, Instant};
 
 use crate::instruments::fundamentals::metrics_meter::{Meter as MMeter, StdMeter};
+use crate::instruments::fundamentals::{Clock, SharedClock};
 
 use crate::instruments::{
     AcceptAllLabels, Instrument, InstrumentAdapter, LabelFilter, LabelPredicate, Update, Updates,
@@ -16,30 +18,68 @@ use crate::{Descriptive, PutsSnapshot};
 /// counted as 1 occurrence.
 ///
 /// To get rates on values use `instruments::other_instruments::ValueMeter`
+///
+/// The rates are exponentially weighted moving averages over
+/// 1, 5 and 15 minutes as known from Dropwizard metrics. They
+/// are ticked every 5 seconds and appear in the `Snapshot` as
+/// `one_minute/rate`, `five_minutes/rate` and `fifteen_minutes/rate`
+/// next to the total `count`.
+///
+/// Only the one minute rate is enabled by default. The five and
+/// fifteen minute rates are disabled by default and have to be
+/// enabled with `set_five_minute_rate_enabled` and
+/// `set_fifteen_minute_rate_enabled`.
+///
+/// Ticks missed while nothing was observed are caught up on whenever
+/// the rates are read so the rates of an idle meter decay towards zero.
+/// See also `tick`.
+///
+/// The tick interval and an additional rate over a custom window
+/// can be configured to match the cadence of the observations.
+/// See `set_tick_interval` and `set_custom_window`.
+///
+/// Optionally the mean rate since the previous exported snapshot can be
+/// shown as `last_interval/rate`. See `set_last_interval_rate_enabled`.
+///
+/// Optionally the highest rate within a short sliding window can be
+/// shown as `peak_per_second`. See `set_peak_rate_window`.
 pub struct Meter {
     name: String,
     title: Option<String>,
     description: Option<String>,
     last_tick: Cell<Instant>,
+    tick_interval: Duration,
+    custom_window: Option<Duration>,
     inner_meter: StdMeter,
     lower_cutoff: f64,
     one_minute_rate_enabled: bool,
     five_minute_rate_enabled: bool,
     fifteen_minute_rate_enabled: bool,
+    last_interval: Option<Cell<(u64, Instant)>>,
+    peak: Option<PeakRate>,
+    update_tracker: util::UpdateTracker,
+    clock: SharedClock,
 }
 
 impl Meter {
     pub fn new<T: Into<String>>(name: T) -> Meter {
+        let clock = SharedClock::default();
         Meter {
             name: name.into(),
             title: None,
             description: None,
-            last_tick: Cell::new(Instant::now()),
-            inner_meter: StdMeter::default(),
+            last_tick: Cell::new(clock.now()),
+            tick_interval: Duration::from_secs(5),
+            custom_window: None,
+            inner_meter: StdMeter::with_clock(clock.clone()),
             lower_cutoff: 0.001,
             one_minute_rate_enabled: true,
             five_minute_rate_enabled: false,
             fifteen_minute_rate_enabled: false,
+            last_interval: None,
+            peak: None,
+            update_tracker: util::UpdateTracker::default(),
+            clock,
         }
     }
 
@@ -47,6 +87,33 @@ impl Meter {
         Self::new(name)
     }
 
+    /// Returns a meter with the same configuration which
+    /// has not observed anything yet.
+    pub(crate) fn template_clone(&self) -> Meter {
+        let now = self.clock.now();
+        Meter {
+            name: self.name.clone(),
+            title: self.title.clone(),
+            description: self.description.clone(),
+            last_tick: Cell::new(now),
+            tick_interval: self.tick_interval,
+            custom_window: self.custom_window,
+            inner_meter: StdMeter::with_config(
+                self.clock.clone(),
+                self.tick_interval,
+                self.custom_window,
+            ),
+            lower_cutoff: self.lower_cutoff,
+            one_minute_rate_enabled: self.one_minute_rate_enabled,
+            five_minute_rate_enabled: self.five_minute_rate_enabled,
+            fifteen_minute_rate_enabled: self.fifteen_minute_rate_enabled,
+            last_interval: self.last_interval.as_ref().map(|_| Cell::new((0, now))),
+            peak: self.peak.as_ref().map(|peak| PeakRate::new(peak.window)),
+            update_tracker: self.update_tracker.template(),
+            clock: self.clock.clone(),
+        }
+    }
+
     pub fn get_name(&self) -> &str {
         &self.name
     }
@@ -123,14 +190,14 @@ impl Meter {
         self
     }
 
-    /// Enable tracking of one minute rates.
+    /// Enable tracking of fifteen minute rates.
     ///
     /// Default: disabled
     pub fn set_fifteen_minute_rate_enabled(&mut self, enabled: bool) {
         self.fifteen_minute_rate_enabled = enabled;
     }
 
-    /// Enable tracking of one minute rates.
+    /// Enable tracking of fifteen minute rates.
     ///
     /// Default: disabled
     pub fn fifteen_minute_rate_enabled(mut self, enabled: bool) -> Self {
@@ -138,6 +205,188 @@ impl Meter {
         self
     }
 
+    /// Enable tracking of the mean rate since the previous exported
+    /// snapshot.
+    ///
+    /// Each exported snapshot starts a new interval so that the rate
+    /// only reflects the occurrences since the previous one. See
+    /// `PutsSnapshot::put_exported_snapshot`. The first interval starts
+    /// when this is enabled. If no time elapsed since the previous
+    /// exported snapshot the rate is zero and the interval continues.
+    ///
+    /// Default: disabled
+    pub fn set_last_interval_rate_enabled(&mut self, enabled: bool) {
+        if !enabled {
+            self.last_interval = None;
+        } else if self.last_interval.is_none() {
+            self.last_interval = Some(Cell::new((0, self.clock.now())));
+        }
+    }
+
+    /// Enable tracking of the mean rate since the previous exported
+    /// snapshot.
+    ///
+    /// See `set_last_interval_rate_enabled`.
+    ///
+    /// Default: disabled
+    pub fn last_interval_rate_enabled(mut self, enabled: bool) -> Self {
+        self.set_last_interval_rate_enabled(enabled);
+        self
+    }
+
+    /// Sets the interval in which the rates are updated.
+    ///
+    /// A shorter interval makes the rates follow the observations more
+    /// closely. The windows of the rates are not affected.
+    ///
+    /// All values observed so far are discarded so this should be done
+    /// right after creation.
+    ///
+    /// Default is 5 seconds.
+    ///
+    /// # Panics
+    ///
+    /// If `interval` is zero.
+    pub fn set_tick_interval(&mut self, interval: Duration) {
+        if interval == Duration::from_secs(0) {
+            panic!("the tick interval must not be zero");
+        }
+        self.tick_interval = interval;
+        self.reset();
+    }
+
+    /// Sets the interval in which the rates are updated.
+    ///
+    /// See `set_tick_interval`.
+    ///
+    /// Default is 5 seconds.
+    pub fn tick_interval(mut self, interval: Duration) -> Self {
+        self.set_tick_interval(interval);
+        self
+    }
+
+    /// Enables an additional rate which is an exponentially weighted
+    /// moving average over the given window. It is shown as
+    /// `custom_window/rate`.
+    ///
+    /// The window should be a multiple of the tick interval.
+    ///
+    /// All values observed so far are discarded so this should be done
+    /// right after creation.
+    ///
+    /// Default is disabled.
+    ///
+    /// # Panics
+    ///
+    /// If `window` is zero.
+    pub fn set_custom_window(&mut self, window: Duration) {
+        if window == Duration::from_secs(0) {
+            panic!("the window must not be zero");
+        }
+        self.custom_window = Some(window);
+        self.reset();
+    }
+
+    /// Enables an additional rate which is an exponentially weighted
+    /// moving average over the given window.
+    ///
+    /// See `set_custom_window`.
+    ///
+    /// Default is disabled.
+    pub fn custom_window(mut self, window: Duration) -> Self {
+        self.set_custom_window(window);
+        self
+    }
+
+    /// Enables tracking of the highest rate observed within
+    /// a sliding window of the given length.
+    ///
+    /// Whenever the meter is updated the number of occurrences within
+    /// the preceding window divided by the window length is taken as the
+    /// current rate. The highest of these rates is shown as
+    /// `peak_per_second` until it is cleared with `reset_peak`.
+    ///
+    /// Default: disabled
+    ///
+    /// # Panics
+    ///
+    /// If `window` is zero.
+    pub fn set_peak_rate_window(&mut self, window: Duration) {
+        if window == Duration::from_secs(0) {
+            panic!("the peak rate window must not be zero");
+        }
+        self.peak = Some(PeakRate::new(window));
+    }
+
+    /// Enables tracking of the highest rate observed within
+    /// a sliding window of the given length.
+    ///
+    /// See `set_peak_rate_window`.
+    ///
+    /// Default: disabled
+    pub fn peak_rate_window(mut self, window: Duration) -> Self {
+        self.set_peak_rate_window(window);
+        self
+    }
+
+    /// Returns the highest rate observed since the peak was
+    /// cleared last if peak rate tracking is enabled.
+    pub fn get_peak_rate(&self) -> Option<f64> {
+        self.peak.as_ref().map(|peak| peak.peak)
+    }
+
+    /// Clears the peak rate so that the next updates determine
+    /// a new peak. The occurrences within the current window are kept.
+    pub fn reset_peak(&mut self) {
+        if let Some(ref mut peak) = self.peak {
+            peak.peak = 0.0;
+        }
+    }
+
+    /// Enables tracking when this meter was updated last.
+    ///
+    /// The number of seconds since the last update will be put into
+    /// the `Snapshot` as `_updated_secs_ago`. Nothing is added before the first update.
+    ///
+    /// Default is disabled.
+    pub fn set_updated_secs_ago_enabled(&mut self, enabled: bool) {
+        self.update_tracker.set_enabled(enabled);
+    }
+
+    /// Enables tracking when this meter was updated last.
+    ///
+    /// The number of seconds since the last update will be put into
+    /// the `Snapshot` as `_updated_secs_ago`. Nothing is added before the first update.
+    ///
+    /// Default is disabled.
+    pub fn updated_secs_ago_enabled(mut self, enabled: bool) -> Self {
+        self.set_updated_secs_ago_enabled(enabled);
+        self
+    }
+
+    /// Sets the `Clock` used for ticking the rates and for the time
+    /// since the last update.
+    ///
+    /// All values observed so far are discarded so this should be done
+    /// right after creation. Mostly useful for testing with
+    /// a `ManualOffsetClock`.
+    ///
+    /// Default is the `WallClock`.
+    pub fn set_clock<C: Clock + Send + Sync + 'static>(&mut self, clock: C) {
+        self.clock = SharedClock::new(clock);
+        self.update_tracker.set_clock(self.clock.clone());
+        self.reset();
+    }
+
+    /// Sets the `Clock` used for ticking the rates and for the time
+    /// since the last update.
+    ///
+    /// Default is the `WallClock`.
+    pub fn clock<C: Clock + Send + Sync + 'static>(mut self, clock: C) -> Self {
+        self.set_clock(clock);
+        self
+    }
+
     pub fn accept<L: Eq + Send + 'static, F: Into<LabelFilter<L>>>(
         self,
         accept: F,
@@ -181,10 +430,25 @@ impl Meter {
         InstrumentAdapter::deaf(self)
     }
 
+    /// Updates the rates for the time elapsed since the last update.
+    ///
+    /// The rates decay towards zero for all tick intervals in which
+    /// nothing was observed. Taking a `Snapshot` does this anyways so
+    /// this is only needed to keep the rates current in between.
+    pub fn tick(&mut self) {
+        self.inner_meter.tick();
+        self.last_tick.set(self.clock.now());
+    }
+
     pub(crate) fn get_snapshot(&self) -> MeterSnapshot {
-        if self.last_tick.get().elapsed() >= Duration::from_secs(5) {
+        self.snapshot_values(false)
+    }
+
+    /// Starts a new interval for the last interval rate if `exported`
+    fn snapshot_values(&self, exported: bool) -> MeterSnapshot {
+        if self.clock.now() - self.last_tick.get() >= self.tick_interval {
             self.inner_meter.tick();
-            self.last_tick.set(Instant::now());
+            self.last_tick.set(self.clock.now());
         }
 
         let snapshot = self.inner_meter.snapshot();
@@ -194,6 +458,7 @@ impl Meter {
             title: self.title.as_ref().map(|x| &**x),
             description: self.description.as_ref().map(|x| &**x),
             count: snapshot.count as u64,
+            total_sum: None,
             one_minute: if self.one_minute_rate_enabled {
                 Some(MeterRate {
                     rate: if snapshot.rates[0] < self.lower_cutoff {
@@ -230,13 +495,117 @@ impl Meter {
             } else {
                 None
             },
+            custom_window: snapshot.custom_rate.map(|rate| MeterRate {
+                rate: if rate < self.lower_cutoff { 0.0 } else { rate },
+                share: None,
+            }),
+            last_interval: self.last_interval_rate(exported),
+            peak_per_second: self.get_peak_rate(),
+            bytes_per_second: None,
+            update_tracker: Some(&self.update_tracker),
         };
 
         meter_snapshot
     }
+
+    fn last_interval_rate(&self, exported: bool) -> Option<MeterRate> {
+        let interval = self.last_interval.as_ref()?;
+        let (count, started) = interval.get();
+        let now = self.clock.now();
+        let elapsed = now - started;
+
+        let rate = if elapsed > Duration::from_secs(0) {
+            if exported {
+                interval.set((0, now));
+            }
+            count as f64 / elapsed.as_secs_f64()
+        } else {
+            0.0
+        };
+
+        Some(MeterRate {
+            rate: if rate < self.lower_cutoff { 0.0 } else { rate },
+            share: None,
+        })
+    }
+
+    fn count_in_last_interval(&self, n: u64) {
+        if let Some(ref interval) = self.last_interval {
+            let (count, started) = interval.get();
+            interval.set((count.saturating_add(n), started));
+        }
+    }
+
+    fn count_for_peak(&mut self, n: u64) {
+        let now = self.clock.now();
+        if let Some(ref mut peak) = self.peak {
+            peak.mark(n, now);
+        }
+    }
+}
+
+/// The occurrences within a sliding window and the
+/// highest rate seen so far
+struct PeakRate {
+    window: Duration,
+    marks: VecDeque<(Instant, u64)>,
+    in_window: u64,
+    peak: f64,
 }
 
-impl Instrument for Meter {}
+impl PeakRate {
+    fn new(window: Duration) -> Self {
+        PeakRate {
+            window,
+            marks: VecDeque::new(),
+            in_window: 0,
+            peak: 0.0,
+        }
+    }
+
+    fn mark(&mut self, n: u64, now: Instant) {
+        while let Some(&(at, count)) = self.marks.front() {
+            if now.saturating_duration_since(at) < self.window {
+                break;
+            }
+            self.in_window -= count;
+            self.marks.pop_front();
+        }
+
+        self.marks.push_back((now, n));
+        self.in_window = self.in_window.saturating_add(n);
+
+        let rate = self.in_window as f64 / self.window.as_secs_f64();
+        if rate > self.peak {
+            self.peak = rate;
+        }
+    }
+
+    fn clear(&mut self) {
+        self.marks.clear();
+        self.in_window = 0;
+        self.peak = 0.0;
+    }
+}
+
+impl Instrument for Meter {
+    fn name(&self) -> Option<&str> {
+        Some(self.get_name())
+    }
+
+    fn reset(&mut self) {
+        self.inner_meter =
+            StdMeter::with_config(self.clock.clone(), self.tick_interval, self.custom_window);
+        self.last_tick.set(self.clock.now());
+        if let Some(ref interval) = self.last_interval {
+            interval.set((0, self.clock.now()));
+        }
+        if let Some(ref mut peak) = self.peak {
+            peak.clear();
+        }
+        self.update_tracker.reset();
+    }
+}
 
 impl PutsSnapshot for Meter {
     fn put_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
@@ -244,23 +613,45 @@ impl PutsSnapshot for Meter {
 
         meter_snapshot.put_snapshot(into, descriptive);
     }
+
+    fn put_exported_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
+        let meter_snapshot = self.snapshot_values(true);
+
+        meter_snapshot.put_snapshot(into, descriptive);
+    }
+
+    fn put_names(&self, into: &mut Snapshot) {
+        util::put_name(&self.name, into)
+    }
 }
 
 impl Updates for Meter {
     fn update(&mut self, with: &Update) -> usize {
-        if self.last_tick.get().elapsed() >= Duration::from_secs(5) {
+        if self.clock.now() - self.last_tick.get() >= self.tick_interval {
             self.inner_meter.tick();
-            self.last_tick.set(Instant::now());
+            self.last_tick.set(self.clock.now());
         }
 
+        self.update_tracker.updated();
+
         match *with {
-            Update::ObservationWithValue(_, _) => self.inner_meter.mark(1),
-            Update::Observations(n, _) => {
+            Update::ObservationWithValue(_, _) => {
+                self.inner_meter.mark(1);
+                self.count_in_last_interval(1);
+                self.count_for_peak(1);
+            }
+            Update::Observations(n, _) | Update::ObservationsWithValue(_, n, _) => {
                 if n <= ::std::i64::MAX as u64 && n != 0 {
-                    self.inner_meter.mark(n as i64)
+                    self.inner_meter.mark(n as i64);
+                    self.count_in_last_interval(n);
+                    self.count_for_peak(n);
                 }
             }
-            Update::Observation(_) => self.inner_meter.mark(1),
+            Update::Observation(_) => {
+                self.inner_meter.mark(1);
+                self.count_in_last_interval(1);
+                self.count_for_peak(1);
+            }
         }
 
         1
@@ -282,9 +673,15 @@ pub(crate) struct MeterSnapshot<'a> {
     pub title: Option<&'a str>,
     pub description: Option<&'a str>,
     pub count: u64,
+    pub total_sum: Option<i64>,
     pub one_minute: Option<MeterRate>,
     pub five_minutes: Option<MeterRate>,
     pub fifteen_minutes: Option<MeterRate>,
+    pub custom_window: Option<MeterRate>,
+    pub last_interval: Option<MeterRate>,
+    pub peak_per_second: Option<f64>,
+    pub bytes_per_second: Option<f64>,
+    pub update_tracker: Option<&'a util::UpdateTracker>,
 }
 
 impl<'a> MeterSnapshot<'a> {
@@ -296,6 +693,12 @@ impl<'a> MeterSnapshot<'a> {
             .items
             .push(("count".to_string(), self.count.into()));
 
+        if let Some(total_sum) = self.total_sum {
+            new_level
+                .items
+                .push(("total_sum".to_string(), total_sum.into()));
+        }
+
         if let Some(ref one_minute_data) = self.one_minute {
             let mut one_minute = Snapshot::default();
             one_minute_data.put_snapshot(&mut one_minute);
@@ -321,6 +724,48 @@ impl<'a> MeterSnapshot<'a> {
             ));
         }
 
+        if let Some(ref custom_window_data) = self.custom_window {
+            let mut custom_window = Snapshot::default();
+            custom_window_data.put_snapshot(&mut custom_window);
+            new_level.items.push((
+                "custom_window".to_string(),
+                ItemKind::Snapshot(custom_window),
+            ));
+        }
+
+        if let Some(ref last_interval_data) = self.last_interval {
+            let mut last_interval = Snapshot::default();
+            last_interval_data.put_snapshot(&mut last_interval);
+            new_level.items.push((
+                "last_interval".to_string(),
+                ItemKind::Snapshot(last_interval),
+            ));
+        }
+
+        if let Some(peak_per_second) = self.peak_per_second {
+            new_level
+                .items
+                .push(("peak_per_second".to_string(), peak_per_second.into()));
+        }
+
+        if let Some(bytes_per_second) = self.bytes_per_second {
+            let (throughput, unit) = scale_throughput(bytes_per_second);
+            new_level
+                .items
+                .push(("bytes_per_second".to_string(), bytes_per_second.into()));
+            new_level
+                .items
+                .push(("throughput".to_string(), throughput.into()));
+            new_level.items.push((
+                "throughput_unit".to_string(),
+                ItemKind::Text(unit.to_string()),
+            ));
+        }
+
+        if let Some(update_tracker) = self.update_tracker {
+            update_tracker.put(&mut new_level);
+        }
+
         into_container.push(self.name, ItemKind::Snapshot(new_level));
     }
 }
@@ -335,6 +780,20 @@ impl<'a> Descriptive for MeterSnapshot<'a> {
     }
 }
 
+/// Scales bytes per second to the largest binary unit
+/// which keeps the value at or above 1
+fn scale_throughput(bytes_per_second: f64) -> (f64, &'static str) {
+    const KIB: f64 = 1024.0;
+    const MIB: f64 = 1024.0 * 1024.0;
+    if bytes_per_second >= MIB {
+        (bytes_per_second / MIB, "MiB/s")
+    } else if bytes_per_second >= KIB {
+        (bytes_per_second / KIB, "KiB/s")
+    } else {
+        (bytes_per_second, "B/s")
+    }
+}
+
 pub(crate) struct MeterRate {
     pub rate: f64,
     pub share: Option<f64>,
@@ -348,3 +807,250 @@ impl MeterRate {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::instruments::ManualOffsetClock;
+    use crate::snapshot::FindItem;
+
+    fn last_interval_rate(meter: &Meter) -> f64 {
+        let mut snapshot = Snapshot::default();
+        meter.put_exported_snapshot(&mut snapshot, false);
+        match snapshot.find("meter/last_interval/rate") {
+            FindItem::Found(ItemKind::Float(rate)) => *rate,
+            other => panic!("no rate found: {}", other),
+        }
+    }
+
+    #[test]
+    fn last_interval_rate_only_reflects_the_last_interval() {
+        let clock = ManualOffsetClock::default();
+        let mut meter = Meter::new("meter")
+            .clock(clock.clone())
+            .last_interval_rate_enabled(true);
+
+        meter.update(&Update::Observations(100, clock.now()));
+        clock.advance_n_seconds(2);
+        assert_eq!(last_interval_rate(&meter), 50.0);
+
+        meter.update(&Update::Observations(10, clock.now()));
+        clock.advance_n_seconds(5);
+        assert_eq!(last_interval_rate(&meter), 2.0);
+
+        clock.advance_a_second();
+        assert_eq!(last_interval_rate(&meter), 0.0);
+    }
+
+    #[test]
+    fn only_exported_snapshots_start_a_new_interval() {
+        let clock = ManualOffsetClock::default();
+        let mut meter = Meter::new("meter")
+            .clock(clock.clone())
+            .last_interval_rate_enabled(true);
+
+        meter.update(&Update::Observations(100, clock.now()));
+        clock.advance_n_seconds(2);
+        meter.put_snapshot(&mut Snapshot::default(), false);
+        clock.advance_n_seconds(2);
+        assert_eq!(last_interval_rate(&meter), 25.0);
+    }
+
+    fn one_minute_rate(meter: &Meter) -> f64 {
+        let mut snapshot = Snapshot::default();
+        meter.put_snapshot(&mut snapshot, false);
+        match snapshot.find("meter/one_minute/rate") {
+            FindItem::Found(ItemKind::Float(rate)) => *rate,
+            other => panic!("no rate found: {}", other),
+        }
+    }
+
+    #[test]
+    fn the_count_is_the_total_of_all_marks() {
+        let mut meter = Meter::new("meter");
+        meter.update(&Update::Observation(Instant::now()));
+        meter.update(&Update::Observations(5, Instant::now()));
+
+        let mut snapshot = Snapshot::default();
+        meter.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("meter/count"),
+            FindItem::Found(&ItemKind::UInt(6))
+        );
+    }
+
+    #[test]
+    fn a_manual_clock_drives_the_rates() {
+        let clock = ManualOffsetClock::default();
+        let mut meter = Meter::new("meter").clock(clock.clone());
+
+        meter.update(&Update::Observations(300, clock.now()));
+        assert_eq!(one_minute_rate(&meter), 0.0);
+
+        clock.advance_n_seconds(5);
+        let rate = one_minute_rate(&meter);
+        assert!((rate - 60.0).abs() < 1e-9, "rate: {}", rate);
+
+        clock.advance_n_seconds(5);
+        let rate = one_minute_rate(&meter);
+        let expected = 60.0 * (-5.0f64 / 60.0).exp();
+        assert!((rate - expected).abs() < 1e-9, "rate: {}", rate);
+    }
+
+    #[test]
+    fn only_the_one_minute_rate_is_enabled_by_default() {
+        let mut meter = Meter::new("meter");
+        meter.update(&Update::Observation(Instant::now()));
+
+        let mut snapshot = Snapshot::default();
+        meter.put_snapshot(&mut snapshot, false);
+        assert!(matches!(
+            snapshot.find("meter/one_minute/rate"),
+            FindItem::Found(ItemKind::Float(_))
+        ));
+        assert_eq!(snapshot.find("meter/five_minutes"), FindItem::NotFound);
+        assert_eq!(snapshot.find("meter/fifteen_minutes"), FindItem::NotFound);
+    }
+
+    #[test]
+    fn the_moving_rates_converge_to_a_steady_rate() {
+        let clock = ManualOffsetClock::default();
+        let mut meter = Meter::new("meter")
+            .five_minute_rate_enabled(true)
+            .fifteen_minute_rate_enabled(true)
+            .clock(clock.clone());
+
+        let rates = |meter: &Meter| {
+            let mut snapshot = Snapshot::default();
+            meter.put_snapshot(&mut snapshot, false);
+            let rate = |path: &str| match snapshot.find(path) {
+                FindItem::Found(ItemKind::Float(rate)) => *rate,
+                other => panic!("no rate found at {}: {}", path, other),
+            };
+            (
+                rate("meter/one_minute/rate"),
+                rate("meter/five_minutes/rate"),
+                rate("meter/fifteen_minutes/rate"),
+            )
+        };
+
+        // 2 occurrences per second for 30 minutes
+        for _ in 0..(30 * 60) {
+            meter.update(&Update::Observations(2, clock.now()));
+            clock.advance_a_second();
+        }
+
+        let (one, five, fifteen) = rates(&meter);
+        assert!((one - 2.0).abs() < 0.01, "one: {}", one);
+        assert!((five - 2.0).abs() < 0.01, "five: {}", five);
+        assert!((fifteen - 2.0).abs() < 0.01, "fifteen: {}", fifteen);
+
+        // The shorter the window the faster the rate decays
+        clock.advance_n_seconds(60);
+        let (one, five, fifteen) = rates(&meter);
+        assert!(one < five, "one: {}, five: {}", one, five);
+        assert!(five < fifteen, "five: {}, fifteen: {}", five, fifteen);
+        assert!(fifteen < 2.0, "fifteen: {}", fifteen);
+    }
+
+    #[test]
+    fn an_idle_meter_decays_when_ticked() {
+        let clock = ManualOffsetClock::default();
+        let mut meter = Meter::new("meter").clock(clock.clone());
+        let mut idle = Meter::new("meter").clock(clock.clone());
+
+        meter.update(&Update::Observations(600, clock.now()));
+        clock.advance_n_seconds(5);
+        meter.tick();
+        idle.tick();
+        let mut previous = one_minute_rate(&meter);
+        assert!(previous > 0.0);
+
+        for _ in 0..5 {
+            clock.advance_n_seconds(5);
+            meter.tick();
+            idle.tick();
+            let rate = one_minute_rate(&meter);
+            assert!(rate < previous, "rate: {}, previous: {}", rate, previous);
+            previous = rate;
+        }
+        assert_eq!(one_minute_rate(&idle), 0.0);
+    }
+
+    #[test]
+    fn a_burst_raises_the_peak_rate_above_the_steady_rate() {
+        let clock = ManualOffsetClock::default();
+        let mut meter = Meter::new("meter")
+            .clock(clock.clone())
+            .peak_rate_window(Duration::from_secs(1));
+
+        for _ in 0..600 {
+            meter.update(&Update::Observation(clock.now()));
+            clock.advance_millis(100);
+        }
+        let steady = meter.get_peak_rate().unwrap();
+        assert!((steady - 10.0).abs() < 1e-9, "steady: {}", steady);
+
+        for _ in 0..50 {
+            meter.update(&Update::Observation(clock.now()));
+            clock.advance_millis(10);
+        }
+        let peak = meter.get_peak_rate().unwrap();
+        assert!(peak > 5.0 * steady, "peak: {}", peak);
+        assert!(peak > one_minute_rate(&meter));
+
+        let mut snapshot = Snapshot::default();
+        meter.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("meter/peak_per_second"),
+            FindItem::Found(&ItemKind::Float(peak))
+        );
+
+        meter.reset_peak();
+        clock.advance_n_seconds(2);
+        meter.update(&Update::Observation(clock.now()));
+        assert_eq!(meter.get_peak_rate(), Some(1.0));
+    }
+
+    fn custom_window_rate(meter: &Meter) -> f64 {
+        let mut snapshot = Snapshot::default();
+        meter.put_snapshot(&mut snapshot, false);
+        match snapshot.find("meter/custom_window/rate") {
+            FindItem::Found(ItemKind::Float(rate)) => *rate,
+            other => panic!("no rate found: {}", other),
+        }
+    }
+
+    #[test]
+    fn a_short_window_follows_a_step_change_faster() {
+        let clock = ManualOffsetClock::default();
+        let mut meter = Meter::new("meter")
+            .clock(clock.clone())
+            .tick_interval(Duration::from_millis(10))
+            .custom_window(Duration::from_millis(100));
+        let mut default_meter = Meter::new("meter").clock(clock.clone());
+
+        // Nothing happens for a while
+        clock.advance_n_seconds(5);
+        assert_eq!(custom_window_rate(&meter), 0.0);
+        assert_eq!(one_minute_rate(&default_meter), 0.0);
+
+        // A step from 0 to 1000 per second for five seconds
+        for _ in 0..500 {
+            meter.update(&Update::Observations(10, clock.now()));
+            default_meter.update(&Update::Observations(10, clock.now()));
+            clock.advance_millis(10);
+        }
+
+        let fast = custom_window_rate(&meter);
+        assert!((fast - 1_000.0).abs() < 1.0, "fast: {}", fast);
+        let slow = one_minute_rate(&default_meter);
+        assert!(slow < 200.0, "slow: {}", slow);
+    }
+
+    #[test]
+    #[should_panic]
+    fn the_tick_interval_must_be_positive() {
+        Meter::new("meter").tick_interval(Duration::from_secs(0));
+    }
+}
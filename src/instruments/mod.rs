@@ -5,29 +5,42 @@ use std::time::Instant;
 use crate::{Observation, ObservedValue, PutsSnapshot, TimeUnit};
 
 pub use self::counter::Counter;
+pub use self::distinct_counter::DistinctCounter;
+pub use self::distribution_gauge::DistributionGauge;
+pub use self::fundamentals::{Clock, ManualOffsetClock, WallClock};
 pub use self::gauge::*;
 pub use self::histogram::Histogram;
 pub use self::instrument_adapter::*;
 pub use self::label_filter::*;
+pub use self::labeled_counter::LabeledCounter;
 pub use self::meter::Meter;
 pub use self::other_instruments::*;
 pub use self::panel::*;
 pub use self::polled::*;
+pub use self::ratio_instrument::RatioInstrument;
+pub use self::shared::{SharedCounter, SharedGauge};
+pub use self::summary::Summary;
 pub use self::switches::*;
 pub use crate::cockpit::Cockpit;
 
 mod counter;
-mod fundamentals;
+mod distinct_counter;
+mod distribution_gauge;
+pub(crate) mod fundamentals;
 mod gauge;
 mod histogram;
 mod instrument_adapter;
 #[cfg(feature = "jemalloc-ctl")]
 pub mod jemalloc;
 mod label_filter;
+mod labeled_counter;
 mod meter;
 pub mod other_instruments;
 mod panel;
 pub mod polled;
+mod ratio_instrument;
+mod shared;
+mod summary;
 pub mod switches;
 
 #[derive(Debug, Clone)]
@@ -39,6 +52,8 @@ pub enum Update {
     Observation(Instant),
     /// One observation with a value observed at a given time
     ObservationWithValue(ObservedValue, Instant),
+    /// Many observations each with the same value observed at a given time
+    ObservationsWithValue(ObservedValue, u64, Instant),
 }
 
 /// A label with the associated `Update`
@@ -64,6 +79,15 @@ impl<T> From<Observation<T>> for LabelAndUpdate<T> {
                 timestamp,
                 ..
             } => LabelAndUpdate(label, Update::ObservationWithValue(value, timestamp)),
+            Observation::ObservedValues {
+                label,
+                value,
+                count,
+                timestamp,
+            } => LabelAndUpdate(
+                label,
+                Update::ObservationsWithValue(value, count, timestamp),
+            ),
         }
     }
 }
@@ -91,6 +115,15 @@ impl<'a, T> From<&'a Observation<T>> for BorrowedLabelAndUpdate<'a, T> {
                 timestamp,
                 ..
             } => BorrowedLabelAndUpdate(label, Update::ObservationWithValue(*value, *timestamp)),
+            Observation::ObservedValues {
+                label,
+                value,
+                count,
+                timestamp,
+            } => BorrowedLabelAndUpdate(
+                label,
+                Update::ObservationsWithValue(*value, *count, *timestamp),
+            ),
         }
     }
 }
@@ -111,7 +144,26 @@ pub trait Updates {
 }
 
 /// Requirement for an instrument
-pub trait Instrument: Updates + PutsSnapshot {}
+pub trait Instrument: Updates + PutsSnapshot {
+    /// Returns the name under which the instrument puts its values
+    /// into the `Snapshot`.
+    ///
+    /// An `InstrumentAdapter` puts its markers for disabled or
+    /// inactive instruments next to or into the values with this name
+    /// and leaves them out for instruments without a name.
+    ///
+    /// The default is `None`.
+    fn name(&self) -> Option<&str> {
+        None
+    }
+
+    /// Resets the instrument to the state it had when it was created.
+    ///
+    /// The configuration (e.g. name, title) is kept.
+    ///
+    /// The default does nothing.
+    fn reset(&mut self) {}
+}
 
 fn duration_to_display_value(time: u64, current_unit: TimeUnit, target_unit: TimeUnit) -> u64 {
     use TimeUnit::*;
@@ -239,4 +291,22 @@ mod test_time_conversion {
             "to seconds"
         );
     }
+
+    #[test]
+    fn auto_scale_selects_a_readable_unit() {
+        let cases = [
+            (0, 0.0, "nanoseconds"),
+            (999, 999.0, "nanoseconds"),
+            (1_000, 1.0, "microseconds"),
+            (2_500_000, 2.5, "milliseconds"),
+            (1_500_000_000, 1.5, "seconds"),
+            (3_600_000_000_000, 3_600.0, "seconds"),
+        ];
+
+        for &(nanos, expected_value, expected_unit) in &cases {
+            let (value, unit) = TimeUnit::auto_scale(nanos);
+            assert_eq!(unit.name(), expected_unit, "{}", nanos);
+            assert!((value - expected_value).abs() < 0.0001, "{}", nanos);
+        }
+    }
 }
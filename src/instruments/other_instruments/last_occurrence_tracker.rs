@@ -8,13 +8,24 @@ use crate::util;
 use crate::{Descriptive, PutsSnapshot};
 
 /// Tracks how many seconds elapsed since the last occurrence
+///
+/// Next to the seconds since the last occurrence the snapshot contains
+/// the seconds since the first occurrence as
+/// `[name]_first_occurrence_secs_ago`. Without any occurrence the ages
+/// are omitted unless `make_none_zero` is enabled.
+///
+/// The number of occurrences can be added as `[name]_occurrence_count`.
+/// See `set_occurrence_count_enabled`.
 pub struct LastOccurrenceTracker {
     name: String,
     title: Option<String>,
     description: Option<String>,
+    happened_first: Option<Instant>,
     happened_last: Option<Instant>,
+    occurrences: u64,
     invert: bool,
     make_none_zero: bool,
+    occurrence_count_enabled: bool,
 }
 
 impl LastOccurrenceTracker {
@@ -23,9 +34,12 @@ impl LastOccurrenceTracker {
             name: name.into(),
             title: None,
             description: None,
+            happened_first: None,
             happened_last: None,
+            occurrences: 0,
             invert: false,
             make_none_zero: false,
+            occurrence_count_enabled: false,
         }
     }
 
@@ -116,6 +130,33 @@ impl LastOccurrenceTracker {
         self.make_none_zero
     }
 
+    /// Returns how many occurrences were tracked
+    pub fn get_occurrence_count(&self) -> u64 {
+        self.occurrences
+    }
+
+    /// If enabled the number of occurrences is put into the `Snapshot`
+    /// as `[name]_occurrence_count`.
+    ///
+    /// Default is disabled.
+    pub fn set_occurrence_count_enabled(&mut self, enabled: bool) {
+        self.occurrence_count_enabled = enabled
+    }
+
+    /// If enabled the number of occurrences is put into the `Snapshot`
+    /// as `[name]_occurrence_count`.
+    ///
+    /// Default is disabled.
+    pub fn occurrence_count_enabled(mut self, enabled: bool) -> Self {
+        self.set_occurrence_count_enabled(enabled);
+        self
+    }
+
+    /// Returns whether the number of occurrences is put into the `Snapshot`
+    pub fn get_occurrence_count_enabled(&self) -> bool {
+        self.occurrence_count_enabled
+    }
+
     pub fn accept<L: Eq + Send + 'static, F: Into<LabelFilter<L>>>(
         self,
         accept: F,
@@ -163,27 +204,58 @@ impl LastOccurrenceTracker {
         self.happened_last
             .map(|last| (Instant::now() - last).as_secs())
     }
+
+    fn elapsed_since_first_occurrence(&self) -> Option<u64> {
+        self.happened_first
+            .map(|first| (Instant::now() - first).as_secs())
+    }
+
+    fn put_age(&self, key: String, age: Option<u64>, into: &mut Snapshot) {
+        if let Some(v) = age {
+            into.items.push((key, v.into()));
+        } else if self.get_make_none_zero() {
+            into.items.push((key, 0.into()));
+        }
+    }
 }
 
-impl Instrument for LastOccurrenceTracker {}
+impl Instrument for LastOccurrenceTracker {
+    fn name(&self) -> Option<&str> {
+        Some(self.get_name())
+    }
+}
 
 impl PutsSnapshot for LastOccurrenceTracker {
     fn put_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
         util::put_postfixed_descriptives(self, &self.name, into, descriptive);
 
-        if let Some(v) = self.elapsed_since_last_occurrence() {
-            into.items.push((self.name.clone(), v.into()));
-        } else {
-            if self.get_make_none_zero() {
-                into.items.push((self.name.clone(), 0.into()));
-            }
+        self.put_age(
+            self.name.clone(),
+            self.elapsed_since_last_occurrence(),
+            into,
+        );
+        if self.occurrence_count_enabled {
+            into.items.push((
+                format!("{}_occurrence_count", self.name),
+                self.occurrences.into(),
+            ));
         }
+        self.put_age(
+            format!("{}_first_occurrence_secs_ago", self.name),
+            self.elapsed_since_first_occurrence(),
+            into,
+        );
     }
 }
 
 impl Updates for LastOccurrenceTracker {
     fn update(&mut self, _: &Update) -> usize {
-        self.happened_last = Some(Instant::now());
+        let now = Instant::now();
+        if self.happened_first.is_none() {
+            self.happened_first = Some(now);
+        }
+        self.happened_last = Some(now);
+        self.occurrences += 1;
         1
     }
 }
@@ -197,3 +269,53 @@ impl Descriptive for LastOccurrenceTracker {
         self.description.as_ref().map(|n| &**n)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::snapshot::{FindItem, ItemKind};
+
+    #[test]
+    fn occurrences_are_counted() {
+        let mut tracker = LastOccurrenceTracker::new("error").occurrence_count_enabled(true);
+
+        let mut snapshot = Snapshot::default();
+        tracker.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("error_occurrence_count"),
+            FindItem::Found(&ItemKind::UInt(0))
+        );
+        assert_eq!(snapshot.find("error"), FindItem::NotFound);
+        assert_eq!(
+            snapshot.find("error_first_occurrence_secs_ago"),
+            FindItem::NotFound
+        );
+
+        for _ in 0..3 {
+            tracker.update(&Update::ObservationWithValue(1.into(), Instant::now()));
+        }
+
+        let mut snapshot = Snapshot::default();
+        tracker.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("error_occurrence_count"),
+            FindItem::Found(&ItemKind::UInt(3))
+        );
+        assert_eq!(snapshot.find("error"), FindItem::Found(&ItemKind::UInt(0)));
+        assert_eq!(
+            snapshot.find("error_first_occurrence_secs_ago"),
+            FindItem::Found(&ItemKind::UInt(0))
+        );
+    }
+
+    #[test]
+    fn the_occurrence_count_is_left_out_by_default() {
+        let mut tracker = LastOccurrenceTracker::new("error");
+        tracker.update(&Update::ObservationWithValue(1.into(), Instant::now()));
+
+        let mut snapshot = Snapshot::default();
+        tracker.put_snapshot(&mut snapshot, false);
+        assert_eq!(snapshot.find("error_occurrence_count"), FindItem::NotFound);
+        assert_eq!(tracker.get_occurrence_count(), 1);
+    }
+}
@@ -1,8 +1,10 @@
 //! Other instruments
 pub use self::last_occurrence_tracker::LastOccurrenceTracker;
 //pub use self::multi_meter::*;
-pub use self::value_meter::ValueMeter;
+pub use self::value_meter::{RateUnit, ValueMeter};
+pub use self::wrapping_counter::WrappingCounter;
 
 mod last_occurrence_tracker;
 //mod multi_meter;
 mod value_meter;
+mod wrapping_counter;
@@ -2,6 +2,7 @@ use std::cell::Cell;
 use std::time::{Duration, Instant};
 
 use crate::instruments::fundamentals::metrics_meter::{Meter as MMeter, StdMeter};
+use crate::instruments::fundamentals::{Clock, SharedClock};
 
 use crate::instruments::meter::{MeterRate, MeterSnapshot};
 use crate::instruments::{
@@ -10,7 +11,31 @@ use crate::instruments::{
 use crate::snapshot::Snapshot;
 use crate::{Descriptive, ObservedValue, PutsSnapshot, TimeUnit};
 
+/// The time unit the rates of a `ValueMeter` are given for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RateUnit {
+    /// The sum of the values per second. This is the default.
+    #[default]
+    Second,
+    /// The sum of the values per minute
+    Minute,
+}
+
+impl RateUnit {
+    fn seconds(self) -> f64 {
+        match self {
+            RateUnit::Second => 1.0,
+            RateUnit::Minute => 60.0,
+        }
+    }
+}
+
 /// A meter that is ticked by values instead of observations
+///
+/// The sum of all values is put into the snapshot as `total_sum`.
+///
+/// When the values are byte counts the throughput can be reported
+/// in a human readable unit. See `set_throughput_enabled`.
 pub struct ValueMeter {
     name: String,
     title: Option<String>,
@@ -22,21 +47,28 @@ pub struct ValueMeter {
     five_minute_rate_enabled: bool,
     fifteen_minute_rate_enabled: bool,
     display_time_unit: TimeUnit,
+    rate_unit: RateUnit,
+    throughput_enabled: bool,
+    clock: SharedClock,
 }
 
 impl ValueMeter {
     pub fn new<T: Into<String>>(name: T) -> ValueMeter {
+        let clock = SharedClock::default();
         ValueMeter {
             name: name.into(),
             title: None,
             description: None,
-            last_tick: Cell::new(Instant::now()),
-            inner_meter: StdMeter::default(),
+            last_tick: Cell::new(clock.now()),
+            inner_meter: StdMeter::with_clock(clock.clone()),
             lower_cutoff: 0.001,
             one_minute_rate_enabled: true,
             five_minute_rate_enabled: false,
             fifteen_minute_rate_enabled: false,
             display_time_unit: TimeUnit::default(),
+            rate_unit: RateUnit::default(),
+            throughput_enabled: false,
+            clock,
         }
     }
 
@@ -143,6 +175,72 @@ impl ValueMeter {
         self
     }
 
+    /// Sets the time unit the rates are given for, e.g. the
+    /// sum of the values per minute with `RateUnit::Minute`.
+    ///
+    /// Default is `RateUnit::Second`
+    pub fn set_rate_unit(&mut self, rate_unit: RateUnit) {
+        self.rate_unit = rate_unit
+    }
+
+    /// Sets the time unit the rates are given for, e.g. the
+    /// sum of the values per minute with `RateUnit::Minute`.
+    ///
+    /// Default is `RateUnit::Second`
+    pub fn rate_unit(mut self, rate_unit: RateUnit) -> Self {
+        self.set_rate_unit(rate_unit);
+        self
+    }
+
+    pub fn get_rate_unit(&self) -> RateUnit {
+        self.rate_unit
+    }
+
+    /// Treats the values as byte counts and reports the one minute
+    /// rate as a throughput.
+    ///
+    /// The rate is put into the `Snapshot` as `bytes_per_second` and
+    /// scaled by magnitude as `throughput` with the unit `B/s`, `KiB/s`
+    /// or `MiB/s` in `throughput_unit`. This does not depend on the
+    /// rate unit or on whether the one minute rate is enabled.
+    ///
+    /// Default is disabled.
+    pub fn set_throughput_enabled(&mut self, enabled: bool) {
+        self.throughput_enabled = enabled;
+    }
+
+    /// Treats the values as byte counts and reports the one minute
+    /// rate as a throughput.
+    ///
+    /// See `set_throughput_enabled`.
+    ///
+    /// Default is disabled.
+    pub fn throughput_enabled(mut self, enabled: bool) -> Self {
+        self.set_throughput_enabled(enabled);
+        self
+    }
+
+    /// Sets the `Clock` used for ticking the rates.
+    ///
+    /// All values observed so far are discarded so this should be done
+    /// right after creation. Mostly useful for testing with
+    /// a `ManualOffsetClock`.
+    ///
+    /// Default is the `WallClock`.
+    pub fn set_clock<C: Clock + Send + Sync + 'static>(&mut self, clock: C) {
+        self.clock = SharedClock::new(clock);
+        self.inner_meter = StdMeter::with_clock(self.clock.clone());
+        self.last_tick.set(self.clock.now());
+    }
+
+    /// Sets the `Clock` used for ticking the rates.
+    ///
+    /// Default is the `WallClock`.
+    pub fn clock<C: Clock + Send + Sync + 'static>(mut self, clock: C) -> Self {
+        self.set_clock(clock);
+        self
+    }
+
     pub fn accept<L: Eq + Send + 'static, F: Into<LabelFilter<L>>>(
         self,
         accept: F,
@@ -187,61 +285,55 @@ impl ValueMeter {
     }
 
     pub(crate) fn get_snapshot(&self) -> MeterSnapshot {
-        if self.last_tick.get().elapsed() >= Duration::from_secs(5) {
+        if self.clock.now() - self.last_tick.get() >= Duration::from_secs(5) {
             self.inner_meter.tick();
-            self.last_tick.set(Instant::now());
+            self.last_tick.set(self.clock.now());
         }
 
         let snapshot = self.inner_meter.snapshot();
 
+        // The inner meter's rates are per second
+        let seconds_per_unit = self.rate_unit.seconds();
+        let make_rate = |enabled: bool, per_second: f64| {
+            if !enabled {
+                return None;
+            }
+            let rate = per_second * seconds_per_unit;
+            Some(MeterRate {
+                rate: if rate < self.lower_cutoff { 0.0 } else { rate },
+                share: None,
+            })
+        };
+
         let meter_snapshot = MeterSnapshot {
             name: &self.name,
             title: self.title.as_ref().map(|x| &**x),
             description: self.description.as_ref().map(|x| &**x),
             count: snapshot.count as u64,
-            one_minute: if self.one_minute_rate_enabled {
-                Some(MeterRate {
-                    rate: if snapshot.rates[0] < self.lower_cutoff {
-                        0.0
-                    } else {
-                        snapshot.rates[0]
-                    },
-                    share: None,
-                })
-            } else {
-                None
-            },
-            five_minutes: if self.five_minute_rate_enabled {
-                Some(MeterRate {
-                    rate: if snapshot.rates[1] < self.lower_cutoff {
-                        0.0
-                    } else {
-                        snapshot.rates[1]
-                    },
-                    share: None,
-                })
-            } else {
-                None
-            },
-            fifteen_minutes: if self.fifteen_minute_rate_enabled {
-                Some(MeterRate {
-                    rate: if snapshot.rates[2] < self.lower_cutoff {
-                        0.0
-                    } else {
-                        snapshot.rates[2]
-                    },
-                    share: None,
-                })
+            total_sum: Some(snapshot.count),
+            one_minute: make_rate(self.one_minute_rate_enabled, snapshot.rates[0]),
+            five_minutes: make_rate(self.five_minute_rate_enabled, snapshot.rates[1]),
+            fifteen_minutes: make_rate(self.fifteen_minute_rate_enabled, snapshot.rates[2]),
+            custom_window: None,
+            last_interval: None,
+            peak_per_second: None,
+            bytes_per_second: if self.throughput_enabled {
+                Some(snapshot.rates[0])
             } else {
                 None
             },
+            update_tracker: None,
         };
 
         meter_snapshot
     }
 }
 
-impl Instrument for ValueMeter {}
+impl Instrument for ValueMeter {
+    fn name(&self) -> Option<&str> {
+        Some(self.get_name())
+    }
+}
 
 impl PutsSnapshot for ValueMeter {
     fn put_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
@@ -271,6 +363,22 @@ impl Updates for ValueMeter {
                     }
                 }
             },
+            Update::ObservationsWithValue(observed_value, n, _) => {
+                let v = match observed_value {
+                    ObservedValue::Duration(time, unit) => Some(
+                        super::super::duration_to_display_value(time, unit, self.display_time_unit)
+                            as i64,
+                    ),
+                    other => other.convert_to_i64(),
+                };
+                if let Some(v) = v {
+                    self.inner_meter
+                        .mark(v.saturating_mul(n.min(i64::MAX as u64) as i64));
+                    1
+                } else {
+                    0
+                }
+            }
             _ => 0,
         }
     }
@@ -285,3 +393,91 @@ impl Descriptive for ValueMeter {
         self.description.as_ref().map(|n| &**n)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::instruments::ManualOffsetClock;
+    use crate::snapshot::{FindItem, ItemKind};
+
+    #[test]
+    fn the_sum_and_the_scaled_rate_are_put_into_the_snapshot() {
+        let clock = ManualOffsetClock::default();
+        let mut meter = ValueMeter::new("bytes").clock(clock.clone());
+
+        meter.update(&Update::ObservationWithValue(300.into(), clock.now()));
+        meter.update(&Update::ObservationWithValue(600.into(), clock.now()));
+        clock.advance_n_seconds(5);
+
+        let mut snapshot = Snapshot::default();
+        meter.put_snapshot(&mut snapshot, false);
+
+        assert_eq!(
+            snapshot.find("bytes/total_sum"),
+            FindItem::Found(&ItemKind::Int(900))
+        );
+        match snapshot.find("bytes/one_minute/rate") {
+            // 900 within 5 seconds are 180 per second
+            FindItem::Found(ItemKind::Float(rate)) => {
+                assert!((rate - 180.0).abs() < 1e-9, "rate: {}", rate)
+            }
+            other => panic!("no rate found: {}", other),
+        }
+    }
+
+    #[test]
+    fn rates_can_be_given_per_minute() {
+        let clock = ManualOffsetClock::default();
+        let mut meter = ValueMeter::new("bytes")
+            .rate_unit(RateUnit::Minute)
+            .clock(clock.clone());
+
+        meter.update(&Update::ObservationWithValue(900.into(), clock.now()));
+        clock.advance_n_seconds(5);
+
+        let mut snapshot = Snapshot::default();
+        meter.put_snapshot(&mut snapshot, false);
+
+        match snapshot.find("bytes/one_minute/rate") {
+            // 900 within 5 seconds are 10800 per minute
+            FindItem::Found(ItemKind::Float(rate)) => {
+                assert!((rate - 10_800.0).abs() < 1e-6, "rate: {}", rate)
+            }
+            other => panic!("no rate found: {}", other),
+        }
+    }
+
+    #[test]
+    fn the_throughput_is_scaled_by_magnitude() {
+        let clock = ManualOffsetClock::default();
+        let mut meter = ValueMeter::new("received")
+            .throughput_enabled(true)
+            .clock(clock.clone());
+
+        for _ in 0..5 {
+            meter.update(&Update::ObservationWithValue(2048.into(), clock.now()));
+        }
+        clock.advance_n_seconds(5);
+
+        let mut snapshot = Snapshot::default();
+        meter.put_snapshot(&mut snapshot, false);
+
+        assert_eq!(
+            snapshot.find("received/throughput_unit"),
+            FindItem::Found(&ItemKind::Text("KiB/s".to_string()))
+        );
+        match snapshot.find("received/throughput") {
+            // 10240 bytes within 5 seconds are 2 KiB per second
+            FindItem::Found(ItemKind::Float(rate)) => {
+                assert!((rate - 2.0).abs() < 1e-9, "throughput: {}", rate)
+            }
+            other => panic!("no throughput found: {}", other),
+        }
+        match snapshot.find("received/bytes_per_second") {
+            FindItem::Found(ItemKind::Float(rate)) => {
+                assert!((rate - 2048.0).abs() < 1e-6, "bytes per second: {}", rate)
+            }
+            other => panic!("no bytes per second found: {}", other),
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use crate::instruments::{
+    AcceptAllLabels, Instrument, InstrumentAdapter, LabelFilter, LabelPredicate, Update, Updates,
+};
+use crate::snapshot::Snapshot;
+use crate::util;
+use crate::{Descriptive, PutsSnapshot};
+
+/// Keeps a monotonic total of readings taken from a counter
+/// which wraps around at a given modulus, e.g. a 32 bit
+/// counter of a network interface.
+///
+/// Reacts to the following `Observation`s:
+///
+/// * `Obervation::ObservedOneValue`(Update::ObservationWithValue)
+///
+/// The observed values are the readings of the external counter.
+/// Values which can not be converted to a `u64` are ignored.
+///
+/// If a reading is lower than the previous reading, the external
+/// counter either wrapped around or was reset. If the reading dropped
+/// by at least the reset threshold it is considered a wrap and the
+/// modulus is added to the total. Otherwise the external counter is
+/// considered to have restarted from zero and the reading is added to
+/// the total. The default threshold is half of the modulus.
+///
+/// The first reading is taken as the initial total.
+///
+/// # Example
+///
+/// ```
+/// use metrix::instruments::other_instruments::WrappingCounter;
+///
+/// let mut counter = WrappingCounter::new("bytes", 1 << 32);
+///
+/// counter.observe(4_294_967_000);
+/// counter.observe(100);
+///
+/// assert_eq!(counter.get(), 4_294_967_396);
+/// ```
+pub struct WrappingCounter {
+    name: String,
+    title: Option<String>,
+    description: Option<String>,
+    modulus: u64,
+    reset_threshold: u64,
+    last_reading: Option<u64>,
+    total: u64,
+}
+
+impl WrappingCounter {
+    /// Creates a new `WrappingCounter` for an external counter
+    /// wrapping around at `modulus`.
+    ///
+    /// # Panics
+    ///
+    /// If `modulus` is 0.
+    pub fn new<T: Into<String>>(name: T, modulus: u64) -> WrappingCounter {
+        if modulus == 0 {
+            panic!("the modulus must be at least 1");
+        }
+
+        WrappingCounter {
+            name: name.into(),
+            title: None,
+            description: None,
+            modulus,
+            reset_threshold: modulus / 2,
+            last_reading: None,
+            total: 0,
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_name<T: Into<String>>(&mut self, name: T) {
+        self.name = name.into();
+    }
+
+    pub fn name<T: Into<String>>(mut self, name: T) -> Self {
+        self.set_name(name);
+        self
+    }
+
+    pub fn set_title<T: Into<String>>(&mut self, title: T) {
+        self.title = Some(title.into())
+    }
+
+    pub fn title<T: Into<String>>(mut self, title: T) -> Self {
+        self.set_title(title);
+        self
+    }
+
+    pub fn set_description<T: Into<String>>(&mut self, description: T) {
+        self.description = Some(description.into())
+    }
+
+    pub fn description<T: Into<String>>(mut self, description: T) -> Self {
+        self.set_description(description);
+        self
+    }
+
+    /// Returns the modulus at which the external counter wraps
+    pub fn get_modulus(&self) -> u64 {
+        self.modulus
+    }
+
+    /// Sets by how much a reading must at least drop to be
+    /// considered a wrap instead of a reset of the external counter.
+    ///
+    /// Default is half of the modulus.
+    pub fn set_reset_threshold(&mut self, threshold: u64) {
+        self.reset_threshold = threshold;
+    }
+
+    /// Sets by how much a reading must at least drop to be
+    /// considered a wrap instead of a reset of the external counter.
+    ///
+    /// Default is half of the modulus.
+    pub fn reset_threshold(mut self, threshold: u64) -> Self {
+        self.set_reset_threshold(threshold);
+        self
+    }
+
+    /// Returns the reset threshold
+    pub fn get_reset_threshold(&self) -> u64 {
+        self.reset_threshold
+    }
+
+    /// Takes a reading of the external counter.
+    ///
+    /// Readings not below the modulus are taken modulo the modulus.
+    pub fn observe(&mut self, reading: u64) {
+        let reading = reading % self.modulus;
+
+        let increment = match self.last_reading {
+            None => reading,
+            Some(last) if reading >= last => reading - last,
+            Some(last) if last - reading >= self.reset_threshold => self.modulus - last + reading,
+            Some(_) => reading,
+        };
+
+        self.total = self.total.saturating_add(increment);
+        self.last_reading = Some(reading);
+    }
+
+    /// Get the accumulated total
+    pub fn get(&self) -> u64 {
+        self.total
+    }
+
+    pub fn accept<L: Eq + Send + 'static, F: Into<LabelFilter<L>>>(
+        self,
+        accept: F,
+    ) -> InstrumentAdapter<L, Self> {
+        InstrumentAdapter::accept(accept, self)
+    }
+
+    /// Creates an `InstrumentAdapter` that makes this instrument
+    /// react on observations on the given label.
+    pub fn for_label<L: Eq + Send + 'static>(self, label: L) -> InstrumentAdapter<L, Self> {
+        self.accept(label)
+    }
+
+    /// Creates an `InstrumentAdapter` that makes this instrument
+    /// react on observations with the given labels.
+    ///
+    /// If `labels` is empty the instrument will not react to any observations
+    pub fn for_labels<L: Eq + Send + 'static>(self, labels: Vec<L>) -> InstrumentAdapter<L, Self> {
+        self.accept(labels)
+    }
+
+    /// Creates an `InstrumentAdapter` that makes this instrument react on
+    /// all observations.
+    pub fn for_all_labels<L: Eq + Send + 'static>(self) -> InstrumentAdapter<L, Self> {
+        self.accept(AcceptAllLabels)
+    }
+
+    /// Creates an `InstrumentAdapter` that makes this instrument react on
+    /// observations with labels specified by the predicate.
+    pub fn for_labels_by_predicate<L, P>(self, label_predicate: P) -> InstrumentAdapter<L, Self>
+    where
+        L: Eq + Send + 'static,
+        P: Fn(&L) -> bool + Send + 'static,
+    {
+        self.accept(LabelPredicate(label_predicate))
+    }
+
+    /// Creates an `InstrumentAdapter` that makes this instrument to no
+    /// observations.
+    pub fn adapter<L: Eq + Send + 'static>(self) -> InstrumentAdapter<L, Self> {
+        InstrumentAdapter::deaf(self)
+    }
+}
+
+impl Instrument for WrappingCounter {
+    fn name(&self) -> Option<&str> {
+        Some(self.get_name())
+    }
+
+    fn reset(&mut self) {
+        self.last_reading = None;
+        self.total = 0;
+    }
+}
+
+impl PutsSnapshot for WrappingCounter {
+    fn put_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
+        util::put_postfixed_descriptives(self, &self.name, into, descriptive);
+        into.items.push((self.name.clone(), self.total.into()));
+    }
+}
+
+impl Updates for WrappingCounter {
+    fn update(&mut self, with: &Update) -> usize {
+        match *with {
+            Update::ObservationWithValue(value, _) => {
+                if let Some(reading) = value.convert_to_u64() {
+                    self.observe(reading);
+                    1
+                } else {
+                    0
+                }
+            }
+            _ => 0,
+        }
+    }
+}
+
+impl Descriptive for WrappingCounter {
+    fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Instant;
+
+    use super::*;
+
+    const MODULUS: u64 = 1 << 32;
+
+    #[test]
+    fn the_total_is_continuous_over_a_wrap() {
+        let mut counter = WrappingCounter::new("", MODULUS);
+
+        let mut reading = MODULUS - 1_000;
+        counter.observe(reading);
+        let start = counter.get();
+
+        for _ in 0..10 {
+            reading = (reading + 300) % MODULUS;
+            counter.update(&Update::ObservationWithValue(
+                reading.into(),
+                Instant::now(),
+            ));
+        }
+
+        assert_eq!(reading, 2_000);
+        assert_eq!(counter.get(), start + 3_000);
+    }
+
+    #[test]
+    fn a_small_drop_is_a_reset() {
+        let mut counter = WrappingCounter::new("", MODULUS).reset_threshold(1_000_000);
+
+        counter.observe(5_000);
+        counter.observe(20);
+
+        assert_eq!(counter.get(), 5_020);
+
+        counter.observe(MODULUS - 10);
+        counter.observe(10);
+
+        assert_eq!(counter.get(), MODULUS + 5_010);
+    }
+}
@@ -1,8 +1,13 @@
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+use crate::instruments::fundamentals::{Clock, SharedClock};
 use crate::snapshot::{ItemKind, Snapshot};
 use crate::util;
-use crate::{Descriptive, HandlesObservations, Observation, PutsSnapshot};
+use crate::{Descriptive, HandlesObservations, Observation, PutsSnapshot, TimeUnit};
 
 use super::*;
 
@@ -60,7 +65,12 @@ pub struct Panel<L> {
     handlers: Vec<Box<dyn HandlesObservations<Label = L>>>,
     snapshooters: Vec<Box<dyn PutsSnapshot>>,
     last_update: Instant,
+    clock: SharedClock,
     max_inactivity_duration: Option<Duration>,
+    activity_markers: util::ActivityMarkers,
+    default_time_unit: Option<TimeUnit>,
+    snapshot_order: SnapshotOrder,
+    descriptive_override: Option<bool>,
 }
 
 impl<L> Panel<L>
@@ -83,7 +93,12 @@ where
             handlers: Vec::new(),
             snapshooters: Vec::new(),
             last_update: Instant::now(),
+            clock: SharedClock::default(),
             max_inactivity_duration: None,
+            activity_markers: util::ActivityMarkers::default(),
+            default_time_unit: None,
+            snapshot_order: SnapshotOrder::InsertionOrder,
+            descriptive_override: None,
         }
     }
 
@@ -95,6 +110,38 @@ where
         panel
     }
 
+    /// Create a new `Panel` which dispatches observations with the given
+    /// label and is named after the label's `Display` representation.
+    ///
+    /// Characters which are not safe for a snapshot key (everything but
+    /// ASCII letters, digits, `_` and `-`) are replaced by `_`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::fmt;
+    /// use metrix::instruments::Panel;
+    ///
+    /// #[derive(Clone, Copy, PartialEq, Eq)]
+    /// struct Endpoint(&'static str);
+    ///
+    /// impl fmt::Display for Endpoint {
+    ///     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    ///         write!(f, "{}", self.0)
+    ///     }
+    /// }
+    ///
+    /// let panel = Panel::named_from_label(Endpoint("get users"));
+    /// assert_eq!(panel.name(), Some("get_users"));
+    /// ```
+    pub fn named_from_label(label: L) -> Panel<L>
+    where
+        L: fmt::Display,
+    {
+        let name = sanitize_name(&label.to_string());
+        Self::named(label, name)
+    }
+
     /// Create a new `Panel` with the given name which dispatches observations
     /// with the given label
     #[deprecated(since = "0.9.24", note = "use 'named'")]
@@ -126,6 +173,60 @@ where
         Self::new(AcceptAllLabels)
     }
 
+    /// Create a new `Panel` without a name which dispatches observations
+    /// with labels for which the predicate returns `true`
+    pub fn accept_predicate<P>(predicate: P) -> Panel<L>
+    where
+        P: Fn(&L) -> bool + Send + 'static,
+    {
+        Self::new(LabelFilter::predicate(predicate))
+    }
+
+    /// Create a new `Panel` with the given name which dispatches observations
+    /// with labels for which the predicate returns `true`
+    pub fn accept_predicate_named<T: Into<String>, P>(predicate: P, name: T) -> Panel<L>
+    where
+        P: Fn(&L) -> bool + Send + 'static,
+    {
+        Self::named(LabelFilter::predicate(predicate), name)
+    }
+
+    /// Create a new `Panel` with the given name for tracking the
+    /// latencies of e.g. an endpoint.
+    ///
+    /// The panel contains
+    ///
+    /// * a `Counter` named `count`
+    /// * a `Meter` named `per_second`
+    /// * a `Histogram` named `latency` which displays durations
+    ///   in `time_unit`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use metrix::instruments::*;
+    /// use metrix::{HandlesObservations, Observation, ObservedValue, TimeUnit};
+    ///
+    /// let mut panel = Panel::latency_triad("get_user", "get_user", TimeUnit::Milliseconds);
+    ///
+    /// let value = ObservedValue::Duration(12, TimeUnit::Milliseconds);
+    /// panel.handle_observation(&Observation::observed_one_value_now("get_user", value));
+    ///
+    /// assert_eq!(panel.get_counter().unwrap().get(), 1);
+    /// ```
+    pub fn latency_triad<T: Into<String>, F: Into<LabelFilter<L>>>(
+        accept: F,
+        name: T,
+        time_unit: TimeUnit,
+    ) -> Panel<L> {
+        Self::named(accept, name)
+            .counter(InstrumentAdapter::new(Counter::new("count")))
+            .meter(InstrumentAdapter::new(Meter::new("per_second")))
+            .histogram(InstrumentAdapter::new(
+                Histogram::new("latency").display_time_unit(time_unit),
+            ))
+    }
+
     #[deprecated(since = "0.10.9", note = "use 'add_histogram'")]
     pub fn set_counter<I: Into<InstrumentAdapter<L, Counter>>>(&mut self, counter: I) {
         self.counter = Some(counter.into());
@@ -155,10 +256,14 @@ where
     }
 
     pub fn add_gauge<I: Into<GaugeAdapter<L>>>(&mut self, gauge: I) {
+        let mut gauge = gauge.into();
+        if let Some(time_unit) = self.default_time_unit {
+            gauge.gauge_mut().set_default_display_time_unit(time_unit);
+        }
         if self.gauge.is_none() {
-            self.gauge = Some(gauge.into());
+            self.gauge = Some(gauge);
         } else {
-            self.add_handler(gauge.into())
+            self.add_handler(gauge)
         }
     }
 
@@ -186,10 +291,16 @@ where
     }
 
     pub fn add_histogram<I: Into<InstrumentAdapter<L, Histogram>>>(&mut self, histogram: I) {
+        let mut histogram = histogram.into();
+        if let Some(time_unit) = self.default_time_unit {
+            histogram
+                .instrument_mut()
+                .set_default_display_time_unit(time_unit);
+        }
         if self.histogram.is_none() {
-            self.histogram = Some(histogram.into());
+            self.histogram = Some(histogram);
         } else {
-            self.add_handler(histogram.into())
+            self.add_handler(histogram)
         }
     }
 
@@ -198,6 +309,130 @@ where
         self
     }
 
+    /// Returns the `Counter` of this `Panel` if there is one.
+    ///
+    /// Additional `Counter`s added via `add_counter` are handlers
+    /// and can not be accessed.
+    pub fn get_counter(&self) -> Option<&Counter> {
+        self.counter.as_ref().map(|x| x.instrument())
+    }
+
+    /// Returns the `Counter` of this `Panel` mutably if there is one.
+    pub fn get_counter_mut(&mut self) -> Option<&mut Counter> {
+        self.counter.as_mut().map(|x| x.instrument_mut())
+    }
+
+    /// Moves the `Counter` out of this `Panel`.
+    ///
+    /// The slot is empty afterwards so that the next `Counter`
+    /// added via `add_counter` takes its place.
+    pub fn remove_counter(&mut self) -> Option<InstrumentAdapter<L, Counter>> {
+        self.counter.take()
+    }
+
+    /// Enables or disables the `Counter` of this `Panel` if there is one.
+    ///
+    /// A disabled `Counter` ignores all observations but stays
+    /// part of the `Snapshot`.
+    pub fn set_counter_enabled(&mut self, enabled: bool) {
+        if let Some(counter) = self.counter.as_mut() {
+            counter.set_enabled(enabled)
+        }
+    }
+
+    /// Returns the `Gauge` of this `Panel` if there is one.
+    ///
+    /// Additional `Gauge`s added via `add_gauge` are handlers
+    /// and can not be accessed.
+    pub fn get_gauge(&self) -> Option<&Gauge> {
+        self.gauge.as_ref().map(|x| x.gauge())
+    }
+
+    /// Returns the `Gauge` of this `Panel` mutably if there is one.
+    pub fn get_gauge_mut(&mut self) -> Option<&mut Gauge> {
+        self.gauge.as_mut().map(|x| x.gauge_mut())
+    }
+
+    /// Moves the `Gauge` out of this `Panel`.
+    ///
+    /// The slot is empty afterwards so that the next `Gauge`
+    /// added via `add_gauge` takes its place.
+    pub fn remove_gauge(&mut self) -> Option<GaugeAdapter<L>> {
+        self.gauge.take()
+    }
+
+    /// Enables or disables the `Gauge` of this `Panel` if there is one.
+    ///
+    /// A disabled `Gauge` ignores all observations but stays
+    /// part of the `Snapshot`.
+    pub fn set_gauge_enabled(&mut self, enabled: bool) {
+        if let Some(gauge) = self.gauge.as_mut() {
+            gauge.set_enabled(enabled)
+        }
+    }
+
+    /// Returns the `Meter` of this `Panel` if there is one.
+    ///
+    /// Additional `Meter`s added via `add_meter` are handlers
+    /// and can not be accessed.
+    pub fn get_meter(&self) -> Option<&Meter> {
+        self.meter.as_ref().map(|x| x.instrument())
+    }
+
+    /// Returns the `Meter` of this `Panel` mutably if there is one.
+    pub fn get_meter_mut(&mut self) -> Option<&mut Meter> {
+        self.meter.as_mut().map(|x| x.instrument_mut())
+    }
+
+    /// Moves the `Meter` out of this `Panel`.
+    ///
+    /// The slot is empty afterwards so that the next `Meter`
+    /// added via `add_meter` takes its place.
+    pub fn remove_meter(&mut self) -> Option<InstrumentAdapter<L, Meter>> {
+        self.meter.take()
+    }
+
+    /// Enables or disables the `Meter` of this `Panel` if there is one.
+    ///
+    /// A disabled `Meter` ignores all observations but stays
+    /// part of the `Snapshot`.
+    pub fn set_meter_enabled(&mut self, enabled: bool) {
+        if let Some(meter) = self.meter.as_mut() {
+            meter.set_enabled(enabled)
+        }
+    }
+
+    /// Returns the `Histogram` of this `Panel` if there is one.
+    ///
+    /// Additional `Histogram`s added via `add_histogram` are handlers
+    /// and can not be accessed.
+    pub fn get_histogram(&self) -> Option<&Histogram> {
+        self.histogram.as_ref().map(|x| x.instrument())
+    }
+
+    /// Returns the `Histogram` of this `Panel` mutably if there is one.
+    pub fn get_histogram_mut(&mut self) -> Option<&mut Histogram> {
+        self.histogram.as_mut().map(|x| x.instrument_mut())
+    }
+
+    /// Moves the `Histogram` out of this `Panel`.
+    ///
+    /// The slot is empty afterwards so that the next `Histogram`
+    /// added via `add_histogram` takes its place.
+    pub fn remove_histogram(&mut self) -> Option<InstrumentAdapter<L, Histogram>> {
+        self.histogram.take()
+    }
+
+    /// Enables or disables the `Histogram` of this `Panel` if there is one.
+    ///
+    /// A disabled `Histogram` ignores all observations but stays
+    /// part of the `Snapshot`.
+    pub fn set_histogram_enabled(&mut self, enabled: bool) {
+        if let Some(histogram) = self.histogram.as_mut() {
+            histogram.set_enabled(enabled)
+        }
+    }
+
     pub fn add_snapshooter<T: PutsSnapshot>(&mut self, snapshooter: T) {
         self.snapshooters.push(Box::new(snapshooter));
     }
@@ -217,7 +452,10 @@ where
         self
     }
 
-    pub fn add_panel(&mut self, panel: Panel<L>) {
+    pub fn add_panel(&mut self, mut panel: Panel<L>) {
+        if let Some(time_unit) = self.default_time_unit {
+            panel.apply_default_time_unit(time_unit);
+        }
         self.panels.push(panel);
     }
 
@@ -226,6 +464,49 @@ where
         self
     }
 
+    /// Sets the time unit for `Gauge`s, `Histogram`s and `Panel`s added
+    /// to this panel afterwards which do not have a time unit of their own.
+    ///
+    /// Instruments already added keep their time unit.
+    pub fn set_default_time_unit(&mut self, time_unit: TimeUnit) {
+        self.default_time_unit = Some(time_unit);
+    }
+
+    /// Sets the time unit for `Gauge`s, `Histogram`s and `Panel`s added
+    /// to this panel afterwards which do not have a time unit of their own.
+    ///
+    /// Instruments already added keep their time unit.
+    pub fn default_time_unit(mut self, time_unit: TimeUnit) -> Self {
+        self.set_default_time_unit(time_unit);
+        self
+    }
+
+    /// Returns the default time unit if one was set
+    pub fn get_default_time_unit(&self) -> Option<TimeUnit> {
+        self.default_time_unit
+    }
+
+    /// Applies the time unit to the `Gauge` and the `Histogram` and all
+    /// nested `Panel`s which do not have a time unit of their own.
+    ///
+    /// Instruments which are only handlers can not be reached anymore.
+    pub(crate) fn apply_default_time_unit(&mut self, time_unit: TimeUnit) {
+        if self.default_time_unit.is_none() {
+            self.default_time_unit = Some(time_unit);
+        }
+        if let Some(gauge) = self.gauge.as_mut() {
+            gauge.gauge_mut().set_default_display_time_unit(time_unit);
+        }
+        if let Some(histogram) = self.histogram.as_mut() {
+            histogram
+                .instrument_mut()
+                .set_default_display_time_unit(time_unit);
+        }
+        self.panels
+            .iter_mut()
+            .for_each(|panel| panel.apply_default_time_unit(time_unit));
+    }
+
     pub fn add_handler<H: HandlesObservations<Label = L>>(&mut self, handler: H) {
         self.handlers.push(Box::new(handler));
     }
@@ -235,6 +516,66 @@ where
         self
     }
 
+    /// Returns a copy of this `Panel` with the same structure and
+    /// configuration where all instruments start from scratch.
+    ///
+    /// This is useful to stamp out identical panels e.g. per tenant.
+    ///
+    /// Handlers, snapshooters, label filters based on predicates and
+    /// update modifiers are functions or trait objects which can not
+    /// be copied. If the panel or one of its nested panels contains
+    /// any of these a `TemplateError` is returned.
+    pub fn template_clone(&self) -> Result<Panel<L>, TemplateError>
+    where
+        L: Clone,
+    {
+        if !self.handlers.is_empty() {
+            return Err(TemplateError::Handler);
+        }
+        if !self.snapshooters.is_empty() {
+            return Err(TemplateError::Snapshooter);
+        }
+
+        Ok(Panel {
+            label_filter: self.label_filter.try_clone()?,
+            name: self.name.clone(),
+            title: self.title.clone(),
+            description: self.description.clone(),
+            counter: match self.counter {
+                Some(ref counter) => Some(counter.template_clone_with(Counter::template_clone)?),
+                None => None,
+            },
+            gauge: match self.gauge {
+                Some(ref gauge) => Some(gauge.template_clone()?),
+                None => None,
+            },
+            meter: match self.meter {
+                Some(ref meter) => Some(meter.template_clone_with(Meter::template_clone)?),
+                None => None,
+            },
+            histogram: match self.histogram {
+                Some(ref histogram) => {
+                    Some(histogram.template_clone_with(Histogram::template_clone)?)
+                }
+                None => None,
+            },
+            panels: self
+                .panels
+                .iter()
+                .map(Panel::template_clone)
+                .collect::<Result<_, _>>()?,
+            handlers: Vec::new(),
+            snapshooters: Vec::new(),
+            last_update: self.clock.now(),
+            clock: self.clock.clone(),
+            max_inactivity_duration: self.max_inactivity_duration,
+            activity_markers: self.activity_markers.clone(),
+            default_time_unit: self.default_time_unit,
+            snapshot_order: self.snapshot_order.clone(),
+            descriptive_override: self.descriptive_override,
+        })
+    }
+
     /// Gets the name of this `Panel`
     pub fn name(&self) -> Option<&str> {
         self.name.as_ref().map(|n| &**n)
@@ -261,6 +602,27 @@ where
         self.description = Some(description.into())
     }
 
+    /// Puts the `title` and the `description` of this `Panel` into
+    /// the `Snapshot` if `descriptive` is `true` or leaves them out
+    /// if it is `false` regardless of whether a descriptive
+    /// `Snapshot` was requested.
+    ///
+    /// Nested panels and instruments are not affected.
+    ///
+    /// Default is to follow the request.
+    pub fn set_descriptive_override(&mut self, descriptive: bool) {
+        self.descriptive_override = Some(descriptive);
+    }
+
+    /// Forces the `title` and the `description` of this `Panel` to be
+    /// part of the `Snapshot` or not.
+    ///
+    /// See `set_descriptive_override`.
+    pub fn descriptive_override(mut self, descriptive: bool) -> Self {
+        self.set_descriptive_override(descriptive);
+        self
+    }
+
     /// Sets the maximum amount of time this panel may be
     /// inactive until no more snapshots are taken
     ///
@@ -278,51 +640,141 @@ where
         self.max_inactivity_duration = Some(limit);
     }
 
+    /// Sets the `Clock` used for inactivity tracking.
+    ///
+    /// Mostly useful for testing with a `ManualOffsetClock`.
+    ///
+    /// Default is the `WallClock`.
+    pub fn set_clock<C: Clock + Send + Sync + 'static>(&mut self, clock: C) {
+        self.clock = SharedClock::new(clock);
+        self.last_update = self.clock.now();
+    }
+
+    /// Sets the `Clock` used for inactivity tracking.
+    ///
+    /// Default is the `WallClock`.
+    pub fn clock<C: Clock + Send + Sync + 'static>(mut self, clock: C) -> Self {
+        self.set_clock(clock);
+        self
+    }
+
+    /// Sets the keys of the fields marking whether this panel is
+    /// active if inactivity tracking is enabled.
+    ///
+    /// Default is `_active` and `_inactive`.
+    pub fn set_active_marker_names<A: Into<String>, I: Into<String>>(
+        &mut self,
+        active_key: A,
+        inactive_key: I,
+    ) {
+        self.activity_markers = util::ActivityMarkers::new(active_key, inactive_key);
+    }
+
+    /// Sets the keys of the fields marking whether this panel is
+    /// active if inactivity tracking is enabled.
+    ///
+    /// Default is `_active` and `_inactive`.
+    pub fn active_marker_names<A: Into<String>, I: Into<String>>(
+        mut self,
+        active_key: A,
+        inactive_key: I,
+    ) -> Self {
+        self.set_active_marker_names(active_key, inactive_key);
+        self
+    }
+
+    /// Sets the order in which the components of this `Panel`
+    /// appear in the `Snapshot`.
+    ///
+    /// Default is `SnapshotOrder::InsertionOrder`.
+    pub fn set_snapshot_order(&mut self, order: SnapshotOrder) {
+        self.snapshot_order = order;
+    }
+
+    /// Sets the order in which the components of this `Panel`
+    /// appear in the `Snapshot`.
+    ///
+    /// Default is `SnapshotOrder::InsertionOrder`.
+    pub fn snapshot_order(mut self, order: SnapshotOrder) -> Self {
+        self.set_snapshot_order(order);
+        self
+    }
+
+    /// Checks that no two components put values on the same
+    /// path into the `Snapshot` where one would shadow the other.
+    ///
+    /// Returns the paths used more than once separated by `/`.
+    ///
+    /// The names of the instruments are checked even if they have no
+    /// values yet. Other components only put their names if they
+    /// implement `PutsSnapshot::put_names`. Otherwise their values
+    /// are checked.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        util::validate_names(|names| self.put_names(names))
+    }
+
     pub fn accepts_label(&self, label: &L) -> bool {
         self.label_filter.accepts(label)
     }
 
-    fn put_values_into_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
-        util::put_default_descriptives(self, into, descriptive);
+    fn put_level(&self, into: &mut Snapshot, descriptive: bool, exported: bool) {
+        if let Some(ref name) = self.name {
+            let mut new_level = Snapshot::default();
+            self.put_values_into_snapshot(&mut new_level, descriptive, exported);
+            into.items
+                .push((name.clone(), ItemKind::Snapshot(new_level)));
+        } else {
+            self.put_values_into_snapshot(into, descriptive, exported);
+        }
+    }
+
+    fn put_component_names(&self, into: &mut Snapshot) {
+        self.counter.iter().for_each(|x| x.put_names(into));
+        self.gauge.iter().for_each(|x| x.put_names(into));
+        self.meter.iter().for_each(|x| x.put_names(into));
+        self.histogram.iter().for_each(|x| x.put_names(into));
+        self.panels.iter().for_each(|p| p.put_names(into));
+        self.snapshooters.iter().for_each(|p| p.put_names(into));
+        self.handlers.iter().for_each(|p| p.put_names(into));
+    }
+
+    fn put_values_into_snapshot(&self, into: &mut Snapshot, descriptive: bool, exported: bool) {
+        util::put_default_descriptives(
+            self,
+            into,
+            self.descriptive_override.unwrap_or(descriptive),
+        );
         if let Some(d) = self.max_inactivity_duration {
-            if self.last_update.elapsed() > d {
-                into.items
-                    .push(("_inactive".to_string(), ItemKind::Boolean(true)));
-                into.items
-                    .push(("_active".to_string(), ItemKind::Boolean(false)));
+            if self.clock.now() - self.last_update > d {
+                self.activity_markers.put(false, into);
                 return;
             } else {
-                into.items
-                    .push(("_inactive".to_string(), ItemKind::Boolean(false)));
-                into.items
-                    .push(("_active".to_string(), ItemKind::Boolean(true)));
+                self.activity_markers.put(true, into);
             }
         };
+        let first_component = into.items.len();
         self.counter
-            .as_ref()
             .iter()
-            .for_each(|x| x.put_snapshot(into, descriptive));
+            .for_each(|x| util::put_snapshot_of(x, into, descriptive, exported));
         self.gauge
-            .as_ref()
             .iter()
-            .for_each(|x| x.put_snapshot(into, descriptive));
+            .for_each(|x| util::put_snapshot_of(x, into, descriptive, exported));
         self.meter
-            .as_ref()
             .iter()
-            .for_each(|x| x.put_snapshot(into, descriptive));
+            .for_each(|x| util::put_snapshot_of(x, into, descriptive, exported));
         self.histogram
-            .as_ref()
             .iter()
-            .for_each(|x| x.put_snapshot(into, descriptive));
+            .for_each(|x| util::put_snapshot_of(x, into, descriptive, exported));
         self.panels
             .iter()
-            .for_each(|p| p.put_snapshot(into, descriptive));
+            .for_each(|p| util::put_snapshot_of(p, into, descriptive, exported));
         self.snapshooters
             .iter()
-            .for_each(|p| p.put_snapshot(into, descriptive));
+            .for_each(|p| util::put_snapshot_of(&**p, into, descriptive, exported));
         self.handlers
             .iter()
-            .for_each(|p| p.put_snapshot(into, descriptive));
+            .for_each(|p| util::put_snapshot_of(&**p, into, descriptive, exported));
+        self.snapshot_order.sort(&mut into.items[first_component..]);
     }
 }
 
@@ -331,13 +783,21 @@ where
     L: Eq + Send + 'static,
 {
     fn put_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
+        self.put_level(into, descriptive, false)
+    }
+
+    fn put_exported_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
+        self.put_level(into, descriptive, true)
+    }
+
+    fn put_names(&self, into: &mut Snapshot) {
         if let Some(ref name) = self.name {
             let mut new_level = Snapshot::default();
-            self.put_values_into_snapshot(&mut new_level, descriptive);
+            self.put_component_names(&mut new_level);
             into.items
                 .push((name.clone(), ItemKind::Snapshot(new_level)));
         } else {
-            self.put_values_into_snapshot(into, descriptive);
+            self.put_component_names(into);
         }
     }
 }
@@ -374,8 +834,21 @@ where
             .iter_mut()
             .for_each(|x| instruments_updated += x.handle_observation(&observation));
 
+        if instruments_updated > 0 {
+            self.last_update = self.clock.now();
+        }
+
         instruments_updated
     }
+
+    fn reset(&mut self) {
+        self.counter.iter_mut().for_each(|x| x.reset());
+        self.gauge.iter_mut().for_each(|x| x.reset());
+        self.meter.iter_mut().for_each(|x| x.reset());
+        self.histogram.iter_mut().for_each(|x| x.reset());
+        self.panels.iter_mut().for_each(|x| x.reset());
+        self.handlers.iter_mut().for_each(|x| x.reset());
+    }
 }
 
 impl<L> Descriptive for Panel<L> {
@@ -387,3 +860,418 @@ impl<L> Descriptive for Panel<L> {
         self.description.as_ref().map(|n| &**n)
     }
 }
+
+type KeyComparator = dyn Fn(&str, &str) -> Ordering + Send + Sync;
+
+/// The order of the components of a `Panel` within a `Snapshot`
+///
+/// Only the keys put by the components are ordered. Title,
+/// description and the activity markers always come first.
+#[derive(Clone, Default)]
+pub enum SnapshotOrder {
+    /// The order is fixed by the kind of the components: counter,
+    /// gauge, meter, histogram, panels, snapshooters and handlers.
+    /// Components of the same kind appear in the order they were added.
+    #[default]
+    InsertionOrder,
+    /// The components are ordered by their keys
+    Alphabetical,
+    /// The components are ordered by their keys using the given
+    /// comparator. Components with equal keys keep their
+    /// insertion order.
+    Custom(Arc<KeyComparator>),
+}
+
+impl SnapshotOrder {
+    /// Creates a `SnapshotOrder::Custom` from the given comparator
+    pub fn custom<F>(compare: F) -> Self
+    where
+        F: Fn(&str, &str) -> Ordering + Send + Sync + 'static,
+    {
+        SnapshotOrder::Custom(Arc::new(compare))
+    }
+
+    fn sort(&self, items: &mut [(String, ItemKind)]) {
+        match self {
+            SnapshotOrder::InsertionOrder => {}
+            SnapshotOrder::Alphabetical => items.sort_by(|a, b| a.0.cmp(&b.0)),
+            SnapshotOrder::Custom(compare) => items.sort_by(|a, b| compare(&a.0, &b.0)),
+        }
+    }
+}
+
+impl fmt::Debug for SnapshotOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotOrder::InsertionOrder => write!(f, "InsertionOrder"),
+            SnapshotOrder::Alphabetical => write!(f, "Alphabetical"),
+            SnapshotOrder::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
+
+/// The reason why a `Panel` or a `Cockpit` could not be copied
+/// with `template_clone`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemplateError {
+    /// A label filter based on a predicate
+    LabelPredicate,
+    /// An update modifier set with `modify_with`
+    UpdateModifier,
+    /// A handler added with `add_handler`
+    Handler,
+    /// A snapshooter added with `add_snapshooter`, e.g. a polled instrument
+    Snapshooter,
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let what = match self {
+            TemplateError::LabelPredicate => "a label predicate",
+            TemplateError::UpdateModifier => "an update modifier",
+            TemplateError::Handler => "a handler",
+            TemplateError::Snapshooter => "a snapshooter",
+        };
+        write!(f, "{} can not be cloned", what)
+    }
+}
+
+impl Error for TemplateError {}
+
+fn sanitize_name(name: &str) -> String {
+    let sanitized = util::sanitize_name(name.trim(), &['-']);
+    if sanitized.is_empty() {
+        "_".to_string()
+    } else {
+        sanitized
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::snapshot::FindItem;
+    use crate::ObservedValue;
+
+    #[test]
+    fn access_instruments() {
+        let mut panel = Panel::named(1, "panel")
+            .counter(Counter::new("counter"))
+            .gauge(Gauge::new("gauge"))
+            .histogram(Histogram::new("histogram"));
+
+        panel.handle_observation(&Observation::observed_one_value_now(1, 5));
+
+        assert_eq!(panel.get_counter().map(Counter::get), Some(1));
+        assert_eq!(panel.get_gauge().and_then(Gauge::get), Some(5));
+        assert!(panel.get_meter().is_none());
+        assert_eq!(
+            panel.get_histogram().map(Histogram::get_name),
+            Some("histogram")
+        );
+
+        panel.get_counter_mut().unwrap().inc_by(2);
+        assert_eq!(panel.get_counter().map(Counter::get), Some(3));
+
+        let removed = panel.remove_counter().unwrap();
+        assert_eq!(removed.instrument().get(), 3);
+        assert!(panel.get_counter().is_none());
+
+        panel.add_counter(Counter::new("other_counter"));
+        assert_eq!(
+            panel.get_counter().map(Counter::get_name),
+            Some("other_counter")
+        );
+    }
+
+    #[test]
+    fn a_predicate_selects_the_labels() {
+        #[derive(Clone, PartialEq, Eq)]
+        enum Label {
+            Ok,
+            Error(u16),
+        }
+
+        let mut panel =
+            Panel::accept_predicate_named(|l: &Label| matches!(l, Label::Error(_)), "errors")
+                .counter(Counter::new("count"));
+
+        panel.handle_observation(&Observation::observed_one_now(Label::Ok));
+        panel.handle_observation(&Observation::observed_one_now(Label::Error(404)));
+        panel.handle_observation(&Observation::observed_one_now(Label::Error(500)));
+        panel.handle_observation(&Observation::observed_one_now(Label::Ok));
+
+        assert!(panel.accepts_label(&Label::Error(503)));
+        assert!(!panel.accepts_label(&Label::Ok));
+        assert_eq!(panel.get_counter().map(Counter::get), Some(2));
+    }
+
+    #[test]
+    fn a_disabled_instrument_ignores_observations() {
+        let mut panel = Panel::named(1, "panel").counter(Counter::new("counter"));
+
+        panel.handle_observation(&Observation::observed_one_now(1));
+        panel.set_counter_enabled(false);
+        panel.handle_observation(&Observation::observed_one_now(1));
+        panel.handle_observation(&Observation::observed_one_now(1));
+
+        assert_eq!(panel.get_counter().map(Counter::get), Some(1));
+
+        let mut snapshot = Snapshot::default();
+        panel.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("panel/counter"),
+            FindItem::Found(&ItemKind::UInt(1))
+        );
+        assert_eq!(
+            snapshot.find("panel/_disabled_counter"),
+            FindItem::Found(&ItemKind::Boolean(true))
+        );
+
+        panel.set_counter_enabled(true);
+        panel.handle_observation(&Observation::observed_one_now(1));
+
+        assert_eq!(panel.get_counter().map(Counter::get), Some(2));
+        let mut snapshot = Snapshot::default();
+        panel.put_snapshot(&mut snapshot, false);
+        assert_eq!(snapshot.find("panel/_disabled_counter"), FindItem::NotFound);
+    }
+
+    #[test]
+    fn activity_markers_can_be_renamed() {
+        let panel: Panel<i32> = Panel::named(1, "panel")
+            .inactivity_limit(Duration::from_secs(60))
+            .active_marker_names("is_up", "is_down");
+
+        let mut snapshot = Snapshot::default();
+        panel.put_snapshot(&mut snapshot, false);
+
+        assert_eq!(
+            snapshot.find("panel/is_up"),
+            FindItem::Found(&ItemKind::Boolean(true))
+        );
+        assert_eq!(
+            snapshot.find("panel/is_down"),
+            FindItem::Found(&ItemKind::Boolean(false))
+        );
+        assert_eq!(snapshot.find("panel/_active"), FindItem::NotFound);
+    }
+
+    #[test]
+    fn instruments_report_how_long_ago_they_were_updated() {
+        use crate::instruments::ManualOffsetClock;
+
+        let clock = ManualOffsetClock::default();
+        let mut counter = Counter::new("counter").updated_secs_ago_enabled(true);
+        counter.set_clock(clock.clone());
+        let mut panel = Panel::named(1, "panel")
+            .counter(counter)
+            .gauge(
+                Gauge::new("gauge")
+                    .updated_secs_ago_enabled(true)
+                    .clock(clock.clone()),
+            )
+            .meter(
+                Meter::new("meter")
+                    .updated_secs_ago_enabled(true)
+                    .clock(clock.clone()),
+            )
+            .histogram(
+                Histogram::new("histogram")
+                    .updated_secs_ago_enabled(true)
+                    .clock(clock.clone()),
+            );
+
+        let mut snapshot = Snapshot::default();
+        panel.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("panel/_updated_secs_ago_counter"),
+            FindItem::NotFound
+        );
+
+        panel.handle_observation(&Observation::observed_one_value_now(1, 5));
+        clock.advance_n_seconds(3);
+
+        let mut snapshot = Snapshot::default();
+        panel.put_snapshot(&mut snapshot, false);
+        for path in &[
+            "panel/_updated_secs_ago_counter",
+            "panel/_updated_secs_ago_gauge",
+            "panel/meter/_updated_secs_ago",
+            "panel/histogram/_updated_secs_ago",
+        ] {
+            assert_eq!(
+                snapshot.find(path),
+                FindItem::Found(&ItemKind::Float(3.0)),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn a_latency_triad_tracks_durations() {
+        let mut panel = Panel::latency_triad(1, "endpoint", TimeUnit::Milliseconds);
+
+        assert!(panel.get_counter().is_some());
+        assert!(panel.get_meter().is_some());
+        assert!(panel.get_histogram().is_some());
+
+        panel.handle_observation(&Observation::observed_one_value_now(
+            1,
+            ObservedValue::Duration(2_500, TimeUnit::Microseconds),
+        ));
+
+        let mut snapshot = Snapshot::default();
+        panel.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("endpoint/count"),
+            FindItem::Found(&ItemKind::UInt(1))
+        );
+        assert_eq!(
+            snapshot.find("endpoint/per_second/count"),
+            FindItem::Found(&ItemKind::UInt(1))
+        );
+        assert_eq!(
+            snapshot.find("endpoint/latency/count"),
+            FindItem::Found(&ItemKind::UInt(1))
+        );
+        assert_eq!(
+            snapshot.find("endpoint/latency/max"),
+            FindItem::Found(&ItemKind::Int(2))
+        );
+    }
+
+    #[test]
+    fn observed_values_count_each_occurrence() {
+        let mut panel = Panel::named(1, "panel")
+            .counter(Counter::new("counter"))
+            .histogram(Histogram::new("histogram"));
+
+        panel.handle_observation(&Observation::observed_one_value_now(1, 7));
+        panel.handle_observation(&Observation::observed_values_now(1, 3, 100));
+
+        assert_eq!(panel.get_counter().map(Counter::get), Some(101));
+
+        let mut snapshot = Snapshot::default();
+        panel.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("panel/histogram/count"),
+            FindItem::Found(&ItemKind::UInt(101))
+        );
+        assert_eq!(
+            snapshot.find("panel/histogram/sum"),
+            FindItem::Found(&ItemKind::Int(307))
+        );
+    }
+
+    #[test]
+    fn the_name_is_derived_from_the_label() {
+        #[derive(Clone, Copy, PartialEq, Eq)]
+        enum Label {
+            Requests,
+            Path(&'static str),
+        }
+
+        impl fmt::Display for Label {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                match self {
+                    Label::Requests => write!(f, "requests"),
+                    Label::Path(path) => write!(f, "{}", path),
+                }
+            }
+        }
+
+        let panel = Panel::named_from_label(Label::Requests);
+        assert_eq!(panel.name(), Some("requests"));
+
+        let mut panel =
+            Panel::named_from_label(Label::Path(" /api/users v2 ")).counter(Counter::new("count"));
+        assert_eq!(panel.name(), Some("_api_users_v2"));
+
+        panel.handle_observation(&Observation::observed_one_now(Label::Path(
+            " /api/users v2 ",
+        )));
+        let mut snapshot = Snapshot::default();
+        panel.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("_api_users_v2/count"),
+            FindItem::Found(&ItemKind::UInt(1))
+        );
+
+        assert_eq!(Panel::named_from_label(Label::Path("")).name(), Some("_"));
+    }
+
+    #[test]
+    fn the_components_can_be_ordered() {
+        fn keys(panel: &Panel<i32>) -> Vec<String> {
+            let mut snapshot = Snapshot::default();
+            panel.put_snapshot(&mut snapshot, false);
+            snapshot.items.into_iter().map(|(k, _)| k).collect()
+        }
+
+        let mut panel = Panel::new(1)
+            .counter(Counter::new("requests"))
+            .gauge(Gauge::new("latency"))
+            .meter(Meter::new("per_second"))
+            .panel(Panel::named(1, "errors"));
+        panel.handle_observation(&Observation::observed_one_value_now(1, 5));
+
+        assert_eq!(
+            keys(&panel),
+            vec!["requests", "latency", "per_second", "errors"]
+        );
+
+        panel.set_snapshot_order(SnapshotOrder::Alphabetical);
+        assert_eq!(
+            keys(&panel),
+            vec!["errors", "latency", "per_second", "requests"]
+        );
+
+        panel.set_snapshot_order(SnapshotOrder::custom(|a, b| b.len().cmp(&a.len())));
+        assert_eq!(
+            keys(&panel),
+            vec!["per_second", "requests", "latency", "errors"]
+        );
+    }
+
+    #[test]
+    fn descriptives_can_be_forced_per_panel_and_instrument() {
+        let mut counter = Counter::new("count").title("Count");
+        counter.set_description("The count");
+        let mut panel = Panel::named(1, "panel")
+            .descriptive_override(true)
+            .counter(counter)
+            .panel(Panel::named(1, "leaf").gauge(Gauge::new("gauge").title("Gauge")));
+        panel.set_title("Panel");
+        panel.handle_observation(&Observation::observed_one_value_now(1, 5));
+
+        let mut snapshot = Snapshot::default();
+        panel.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("panel/_title"),
+            FindItem::Found(&ItemKind::Text("Panel".to_string()))
+        );
+        assert_eq!(snapshot.find("panel/_title_count"), FindItem::NotFound);
+        assert_eq!(snapshot.find("panel/leaf/_title_gauge"), FindItem::NotFound);
+
+        let mut panel = Panel::named(1, "panel")
+            .descriptive_override(false)
+            .counter(
+                InstrumentAdapter::new(Counter::new("count").title("Count"))
+                    .descriptive_override(false),
+            )
+            .gauge(Gauge::new("gauge").title("Gauge"));
+        panel.set_title("Panel");
+        panel.handle_observation(&Observation::observed_one_value_now(1, 5));
+
+        let mut snapshot = Snapshot::default();
+        panel.put_snapshot(&mut snapshot, true);
+        assert_eq!(snapshot.find("panel/_title"), FindItem::NotFound);
+        assert_eq!(snapshot.find("panel/_title_count"), FindItem::NotFound);
+        assert_eq!(
+            snapshot.find("panel/_title_gauge"),
+            FindItem::Found(&ItemKind::Text("Gauge".to_string()))
+        );
+    }
+}
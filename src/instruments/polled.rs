@@ -3,9 +3,12 @@
 //! This is useful when you need to query values like currently opened DB
 //! connections etc.
 
+use std::cell::{Cell, RefCell};
+use std::fmt::Display;
+
 use crate::snapshot::*;
 use crate::util;
-use crate::{Descriptive, PutsSnapshot};
+use crate::{Descriptive, ObservedValue, PutsSnapshot};
 
 /// Create an instrument that delivers metrics based on querying values
 /// when a `Snapshot` is requested.
@@ -76,8 +79,20 @@ where
         self.description = Some(description.into())
     }
 
-    fn put_values_into_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
-        self.poll.put_snapshot(into, descriptive);
+    fn put_level(&self, into: &mut Snapshot, descriptive: bool, exported: bool) {
+        util::put_postfixed_descriptives(self, &self.name, into, descriptive);
+        if self.create_group_with_name {
+            let mut new_level = Snapshot::default();
+            self.put_values_into_snapshot(&mut new_level, descriptive, exported);
+            into.items
+                .push((self.name.clone(), ItemKind::Snapshot(new_level)));
+        } else {
+            self.put_values_into_snapshot(into, descriptive, exported);
+        }
+    }
+
+    fn put_values_into_snapshot(&self, into: &mut Snapshot, descriptive: bool, exported: bool) {
+        util::put_snapshot_of(&self.poll, into, descriptive, exported);
     }
 }
 
@@ -96,15 +111,11 @@ where
     P: PutsSnapshot,
 {
     fn put_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
-        util::put_postfixed_descriptives(self, &self.name, into, descriptive);
-        if self.create_group_with_name {
-            let mut new_level = Snapshot::default();
-            self.put_values_into_snapshot(&mut new_level, descriptive);
-            into.items
-                .push((self.name.clone(), ItemKind::Snapshot(new_level)));
-        } else {
-            self.put_values_into_snapshot(into, descriptive);
-        }
+        self.put_level(into, descriptive, false)
+    }
+
+    fn put_exported_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
+        self.put_level(into, descriptive, true)
     }
 }
 
@@ -169,3 +180,186 @@ impl Descriptive for ConstantValue {
         self.description.as_ref().map(|n| &**n)
     }
 }
+
+/// Polls a single value with a function that may fail
+/// when a `Snapshot` is requested.
+///
+/// If polling fails the error is put into the snapshot as `[name]_poll_error`
+/// and the last value polled successfully is kept. Whether the value is
+/// from an earlier poll is put into the snapshot as `[name]_stale` and the
+/// number of failed polls as `[name]_poll_error_count`.
+///
+/// # Example
+///
+/// ```
+/// use metrix::instruments::polled::PolledValue;
+/// use metrix::ObservedValue;
+///
+/// let open_files = PolledValue::new("open_files", || {
+///     std::fs::read_dir("/proc/self/fd").map(|dir| ObservedValue::from(dir.count() as u64))
+/// });
+/// ```
+pub struct PolledValue<F> {
+    name: String,
+    title: Option<String>,
+    description: Option<String>,
+    poll: F,
+    last_value: RefCell<Option<ItemKind>>,
+    error_count: Cell<u64>,
+}
+
+impl<F, E> PolledValue<F>
+where
+    F: Fn() -> Result<ObservedValue, E> + Send + 'static,
+    E: Display,
+{
+    pub fn new<T: Into<String>>(name: T, poll: F) -> Self {
+        PolledValue {
+            name: name.into(),
+            title: None,
+            description: None,
+            poll,
+            last_value: RefCell::new(None),
+            error_count: Cell::new(0),
+        }
+    }
+
+    /// Set the name if this `PolledValue`.
+    pub fn set_name<T: Into<String>>(&mut self, name: T) {
+        self.name = name.into();
+    }
+
+    /// Gets the name of this `PolledValue`
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Sets the `title` of this `PolledValue`.
+    ///
+    /// A title can be part of a descriptive `Snapshot`
+    pub fn set_title<T: Into<String>>(&mut self, title: T) {
+        self.title = Some(title.into())
+    }
+
+    /// Sets the `description` of this `PolledValue`.
+    ///
+    /// A description can be part of a descriptive `Snapshot`
+    pub fn set_description<T: Into<String>>(&mut self, description: T) {
+        self.description = Some(description.into())
+    }
+
+    /// Returns how often polling failed
+    pub fn get_error_count(&self) -> u64 {
+        self.error_count.get()
+    }
+}
+
+impl<F, E> PutsSnapshot for PolledValue<F>
+where
+    F: Fn() -> Result<ObservedValue, E> + Send + 'static,
+    E: Display,
+{
+    fn put_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
+        util::put_postfixed_descriptives(self, &self.name, into, descriptive);
+
+        let stale = match (self.poll)() {
+            Ok(value) => {
+                *self.last_value.borrow_mut() = Some(observed_value_to_item(value));
+                false
+            }
+            Err(err) => {
+                self.error_count.set(self.error_count.get() + 1);
+                into.items.push((
+                    format!("{}_poll_error", self.name),
+                    ItemKind::Text(err.to_string()),
+                ));
+                true
+            }
+        };
+
+        if let Some(value) = self.last_value.borrow().clone() {
+            into.items.push((self.name.clone(), value));
+            into.items
+                .push((format!("{}_stale", self.name), stale.into()));
+        }
+        into.items.push((
+            format!("{}_poll_error_count", self.name),
+            self.error_count.get().into(),
+        ));
+    }
+}
+
+impl<F> Descriptive for PolledValue<F> {
+    fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+}
+
+fn observed_value_to_item(value: ObservedValue) -> ItemKind {
+    match value {
+        ObservedValue::SignedInteger(v) => v.into(),
+        ObservedValue::UnsignedInteger(v) => v.into(),
+        ObservedValue::Float(v) => v.into(),
+        ObservedValue::Bool(v) => v.into(),
+        ObservedValue::Duration(v, _) => v.into(),
+        ObservedValue::ChangedBy(v) => v.into(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn a_failed_poll_keeps_the_last_value() {
+        let calls = AtomicUsize::new(0);
+        let polled = PolledValue::new("value", move || {
+            let call = calls.fetch_add(1, Ordering::SeqCst);
+            if call & 1 == 0 {
+                Ok(ObservedValue::from(call as u64))
+            } else {
+                Err("failed")
+            }
+        });
+
+        let mut snapshot = Snapshot::default();
+        polled.put_snapshot(&mut snapshot, false);
+        assert_eq!(snapshot.find("value"), FindItem::Found(&ItemKind::UInt(0)));
+        assert_eq!(
+            snapshot.find("value_stale"),
+            FindItem::Found(&ItemKind::Boolean(false))
+        );
+        assert_eq!(snapshot.find("value_poll_error"), FindItem::NotFound);
+
+        for round in 1..=2 {
+            let mut snapshot = Snapshot::default();
+            polled.put_snapshot(&mut snapshot, false);
+            assert_eq!(
+                snapshot.find("value"),
+                FindItem::Found(&ItemKind::UInt(2 * (round - 1)))
+            );
+            assert_eq!(
+                snapshot.find("value_stale"),
+                FindItem::Found(&ItemKind::Boolean(true))
+            );
+            assert_eq!(
+                snapshot.find("value_poll_error"),
+                FindItem::Found(&ItemKind::Text("failed".to_string()))
+            );
+            assert_eq!(
+                snapshot.find("value_poll_error_count"),
+                FindItem::Found(&ItemKind::UInt(round))
+            );
+
+            polled.put_snapshot(&mut Snapshot::default(), false);
+        }
+
+        assert_eq!(polled.get_error_count(), 2);
+    }
+}
@@ -0,0 +1,221 @@
+use crate::snapshot::{ItemKind, Snapshot};
+use crate::util;
+use crate::{Descriptive, HandlesObservations, Observation, PutsSnapshot};
+
+/// Tracks the ratio between the observations of two labels
+///
+/// Observations with the numerator label and observations with the
+/// denominator label are counted separately. The `Snapshot` contains
+/// both counts as `numerator` and `denominator` and their `ratio`.
+/// The `ratio` is `0.0` as long as nothing has been counted
+/// for the denominator.
+///
+/// An `Observation::Observed` and an `Observation::ObservedValues`
+/// count by their count. All other `Observation`s count by one.
+///
+/// Since this needs the labels it is not an `Instrument` but
+/// a handler which can be added to a `Panel` via `add_handler`.
+///
+/// # Example
+///
+/// ```
+/// use metrix::instruments::*;
+/// use metrix::{HandlesObservations, Observation};
+///
+/// let mut error_rate = RatioInstrument::new("error_rate", "error", "request");
+///
+/// error_rate.handle_observation(&Observation::observed_one_now("request"));
+/// error_rate.handle_observation(&Observation::observed_one_now("request"));
+/// error_rate.handle_observation(&Observation::observed_one_now("error"));
+///
+/// assert_eq!(error_rate.ratio(), 0.5);
+/// ```
+pub struct RatioInstrument<L> {
+    name: String,
+    title: Option<String>,
+    description: Option<String>,
+    numerator_label: L,
+    denominator_label: L,
+    numerator: u64,
+    denominator: u64,
+}
+
+impl<L> RatioInstrument<L>
+where
+    L: Eq + Send + 'static,
+{
+    /// Creates a new `RatioInstrument` which divides the count of
+    /// `numerator_label` by the count of `denominator_label`.
+    pub fn new<T: Into<String>>(
+        name: T,
+        numerator_label: L,
+        denominator_label: L,
+    ) -> RatioInstrument<L> {
+        RatioInstrument {
+            name: name.into(),
+            title: None,
+            description: None,
+            numerator_label,
+            denominator_label,
+            numerator: 0,
+            denominator: 0,
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_name<T: Into<String>>(&mut self, name: T) {
+        self.name = name.into();
+    }
+
+    pub fn name<T: Into<String>>(mut self, name: T) -> Self {
+        self.set_name(name);
+        self
+    }
+
+    pub fn set_title<T: Into<String>>(&mut self, title: T) {
+        self.title = Some(title.into())
+    }
+
+    pub fn title<T: Into<String>>(mut self, title: T) -> Self {
+        self.set_title(title);
+        self
+    }
+
+    pub fn set_description<T: Into<String>>(&mut self, description: T) {
+        self.description = Some(description.into())
+    }
+
+    pub fn description<T: Into<String>>(mut self, description: T) -> Self {
+        self.set_description(description);
+        self
+    }
+
+    /// Returns the count of the numerator
+    pub fn numerator(&self) -> u64 {
+        self.numerator
+    }
+
+    /// Returns the count of the denominator
+    pub fn denominator(&self) -> u64 {
+        self.denominator
+    }
+
+    /// Returns the ratio of the numerator to the denominator
+    ///
+    /// Returns `0.0` if the denominator is zero.
+    pub fn ratio(&self) -> f64 {
+        if self.denominator == 0 {
+            0.0
+        } else {
+            self.numerator as f64 / self.denominator as f64
+        }
+    }
+}
+
+impl<L> HandlesObservations for RatioInstrument<L>
+where
+    L: Eq + Send + 'static,
+{
+    type Label = L;
+
+    fn handle_observation(&mut self, observation: &Observation<Self::Label>) -> usize {
+        let n = match *observation {
+            Observation::Observed { count, .. } | Observation::ObservedValues { count, .. } => {
+                count
+            }
+            _ => 1,
+        };
+
+        let label = observation.label();
+        let mut updated = 0;
+        if *label == self.numerator_label {
+            self.numerator = self.numerator.saturating_add(n);
+            updated += 1;
+        }
+        if *label == self.denominator_label {
+            self.denominator = self.denominator.saturating_add(n);
+            updated += 1;
+        }
+        updated
+    }
+
+    fn reset(&mut self) {
+        self.numerator = 0;
+        self.denominator = 0;
+    }
+}
+
+impl<L> PutsSnapshot for RatioInstrument<L>
+where
+    L: Eq + Send + 'static,
+{
+    fn put_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
+        util::put_postfixed_descriptives(self, &self.name, into, descriptive);
+        let mut new_level = Snapshot::default();
+        new_level.push("numerator", self.numerator.into());
+        new_level.push("denominator", self.denominator.into());
+        new_level.push("ratio", self.ratio().into());
+        into.push(self.name.clone(), ItemKind::Snapshot(new_level));
+    }
+}
+
+impl<L> Descriptive for RatioInstrument<L> {
+    fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::instruments::Panel;
+    use crate::snapshot::FindItem;
+
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    enum Label {
+        Request,
+        Error,
+    }
+
+    #[test]
+    fn three_errors_out_of_ten_requests() {
+        let mut panel = Panel::named(vec![Label::Request, Label::Error], "panel").handler(
+            RatioInstrument::new("error_rate", Label::Error, Label::Request),
+        );
+
+        let mut snapshot = Snapshot::default();
+        panel.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("panel/error_rate/ratio"),
+            FindItem::Found(&ItemKind::Float(0.0))
+        );
+
+        for _ in 0..10 {
+            panel.handle_observation(&Observation::observed_one_now(Label::Request));
+        }
+        panel.handle_observation(&Observation::observed_one_now(Label::Error));
+        panel.handle_observation(&Observation::observed_now(Label::Error, 2));
+
+        let mut snapshot = Snapshot::default();
+        panel.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("panel/error_rate/numerator"),
+            FindItem::Found(&ItemKind::UInt(3))
+        );
+        assert_eq!(
+            snapshot.find("panel/error_rate/denominator"),
+            FindItem::Found(&ItemKind::UInt(10))
+        );
+        assert_eq!(
+            snapshot.find("panel/error_rate/ratio"),
+            FindItem::Found(&ItemKind::Float(0.3))
+        );
+    }
+}
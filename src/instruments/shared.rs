@@ -0,0 +1,412 @@
+//! Instruments which can be read from other threads
+//!
+//! A `SharedCounter` or a `SharedGauge` is a handle to a value stored
+//! in an atomic. Clones of a handle share the value so that one clone
+//! can be put into a `Panel` to be updated by the processing thread
+//! while another clone is kept by the application to read the value.
+//!
+//! # Consistency
+//!
+//! All reads and writes of a single value are atomic. A read never
+//! sees a partially written value and every update is applied exactly
+//! once even if clones are updated from several threads.
+//!
+//! There is no ordering between different instruments. Reading two
+//! shared instruments updated by the same `Observation` may see
+//! the update on one of them only. The values are the ones of the
+//! `Observation`s processed so far so they lag behind the transmitted
+//! `Observation`s until these have been processed.
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::Arc;
+
+use crate::instruments::counter::CountChange;
+use crate::instruments::{
+    AcceptAllLabels, Counter, Instrument, InstrumentAdapter, LabelFilter, Update, Updates,
+};
+use crate::snapshot::Snapshot;
+use crate::util;
+use crate::{Descriptive, ObservedValue, PutsSnapshot, TimeUnit};
+
+/// A counter whose count can be read through any of its clones
+///
+/// Reacts to the same `Observation`s as a `Counter` and changes
+/// the count the same way. All clones share one `Counter`. The count
+/// never goes below zero. Once it has been decremented it is marked
+/// as a gauge like a `Counter`.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Instant;
+/// use metrix::instruments::*;
+///
+/// let mut counter = SharedCounter::new("requests");
+/// let reader = counter.clone();
+///
+/// counter.update(&Update::Observations(3, Instant::now()));
+///
+/// assert_eq!(reader.get(), 3);
+/// ```
+#[derive(Clone)]
+pub struct SharedCounter {
+    name: String,
+    title: Option<String>,
+    description: Option<String>,
+    counter: Arc<Counter>,
+}
+
+impl SharedCounter {
+    pub fn new<T: Into<String>>(name: T) -> SharedCounter {
+        SharedCounter {
+            name: name.into(),
+            title: None,
+            description: None,
+            counter: Arc::new(Counter::new("")),
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_name<T: Into<String>>(&mut self, name: T) {
+        self.name = name.into();
+    }
+
+    pub fn name<T: Into<String>>(mut self, name: T) -> Self {
+        self.set_name(name);
+        self
+    }
+
+    pub fn set_title<T: Into<String>>(&mut self, title: T) {
+        self.title = Some(title.into())
+    }
+
+    pub fn title<T: Into<String>>(mut self, title: T) -> Self {
+        self.set_title(title);
+        self
+    }
+
+    pub fn set_description<T: Into<String>>(&mut self, description: T) {
+        self.description = Some(description.into())
+    }
+
+    pub fn description<T: Into<String>>(mut self, description: T) -> Self {
+        self.set_description(description);
+        self
+    }
+
+    /// Increase the count by `n`
+    pub fn inc_by(&self, n: u64) {
+        self.counter.inc_by_shared(n);
+    }
+
+    /// Decrease the count by `n`
+    ///
+    /// The count will not go below zero.
+    pub fn dec_by(&self, n: u64) {
+        self.counter.dec_by_shared(n);
+    }
+
+    /// Get the current count
+    pub fn get(&self) -> u64 {
+        self.counter.get()
+    }
+
+    pub fn accept<L: Eq + Send + 'static, F: Into<LabelFilter<L>>>(
+        self,
+        accept: F,
+    ) -> InstrumentAdapter<L, Self> {
+        InstrumentAdapter::accept(accept, self)
+    }
+
+    /// Creates an `InstrumentAdapter` that makes this instrument
+    /// react on observations on the given label.
+    pub fn for_label<L: Eq + Send + 'static>(self, label: L) -> InstrumentAdapter<L, Self> {
+        self.accept(label)
+    }
+
+    /// Creates an `InstrumentAdapter` that makes this instrument react on
+    /// all observations.
+    pub fn for_all_labels<L: Eq + Send + 'static>(self) -> InstrumentAdapter<L, Self> {
+        self.accept(AcceptAllLabels)
+    }
+}
+
+impl Instrument for SharedCounter {
+    fn name(&self) -> Option<&str> {
+        Some(self.get_name())
+    }
+
+    fn reset(&mut self) {
+        self.counter.clear_shared();
+    }
+}
+
+impl PutsSnapshot for SharedCounter {
+    fn put_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
+        util::put_postfixed_descriptives(self, &self.name, into, descriptive);
+        into.items.push((self.name.clone(), self.get().into()));
+        if self.counter.was_decremented() {
+            util::put_gauge_type(&self.name, into);
+        }
+    }
+
+    fn put_names(&self, into: &mut Snapshot) {
+        util::put_name(&self.name, into)
+    }
+}
+
+impl Updates for SharedCounter {
+    fn update(&mut self, with: &Update) -> usize {
+        match CountChange::of(with) {
+            CountChange::Inc(n) => self.inc_by(n),
+            CountChange::Dec(n) => self.dec_by(n),
+        }
+        1
+    }
+}
+
+impl Descriptive for SharedCounter {
+    fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+}
+
+/// A gauge whose value can be read through any of its clones
+///
+/// Reacts to all `Observation`s with a value. The value is set to
+/// the observed value or changed by an `ObservedValue::ChangedBy`.
+/// Durations are stored in the default `TimeUnit` (microseconds).
+/// Values which can not be converted to an `i64` are ignored.
+///
+/// There is no value before the first observed value.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Instant;
+/// use metrix::instruments::*;
+///
+/// let mut gauge = SharedGauge::new("connections");
+/// let reader = gauge.clone();
+/// assert_eq!(reader.get(), None);
+///
+/// gauge.update(&Update::ObservationWithValue(12.into(), Instant::now()));
+///
+/// assert_eq!(reader.get(), Some(12));
+/// ```
+#[derive(Clone)]
+pub struct SharedGauge {
+    name: String,
+    title: Option<String>,
+    description: Option<String>,
+    state: Arc<GaugeState>,
+}
+
+#[derive(Default)]
+struct GaugeState {
+    value: AtomicI64,
+    is_set: AtomicBool,
+}
+
+impl SharedGauge {
+    pub fn new<T: Into<String>>(name: T) -> SharedGauge {
+        SharedGauge {
+            name: name.into(),
+            title: None,
+            description: None,
+            state: Arc::new(GaugeState::default()),
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_name<T: Into<String>>(&mut self, name: T) {
+        self.name = name.into();
+    }
+
+    pub fn name<T: Into<String>>(mut self, name: T) -> Self {
+        self.set_name(name);
+        self
+    }
+
+    pub fn set_title<T: Into<String>>(&mut self, title: T) {
+        self.title = Some(title.into())
+    }
+
+    pub fn title<T: Into<String>>(mut self, title: T) -> Self {
+        self.set_title(title);
+        self
+    }
+
+    pub fn set_description<T: Into<String>>(&mut self, description: T) {
+        self.description = Some(description.into())
+    }
+
+    pub fn description<T: Into<String>>(mut self, description: T) -> Self {
+        self.set_description(description);
+        self
+    }
+
+    /// Sets the value
+    pub fn set(&self, value: i64) {
+        self.state.value.store(value, Ordering::Relaxed);
+        self.state.is_set.store(true, Ordering::Release);
+    }
+
+    /// Changes the value by `delta`
+    ///
+    /// If there is no value yet the value becomes `delta`.
+    pub fn change_by(&self, delta: i64) {
+        let _ = self
+            .state
+            .value
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |value| {
+                Some(value.saturating_add(delta))
+            });
+        self.state.is_set.store(true, Ordering::Release);
+    }
+
+    /// Get the current value if there is one
+    pub fn get(&self) -> Option<i64> {
+        if self.state.is_set.load(Ordering::Acquire) {
+            Some(self.state.value.load(Ordering::Relaxed))
+        } else {
+            None
+        }
+    }
+
+    pub fn accept<L: Eq + Send + 'static, F: Into<LabelFilter<L>>>(
+        self,
+        accept: F,
+    ) -> InstrumentAdapter<L, Self> {
+        InstrumentAdapter::accept(accept, self)
+    }
+
+    /// Creates an `InstrumentAdapter` that makes this instrument
+    /// react on observations on the given label.
+    pub fn for_label<L: Eq + Send + 'static>(self, label: L) -> InstrumentAdapter<L, Self> {
+        self.accept(label)
+    }
+
+    /// Creates an `InstrumentAdapter` that makes this instrument react on
+    /// all observations.
+    pub fn for_all_labels<L: Eq + Send + 'static>(self) -> InstrumentAdapter<L, Self> {
+        self.accept(AcceptAllLabels)
+    }
+
+    fn observe(&self, observed: ObservedValue) -> usize {
+        match observed {
+            ObservedValue::ChangedBy(delta) => self.change_by(delta),
+            ObservedValue::Duration(time, unit) => {
+                let value = super::duration_to_display_value(time, unit, TimeUnit::default());
+                self.set(value.min(i64::MAX as u64) as i64)
+            }
+            other => match other.convert_to_i64() {
+                Some(value) => self.set(value),
+                None => return 0,
+            },
+        }
+        1
+    }
+}
+
+impl Instrument for SharedGauge {
+    fn name(&self) -> Option<&str> {
+        Some(self.get_name())
+    }
+
+    fn reset(&mut self) {
+        self.state.is_set.store(false, Ordering::Release);
+        self.state.value.store(0, Ordering::Relaxed);
+    }
+}
+
+impl PutsSnapshot for SharedGauge {
+    fn put_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
+        util::put_postfixed_descriptives(self, &self.name, into, descriptive);
+        if let Some(value) = self.get() {
+            into.items.push((self.name.clone(), value.into()));
+        }
+    }
+}
+
+impl Updates for SharedGauge {
+    fn update(&mut self, with: &Update) -> usize {
+        match *with {
+            Update::ObservationWithValue(value, _) | Update::ObservationsWithValue(value, _, _) => {
+                self.observe(value)
+            }
+            _ => 0,
+        }
+    }
+}
+
+impl Descriptive for SharedGauge {
+    fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::instruments::{Cockpit, Panel};
+    use crate::processor::{ProcessesTelemetryMessages, ProcessingStrategy, TelemetryProcessor};
+    use crate::snapshot::{FindItem, ItemKind};
+    use crate::{TelemetryTransmitter, TransmitsTelemetryData};
+
+    #[test]
+    fn a_clone_reads_what_the_processor_observed() {
+        let (tx, mut processor): (TelemetryTransmitter<i32>, _) =
+            TelemetryProcessor::new_pair_without_name();
+        let counter = SharedCounter::new("count");
+        let gauge = SharedGauge::new("latency");
+        processor.add_cockpit(
+            Cockpit::without_name().panel(
+                Panel::named(1, "panel")
+                    .handler(counter.clone().for_label(1))
+                    .handler(gauge.clone().for_label(1)),
+            ),
+        );
+
+        tx.observed_one_value_now(1, 5);
+        tx.observed_one_value_now(1, 7);
+        tx.observed_one_value_now(2, 9);
+        processor.process(100, ProcessingStrategy::ProcessAll);
+
+        let reader = std::thread::spawn(move || (counter.get(), gauge.get()));
+        assert_eq!(reader.join().unwrap(), (2, Some(7)));
+
+        let mut snapshot = Snapshot::default();
+        processor.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("panel/count"),
+            FindItem::Found(&ItemKind::UInt(2))
+        );
+        assert_eq!(
+            snapshot.find("panel/latency"),
+            FindItem::Found(&ItemKind::Int(7))
+        );
+    }
+
+    #[test]
+    fn a_panel_validates_the_names_of_shared_counters() {
+        let panel: Panel<i32> = Panel::named(1, "panel")
+            .handler(SharedCounter::new("count").for_label(1))
+            .handler(SharedCounter::new("count").for_label(2));
+
+        assert_eq!(panel.validate(), Err(vec!["panel/count".to_string()]));
+    }
+}
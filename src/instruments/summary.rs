@@ -0,0 +1,354 @@
+use crate::instruments::{
+    AcceptAllLabels, Instrument, InstrumentAdapter, LabelFilter, LabelPredicate, Update, Updates,
+};
+use crate::snapshot::{ItemKind, Snapshot};
+use crate::util;
+use crate::{Descriptive, ObservedValue, PutsSnapshot, TimeUnit};
+
+/// Tracks the count, sum, minimum, maximum and mean of observed values
+///
+/// Unlike a `Histogram` no values are stored so this instrument is cheap
+/// and uses constant memory. It does not calculate quantiles.
+///
+/// Reacts to `Observation::ObservedOneValue`(Update::ObservationWithValue)
+/// and `Observation::ObservedValues`(Update::ObservationsWithValue)
+/// with all values that can be converted to an `i64`. Durations are
+/// converted to the `display_time_unit`.
+///
+/// The `Snapshot` contains the fields `count` and `sum`. Once a value was
+/// observed it also contains `min`, `max` and `mean`.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Instant;
+/// use metrix::instruments::*;
+///
+/// let mut summary = Summary::new("example");
+/// summary.update(&Update::ObservationWithValue(4.into(), Instant::now()));
+/// summary.update(&Update::ObservationWithValue(8.into(), Instant::now()));
+///
+/// assert_eq!(2, summary.count());
+/// assert_eq!(12, summary.sum());
+/// assert_eq!(Some(4), summary.min());
+/// assert_eq!(Some(8), summary.max());
+/// assert_eq!(Some(6.0), summary.mean());
+/// ```
+pub struct Summary {
+    name: String,
+    title: Option<String>,
+    description: Option<String>,
+    count: u64,
+    sum: i64,
+    min_max: Option<(i64, i64)>,
+    display_time_unit: Option<TimeUnit>,
+}
+
+impl Summary {
+    pub fn new<T: Into<String>>(name: T) -> Summary {
+        Summary {
+            name: name.into(),
+            title: None,
+            description: None,
+            count: 0,
+            sum: 0,
+            min_max: None,
+            display_time_unit: None,
+        }
+    }
+
+    pub fn new_with_defaults<T: Into<String>>(name: T) -> Summary {
+        Self::new(name)
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_name<T: Into<String>>(&mut self, name: T) {
+        self.name = name.into();
+    }
+
+    pub fn name<T: Into<String>>(mut self, name: T) -> Self {
+        self.set_name(name);
+        self
+    }
+
+    pub fn set_title<T: Into<String>>(&mut self, title: T) {
+        self.title = Some(title.into())
+    }
+
+    pub fn title<T: Into<String>>(mut self, title: T) -> Self {
+        self.set_title(title);
+        self
+    }
+
+    pub fn set_description<T: Into<String>>(&mut self, description: T) {
+        self.description = Some(description.into())
+    }
+
+    pub fn description<T: Into<String>>(mut self, description: T) -> Self {
+        self.set_description(description);
+        self
+    }
+
+    /// Sets the unit durations will be converted to.
+    ///
+    /// If set, the unit will be part of a descriptive `Snapshot`
+    /// as `_unit_[name]`.
+    ///
+    /// Default is `TimeUnit::Microseconds`.
+    pub fn set_display_time_unit(&mut self, display_time_unit: TimeUnit) {
+        self.display_time_unit = Some(display_time_unit)
+    }
+
+    /// Sets the unit durations will be converted to.
+    ///
+    /// If set, the unit will be part of a descriptive `Snapshot`
+    /// as `_unit_[name]`.
+    ///
+    /// Default is `TimeUnit::Microseconds`.
+    pub fn display_time_unit(mut self, display_time_unit: TimeUnit) -> Self {
+        self.set_display_time_unit(display_time_unit);
+        self
+    }
+
+    /// Adds a value
+    pub fn add(&mut self, value: i64) {
+        self.add_n(value, 1)
+    }
+
+    /// Adds a value `n` times
+    pub fn add_n(&mut self, value: i64, n: u64) {
+        if n == 0 {
+            return;
+        }
+        self.count = self.count.saturating_add(n);
+        self.sum = self
+            .sum
+            .saturating_add(value.saturating_mul(n.min(i64::MAX as u64) as i64));
+        self.min_max = match self.min_max {
+            Some((min, max)) => Some((std::cmp::min(min, value), std::cmp::max(max, value))),
+            None => Some((value, value)),
+        };
+    }
+
+    /// The number of values observed
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// The sum of all values observed
+    pub fn sum(&self) -> i64 {
+        self.sum
+    }
+
+    /// The smallest value observed
+    pub fn min(&self) -> Option<i64> {
+        self.min_max.map(|(min, _)| min)
+    }
+
+    /// The biggest value observed
+    pub fn max(&self) -> Option<i64> {
+        self.min_max.map(|(_, max)| max)
+    }
+
+    /// The average of all values observed
+    pub fn mean(&self) -> Option<f64> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(self.sum as f64 / self.count as f64)
+        }
+    }
+
+    pub fn accept<L: Eq + Send + 'static, F: Into<LabelFilter<L>>>(
+        self,
+        accept: F,
+    ) -> InstrumentAdapter<L, Self> {
+        InstrumentAdapter::accept(accept, self)
+    }
+
+    /// Creates an `InstrumentAdapter` that makes this instrument
+    /// react on observations on the given label.
+    pub fn for_label<L: Eq + Send + 'static>(self, label: L) -> InstrumentAdapter<L, Self> {
+        self.accept(label)
+    }
+
+    /// Creates an `InstrumentAdapter` that makes this instrument
+    /// react on observations with the given labels.
+    ///
+    /// If `labels` is empty the instrument will not react to any observations
+    pub fn for_labels<L: Eq + Send + 'static>(self, labels: Vec<L>) -> InstrumentAdapter<L, Self> {
+        self.accept(labels)
+    }
+
+    /// Creates an `InstrumentAdapter` that makes this instrument react on
+    /// all observations.
+    pub fn for_all_labels<L: Eq + Send + 'static>(self) -> InstrumentAdapter<L, Self> {
+        self.accept(AcceptAllLabels)
+    }
+
+    /// Creates an `InstrumentAdapter` that makes this instrument react on
+    /// observations with labels specified by the predicate.
+    pub fn for_labels_by_predicate<L, P>(self, label_predicate: P) -> InstrumentAdapter<L, Self>
+    where
+        L: Eq + Send + 'static,
+        P: Fn(&L) -> bool + Send + 'static,
+    {
+        self.accept(LabelPredicate(label_predicate))
+    }
+
+    /// Creates an `InstrumentAdapter` that makes this instrument to no
+    /// observations.
+    pub fn adapter<L: Eq + Send + 'static>(self) -> InstrumentAdapter<L, Self> {
+        InstrumentAdapter::deaf(self)
+    }
+}
+
+impl Instrument for Summary {
+    fn name(&self) -> Option<&str> {
+        Some(self.get_name())
+    }
+
+    fn reset(&mut self) {
+        self.count = 0;
+        self.sum = 0;
+        self.min_max = None;
+    }
+}
+
+impl PutsSnapshot for Summary {
+    fn put_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
+        util::put_postfixed_descriptives(self, &self.name, into, descriptive);
+        util::put_postfixed_time_unit(self.display_time_unit, &self.name, into, descriptive);
+        let mut new_level = Snapshot::default();
+        new_level.push("count", self.count.into());
+        new_level.push("sum", self.sum.into());
+        if let Some((min, max)) = self.min_max {
+            new_level.push("min", min.into());
+            new_level.push("max", max.into());
+        }
+        if let Some(mean) = self.mean() {
+            new_level.push("mean", mean.into());
+        }
+        into.push(self.name.clone(), ItemKind::Snapshot(new_level));
+    }
+}
+
+impl Updates for Summary {
+    fn update(&mut self, with: &Update) -> usize {
+        match *with {
+            Update::ObservationWithValue(ObservedValue::Duration(time, time_unit), _) => {
+                let d = super::duration_to_display_value(
+                    time,
+                    time_unit,
+                    self.display_time_unit.unwrap_or_default(),
+                );
+                self.add(d as i64);
+                1
+            }
+            Update::ObservationWithValue(v, _) => {
+                if let Some(v) = v.convert_to_i64() {
+                    self.add(v);
+                    1
+                } else {
+                    0
+                }
+            }
+            Update::ObservationsWithValue(ObservedValue::Duration(time, time_unit), n, _) => {
+                let d = super::duration_to_display_value(
+                    time,
+                    time_unit,
+                    self.display_time_unit.unwrap_or_default(),
+                );
+                self.add_n(d as i64, n);
+                1
+            }
+            Update::ObservationsWithValue(v, n, _) => {
+                if let Some(v) = v.convert_to_i64() {
+                    self.add_n(v, n);
+                    1
+                } else {
+                    0
+                }
+            }
+            _ => 0,
+        }
+    }
+}
+
+impl Descriptive for Summary {
+    fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    #[test]
+    fn empty() {
+        let summary = Summary::new("summary");
+
+        assert_eq!(summary.count(), 0);
+        assert_eq!(summary.sum(), 0);
+        assert_eq!(summary.min(), None);
+        assert_eq!(summary.max(), None);
+        assert_eq!(summary.mean(), None);
+    }
+
+    #[test]
+    fn a_single_observation() {
+        let mut summary = Summary::new("summary");
+
+        summary.update(&Update::ObservationWithValue((-3).into(), Instant::now()));
+
+        assert_eq!(summary.count(), 1);
+        assert_eq!(summary.sum(), -3);
+        assert_eq!(summary.min(), Some(-3));
+        assert_eq!(summary.max(), Some(-3));
+        assert_eq!(summary.mean(), Some(-3.0));
+    }
+
+    #[test]
+    fn many_observations() {
+        let mut summary = Summary::new("summary").display_time_unit(TimeUnit::Milliseconds);
+
+        summary.update(&Update::ObservationWithValue(10.into(), Instant::now()));
+        summary.update(&Update::ObservationWithValue((-2).into(), Instant::now()));
+        summary.update(&Update::ObservationWithValue(
+            Duration::from_millis(7).into(),
+            Instant::now(),
+        ));
+        summary.update(&Update::ObservationWithValue(1.into(), Instant::now()));
+        assert_eq!(summary.update(&Update::Observation(Instant::now())), 0);
+
+        assert_eq!(summary.count(), 4);
+        assert_eq!(summary.sum(), 16);
+        assert_eq!(summary.min(), Some(-2));
+        assert_eq!(summary.max(), Some(10));
+        assert_eq!(summary.mean(), Some(4.0));
+
+        let mut snapshot = Snapshot::default();
+        summary.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("summary/count").opt(),
+            Some(&ItemKind::UInt(4))
+        );
+        assert_eq!(snapshot.find("summary/sum").opt(), Some(&ItemKind::Int(16)));
+        assert_eq!(snapshot.find("summary/min").opt(), Some(&ItemKind::Int(-2)));
+        assert_eq!(snapshot.find("summary/max").opt(), Some(&ItemKind::Int(10)));
+        assert_eq!(
+            snapshot.find("summary/mean").opt(),
+            Some(&ItemKind::Float(4.0))
+        );
+    }
+}
@@ -164,7 +164,11 @@ impl Flag {
     }
 }
 
-impl Instrument for Flag {}
+impl Instrument for Flag {
+    fn name(&self) -> Option<&str> {
+        Some(self.get_name())
+    }
+}
 
 impl PutsSnapshot for Flag {
     fn put_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
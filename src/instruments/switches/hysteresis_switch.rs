@@ -0,0 +1,376 @@
+use std::time::{Duration, Instant};
+
+use crate::instruments::fundamentals::SharedClock;
+use crate::instruments::{
+    AcceptAllLabels, Clock, Instrument, InstrumentAdapter, LabelFilter, LabelPredicate, Update,
+    Updates,
+};
+use crate::snapshot::Snapshot;
+use crate::util;
+use crate::{Descriptive, PutsSnapshot};
+
+use super::NameAlternation;
+
+/// A switch that is turned on and off by observed values
+/// with hysteresis.
+///
+/// The switch turns on once a value reaches the activation
+/// threshold and turns off once a value drops to the deactivation
+/// threshold. Values in between do not change the state so that a
+/// signal flapping around a single value does not toggle the switch.
+/// Additionally the switch can be required to stay in a state for
+/// a minimum dwell time before it can flip back. The dwell time is
+/// measured from the last flip with the clock of the switch.
+///
+/// Values which can not be converted to a float are ignored.
+///
+/// The time in the current state is put into the snapshot
+/// as `[name]_secs_in_state`.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Instant;
+/// use metrix::instruments::*;
+/// use metrix::instruments::switches::HysteresisSwitch;
+///
+/// let mut switch = HysteresisSwitch::new("overloaded", 0.9, 0.7);
+///
+/// switch.update(&Update::ObservationWithValue(0.95.into(), Instant::now()));
+/// assert!(switch.state());
+///
+/// switch.update(&Update::ObservationWithValue(0.8.into(), Instant::now()));
+/// assert!(switch.state());
+///
+/// switch.update(&Update::ObservationWithValue(0.6.into(), Instant::now()));
+/// assert!(!switch.state());
+/// ```
+pub struct HysteresisSwitch {
+    name: String,
+    title: Option<String>,
+    description: Option<String>,
+    activate_at: f64,
+    deactivate_at: f64,
+    min_dwell: Duration,
+    state: bool,
+    in_state_since: Instant,
+    show_inverted: Option<NameAlternation>,
+    clock: SharedClock,
+}
+
+impl HysteresisSwitch {
+    /// Creates a new switch which is off.
+    ///
+    /// The switch turns on at values `>= activate_at` and
+    /// off at values `<= deactivate_at`.
+    ///
+    /// # Panics
+    ///
+    /// If `deactivate_at` is greater than `activate_at`.
+    pub fn new<T: Into<String>>(name: T, activate_at: f64, deactivate_at: f64) -> Self {
+        if deactivate_at > activate_at {
+            panic!("the deactivation threshold must not be above the activation threshold");
+        }
+
+        let clock = SharedClock::default();
+        HysteresisSwitch {
+            name: name.into(),
+            title: None,
+            description: None,
+            activate_at,
+            deactivate_at,
+            min_dwell: Duration::from_secs(0),
+            state: false,
+            in_state_since: clock.now(),
+            show_inverted: None,
+            clock,
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_name<T: Into<String>>(&mut self, name: T) {
+        self.name = name.into();
+    }
+
+    pub fn name<T: Into<String>>(mut self, name: T) -> Self {
+        self.set_name(name);
+        self
+    }
+
+    pub fn set_title<T: Into<String>>(&mut self, title: T) {
+        self.title = Some(title.into())
+    }
+
+    pub fn title<T: Into<String>>(mut self, title: T) -> Self {
+        self.set_title(title);
+        self
+    }
+
+    pub fn set_description<T: Into<String>>(&mut self, description: T) {
+        self.description = Some(description.into())
+    }
+
+    pub fn description<T: Into<String>>(mut self, description: T) -> Self {
+        self.set_description(description);
+        self
+    }
+
+    /// Sets how long the switch has to stay in a state
+    /// before it can flip back.
+    ///
+    /// Default is zero
+    pub fn set_min_dwell(&mut self, min_dwell: Duration) {
+        self.min_dwell = min_dwell;
+    }
+
+    /// Sets how long the switch has to stay in a state
+    /// before it can flip back.
+    ///
+    /// Default is zero
+    pub fn min_dwell(mut self, min_dwell: Duration) -> Self {
+        self.set_min_dwell(min_dwell);
+        self
+    }
+
+    /// Sets the `Clock` used for the time in the current state and
+    /// the minimum dwell time.
+    ///
+    /// The time in the current state starts anew. Mostly useful for
+    /// testing with a `ManualOffsetClock`.
+    ///
+    /// Default is the `WallClock`.
+    pub fn set_clock<C: Clock + Send + Sync + 'static>(&mut self, clock: C) {
+        self.clock = SharedClock::new(clock);
+        self.in_state_since = self.clock.now();
+    }
+
+    /// Sets the `Clock` used for the time in the current state and
+    /// the minimum dwell time.
+    ///
+    /// Default is the `WallClock`.
+    pub fn clock<C: Clock + Send + Sync + 'static>(mut self, clock: C) -> Self {
+        self.set_clock(clock);
+        self
+    }
+
+    /// Show the inverted value. Name will be adjusted with `name_alternation`.
+    pub fn set_show_inverted(&mut self, name_alternation: NameAlternation) {
+        self.show_inverted = Some(name_alternation)
+    }
+
+    /// Show the inverted value. Name will be adjusted with `name_alternation`.
+    pub fn show_inverted(mut self, name_alternation: NameAlternation) -> Self {
+        self.set_show_inverted(name_alternation);
+        self
+    }
+
+    pub fn accept<L: Eq + Send + 'static, F: Into<LabelFilter<L>>>(
+        self,
+        accept: F,
+    ) -> InstrumentAdapter<L, Self> {
+        InstrumentAdapter::accept(accept, self)
+    }
+
+    /// Creates an `InstrumentAdapter` that makes this instrument
+    /// react on observations on the given label.
+    pub fn for_label<L: Eq + Send + 'static>(self, label: L) -> InstrumentAdapter<L, Self> {
+        self.accept(label)
+    }
+
+    /// Creates an `InstrumentAdapter` that makes this instrument
+    /// react on observations with the given labels.
+    ///
+    /// If `labels` is empty the instrument will not react to any observations
+    pub fn for_labels<L: Eq + Send + 'static>(self, labels: Vec<L>) -> InstrumentAdapter<L, Self> {
+        self.accept(labels)
+    }
+
+    /// Creates an `InstrumentAdapter` that makes this instrument react on
+    /// all observations.
+    pub fn for_all_labels<L: Eq + Send + 'static>(self) -> InstrumentAdapter<L, Self> {
+        self.accept(AcceptAllLabels)
+    }
+
+    /// Creates an `InstrumentAdapter` that makes this instrument react on
+    /// observations with labels specified by the predicate.
+    pub fn for_labels_by_predicate<L, P>(self, label_predicate: P) -> InstrumentAdapter<L, Self>
+    where
+        L: Eq + Send + 'static,
+        P: Fn(&L) -> bool + Send + 'static,
+    {
+        self.accept(LabelPredicate(label_predicate))
+    }
+
+    /// Creates an `InstrumentAdapter` that makes this instrument to no
+    /// observations.
+    pub fn adapter<L: Eq + Send + 'static>(self) -> InstrumentAdapter<L, Self> {
+        InstrumentAdapter::deaf(self)
+    }
+
+    /// Returns the current state
+    pub fn state(&self) -> bool {
+        self.state
+    }
+
+    /// Returns how long the switch is already in its current state
+    pub fn time_in_state(&self) -> Duration {
+        self.clock
+            .now()
+            .saturating_duration_since(self.in_state_since)
+    }
+
+    fn observe(&mut self, value: f64) {
+        let flip = if self.state {
+            value <= self.deactivate_at
+        } else {
+            value >= self.activate_at
+        };
+
+        if flip && self.time_in_state() >= self.min_dwell {
+            self.state = !self.state;
+            self.in_state_since = self.clock.now();
+        }
+    }
+}
+
+impl Instrument for HysteresisSwitch {
+    fn name(&self) -> Option<&str> {
+        Some(self.get_name())
+    }
+}
+
+impl PutsSnapshot for HysteresisSwitch {
+    fn put_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
+        util::put_postfixed_descriptives(self, &self.name, into, descriptive);
+
+        into.items.push((self.name.clone(), self.state.into()));
+        if let Some(alternation) = &self.show_inverted {
+            let label = alternation.adjust_name(&self.name);
+            into.items.push((label.into(), (!self.state).into()));
+        }
+        into.items.push((
+            format!("{}_secs_in_state", self.name),
+            self.time_in_state().as_secs().into(),
+        ));
+    }
+}
+
+impl Updates for HysteresisSwitch {
+    fn update(&mut self, with: &Update) -> usize {
+        match *with {
+            Update::ObservationWithValue(value, _) => {
+                if let Some(value) = value.convert_to_f64() {
+                    self.observe(value);
+                    1
+                } else {
+                    0
+                }
+            }
+            _ => 0,
+        }
+    }
+}
+
+impl Descriptive for HysteresisSwitch {
+    fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::instruments::ManualOffsetClock;
+    use crate::snapshot::{FindItem, ItemKind};
+
+    fn observe(switch: &mut HysteresisSwitch, value: f64, timestamp: Instant) {
+        switch.update(&Update::ObservationWithValue(value.into(), timestamp));
+    }
+
+    #[test]
+    fn a_flapping_signal_does_not_toggle_the_switch() {
+        let mut switch = HysteresisSwitch::new("switch", 12.0, 8.0);
+        let now = Instant::now();
+
+        for _ in 0..5 {
+            observe(&mut switch, 9.0, now);
+            observe(&mut switch, 11.0, now);
+            assert!(!switch.state());
+        }
+
+        observe(&mut switch, 12.0, now);
+        assert!(switch.state());
+
+        for _ in 0..5 {
+            observe(&mut switch, 9.0, now);
+            observe(&mut switch, 11.0, now);
+            assert!(switch.state());
+        }
+
+        observe(&mut switch, 8.0, now);
+        assert!(!switch.state());
+
+        let mut snapshot = Snapshot::default();
+        switch.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("switch"),
+            FindItem::Found(&ItemKind::Boolean(false))
+        );
+        assert_eq!(
+            snapshot.find("switch_secs_in_state"),
+            FindItem::Found(&ItemKind::UInt(0))
+        );
+    }
+
+    #[test]
+    fn the_switch_stays_in_a_state_for_the_minimum_dwell_time() {
+        let clock = ManualOffsetClock::default();
+        let mut switch = HysteresisSwitch::new("switch", 1.0, 0.0)
+            .min_dwell(Duration::from_secs(10))
+            .clock(clock.clone());
+
+        clock.advance_n_seconds(10);
+        observe(&mut switch, 1.0, Instant::now());
+        assert!(switch.state());
+
+        clock.advance_n_seconds(5);
+        observe(&mut switch, 0.0, Instant::now());
+        assert!(switch.state());
+        assert_eq!(switch.time_in_state(), Duration::from_secs(5));
+
+        clock.advance_n_seconds(5);
+        observe(&mut switch, 0.0, Instant::now());
+        assert!(!switch.state());
+        assert_eq!(switch.time_in_state(), Duration::from_secs(0));
+    }
+
+    #[test]
+    fn the_dwell_time_does_not_depend_on_the_timestamps_of_observations() {
+        let clock = ManualOffsetClock::default();
+        let mut switch = HysteresisSwitch::new("switch", 1.0, 0.0)
+            .min_dwell(Duration::from_secs(10))
+            .clock(clock.clone());
+
+        observe(
+            &mut switch,
+            1.0,
+            clock.in_the_past_by(Duration::from_secs(60)),
+        );
+        assert!(!switch.state());
+
+        clock.advance_n_seconds(10);
+        observe(
+            &mut switch,
+            1.0,
+            clock.in_the_past_by(Duration::from_secs(60)),
+        );
+        assert!(switch.state());
+    }
+}
@@ -5,11 +5,13 @@
 use std::borrow::Cow;
 
 mod flag;
+mod hysteresis_switch;
 mod non_occurrence_indicator;
 mod occurrence_indicator;
 mod staircase_timer;
 
 pub use self::flag::Flag;
+pub use self::hysteresis_switch::HysteresisSwitch;
 pub use self::non_occurrence_indicator::NonOccurrenceIndicator;
 pub use self::occurrence_indicator::OccurrenceIndicator;
 pub use self::staircase_timer::StaircaseTimer;
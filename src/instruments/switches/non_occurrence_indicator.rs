@@ -219,7 +219,11 @@ impl NonOccurrenceIndicator {
     }
 }
 
-impl Instrument for NonOccurrenceIndicator {}
+impl Instrument for NonOccurrenceIndicator {
+    fn name(&self) -> Option<&str> {
+        Some(self.get_name())
+    }
+}
 
 impl PutsSnapshot for NonOccurrenceIndicator {
     fn put_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
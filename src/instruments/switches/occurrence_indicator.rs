@@ -213,7 +213,11 @@ impl OccurrenceIndicator {
     }
 }
 
-impl Instrument for OccurrenceIndicator {}
+impl Instrument for OccurrenceIndicator {
+    fn name(&self) -> Option<&str> {
+        Some(self.get_name())
+    }
+}
 
 impl PutsSnapshot for OccurrenceIndicator {
     fn put_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
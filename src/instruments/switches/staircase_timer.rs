@@ -220,7 +220,11 @@ impl StaircaseTimer {
     }
 }
 
-impl Instrument for StaircaseTimer {}
+impl Instrument for StaircaseTimer {
+    fn name(&self) -> Option<&str> {
+        Some(self.get_name())
+    }
+}
 
 impl PutsSnapshot for StaircaseTimer {
     fn put_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
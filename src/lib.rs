@@ -115,21 +115,32 @@
 extern crate log;
 
 use snapshot::Snapshot;
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
+use channel::{MessageSender, SendError, Waiter};
 use cockpit::Cockpit;
+use futures::{Async, AsyncSink, Poll, Sink, StartSend};
 use instruments::Panel;
-use processor::TelemetryMessage;
+use processor::{Priority, TelemetryMessage};
 
+pub use chained::Chained;
 pub use observation::*;
 pub use processor::AggregatesProcessors;
+pub use sampling::SamplingTransmitter;
 
+mod chained;
+mod channel;
 pub mod cockpit;
 pub mod driver;
+pub mod exporters;
 pub mod instruments;
 mod observation;
 pub mod processor;
+mod sampling;
 pub mod snapshot;
 
 pub(crate) mod util;
@@ -141,6 +152,24 @@ pub(crate) mod util;
 pub trait HandlesObservations: PutsSnapshot + Send + 'static {
     type Label: Send + 'static;
     fn handle_observation(&mut self, observation: &Observation<Self::Label>) -> usize;
+
+    /// Resets all contained instruments to the state they had
+    /// when they were created.
+    ///
+    /// The default does nothing.
+    fn reset(&mut self) {}
+
+    /// Combines this with another `HandlesObservations` so that
+    /// `Observation`s are handled by both.
+    ///
+    /// See `Chained`.
+    fn chain<B>(self, other: B) -> Chained<Self, B>
+    where
+        Self: Sized,
+        B: HandlesObservations<Label = Self::Label>,
+    {
+        Chained::new(self, other)
+    }
 }
 
 /// Const for setting boolean values. `true` is `1`.
@@ -178,10 +207,53 @@ pub struct ChangeBy(pub i64);
 /// Implementors should transfer `Observations` to
 /// a backend and manipulate the instruments there to not
 /// to interfere to much with the actual task being measured/observed
+///
+/// The methods ending with `_now` create the `Observation`
+/// with the current timestamp.
+///
+/// # Example
+///
+/// ```
+/// use metrix::{TelemetryTransmitter, TransmitsTelemetryData};
+/// use metrix::processor::TelemetryProcessor;
+///
+/// let (tx, _processor): (TelemetryTransmitter<&str>, _) =
+///     TelemetryProcessor::new_pair_without_name();
+///
+/// tx.observed_one_now("requests")
+///     .observed_now("bytes_received", 512)
+///     .observed_one_value_now("queue_length", 42);
+/// ```
 pub trait TransmitsTelemetryData<L> {
     /// Transit an observation to the backend.
     fn transmit(&self, observation: Observation<L>) -> &Self;
 
+    /// Transmit an observation with a `Priority` to the backend.
+    ///
+    /// A full bounded channel which drops observations drops those
+    /// with the lowest priority first. See `Priority`.
+    ///
+    /// The default implementation ignores the priority and simply
+    /// calls `transmit`.
+    fn transmit_prioritized(&self, observation: Observation<L>, _priority: Priority) -> &Self {
+        self.transmit(observation)
+    }
+
+    /// Transmit many observations to the backend at once.
+    ///
+    /// This reduces the overhead compared to transmitting
+    /// each observation on its own when many observations
+    /// are made in a short time.
+    ///
+    /// The default implementation simply calls `transmit`
+    /// for each observation.
+    fn transmit_many(&self, observations: Vec<Observation<L>>) -> &Self {
+        observations.into_iter().for_each(|obs| {
+            self.transmit(obs);
+        });
+        self
+    }
+
     /// Observed `count` occurrences at time `timestamp`
     ///
     /// Convenience method. Simply calls `transmit`
@@ -216,6 +288,24 @@ pub trait TransmitsTelemetryData<L> {
         })
     }
 
+    /// Observed `count` occurrences each with value `value` at time `timestamp`
+    ///
+    /// Convenience method. Simply calls `transmit`
+    fn observed_values<V: Into<ObservedValue>>(
+        &self,
+        label: L,
+        value: V,
+        count: u64,
+        timestamp: Instant,
+    ) -> &Self {
+        self.transmit(Observation::ObservedValues {
+            label,
+            value: value.into(),
+            count,
+            timestamp,
+        })
+    }
+
     /// Sends a `Duration` as an observed value observed at `timestamp`.
     /// The `Duration` is converted to nanoseconds.
     fn observed_duration(&self, label: L, duration: Duration, timestamp: Instant) -> &Self {
@@ -246,6 +336,14 @@ pub trait TransmitsTelemetryData<L> {
         self.observed_one_value(label, value, Instant::now())
     }
 
+    /// Observed `count` occurrences each with value `value` now
+    ///
+    /// Convenience method. Simply calls `observed_values` with
+    /// the current timestamp.
+    fn observed_values_now<V: Into<ObservedValue>>(&self, label: L, value: V, count: u64) -> &Self {
+        self.observed_values(label, value, count, Instant::now())
+    }
+
     /// Sends a `Duration` as an observed value observed with the current
     /// timestamp.
     ///
@@ -268,26 +366,88 @@ pub trait TransmitsTelemetryData<L> {
     }
 
     /// Add a handler.
+    ///
+    /// Like an `Observation` the handler is sent to the backend and
+    /// added once the backend processes its pending messages.
+    /// `Observation`s transmitted afterwards will reach it.
     fn add_handler<H: HandlesObservations<Label = L>>(&self, handler: H) -> &Self
     where
         L: Send + 'static;
 
-    /// Add a `Copckpit`
+    /// Add a `Cockpit`
+    ///
+    /// Like an `Observation` the `Cockpit` is sent to the backend and
+    /// added once the backend processes its pending messages.
+    /// `Observation`s transmitted afterwards will reach it.
     fn add_cockpit(&self, cockpit: Cockpit<L>) -> &Self;
 
     /// Add a `Panel` to a `Cockpit` if that `Cockpit` has the
     /// given name.
+    ///
+    /// The `Panel` is dropped if the backend has no such `Cockpit`
+    /// by the time it processes the message.
     fn add_panel_to_cockpit(&self, cockpit_name: String, panel: Panel<L>) -> &Self;
 }
 
+/// An `Observation` which could not be transmitted
+#[derive(Debug)]
+pub enum TransmitError<L> {
+    /// The bounded channel to the backend is full and rejected the
+    /// `Observation`. See `OverflowPolicy::Reject`.
+    Full(Observation<L>),
+    /// The backend has been dropped
+    Disconnected(Observation<L>),
+}
+
+impl<L> TransmitError<L> {
+    /// Returns the `Observation` which could not be transmitted
+    pub fn into_observation(self) -> Observation<L> {
+        match self {
+            TransmitError::Full(observation) | TransmitError::Disconnected(observation) => {
+                observation
+            }
+        }
+    }
+}
+
+impl<L> fmt::Display for TransmitError<L> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TransmitError::Full(_) => write!(f, "the channel to the backend is full"),
+            TransmitError::Disconnected(_) => write!(f, "the backend has been dropped"),
+        }
+    }
+}
+
+impl<L: fmt::Debug> Error for TransmitError<L> {}
+
 /// Transmits `Observation`s to the backend
 ///
 /// This struct does **not** implement the `Sync` trait
 /// and can therefore not be shared between threads.
 /// See `synced()` method.
+///
+/// Transmitting never blocks since the channel to the backend
+/// is unbounded. A `TelemetryTransmitter` can therefore be used
+/// from within async tasks without blocking the executor. For
+/// use with streams it also is a `futures::Sink` of `Observation`s
+/// which is always ready to accept an `Observation`.
+///
+/// The exception is a transmitter created with
+/// `TelemetryProcessor::new_pair_bounded` and `OverflowPolicy::Block`
+/// which blocks while the channel is full. As a `futures::Sink` it
+/// does not block but is not ready to accept an `Observation`
+/// until there is capacity again. Async tasks can also await
+/// capacity with `transmit_async`.
+///
+/// Once the backend has been dropped nothing can be transmitted
+/// anymore. Such failures are logged and counted. See `failed_sends`.
 #[derive(Clone)]
 pub struct TelemetryTransmitter<L> {
-    sender: crossbeam_channel::Sender<TelemetryMessage<L>>,
+    sender: MessageSender<L>,
+    anchor: WallClockAnchor,
+    failed_sends: Arc<AtomicU64>,
+    discarded_on_drop: Arc<AtomicU64>,
 }
 
 impl<L> TelemetryTransmitter<L>
@@ -298,46 +458,210 @@ where
     pub fn synced(&self) -> TelemetryTransmitterSync<L> {
         TelemetryTransmitterSync {
             sender: Arc::new(Mutex::new(self.sender.clone())),
+            anchor: self.anchor,
+            failed_sends: Arc::clone(&self.failed_sends),
+            discarded_on_drop: Arc::clone(&self.discarded_on_drop),
         }
     }
+
+    /// Returns the `WallClockAnchor` captured when this transmitter
+    /// was created to convert the timestamps of `Observation`s
+    /// to wall clock times.
+    pub fn wall_clock_anchor(&self) -> WallClockAnchor {
+        self.anchor
+    }
+
+    /// Returns the number of messages which could not be sent
+    /// because the backend has been dropped or because they were
+    /// rejected by a full channel. See `OverflowPolicy::Reject`.
+    ///
+    /// The count is shared with all clones and synced transmitters.
+    pub fn failed_sends(&self) -> u64 {
+        self.failed_sends.load(Ordering::Relaxed)
+    }
+
+    /// Transmits the `Observation` and hands it back if it could
+    /// not be transmitted.
+    ///
+    /// This only fails if the backend has been dropped or if the
+    /// channel is bounded with `OverflowPolicy::Reject` and full.
+    /// Failures are counted (see `failed_sends`) but not logged.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use metrix::processor::{OverflowPolicy, TelemetryProcessor};
+    /// use metrix::{Observation, TransmitError};
+    ///
+    /// let (tx, _processor) =
+    ///     TelemetryProcessor::new_pair_bounded("bounded", 1, OverflowPolicy::Reject);
+    ///
+    /// assert!(tx.try_transmit(Observation::observed_one_now("a")).is_ok());
+    ///
+    /// match tx.try_transmit(Observation::observed_one_now("b")) {
+    ///     Err(TransmitError::Full(observation)) => assert_eq!(*observation.label(), "b"),
+    ///     _ => panic!("the channel should be full"),
+    /// }
+    /// ```
+    pub fn try_transmit(&self, observation: Observation<L>) -> Result<(), TransmitError<L>> {
+        self.sender
+            .send(TelemetryMessage::Observation(observation))
+            .map_err(|err| {
+                self.failed_sends.fetch_add(1, Ordering::Relaxed);
+                err.into_transmit_error()
+            })
+    }
+
+    /// Transmits the `Observation` without ever blocking the thread.
+    ///
+    /// If the channel is bounded with `OverflowPolicy::Block` and full
+    /// this waits until the `TelemetryProcessor` has made room. So
+    /// observations can be recorded from within async tasks without
+    /// blocking the executor while still applying backpressure.
+    /// Otherwise this completes immediately like `try_transmit`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use metrix::processor::{OverflowPolicy, TelemetryProcessor};
+    /// use metrix::Observation;
+    ///
+    /// let (tx, _processor) =
+    ///     TelemetryProcessor::new_pair_bounded("bounded", 100, OverflowPolicy::Block);
+    ///
+    /// async fn record(tx: &metrix::TelemetryTransmitter<&'static str>) {
+    ///     tx.transmit_async(Observation::observed_one_now("request"))
+    ///         .await
+    ///         .unwrap();
+    /// }
+    /// # let _ = record(&tx);
+    /// ```
+    #[allow(clippy::result_large_err)]
+    pub async fn transmit_async(
+        &self,
+        observation: Observation<L>,
+    ) -> Result<(), TransmitError<L>> {
+        let sender = &self.sender;
+        let message = TelemetryMessage::Observation(observation);
+        channel::send_async(message, |message, waker| {
+            sender.try_send(message, || Waiter::Waker(waker.clone()))
+        })
+        .await
+        .map_err(|err| {
+            self.failed_sends.fetch_add(1, Ordering::Relaxed);
+            err.into_transmit_error()
+        })
+    }
+
+    /// Returns the number of messages which were still queued
+    /// when the backend was dropped.
+    ///
+    /// Only counted if the `TelemetryProcessor` has `drain_on_drop`
+    /// enabled.
+    pub fn discarded_on_drop(&self) -> u64 {
+        self.discarded_on_drop.load(Ordering::Relaxed)
+    }
 }
 
-impl<L> TransmitsTelemetryData<L> for TelemetryTransmitter<L> {
-    fn transmit(&self, observation: Observation<L>) -> &Self {
-        if let Err(err) = self.sender.send(TelemetryMessage::Observation(observation)) {
-            util::log_error(format!("Failed to transmit observation: {}", err));
+impl<L> TelemetryTransmitter<L> {
+    fn send(&self, message: TelemetryMessage<L>, failure: &str) -> &Self {
+        self.send_prioritized(message, Priority::default(), failure)
+    }
+
+    fn send_prioritized(
+        &self,
+        message: TelemetryMessage<L>,
+        priority: Priority,
+        failure: &str,
+    ) -> &Self {
+        if let Err(err) = self.sender.send_prioritized(message, priority) {
+            self.failed_sends.fetch_add(1, Ordering::Relaxed);
+            // Rejections are expected under load and only counted
+            if let SendError::Disconnected(_) = err {
+                util::log_error(format!("{}: {}", failure, err));
+            }
         };
         self
     }
+}
+
+impl<L> TransmitsTelemetryData<L> for TelemetryTransmitter<L> {
+    fn transmit(&self, observation: Observation<L>) -> &Self {
+        self.send(
+            TelemetryMessage::Observation(observation),
+            "Failed to transmit observation",
+        )
+    }
+
+    fn transmit_prioritized(&self, observation: Observation<L>, priority: Priority) -> &Self {
+        self.send_prioritized(
+            TelemetryMessage::Observation(observation),
+            priority,
+            "Failed to transmit observation",
+        )
+    }
+
+    fn transmit_many(&self, observations: Vec<Observation<L>>) -> &Self {
+        self.send(
+            TelemetryMessage::Observations(observations),
+            "Failed to transmit observations",
+        )
+    }
 
     fn add_handler<H: HandlesObservations<Label = L>>(&self, handler: H) -> &Self
     where
         L: Send + 'static,
     {
-        if let Err(err) = self
-            .sender
-            .send(TelemetryMessage::AddHandler(Box::new(handler)))
-        {
-            util::log_error(format!("Failed to add handler: {}", err));
-        };
-        self
+        self.send(
+            TelemetryMessage::AddHandler(Box::new(handler)),
+            "Failed to add handler",
+        )
     }
 
     fn add_cockpit(&self, cockpit: Cockpit<L>) -> &Self {
-        if let Err(err) = self.sender.send(TelemetryMessage::AddCockpit(cockpit)) {
-            util::log_error(format!("Failed to add cockpit: {}", err));
-        };
-        self
+        self.send(
+            TelemetryMessage::AddCockpit(cockpit),
+            "Failed to add cockpit",
+        )
     }
 
     fn add_panel_to_cockpit(&self, cockpit_name: String, panel: Panel<L>) -> &Self {
-        if let Err(err) = self.sender.send(TelemetryMessage::AddPanel {
-            cockpit_name,
-            panel,
-        }) {
-            util::log_error(format!("Failed to add panel to cockpit: {}", err));
-        };
-        self
+        self.send(
+            TelemetryMessage::AddPanel {
+                cockpit_name,
+                panel: Box::new(panel),
+            },
+            "Failed to add panel to cockpit",
+        )
+    }
+}
+
+impl<L> Sink for TelemetryTransmitter<L> {
+    type SinkItem = Observation<L>;
+    type SinkError = ();
+
+    fn start_send(&mut self, item: Observation<L>) -> StartSend<Observation<L>, ()> {
+        let message = TelemetryMessage::Observation(item);
+        match self
+            .sender
+            .try_send(message, || Waiter::Task(futures::task::current()))
+        {
+            Ok(()) => {}
+            Err(SendError::Pending(TelemetryMessage::Observation(item))) => {
+                return Ok(AsyncSink::NotReady(item))
+            }
+            Err(err) => {
+                self.failed_sends.fetch_add(1, Ordering::Relaxed);
+                if let SendError::Disconnected(_) = err {
+                    util::log_error(format!("Failed to transmit observation: {}", err));
+                }
+            }
+        }
+        Ok(AsyncSink::Ready)
+    }
+
+    fn poll_complete(&mut self) -> Poll<(), ()> {
+        Ok(Async::Ready(()))
     }
 }
 
@@ -350,64 +674,156 @@ impl<L> TransmitsTelemetryData<L> for TelemetryTransmitter<L> {
 /// it can be shared between threads.
 #[derive(Clone)]
 pub struct TelemetryTransmitterSync<L> {
-    sender: Arc<Mutex<crossbeam_channel::Sender<TelemetryMessage<L>>>>,
+    sender: Arc<Mutex<MessageSender<L>>>,
+    anchor: WallClockAnchor,
+    failed_sends: Arc<AtomicU64>,
+    discarded_on_drop: Arc<AtomicU64>,
 }
 
-impl<L> TelemetryTransmitterSync<L> where L: Send + 'static {}
+impl<L> TelemetryTransmitterSync<L>
+where
+    L: Send + 'static,
+{
+    /// Returns the `WallClockAnchor` captured when the transmitter
+    /// this was created from was created to convert the timestamps
+    /// of `Observation`s to wall clock times.
+    pub fn wall_clock_anchor(&self) -> WallClockAnchor {
+        self.anchor
+    }
 
-impl<L> TransmitsTelemetryData<L> for TelemetryTransmitterSync<L> {
-    fn transmit(&self, observation: Observation<L>) -> &Self {
+    /// Returns the number of messages which could not be sent
+    /// because the backend has been dropped or because they were
+    /// rejected by a full channel. See `OverflowPolicy::Reject`.
+    ///
+    /// The count is shared with the transmitter this was
+    /// created from.
+    pub fn failed_sends(&self) -> u64 {
+        self.failed_sends.load(Ordering::Relaxed)
+    }
+
+    /// Transmits the `Observation` and hands it back if it could
+    /// not be transmitted.
+    ///
+    /// See `TelemetryTransmitter::try_transmit`.
+    pub fn try_transmit(&self, observation: Observation<L>) -> Result<(), TransmitError<L>> {
+        self.sender
+            .lock()
+            .unwrap()
+            .send(TelemetryMessage::Observation(observation))
+            .map_err(|err| {
+                self.failed_sends.fetch_add(1, Ordering::Relaxed);
+                err.into_transmit_error()
+            })
+    }
+
+    /// Transmits the `Observation` without ever blocking the thread.
+    ///
+    /// See `TelemetryTransmitter::transmit_async`.
+    #[allow(clippy::result_large_err)]
+    pub async fn transmit_async(
+        &self,
+        observation: Observation<L>,
+    ) -> Result<(), TransmitError<L>> {
+        let sender = &self.sender;
+        let message = TelemetryMessage::Observation(observation);
+        channel::send_async(message, |message, waker| {
+            sender
+                .lock()
+                .unwrap()
+                .try_send(message, || Waiter::Waker(waker.clone()))
+        })
+        .await
+        .map_err(|err| {
+            self.failed_sends.fetch_add(1, Ordering::Relaxed);
+            err.into_transmit_error()
+        })
+    }
+
+    /// Returns the number of messages which were still queued
+    /// when the backend was dropped.
+    ///
+    /// Only counted if the `TelemetryProcessor` has `drain_on_drop`
+    /// enabled.
+    pub fn discarded_on_drop(&self) -> u64 {
+        self.discarded_on_drop.load(Ordering::Relaxed)
+    }
+}
+
+impl<L> TelemetryTransmitterSync<L> {
+    fn send(&self, message: TelemetryMessage<L>, failure: &str) -> &Self {
+        self.send_prioritized(message, Priority::default(), failure)
+    }
+
+    fn send_prioritized(
+        &self,
+        message: TelemetryMessage<L>,
+        priority: Priority,
+        failure: &str,
+    ) -> &Self {
         if let Err(err) = self
             .sender
             .lock()
             .unwrap()
-            .send(TelemetryMessage::Observation(observation))
+            .send_prioritized(message, priority)
         {
-            util::log_error(format!("Failed to transmit observation: {}", err));
+            self.failed_sends.fetch_add(1, Ordering::Relaxed);
+            // Rejections are expected under load and only counted
+            if let SendError::Disconnected(_) = err {
+                util::log_error(format!("{}: {}", failure, err));
+            }
         };
         self
     }
+}
+
+impl<L> TransmitsTelemetryData<L> for TelemetryTransmitterSync<L> {
+    fn transmit(&self, observation: Observation<L>) -> &Self {
+        self.send(
+            TelemetryMessage::Observation(observation),
+            "Failed to transmit observation",
+        )
+    }
+
+    fn transmit_prioritized(&self, observation: Observation<L>, priority: Priority) -> &Self {
+        self.send_prioritized(
+            TelemetryMessage::Observation(observation),
+            priority,
+            "Failed to transmit observation",
+        )
+    }
+
+    fn transmit_many(&self, observations: Vec<Observation<L>>) -> &Self {
+        self.send(
+            TelemetryMessage::Observations(observations),
+            "Failed to transmit observations",
+        )
+    }
 
     fn add_handler<H: HandlesObservations<Label = L>>(&self, handler: H) -> &Self
     where
         L: Send + 'static,
     {
-        if let Err(err) = self
-            .sender
-            .lock()
-            .unwrap()
-            .send(TelemetryMessage::AddHandler(Box::new(handler)))
-        {
-            util::log_error(format!("Failed to add handler: {}", err));
-        };
-        self
+        self.send(
+            TelemetryMessage::AddHandler(Box::new(handler)),
+            "Failed to add handler",
+        )
     }
 
     fn add_cockpit(&self, cockpit: Cockpit<L>) -> &Self {
-        if let Err(err) = self
-            .sender
-            .lock()
-            .unwrap()
-            .send(TelemetryMessage::AddCockpit(cockpit))
-        {
-            util::log_error(format!("Failed to add cockpit: {}", err));
-        };
-        self
+        self.send(
+            TelemetryMessage::AddCockpit(cockpit),
+            "Failed to add cockpit",
+        )
     }
 
     fn add_panel_to_cockpit(&self, cockpit_name: String, panel: Panel<L>) -> &Self {
-        if let Err(err) = self
-            .sender
-            .lock()
-            .unwrap()
-            .send(TelemetryMessage::AddPanel {
+        self.send(
+            TelemetryMessage::AddPanel {
                 cockpit_name,
-                panel,
-            })
-        {
-            util::log_error(format!("Failed to add panel to cockpit: {}", err));
-        };
-        self
+                panel: Box::new(panel),
+            },
+            "Failed to add panel to cockpit",
+        )
     }
 }
 
@@ -446,6 +862,9 @@ pub trait Descriptive {
 /// should put its description into the snapshot it got passed therby adding the
 /// suffixes "_title" and "_description" to its name.
 ///
+/// * A `PutsSnapshot` containing other components should pass on
+///   `put_exported_snapshot`.
+///
 /// Implementors of this trait can be added to almost all components via
 /// the `add_snapshooter` method which is also defined on trait
 /// `AggregatesProcessors`.
@@ -453,4 +872,26 @@ pub trait PutsSnapshot: Send + 'static {
     /// Puts the current snapshot values into the given `Snapshot` thereby
     /// following the guidelines of `PutsSnapshot`.
     fn put_snapshot(&self, into: &mut Snapshot, descriptive: bool);
+
+    /// Puts the current snapshot values into a `Snapshot` which will be
+    /// exported. Values which start over with each exported `Snapshot`
+    /// like the one of a `Counter` with `reset_on_snapshot` are only
+    /// reset here.
+    ///
+    /// The default calls `put_snapshot`.
+    fn put_exported_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
+        self.put_snapshot(into, descriptive)
+    }
+
+    /// Puts the names of the values this component puts into a
+    /// `Snapshot` without taking a `Snapshot`. Used to detect name
+    /// collisions like with `Panel::validate`.
+    ///
+    /// Instruments put their name even if they have no value yet.
+    /// Components containing other components put the names of these.
+    ///
+    /// The default puts a non-descriptive `Snapshot`.
+    fn put_names(&self, into: &mut Snapshot) {
+        self.put_snapshot(into, false)
+    }
 }
@@ -1,6 +1,7 @@
-use std::time::{Duration, Instant};
+use std::ops::{Add, Mul, Sub};
+use std::time::{Duration, Instant, SystemTime};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TimeUnit {
     Nanoseconds,
     Microseconds,
@@ -8,6 +9,47 @@ pub enum TimeUnit {
     Seconds,
 }
 
+impl TimeUnit {
+    /// Returns the name of the unit in lower case, e.g. `milliseconds`
+    pub fn name(self) -> &'static str {
+        match self {
+            TimeUnit::Nanoseconds => "nanoseconds",
+            TimeUnit::Microseconds => "microseconds",
+            TimeUnit::Milliseconds => "milliseconds",
+            TimeUnit::Seconds => "seconds",
+        }
+    }
+
+    /// Selects the unit in which the given nanoseconds are displayed
+    /// best and returns the value in that unit.
+    ///
+    /// The selected unit is the biggest unit in which the value is
+    /// at least 1. Zero is displayed in nanoseconds.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use metrix::TimeUnit;
+    ///
+    /// let (value, unit) = TimeUnit::auto_scale(1_500_000_000);
+    ///
+    /// assert_eq!(value, 1.5);
+    /// assert_eq!(unit.name(), "seconds");
+    /// ```
+    pub fn auto_scale(nanoseconds: u64) -> (f64, TimeUnit) {
+        let (factor, unit) = if nanoseconds >= 1_000_000_000 {
+            (1_000_000_000, TimeUnit::Seconds)
+        } else if nanoseconds >= 1_000_000 {
+            (1_000_000, TimeUnit::Milliseconds)
+        } else if nanoseconds >= 1_000 {
+            (1_000, TimeUnit::Microseconds)
+        } else {
+            (1, TimeUnit::Nanoseconds)
+        };
+        (nanoseconds as f64 / factor as f64, unit)
+    }
+}
+
 impl Default for TimeUnit {
     fn default() -> Self {
         TimeUnit::Microseconds
@@ -21,7 +63,7 @@ impl Default for TimeUnit {
 /// E.g. a `Meter` does not take the `value` of
 /// an `Observation::ObservedOneValue` into account but
 /// simply counts the observation as one occurrence.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Observation<L> {
     /// Observed many occurrences with no value at the given timestamp
     Observed {
@@ -37,6 +79,18 @@ pub enum Observation<L> {
         value: ObservedValue,
         timestamp: Instant,
     },
+    /// Observed `count` occurrences each with the same value at the
+    /// given timestamp.
+    ///
+    /// Useful for pre-aggregated data. Instruments recording values
+    /// record the value `count` times while instruments counting
+    /// occurrences count `count` occurrences.
+    ObservedValues {
+        label: L,
+        value: ObservedValue,
+        count: u64,
+        timestamp: Instant,
+    },
 }
 
 impl<L> Observation<L> {
@@ -75,9 +129,144 @@ impl<L> Observation<L> {
     pub fn observed_one_value_now<T: Into<ObservedValue>>(label: L, value: T) -> Self {
         Self::observed_one_value(label, value, Instant::now())
     }
+
+    pub fn observed_values<T: Into<ObservedValue>>(
+        label: L,
+        value: T,
+        count: u64,
+        timestamp: Instant,
+    ) -> Self {
+        Observation::ObservedValues {
+            label,
+            value: value.into(),
+            count,
+            timestamp,
+        }
+    }
+
+    pub fn observed_values_now<T: Into<ObservedValue>>(label: L, value: T, count: u64) -> Self {
+        Self::observed_values(label, value, count, Instant::now())
+    }
+
+    /// Starts building an `Observation` for the given label.
+    ///
+    /// Without further configuration an `Observation::ObservedOne`
+    /// observed now is built.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Instant;
+    /// use metrix::Observation;
+    ///
+    /// let observation: Observation<&str> = Observation::observe("requests").count(3).build();
+    ///
+    /// match observation {
+    ///     Observation::Observed { count, .. } => assert_eq!(count, 3),
+    ///     _ => panic!("not observed many"),
+    /// }
+    ///
+    /// let observation = Observation::observe("latency")
+    ///     .value(42)
+    ///     .at(Instant::now())
+    ///     .build();
+    /// ```
+    pub fn observe(label: L) -> ObservationBuilder<L> {
+        ObservationBuilder {
+            label,
+            what: Observed::One,
+            timestamp: Instant::now(),
+        }
+    }
+}
+
+/// Builds an `Observation`. Created with `Observation::observe`.
+///
+/// Setting a value or a count replaces a previously set value or count.
+#[derive(Debug, Clone)]
+pub struct ObservationBuilder<L> {
+    label: L,
+    what: Observed,
+    timestamp: Instant,
 }
 
 #[derive(Debug, Clone, Copy)]
+enum Observed {
+    One,
+    Many(u64),
+    Value(ObservedValue),
+}
+
+impl<L> ObservationBuilder<L> {
+    /// Builds an `Observation::ObservedOneValue` with the given value
+    pub fn value<T: Into<ObservedValue>>(mut self, value: T) -> Self {
+        self.what = Observed::Value(value.into());
+        self
+    }
+
+    /// Builds an `Observation::Observed` with the given count
+    pub fn count(mut self, count: u64) -> Self {
+        self.what = Observed::Many(count);
+        self
+    }
+
+    /// Sets the timestamp of the observation.
+    ///
+    /// Default is the time `Observation::observe` was called.
+    pub fn at(mut self, timestamp: Instant) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    pub fn build(self) -> Observation<L> {
+        match self.what {
+            Observed::One => Observation::observed_one(self.label, self.timestamp),
+            Observed::Many(count) => Observation::observed(self.label, count, self.timestamp),
+            Observed::Value(value) => {
+                Observation::observed_one_value(self.label, value, self.timestamp)
+            }
+        }
+    }
+}
+
+impl<L> From<ObservationBuilder<L>> for Observation<L> {
+    fn from(builder: ObservationBuilder<L>) -> Self {
+        builder.build()
+    }
+}
+
+/// A value observed with an `Observation::ObservedOneValue`
+///
+/// `ObservedValue`s can be added, subtracted and multiplied where this is
+/// meaningful which results in `Some(value)`:
+///
+/// * Integers and floats can be combined. If a float is involved the result
+///   is a float. Integers saturate and stay unsigned if both operands are
+///   unsigned and the result is not negative.
+/// * Durations can be added to and subtracted from durations. The result is
+///   in the finer unit of both and saturates at zero.
+/// * Durations can be multiplied with non negative numbers.
+/// * Changes can be added to and subtracted from changes.
+///
+/// All other combinations result in `None`.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use metrix::{ObservedValue, TimeUnit};
+///
+/// assert_eq!(
+///     ObservedValue::from(2u64) + ObservedValue::from(0.5),
+///     Some(ObservedValue::Float(2.5))
+/// );
+/// assert_eq!(
+///     ObservedValue::from(Duration::from_millis(2)) * ObservedValue::from(3),
+///     Some(ObservedValue::Duration(6_000_000, TimeUnit::Nanoseconds))
+/// );
+/// assert_eq!(ObservedValue::from(true) + ObservedValue::from(1), None);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ObservedValue {
     SignedInteger(i64),
     UnsignedInteger(u64),
@@ -267,6 +456,33 @@ impl ObservedValue {
         }
     }
 
+    pub fn convert_to_f64(&self) -> Option<f64> {
+        match *self {
+            ObservedValue::SignedInteger(v) => Some(v as f64),
+            ObservedValue::UnsignedInteger(v) => Some(v as f64),
+            ObservedValue::Float(v) => Some(v),
+            ObservedValue::Bool(_) => None,
+            ObservedValue::Duration(_, _) => None,
+            ObservedValue::ChangedBy(_) => None,
+        }
+    }
+
+    /// Converts to an `u64` where negative values become 0.
+    ///
+    /// Floats are rounded. Values which are not numbers and `NaN`
+    /// result in `None`.
+    pub fn convert_to_u64_saturating(&self) -> Option<u64> {
+        match *self {
+            ObservedValue::SignedInteger(v) => Some(std::cmp::max(v, 0) as u64),
+            ObservedValue::UnsignedInteger(v) => Some(v),
+            ObservedValue::Float(v) if v.is_nan() => None,
+            ObservedValue::Float(v) => Some(v.round() as u64),
+            ObservedValue::Bool(_) => None,
+            ObservedValue::Duration(_, _) => None,
+            ObservedValue::ChangedBy(_) => None,
+        }
+    }
+
     pub fn convert_to_bool(&self) -> Option<bool> {
         match *self {
             ObservedValue::SignedInteger(v) => Some(v != 0),
@@ -279,6 +495,112 @@ impl ObservedValue {
     }
 }
 
+impl Add for ObservedValue {
+    type Output = Option<ObservedValue>;
+
+    fn add(self, other: ObservedValue) -> Option<ObservedValue> {
+        use ObservedValue::*;
+        match (self, other) {
+            (Duration(a, unit_a), Duration(b, unit_b)) => {
+                let (a, b, unit) = to_common_unit(a, unit_a, b, unit_b);
+                Some(Duration(a.saturating_add(b), unit))
+            }
+            (ChangedBy(a), ChangedBy(b)) => Some(ChangedBy(a.saturating_add(b))),
+            (a, b) => combine_numbers(a, b, |a, b| a + b, |a, b| a + b),
+        }
+    }
+}
+
+impl Sub for ObservedValue {
+    type Output = Option<ObservedValue>;
+
+    fn sub(self, other: ObservedValue) -> Option<ObservedValue> {
+        use ObservedValue::*;
+        match (self, other) {
+            (Duration(a, unit_a), Duration(b, unit_b)) => {
+                let (a, b, unit) = to_common_unit(a, unit_a, b, unit_b);
+                Some(Duration(a.saturating_sub(b), unit))
+            }
+            (ChangedBy(a), ChangedBy(b)) => Some(ChangedBy(a.saturating_sub(b))),
+            (a, b) => combine_numbers(a, b, |a, b| a - b, |a, b| a - b),
+        }
+    }
+}
+
+impl Mul for ObservedValue {
+    type Output = Option<ObservedValue>;
+
+    fn mul(self, other: ObservedValue) -> Option<ObservedValue> {
+        use ObservedValue::*;
+        match (self, other) {
+            (Duration(time, unit), factor) | (factor, Duration(time, unit)) => {
+                let factor = factor.convert_to_f64()?;
+                if factor < 0.0 {
+                    return None;
+                }
+                Some(Duration((time as f64 * factor).round() as u64, unit))
+            }
+            (a, b) => combine_numbers(a, b, |a, b| a * b, |a, b| a * b),
+        }
+    }
+}
+
+fn combine_numbers<I, F>(
+    a: ObservedValue,
+    b: ObservedValue,
+    on_integers: I,
+    on_floats: F,
+) -> Option<ObservedValue>
+where
+    I: Fn(i128, i128) -> i128,
+    F: Fn(f64, f64) -> f64,
+{
+    use ObservedValue::*;
+    if let (Float(_), _) | (_, Float(_)) = (a, b) {
+        return Some(Float(on_floats(a.convert_to_f64()?, b.convert_to_f64()?)));
+    }
+
+    let integer = |v: ObservedValue| match v {
+        SignedInteger(v) => Some((i128::from(v), false)),
+        UnsignedInteger(v) => Some((i128::from(v), true)),
+        _ => None,
+    };
+    let (a_int, a_unsigned) = integer(a)?;
+    let (b_int, b_unsigned) = integer(b)?;
+
+    // Products of two 64 bit integers always fit into an i128
+    let result = on_integers(a_int, b_int);
+    if a_unsigned && b_unsigned && result >= 0 {
+        Some(UnsignedInteger(
+            std::cmp::min(result, i128::from(u64::MAX)) as u64
+        ))
+    } else {
+        let clamped = result.clamp(i128::from(i64::MIN), i128::from(i64::MAX));
+        Some(SignedInteger(clamped as i64))
+    }
+}
+
+fn nanos_per_unit(unit: TimeUnit) -> u64 {
+    match unit {
+        TimeUnit::Nanoseconds => 1,
+        TimeUnit::Microseconds => 1_000,
+        TimeUnit::Milliseconds => 1_000_000,
+        TimeUnit::Seconds => 1_000_000_000,
+    }
+}
+
+/// Converts both durations to the finer of both units
+fn to_common_unit(a: u64, unit_a: TimeUnit, b: u64, unit_b: TimeUnit) -> (u64, u64, TimeUnit) {
+    let unit = if nanos_per_unit(unit_a) < nanos_per_unit(unit_b) {
+        unit_a
+    } else {
+        unit_b
+    };
+    let convert =
+        |v: u64, from: TimeUnit| v.saturating_mul(nanos_per_unit(from) / nanos_per_unit(unit));
+    (convert(a, unit_a), convert(b, unit_b), unit)
+}
+
 impl<L> Observation<L> {
     /// Extracts the label `L` from an observation.
     pub fn label(&self) -> &L {
@@ -286,6 +608,7 @@ impl<L> Observation<L> {
             Observation::Observed { ref label, .. } => label,
             Observation::ObservedOne { ref label, .. } => label,
             Observation::ObservedOneValue { ref label, .. } => label,
+            Observation::ObservedValues { ref label, .. } => label,
         }
     }
 }
@@ -296,6 +619,81 @@ impl<L> Observation<L> {
             Observation::Observed { timestamp, .. } => timestamp,
             Observation::ObservedOne { timestamp, .. } => timestamp,
             Observation::ObservedOneValue { timestamp, .. } => timestamp,
+            Observation::ObservedValues { timestamp, .. } => timestamp,
+        }
+    }
+
+    /// Returns the timestamp as a wall clock time using the given anchor.
+    ///
+    /// See `WallClockAnchor`.
+    pub fn system_time(&self, anchor: &WallClockAnchor) -> SystemTime {
+        anchor.system_time(self.timestamp())
+    }
+}
+
+/// Converts the monotonic `Instant`s of `Observation`s to wall clock times
+///
+/// An `Instant` can not be converted to a `SystemTime` directly. The anchor
+/// captures both at the same moment and converts an `Instant` by adding
+/// its distance to the captured `Instant` to the captured `SystemTime`.
+///
+/// Since only the distance between `Instant`s is used, adjustments of the
+/// system clock after the anchor was captured do not affect the converted
+/// times. Later `Instant`s always result in later `SystemTime`s.
+///
+/// A `TelemetryTransmitter` captures an anchor when it is created.
+///
+/// # Example
+///
+/// ```
+/// use std::time::{Duration, Instant};
+/// use metrix::WallClockAnchor;
+///
+/// let anchor = WallClockAnchor::now();
+/// let earlier = anchor.system_time(Instant::now());
+/// let later = anchor.system_time(Instant::now() + Duration::from_secs(1));
+///
+/// assert!(later > earlier);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct WallClockAnchor {
+    instant: Instant,
+    system_time: SystemTime,
+}
+
+impl WallClockAnchor {
+    /// Captures the current `Instant` and `SystemTime`
+    pub fn now() -> Self {
+        WallClockAnchor {
+            instant: Instant::now(),
+            system_time: SystemTime::now(),
+        }
+    }
+
+    /// Creates an anchor where `instant` happened at `system_time`
+    pub fn new(instant: Instant, system_time: SystemTime) -> Self {
+        WallClockAnchor {
+            instant,
+            system_time,
+        }
+    }
+
+    /// The `Instant` the anchor was captured at
+    pub fn instant(&self) -> Instant {
+        self.instant
+    }
+
+    /// The wall clock time the anchor was captured at
+    pub fn anchor_system_time(&self) -> SystemTime {
+        self.system_time
+    }
+
+    /// Converts the `Instant` to the wall clock time it happened at
+    pub fn system_time(&self, instant: Instant) -> SystemTime {
+        if instant >= self.instant {
+            self.system_time + (instant - self.instant)
+        } else {
+            self.system_time - (self.instant - instant)
         }
     }
 }
@@ -310,6 +708,161 @@ impl<L> ObservationLike for Observation<L> {
             Observation::Observed { timestamp, .. } => timestamp,
             Observation::ObservedOne { timestamp, .. } => timestamp,
             Observation::ObservedOneValue { timestamp, .. } => timestamp,
+            Observation::ObservedValues { timestamp, .. } => timestamp,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn the_builder_builds_all_variants() {
+        let at = Instant::now() - Duration::from_secs(5);
+
+        match Observation::observe(1).at(at).build() {
+            Observation::ObservedOne { label, timestamp } => {
+                assert_eq!(label, 1);
+                assert_eq!(timestamp, at);
+            }
+            other => panic!("unexpected: {:?}", other),
         }
+
+        match Observation::observe(2).count(7).at(at).build() {
+            Observation::Observed {
+                label,
+                count,
+                timestamp,
+            } => {
+                assert_eq!(label, 2);
+                assert_eq!(count, 7);
+                assert_eq!(timestamp, at);
+            }
+            other => panic!("unexpected: {:?}", other),
+        }
+
+        match Observation::observe(3).count(7).value(-4).at(at).build() {
+            Observation::ObservedOneValue {
+                label,
+                value: ObservedValue::SignedInteger(value),
+                timestamp,
+            } => {
+                assert_eq!(label, 3);
+                assert_eq!(value, -4);
+                assert_eq!(timestamp, at);
+            }
+            other => panic!("unexpected: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn the_timestamp_defaults_to_now() {
+        let before = Instant::now();
+        let observation: Observation<i32> = Observation::observe(1).into();
+        assert!(observation.timestamp() >= before);
+        assert!(observation.timestamp() <= Instant::now());
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(ObservedValue::from(-3).convert_to_f64(), Some(-3.0));
+        assert_eq!(ObservedValue::from(3u64).convert_to_f64(), Some(3.0));
+        assert_eq!(ObservedValue::from(true).convert_to_f64(), None);
+
+        assert_eq!(ObservedValue::from(-3).convert_to_u64_saturating(), Some(0));
+        assert_eq!(
+            ObservedValue::from(-0.7).convert_to_u64_saturating(),
+            Some(0)
+        );
+        assert_eq!(
+            ObservedValue::from(2.6).convert_to_u64_saturating(),
+            Some(3)
+        );
+        assert_eq!(ObservedValue::from(7).convert_to_u64_saturating(), Some(7));
+        assert_eq!(
+            ObservedValue::from(f64::NAN).convert_to_u64_saturating(),
+            None
+        );
+
+        assert_eq!(
+            ObservedValue::from(Duration::new(1, 5)),
+            ObservedValue::Duration(1_000_000_005, TimeUnit::Nanoseconds)
+        );
+    }
+
+    #[test]
+    fn arithmetic() {
+        let v = ObservedValue::from;
+
+        assert_eq!(v(2) + v(3), Some(ObservedValue::SignedInteger(5)));
+        assert_eq!(
+            ObservedValue::from(2u64) - ObservedValue::from(3u64),
+            Some(ObservedValue::SignedInteger(-1))
+        );
+        assert_eq!(
+            ObservedValue::from(u64::MAX) + ObservedValue::from(1u64),
+            Some(ObservedValue::UnsignedInteger(u64::MAX))
+        );
+        assert_eq!(
+            v(i64::MIN) - v(1),
+            Some(ObservedValue::SignedInteger(i64::MIN))
+        );
+        assert_eq!(v(4) * v(-2), Some(ObservedValue::SignedInteger(-8)));
+        assert_eq!(
+            v(4) * ObservedValue::from(0.5),
+            Some(ObservedValue::Float(2.0))
+        );
+
+        assert_eq!(
+            ObservedValue::Duration(2, TimeUnit::Milliseconds)
+                + ObservedValue::Duration(5, TimeUnit::Microseconds),
+            Some(ObservedValue::Duration(2_005, TimeUnit::Microseconds))
+        );
+        assert_eq!(
+            ObservedValue::Duration(2, TimeUnit::Milliseconds)
+                - ObservedValue::Duration(3, TimeUnit::Seconds),
+            Some(ObservedValue::Duration(0, TimeUnit::Milliseconds))
+        );
+        assert_eq!(ObservedValue::Duration(2, TimeUnit::Seconds) * v(-1), None);
+        assert_eq!(
+            ObservedValue::ChangedBy(2) - ObservedValue::ChangedBy(5),
+            Some(ObservedValue::ChangedBy(-3))
+        );
+        assert_eq!(ObservedValue::ChangedBy(2) + v(1), None);
+        assert_eq!(ObservedValue::Duration(2, TimeUnit::Seconds) + v(1), None);
+    }
+
+    #[test]
+    fn an_instant_is_converted_to_the_wall_clock_time() {
+        let (tx, _processor) = crate::processor::TelemetryProcessor::<i32>::new_pair_without_name();
+        let anchor = tx.wall_clock_anchor();
+
+        let observation = Observation::ObservedOne {
+            label: 1,
+            timestamp: Instant::now() - Duration::from_secs(2),
+        };
+        let expected = SystemTime::now() - Duration::from_secs(2);
+
+        let converted = observation.system_time(&anchor);
+        let difference = converted
+            .duration_since(expected)
+            .unwrap_or_else(|err| err.duration());
+        assert!(
+            difference < Duration::from_millis(100),
+            "off by {:?}",
+            difference
+        );
+
+        let before_anchor = anchor.system_time(anchor.instant() - Duration::from_secs(1));
+        assert_eq!(
+            anchor
+                .anchor_system_time()
+                .duration_since(before_anchor)
+                .unwrap(),
+            Duration::from_secs(1)
+        );
     }
 }
@@ -1,15 +1,23 @@
 //! Transmitting observations and grouping metrics.
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-use crossbeam_channel::{self as channel, Receiver, TryRecvError};
+use crossbeam_channel::TryRecvError;
+
+use crate::channel::{self, MessageReceiver};
 
 use crate::cockpit::Cockpit;
+use crate::instruments::fundamentals::{Clock, SharedClock};
 use crate::instruments::Panel;
 use crate::snapshot::{ItemKind, Snapshot};
 use crate::util;
 use crate::Descriptive;
 use crate::{
     HandlesObservations, Observation, ObservationLike, PutsSnapshot, TelemetryTransmitter,
+    WallClockAnchor,
 };
 
 /// Implementors can group everything that can process
@@ -28,6 +36,8 @@ pub trait AggregatesProcessors {
 pub(crate) enum TelemetryMessage<L> {
     /// An observation has been made
     Observation(Observation<L>),
+    /// Many observations have been made
+    Observations(Vec<Observation<L>>),
     /// A `Cockpit` should be added
     AddCockpit(Cockpit<L>),
     /// An arbitrary `HandlesObservations` should be added
@@ -37,10 +47,67 @@ pub(crate) enum TelemetryMessage<L> {
     /// This means the cockpit must have a name set.
     AddPanel {
         cockpit_name: String,
-        panel: Panel<L>,
+        panel: Box<Panel<L>>,
     },
 }
 
+impl<L> TelemetryMessage<L> {
+    /// Returns the number of observations in this message
+    /// or `None` if it is not an observation.
+    pub(crate) fn observation_count(&self) -> Option<u64> {
+        match *self {
+            TelemetryMessage::Observation(_) => Some(1),
+            TelemetryMessage::Observations(ref observations) => Some(observations.len() as u64),
+            _ => None,
+        }
+    }
+}
+
+/// Defines what a `TelemetryTransmitter` does with observations when
+/// the bounded channel to its `TelemetryProcessor` is full.
+///
+/// See `TelemetryProcessor::new_pair_bounded`.
+///
+/// Dropped observations are counted as dropped in the `ProcessingOutcome`
+/// of the next processing run of the `TelemetryProcessor`.
+///
+/// Only messages containing observations count towards the capacity of
+/// the channel. Messages which add cockpits, handlers or panels are
+/// always queued and never dropped.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Wait until the `TelemetryProcessor` has made room.
+    Block,
+    /// Drop the observations which are about to be transmitted.
+    DropNewest,
+    /// Drop the oldest queued observations to make room.
+    DropOldest,
+    /// Reject the observations which are about to be transmitted.
+    ///
+    /// `TelemetryTransmitter::try_transmit` hands back a rejected
+    /// `Observation` with a `TransmitError::Full`. Otherwise a rejected
+    /// observation is counted as a failed send. Rejected observations
+    /// are not counted as dropped by the `TelemetryProcessor`.
+    Reject,
+}
+
+/// The priority of observations transmitted to a bounded channel
+///
+/// When a bounded channel with `OverflowPolicy::DropNewest` or
+/// `OverflowPolicy::DropOldest` is full the observations with the
+/// lowest priority are dropped first. See
+/// `TransmitsTelemetryData::transmit_prioritized`.
+///
+/// Observations are transmitted with `Priority::Normal` unless
+/// stated otherwise.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
 /// The result of processing
 /// messages.
 ///
@@ -50,6 +117,8 @@ pub struct ProcessingOutcome {
     pub processed: usize,
     pub dropped: usize,
     pub instruments_updated: usize,
+    /// The time spent processing
+    pub elapsed: Duration,
 }
 
 impl ProcessingOutcome {
@@ -58,11 +127,25 @@ impl ProcessingOutcome {
         self.processed += other.processed;
         self.dropped += other.dropped;
         self.instruments_updated += other.instruments_updated;
+        self.elapsed += other.elapsed;
     }
 
     pub fn something_happened(&self) -> bool {
         self.processed > 0 || self.dropped > 0
     }
+
+    /// Returns the number of processed and dropped messages
+    /// per second spent processing.
+    ///
+    /// Returns `None` if no time elapsed.
+    pub fn observations_per_second(&self) -> Option<f64> {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            Some((self.processed + self.dropped) as f64 / secs)
+        } else {
+            None
+        }
+    }
 }
 
 impl Default for ProcessingOutcome {
@@ -71,6 +154,7 @@ impl Default for ProcessingOutcome {
             processed: 0,
             dropped: 0,
             instruments_updated: 0,
+            elapsed: Duration::from_secs(0),
         }
     }
 }
@@ -89,12 +173,12 @@ pub enum ProcessingStrategy {
 }
 
 impl ProcessingStrategy {
-    pub(crate) fn decider(&self) -> ProcessingDecider {
+    pub(crate) fn decider(&self, now: Instant) -> ProcessingDecider {
         match *self {
             ProcessingStrategy::ProcessAll => ProcessingDecider::ProcessAll,
             ProcessingStrategy::DropAll => ProcessingDecider::DropAll,
             ProcessingStrategy::DropOlderThan(max_age) => {
-                ProcessingDecider::DropBeforeDeadline(Instant::now() - max_age)
+                ProcessingDecider::DropBeforeDeadline(now - max_age)
             }
         }
     }
@@ -106,6 +190,43 @@ impl Default for ProcessingStrategy {
     }
 }
 
+/// The ages of the observations processed since the last
+/// exported `Snapshot`
+#[derive(Debug, Clone, Copy, Default)]
+struct ObservationAges {
+    count: u64,
+    sum: Duration,
+    max: Duration,
+}
+
+impl ObservationAges {
+    fn record(&mut self, age: Duration) {
+        self.count += 1;
+        self.sum += age;
+        if age > self.max {
+            self.max = age;
+        }
+    }
+
+    fn put_snapshot(self, into: &mut Snapshot) {
+        let mean_ms = if self.count == 0 {
+            0
+        } else {
+            self.sum.as_millis() / u128::from(self.count)
+        };
+        into.items.push((
+            "_observation_age_max_ms".to_string(),
+            ItemKind::UInt(self.max.as_millis() as u64),
+        ));
+        into.items.push((
+            "_observation_age_mean_ms".to_string(),
+            ItemKind::UInt(mean_ms as u64),
+        ));
+        util::put_gauge_type("_observation_age_max_ms", into);
+        util::put_gauge_type("_observation_age_mean_ms", into);
+    }
+}
+
 pub enum ProcessingDecider {
     ProcessAll,
     DropAll,
@@ -133,8 +254,28 @@ impl ProcessingDecider {
 pub trait ProcessesTelemetryMessages: PutsSnapshot + Send + 'static {
     /// Receive and handle pending operations
     fn process(&mut self, max: usize, strategy: ProcessingStrategy) -> ProcessingOutcome;
+
+    /// Returns the name if there is one.
+    ///
+    /// Used to look up processors, e.g. in a `ProcessorMount`.
+    ///
+    /// The default implementation returns `None`.
+    fn name(&self) -> Option<&str> {
+        None
+    }
+
+    /// Returns the `ProcessingStrategy` to be used instead of the one
+    /// a `ProcessorMount` was told to process with.
+    ///
+    /// The default implementation returns `None`.
+    fn get_default_strategy(&self) -> Option<ProcessingStrategy> {
+        None
+    }
 }
 
+type StrategyOverride<L> = dyn Fn(&L) -> Option<ProcessingStrategy> + Send;
+type ObservationMapper<L> = dyn Fn(Observation<L>) -> Option<Observation<L>> + Send;
+
 /// The counterpart of the `TelemetryTransmitter`. It receives the
 /// `Observation`s and other messages and processes them.
 ///
@@ -149,11 +290,23 @@ pub struct TelemetryProcessor<L> {
     description: Option<String>,
     cockpits: Vec<Cockpit<L>>,
     handlers: Vec<Box<dyn HandlesObservations<Label = L>>>,
-    receiver: Receiver<TelemetryMessage<L>>,
+    receiver: MessageReceiver<L>,
     snapshooters: Vec<Box<dyn PutsSnapshot>>,
     last_activity_at: Instant,
+    clock: SharedClock,
     max_inactivity_duration: Option<Duration>,
+    activity_markers: util::ActivityMarkers,
     is_disconnected: bool,
+    processing_totals: Option<ProcessingOutcome>,
+    observation_ages: Option<Cell<ObservationAges>>,
+    recent_observations: Option<(usize, VecDeque<Observation<L>>)>,
+    dedup_window: Option<(usize, VecDeque<Observation<L>>)>,
+    strategy_override: Option<Box<StrategyOverride<L>>>,
+    observation_mapper: Option<Box<ObservationMapper<L>>>,
+    map_before_deciding: bool,
+    default_strategy: Option<ProcessingStrategy>,
+    drain_on_drop: bool,
+    discarded_on_drop: Arc<AtomicU64>,
 }
 
 impl<L> TelemetryProcessor<L>
@@ -165,27 +318,7 @@ where
     ///
     /// The `name` will cause a grouping in the `Snapshot`.
     pub fn new_pair<T: Into<String>>(name: T) -> (TelemetryTransmitter<L>, TelemetryProcessor<L>) {
-        let (tx, rx) = channel::unbounded();
-
-        let transmitter = TelemetryTransmitter { sender: tx };
-
-        let last_activity_at = Instant::now();
-        let max_inactivity_duration = None;
-
-        let receiver = TelemetryProcessor {
-            name: Some(name.into()),
-            title: None,
-            description: None,
-            cockpits: Vec::new(),
-            handlers: Vec::new(),
-            snapshooters: Vec::new(),
-            receiver: rx,
-            last_activity_at,
-            max_inactivity_duration,
-            is_disconnected: false,
-        };
-
-        (transmitter, receiver)
+        Self::create_pair(Some(name.into()), None)
     }
 
     /// Creates a `TelemetryTransmitter` and the corresponding
@@ -193,15 +326,71 @@ where
     ///
     /// No grouping will occur unless the name is set.
     pub fn new_pair_without_name() -> (TelemetryTransmitter<L>, TelemetryProcessor<L>) {
-        let (tx, rx) = channel::unbounded();
+        Self::create_pair(None, None)
+    }
 
-        let transmitter = TelemetryTransmitter { sender: tx };
+    /// Creates a `TelemetryTransmitter` and the corresponding
+    /// `TelemetryProcessor` connected by a channel which holds at most
+    /// `capacity` messages containing observations.
+    ///
+    /// If the `TelemetryProcessor` falls behind the queued messages
+    /// do not grow without limit. Instead the `OverflowPolicy` decides
+    /// what happens to further observations.
+    ///
+    /// The `name` will cause a grouping in the `Snapshot`.
+    ///
+    /// # Panics
+    ///
+    /// If `capacity` is 0.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use metrix::processor::{
+    ///     OverflowPolicy, ProcessesTelemetryMessages, ProcessingStrategy, TelemetryProcessor,
+    /// };
+    /// use metrix::TransmitsTelemetryData;
+    ///
+    /// let (tx, mut processor) =
+    ///     TelemetryProcessor::new_pair_bounded("bursty", 2, OverflowPolicy::DropNewest);
+    ///
+    /// tx.observed_one_now("a").observed_one_now("b").observed_one_now("c");
+    ///
+    /// let outcome = processor.process(100, ProcessingStrategy::ProcessAll);
+    /// assert_eq!(outcome.processed, 2);
+    /// assert_eq!(outcome.dropped, 1);
+    /// ```
+    pub fn new_pair_bounded<T: Into<String>>(
+        name: T,
+        capacity: usize,
+        policy: OverflowPolicy,
+    ) -> (TelemetryTransmitter<L>, TelemetryProcessor<L>) {
+        assert!(capacity > 0, "the capacity of the channel must not be 0");
+        Self::create_pair(Some(name.into()), Some((capacity, policy)))
+    }
+
+    fn create_pair(
+        name: Option<String>,
+        bounds: Option<(usize, OverflowPolicy)>,
+    ) -> (TelemetryTransmitter<L>, TelemetryProcessor<L>) {
+        let (tx, rx) = match bounds {
+            Some((capacity, policy)) => channel::bounded(capacity, policy),
+            None => channel::unbounded(),
+        };
+        let discarded_on_drop = Arc::new(AtomicU64::new(0));
+
+        let transmitter = TelemetryTransmitter {
+            sender: tx,
+            anchor: WallClockAnchor::now(),
+            failed_sends: Default::default(),
+            discarded_on_drop: Arc::clone(&discarded_on_drop),
+        };
 
         let last_activity_at = Instant::now();
         let max_inactivity_duration = None;
 
         let receiver = TelemetryProcessor {
-            name: None,
+            name,
             title: None,
             description: None,
             cockpits: Vec::new(),
@@ -209,8 +398,20 @@ where
             snapshooters: Vec::new(),
             receiver: rx,
             last_activity_at,
+            clock: SharedClock::default(),
             max_inactivity_duration,
+            activity_markers: util::ActivityMarkers::default(),
             is_disconnected: false,
+            processing_totals: None,
+            observation_ages: None,
+            recent_observations: None,
+            dedup_window: None,
+            strategy_override: None,
+            observation_mapper: None,
+            map_before_deciding: false,
+            default_strategy: None,
+            drain_on_drop: false,
+            discarded_on_drop,
         };
 
         (transmitter, receiver)
@@ -293,6 +494,96 @@ where
         self.snapshooters.iter().map(|p| &**p).collect()
     }
 
+    fn should_be_processed(
+        &self,
+        decider: &ProcessingDecider,
+        now: Instant,
+        observation: &Observation<L>,
+    ) -> bool {
+        match self
+            .strategy_override
+            .as_ref()
+            .and_then(|strategy_for_label| strategy_for_label(observation.label()))
+        {
+            Some(strategy) => strategy.decider(now).should_be_processed(observation),
+            None => decider.should_be_processed(observation),
+        }
+    }
+
+    /// Returns the number of instruments updated or `None`
+    /// if the observation was dropped.
+    fn process_observation(
+        &mut self,
+        decider: &ProcessingDecider,
+        now: Instant,
+        observation: Observation<L>,
+    ) -> Option<usize> {
+        if self.is_duplicate(&observation) {
+            return None;
+        }
+        let observation = if self.map_before_deciding {
+            self.map_observation(observation)?
+        } else {
+            observation
+        };
+        if !self.should_be_processed(decider, now, &observation) {
+            return None;
+        }
+        let observation = if self.map_before_deciding {
+            observation
+        } else {
+            self.map_observation(observation)?
+        };
+
+        self.record_age(now, &observation);
+        Some(self.dispatch_observation(&observation))
+    }
+
+    fn is_duplicate(&mut self, observation: &Observation<L>) -> bool {
+        if let Some((window, ref mut seen)) = self.dedup_window {
+            if seen.contains(observation) {
+                return true;
+            }
+            if seen.len() == window {
+                seen.pop_front();
+            }
+            seen.push_back(observation.clone());
+        }
+        false
+    }
+
+    fn map_observation(&self, observation: Observation<L>) -> Option<Observation<L>> {
+        match self.observation_mapper {
+            Some(ref mapper) => mapper(observation),
+            None => Some(observation),
+        }
+    }
+
+    fn record_age(&mut self, now: Instant, observation: &Observation<L>) {
+        if let Some(ref mut ages) = self.observation_ages {
+            ages.get_mut()
+                .record(now.saturating_duration_since(observation.timestamp()));
+        }
+    }
+
+    fn dispatch_observation(&mut self, observation: &Observation<L>) -> usize {
+        if let Some((capacity, ref mut recent)) = self.recent_observations {
+            if recent.len() == capacity {
+                recent.pop_front();
+            }
+            recent.push_back(observation.clone());
+        }
+
+        let mut instruments_updated = 0;
+        self.cockpits
+            .iter_mut()
+            .for_each(|c| instruments_updated += c.handle_observation(observation));
+        self.handlers
+            .iter_mut()
+            .for_each(|h| instruments_updated += h.handle_observation(observation));
+        instruments_updated
+    }
+
     #[deprecated(
         since = "0.10.6",
         note = "use get_name. this method will change its signature"
@@ -323,35 +614,381 @@ where
         self
     }
 
-    fn put_values_into_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
+    /// Sets the `Clock` used for inactivity tracking and
+    /// for deciding whether observations are too old to be processed.
+    ///
+    /// Mostly useful for testing with a `ManualOffsetClock`.
+    ///
+    /// Default is the `WallClock`.
+    pub fn set_clock<C: Clock + Send + Sync + 'static>(&mut self, clock: C) {
+        self.clock = SharedClock::new(clock);
+        self.last_activity_at = self.clock.now();
+    }
+
+    /// Sets the `Clock` used for inactivity tracking and
+    /// for deciding whether observations are too old to be processed.
+    ///
+    /// Default is the `WallClock`.
+    pub fn clock<C: Clock + Send + Sync + 'static>(mut self, clock: C) -> Self {
+        self.set_clock(clock);
+        self
+    }
+
+    /// Sets the keys of the fields marking whether this processor is
+    /// active if inactivity tracking is enabled.
+    ///
+    /// Default is `_active` and `_inactive`.
+    pub fn set_active_marker_names<A: Into<String>, I: Into<String>>(
+        &mut self,
+        active_key: A,
+        inactive_key: I,
+    ) {
+        self.activity_markers = util::ActivityMarkers::new(active_key, inactive_key);
+    }
+
+    /// Sets the keys of the fields marking whether this processor is
+    /// active if inactivity tracking is enabled.
+    ///
+    /// Default is `_active` and `_inactive`.
+    pub fn active_marker_names<A: Into<String>, I: Into<String>>(
+        mut self,
+        active_key: A,
+        inactive_key: I,
+    ) -> Self {
+        self.set_active_marker_names(active_key, inactive_key);
+        self
+    }
+
+    /// Enables accumulating the `ProcessingOutcome`s of all
+    /// calls to `process`.
+    ///
+    /// The totals will be put into the `Snapshot` under `_processing`
+    /// as `processed`, `dropped` and `instruments_updated`.
+    ///
+    /// Default is disabled.
+    pub fn set_processing_stats_enabled(&mut self, enabled: bool) {
+        if !enabled {
+            self.processing_totals = None;
+        } else if self.processing_totals.is_none() {
+            self.processing_totals = Some(ProcessingOutcome::default());
+        }
+    }
+
+    /// Enables accumulating the `ProcessingOutcome`s of all
+    /// calls to `process`.
+    ///
+    /// The totals will be put into the `Snapshot` under `_processing`
+    /// as `processed`, `dropped` and `instruments_updated`.
+    ///
+    /// Default is disabled.
+    pub fn processing_stats_enabled(mut self, enabled: bool) -> Self {
+        self.set_processing_stats_enabled(enabled);
+        self
+    }
+
+    /// Enables tracking how old the processed `Observation`s were
+    /// when they were processed.
+    ///
+    /// The maximum and the mean age of the observations processed since
+    /// the last exported `Snapshot` will be put into the `Snapshot` as
+    /// `_observation_age_max_ms` and `_observation_age_mean_ms`. Both are
+    /// marked as gauges for exporters. Each exported `Snapshot` starts
+    /// over so that a single spike does not stay in the maximum. See
+    /// `PutsSnapshot::put_exported_snapshot`. Dropped observations are
+    /// not taken into account. This helps with choosing the age for
+    /// `ProcessingStrategy::DropOlderThan`.
+    ///
+    /// Default is disabled.
+    pub fn set_observation_age_stats_enabled(&mut self, enabled: bool) {
+        if !enabled {
+            self.observation_ages = None;
+        } else if self.observation_ages.is_none() {
+            self.observation_ages = Some(Cell::new(ObservationAges::default()));
+        }
+    }
+
+    /// Enables tracking how old the processed `Observation`s were
+    /// when they were processed.
+    ///
+    /// See `set_observation_age_stats_enabled`.
+    ///
+    /// Default is disabled.
+    pub fn observation_age_stats_enabled(mut self, enabled: bool) -> Self {
+        self.set_observation_age_stats_enabled(enabled);
+        self
+    }
+
+    /// Keeps the last `capacity` processed `Observation`s for debugging
+    /// purposes. They can be retrieved with `recent_observations`.
+    ///
+    /// A `capacity` of 0 disables keeping observations which is the default.
+    pub fn set_keep_recent_observations(&mut self, capacity: usize) {
+        self.recent_observations = if capacity == 0 {
+            None
+        } else {
+            Some((capacity, VecDeque::with_capacity(capacity)))
+        };
+    }
+
+    /// Keeps the last `capacity` processed `Observation`s for debugging
+    /// purposes. They can be retrieved with `recent_observations`.
+    ///
+    /// A `capacity` of 0 disables keeping observations which is the default.
+    pub fn keep_recent_observations(mut self, capacity: usize) -> Self {
+        self.set_keep_recent_observations(capacity);
+        self
+    }
+
+    /// Drops an `Observation` if an identical one was among the last
+    /// `window` observations of the same call to `process`.
+    ///
+    /// Observations are identical if they have the same label, value,
+    /// count and timestamp. Identical observations with different
+    /// timestamps are processed. Dropped duplicates are counted
+    /// as `dropped` in the `ProcessingOutcome`.
+    ///
+    /// This protects against clients sending the same observation twice.
+    /// A `window` of 0 disables deduplication which is the default.
+    pub fn set_dedup_window(&mut self, window: usize) {
+        self.dedup_window = if window == 0 {
+            None
+        } else {
+            Some((window, VecDeque::with_capacity(window)))
+        };
+    }
+
+    /// Drops an `Observation` if an identical one was among the last
+    /// `window` observations of the same call to `process`.
+    ///
+    /// See `set_dedup_window`.
+    pub fn dedup_window(mut self, window: usize) -> Self {
+        self.set_dedup_window(window);
+        self
+    }
+
+    /// Overrides the `ProcessingStrategy` passed to `process` for
+    /// observations with certain labels.
+    ///
+    /// If `strategy_for_label` returns a strategy for the label of an
+    /// observation, that strategy decides whether the observation is
+    /// processed or dropped. Otherwise the strategy passed to `process`
+    /// is used.
+    ///
+    /// This is also a way to prioritize observations while processing:
+    /// Once processing falls behind the queued observations become
+    /// stale and low priority observations can be dropped while high
+    /// priority ones are kept with `ProcessingStrategy::ProcessAll`.
+    /// A full bounded channel drops observations by their `Priority`
+    /// before they are even queued.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use metrix::processor::{ProcessingStrategy, TelemetryProcessor};
+    ///
+    /// let (_tx, processor) = TelemetryProcessor::<&str>::new_pair_without_name();
+    /// let processor = processor.strategy_override(|label| match *label {
+    ///     "error" => Some(ProcessingStrategy::ProcessAll),
+    ///     "latency" => Some(ProcessingStrategy::DropOlderThan(Duration::from_secs(5))),
+    ///     _ => None,
+    /// });
+    /// ```
+    pub fn set_strategy_override<F>(&mut self, strategy_for_label: F)
+    where
+        F: Fn(&L) -> Option<ProcessingStrategy> + Send + 'static,
+    {
+        self.strategy_override = Some(Box::new(strategy_for_label));
+    }
+
+    /// Overrides the `ProcessingStrategy` passed to `process` for
+    /// observations with certain labels.
+    ///
+    /// See `set_strategy_override`.
+    pub fn strategy_override<F>(mut self, strategy_for_label: F) -> Self
+    where
+        F: Fn(&L) -> Option<ProcessingStrategy> + Send + 'static,
+    {
+        self.set_strategy_override(strategy_for_label);
+        self
+    }
+
+    /// Sets a function which can rewrite each `Observation` before
+    /// it is dispatched to the instruments, e.g. to change the unit of
+    /// a value or the label. If it returns `None` the observation is
+    /// dropped.
+    ///
+    /// By default the function is applied to the observations which
+    /// were not dropped by the `ProcessingStrategy`.
+    /// See `set_map_before_deciding`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use metrix::processor::TelemetryProcessor;
+    /// use metrix::{Observation, ObservedValue};
+    ///
+    /// let (_tx, processor) = TelemetryProcessor::<&str>::new_pair_without_name();
+    /// let processor = processor.observation_mapper(|observation| match observation {
+    ///     Observation::ObservedOneValue {
+    ///         label: "bytes",
+    ///         value: ObservedValue::UnsignedInteger(bytes),
+    ///         timestamp,
+    ///     } => Some(Observation::ObservedOneValue {
+    ///         label: "kilobytes",
+    ///         value: (bytes / 1024).into(),
+    ///         timestamp,
+    ///     }),
+    ///     other => Some(other),
+    /// });
+    /// ```
+    pub fn set_observation_mapper<F>(&mut self, mapper: F)
+    where
+        F: Fn(Observation<L>) -> Option<Observation<L>> + Send + 'static,
+    {
+        self.observation_mapper = Some(Box::new(mapper));
+    }
+
+    /// Sets a function which can rewrite each `Observation` before
+    /// it is dispatched to the instruments.
+    ///
+    /// See `set_observation_mapper`.
+    pub fn observation_mapper<F>(mut self, mapper: F) -> Self
+    where
+        F: Fn(Observation<L>) -> Option<Observation<L>> + Send + 'static,
+    {
+        self.set_observation_mapper(mapper);
+        self
+    }
+
+    /// If enabled the observation mapper is applied before the
+    /// `ProcessingStrategy` decides whether an observation is dropped.
+    /// The decision is then based on the rewritten observation and
+    /// all observations pass the mapper.
+    ///
+    /// Default is disabled.
+    pub fn set_map_before_deciding(&mut self, enabled: bool) {
+        self.map_before_deciding = enabled;
+    }
+
+    /// If enabled the observation mapper is applied before the
+    /// `ProcessingStrategy` decides whether an observation is dropped.
+    ///
+    /// Default is disabled.
+    pub fn map_before_deciding(mut self, enabled: bool) -> Self {
+        self.set_map_before_deciding(enabled);
+        self
+    }
+
+    /// Sets the `ProcessingStrategy` used when this processor is
+    /// processed by a `ProcessorMount`. The strategy the mount was told
+    /// to process with is then ignored for this processor.
+    ///
+    /// Calling `process` directly still uses the strategy passed.
+    ///
+    /// Default is to use the strategy of the mount.
+    pub fn set_default_strategy(&mut self, strategy: ProcessingStrategy) {
+        self.default_strategy = Some(strategy);
+    }
+
+    /// Sets the `ProcessingStrategy` used when this processor is
+    /// processed by a `ProcessorMount`.
+    ///
+    /// See `set_default_strategy`.
+    pub fn default_strategy(mut self, strategy: ProcessingStrategy) -> Self {
+        self.set_default_strategy(strategy);
+        self
+    }
+
+    /// If enabled all messages still queued when this processor is
+    /// dropped are drained without being processed. Their number is
+    /// logged and can be retrieved with
+    /// `TelemetryTransmitter::discarded_on_drop`.
+    ///
+    /// An `Observations` message counts each contained observation.
+    ///
+    /// Default is disabled.
+    pub fn set_drain_on_drop(&mut self, enabled: bool) {
+        self.drain_on_drop = enabled;
+    }
+
+    /// If enabled all messages still queued when this processor is
+    /// dropped are drained and their number is logged.
+    ///
+    /// See `set_drain_on_drop`.
+    pub fn drain_on_drop(mut self, enabled: bool) -> Self {
+        self.set_drain_on_drop(enabled);
+        self
+    }
+
+    /// Returns the most recently processed `Observation`s with the
+    /// oldest first.
+    ///
+    /// Only contains `Observation`s if enabled
+    /// via `set_keep_recent_observations`.
+    pub fn recent_observations(&self) -> Vec<Observation<L>> {
+        self.recent_observations
+            .as_ref()
+            .map(|(_, recent)| recent.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn put_level(&self, into: &mut Snapshot, descriptive: bool, exported: bool) {
+        if let Some(ref name) = self.name {
+            let mut new_level = Snapshot::default();
+            self.put_values_into_snapshot(&mut new_level, descriptive, exported);
+            into.items
+                .push((name.clone(), ItemKind::Snapshot(new_level)));
+        } else {
+            self.put_values_into_snapshot(into, descriptive, exported);
+        }
+    }
+
+    fn put_values_into_snapshot(&self, into: &mut Snapshot, descriptive: bool, exported: bool) {
         util::put_default_descriptives(self, into, descriptive);
 
+        if let Some(ref totals) = self.processing_totals {
+            let mut processing = Snapshot::default();
+            processing.items.push((
+                "processed".to_string(),
+                ItemKind::UInt(totals.processed as u64),
+            ));
+            processing
+                .items
+                .push(("dropped".to_string(), ItemKind::UInt(totals.dropped as u64)));
+            processing.items.push((
+                "instruments_updated".to_string(),
+                ItemKind::UInt(totals.instruments_updated as u64),
+            ));
+            into.items
+                .push(("_processing".to_string(), ItemKind::Snapshot(processing)));
+        }
+
+        if let Some(ref ages) = self.observation_ages {
+            let ages = if exported { ages.take() } else { ages.get() };
+            ages.put_snapshot(into);
+        }
+
         if let Some(d) = self.max_inactivity_duration {
-            if self.last_activity_at.elapsed() > d {
-                into.items
-                    .push(("_inactive".to_string(), ItemKind::Boolean(true)));
-                into.items
-                    .push(("_active".to_string(), ItemKind::Boolean(false)));
+            if self.clock.now() - self.last_activity_at > d {
+                self.activity_markers.put(false, into);
                 return;
             } else {
-                into.items
-                    .push(("_inactive".to_string(), ItemKind::Boolean(false)));
-                into.items
-                    .push(("_active".to_string(), ItemKind::Boolean(true)));
+                self.activity_markers.put(true, into);
             }
         };
 
         self.cockpits
             .iter()
-            .for_each(|c| c.put_snapshot(into, descriptive));
+            .for_each(|c| util::put_snapshot_of(c, into, descriptive, exported));
 
         self.handlers
             .iter()
-            .for_each(|h| h.put_snapshot(into, descriptive));
+            .for_each(|h| util::put_snapshot_of(&**h, into, descriptive, exported));
 
         self.snapshooters
             .iter()
-            .for_each(|s| s.put_snapshot(into, descriptive));
+            .for_each(|s| util::put_snapshot_of(&**s, into, descriptive, exported));
     }
 }
 
@@ -360,30 +997,100 @@ where
     L: Clone + Eq + Send + 'static,
 {
     fn process(&mut self, max: usize, strategy: ProcessingStrategy) -> ProcessingOutcome {
+        self.receive(max, strategy, false)
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn get_default_strategy(&self) -> Option<ProcessingStrategy> {
+        self.default_strategy
+    }
+}
+
+impl<L> TelemetryProcessor<L>
+where
+    L: Clone + Eq + Send + 'static,
+{
+    /// Processes all messages pending in the channel regardless of
+    /// how many there are. Use this on shutdown to not lose
+    /// observations still in flight.
+    ///
+    /// If all transmitters have been dropped the processor is
+    /// disconnected afterwards. See `is_disconnected`.
+    pub fn drain(&mut self, strategy: ProcessingStrategy) -> ProcessingOutcome {
+        self.receive(usize::MAX, strategy, true)
+    }
+
+    /// Processes all pending messages with `ProcessingStrategy::ProcessAll`
+    /// and returns a final `Snapshot`.
+    pub fn flush(&mut self, descriptive: bool) -> Snapshot {
+        self.drain(ProcessingStrategy::ProcessAll);
+        let mut snapshot = Snapshot::default();
+        self.put_snapshot(&mut snapshot, descriptive);
+        snapshot
+    }
+
+    /// Returns `true` once all transmitters have been dropped and
+    /// all messages have been received.
+    ///
+    /// A disconnected processor will not process any more messages.
+    pub fn is_disconnected(&self) -> bool {
+        self.is_disconnected
+    }
+
+    fn receive(
+        &mut self,
+        max: usize,
+        strategy: ProcessingStrategy,
+        stop_when_empty: bool,
+    ) -> ProcessingOutcome {
+        // Taken first so that observations dropped by a bounded
+        // channel are counted even if the channel is disconnected
+        let dropped_on_overflow = self.receiver.take_dropped() as usize;
+
         if self.is_disconnected {
-            return ProcessingOutcome::default();
+            let outcome = ProcessingOutcome {
+                dropped: dropped_on_overflow,
+                ..ProcessingOutcome::default()
+            };
+            if let Some(ref mut totals) = self.processing_totals {
+                totals.combine_with(&outcome);
+            }
+            return outcome;
         }
 
+        let started = Instant::now();
         let mut num_received = 0;
         let mut processed = 0;
         let mut instruments_updated = 0;
-        let mut dropped = 0;
-        let decider = strategy.decider();
+        let mut dropped = dropped_on_overflow;
+        let now = self.clock.now();
+        let decider = strategy.decider(now);
+        if let Some((_, ref mut seen)) = self.dedup_window {
+            seen.clear();
+        }
         while num_received < max {
             match self.receiver.try_recv() {
                 Ok(TelemetryMessage::Observation(obs)) => {
-                    if decider.should_be_processed(&obs) {
-                        self.cockpits
-                            .iter_mut()
-                            .for_each(|c| instruments_updated += c.handle_observation(&obs));
-                        self.handlers
-                            .iter_mut()
-                            .for_each(|h| instruments_updated += h.handle_observation(&obs));
+                    if let Some(updated) = self.process_observation(&decider, now, obs) {
+                        instruments_updated += updated;
                         processed += 1;
                     } else {
                         dropped += 1;
                     }
                 }
+                Ok(TelemetryMessage::Observations(observations)) => {
+                    for obs in observations {
+                        if let Some(updated) = self.process_observation(&decider, now, obs) {
+                            instruments_updated += updated;
+                            processed += 1;
+                        } else {
+                            dropped += 1;
+                        }
+                    }
+                }
                 Ok(TelemetryMessage::AddCockpit(c)) => {
                     self.add_cockpit(c);
                     processed += 1;
@@ -401,11 +1108,15 @@ where
                         .iter_mut()
                         .find(|c| c.get_name() == Some(&cockpit_name))
                     {
-                        cockpit.add_panel(panel);
+                        cockpit.add_panel(*panel);
                     }
                     processed += 1;
                 }
-                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Empty) => {
+                    if stop_when_empty {
+                        break;
+                    }
+                }
                 Err(TryRecvError::Disconnected) => {
                     let name = self
                         .name
@@ -427,10 +1138,15 @@ where
             processed,
             dropped,
             instruments_updated,
+            elapsed: started.elapsed(),
         };
 
         if outcome.something_happened() {
-            self.last_activity_at = Instant::now();
+            self.last_activity_at = self.clock.now();
+        }
+
+        if let Some(ref mut totals) = self.processing_totals {
+            totals.combine_with(&outcome);
         }
 
         outcome
@@ -442,14 +1158,11 @@ where
     L: Clone + Eq + Send + 'static,
 {
     fn put_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
-        if let Some(ref name) = self.name {
-            let mut new_level = Snapshot::default();
-            self.put_values_into_snapshot(&mut new_level, descriptive);
-            into.items
-                .push((name.clone(), ItemKind::Snapshot(new_level)));
-        } else {
-            self.put_values_into_snapshot(into, descriptive);
-        }
+        self.put_level(into, descriptive, false)
+    }
+
+    fn put_exported_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
+        self.put_level(into, descriptive, true)
     }
 }
 
@@ -463,6 +1176,57 @@ impl<L> Descriptive for TelemetryProcessor<L> {
     }
 }
 
+impl<L> Drop for TelemetryProcessor<L> {
+    fn drop(&mut self) {
+        if !self.drain_on_drop {
+            return;
+        }
+
+        // Senders may still be transmitting so only the messages queued
+        // right now are drained. Otherwise this might never finish.
+        let mut discarded = 0u64;
+        for _ in 0..self.receiver.len() {
+            match self.receiver.try_recv() {
+                Ok(TelemetryMessage::Observations(observations)) => {
+                    discarded += observations.len() as u64
+                }
+                Ok(_) => discarded += 1,
+                Err(_) => break,
+            }
+        }
+
+        if discarded > 0 {
+            self.discarded_on_drop
+                .fetch_add(discarded, Ordering::Relaxed);
+            util::log_warning(format!(
+                "Processor '{}' dropped with {} unprocessed messages which were discarded",
+                self.name.as_deref().unwrap_or("<no name>"),
+                discarded
+            ));
+        }
+    }
+}
+
+/// Defines how a `ProcessorMount` puts processors without a name
+/// into the `Snapshot`
+///
+/// The values of an unnamed processor are put directly into the
+/// level of the mount. Two unnamed processors putting the same keys
+/// there collide and only one of the values survives.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum FlattenPolicy {
+    /// Put the values of unnamed processors directly into the level
+    /// of the mount. This is the default.
+    #[default]
+    Flatten,
+    /// Put the values of each unnamed processor into a level named
+    /// after the position of the processor within the mount, e.g. `0`.
+    PrefixWithIndex,
+    /// Leave the values of unnamed processors out. An error is logged
+    /// once for each unnamed processor when it is mounted.
+    Reject,
+}
+
 /// A building block for grouping
 pub struct ProcessorMount {
     name: Option<String>,
@@ -471,7 +1235,11 @@ pub struct ProcessorMount {
     processors: Vec<Box<dyn ProcessesTelemetryMessages>>,
     snapshooters: Vec<Box<dyn PutsSnapshot>>,
     last_activity_at: Instant,
+    clock: SharedClock,
     max_inactivity_duration: Option<Duration>,
+    activity_markers: util::ActivityMarkers,
+    processing_threads: usize,
+    flatten_policy: FlattenPolicy,
 }
 
 impl ProcessorMount {
@@ -502,6 +1270,74 @@ impl ProcessorMount {
         self.max_inactivity_duration = Some(limit);
     }
 
+    /// Sets the `Clock` used for inactivity tracking.
+    ///
+    /// Mostly useful for testing with a `ManualOffsetClock`.
+    ///
+    /// Default is the `WallClock`.
+    pub fn set_clock<C: Clock + Send + Sync + 'static>(&mut self, clock: C) {
+        self.clock = SharedClock::new(clock);
+        self.last_activity_at = self.clock.now();
+    }
+
+    /// Sets the keys of the fields marking whether this mount is
+    /// active if inactivity tracking is enabled.
+    ///
+    /// Default is `_active` and `_inactive`.
+    pub fn set_active_marker_names<A: Into<String>, I: Into<String>>(
+        &mut self,
+        active_key: A,
+        inactive_key: I,
+    ) {
+        self.activity_markers = util::ActivityMarkers::new(active_key, inactive_key);
+    }
+
+    /// Sets on how many threads the contained processors
+    /// are processed in parallel.
+    ///
+    /// The processors are split into as many groups as there are threads
+    /// and each group is processed on its own scoped thread. The threads
+    /// are spawned on each call to `process`, so this only pays off if
+    /// there are many processors with a lot of work.
+    ///
+    /// Default is 1 which processes sequentially on the calling thread.
+    pub fn set_processing_threads(&mut self, threads: usize) {
+        self.processing_threads = threads;
+    }
+
+    /// Sets how processors without a name are put into the `Snapshot`.
+    ///
+    /// Default is `FlattenPolicy::Flatten`.
+    pub fn set_flatten_policy(&mut self, policy: FlattenPolicy) {
+        self.flatten_policy = policy;
+        if policy == FlattenPolicy::Reject {
+            self.processors
+                .iter()
+                .enumerate()
+                .filter(|(_, processor)| processor.name().is_none())
+                .for_each(|(idx, _)| self.log_rejected(idx));
+        }
+    }
+
+    /// Sets how processors without a name are put into the `Snapshot`.
+    ///
+    /// Default is `FlattenPolicy::Flatten`.
+    pub fn flatten_policy(mut self, policy: FlattenPolicy) -> Self {
+        self.set_flatten_policy(policy);
+        self
+    }
+
+    /// Removes the first processor with the given name and returns it.
+    ///
+    /// Returns `None` if there is no processor with the given name.
+    pub fn remove_processor(&mut self, name: &str) -> Option<Box<dyn ProcessesTelemetryMessages>> {
+        let idx = self
+            .processors
+            .iter()
+            .position(|p| p.name() == Some(name))?;
+        Some(self.processors.remove(idx))
+    }
+
     /// Returns the processors in this `ProcessorMount`
     pub fn processors(&self) -> Vec<&dyn ProcessesTelemetryMessages> {
         self.processors.iter().map(|p| &**p).collect()
@@ -512,31 +1348,59 @@ impl ProcessorMount {
         self.snapshooters.iter().map(|s| &**s).collect()
     }
 
-    fn put_values_into_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
+    fn put_level(&self, into: &mut Snapshot, descriptive: bool, exported: bool) {
+        if let Some(ref name) = self.name {
+            let mut new_level = Snapshot::default();
+            self.put_values_into_snapshot(&mut new_level, descriptive, exported);
+            into.items
+                .push((name.clone(), ItemKind::Snapshot(new_level)));
+        } else {
+            self.put_values_into_snapshot(into, descriptive, exported);
+        }
+    }
+
+    fn put_values_into_snapshot(&self, into: &mut Snapshot, descriptive: bool, exported: bool) {
         util::put_default_descriptives(self, into, descriptive);
 
         if let Some(d) = self.max_inactivity_duration {
-            if self.last_activity_at.elapsed() > d {
-                into.items
-                    .push(("_inactive".to_string(), ItemKind::Boolean(true)));
-                into.items
-                    .push(("_active".to_string(), ItemKind::Boolean(false)));
+            if self.clock.now() - self.last_activity_at > d {
+                self.activity_markers.put(false, into);
                 return;
             } else {
-                into.items
-                    .push(("_inactive".to_string(), ItemKind::Boolean(false)));
-                into.items
-                    .push(("_active".to_string(), ItemKind::Boolean(true)));
+                self.activity_markers.put(true, into);
             }
         };
 
-        self.processors
-            .iter()
-            .for_each(|p| p.put_snapshot(into, descriptive));
+        for (idx, processor) in self.processors.iter().enumerate() {
+            if processor.name().is_some() {
+                util::put_snapshot_of(&**processor, into, descriptive, exported);
+                continue;
+            }
+            match self.flatten_policy {
+                FlattenPolicy::Flatten => {
+                    util::put_snapshot_of(&**processor, into, descriptive, exported)
+                }
+                FlattenPolicy::PrefixWithIndex => {
+                    let mut new_level = Snapshot::default();
+                    util::put_snapshot_of(&**processor, &mut new_level, descriptive, exported);
+                    into.items
+                        .push((idx.to_string(), ItemKind::Snapshot(new_level)));
+                }
+                FlattenPolicy::Reject => {}
+            }
+        }
 
         self.snapshooters
             .iter()
-            .for_each(|s| s.put_snapshot(into, descriptive));
+            .for_each(|s| util::put_snapshot_of(&**s, into, descriptive, exported));
+    }
+
+    fn log_rejected(&self, idx: usize) {
+        util::log_error(format!(
+            "Processor {} of mount '{}' has no name and is left out of the snapshot",
+            idx,
+            self.name.as_deref().unwrap_or("<no name>")
+        ))
     }
 }
 
@@ -549,13 +1413,20 @@ impl Default for ProcessorMount {
             processors: Vec::new(),
             snapshooters: Vec::new(),
             last_activity_at: Instant::now(),
+            clock: SharedClock::default(),
             max_inactivity_duration: None,
+            activity_markers: util::ActivityMarkers::default(),
+            processing_threads: 1,
+            flatten_policy: FlattenPolicy::default(),
         }
     }
 }
 
 impl AggregatesProcessors for ProcessorMount {
     fn add_processor<P: ProcessesTelemetryMessages>(&mut self, processor: P) {
+        if self.flatten_policy == FlattenPolicy::Reject && processor.name().is_none() {
+            self.log_rejected(self.processors.len());
+        }
         self.processors.push(Box::new(processor));
     }
 
@@ -566,30 +1437,66 @@ impl AggregatesProcessors for ProcessorMount {
 
 impl ProcessesTelemetryMessages for ProcessorMount {
     fn process(&mut self, max: usize, strategy: ProcessingStrategy) -> ProcessingOutcome {
-        let mut outcome = ProcessingOutcome::default();
-
-        for processor in self.processors.iter_mut() {
-            outcome.combine_with(&processor.process(max, strategy));
-        }
+        let started = Instant::now();
+        let threads = self.processing_threads.min(self.processors.len());
+        let mut outcome = if threads > 1 {
+            let chunk_size = self.processors.len().div_ceil(threads);
+            std::thread::scope(|scope| {
+                let handles: Vec<_> = self
+                    .processors
+                    .chunks_mut(chunk_size)
+                    .map(|chunk| scope.spawn(move || process_all(chunk, max, strategy)))
+                    .collect();
+
+                let mut outcome = ProcessingOutcome::default();
+                for handle in handles {
+                    match handle.join() {
+                        Ok(chunk_outcome) => outcome.combine_with(&chunk_outcome),
+                        Err(panic) => std::panic::resume_unwind(panic),
+                    }
+                }
+                outcome
+            })
+        } else {
+            process_all(&mut self.processors, max, strategy)
+        };
+        // The processors may have run in parallel
+        outcome.elapsed = started.elapsed();
 
         if outcome.something_happened() {
-            self.last_activity_at = Instant::now();
+            self.last_activity_at = self.clock.now();
         }
 
         outcome
     }
+
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+}
+
+fn process_all(
+    processors: &mut [Box<dyn ProcessesTelemetryMessages>],
+    max: usize,
+    strategy: ProcessingStrategy,
+) -> ProcessingOutcome {
+    let mut outcome = ProcessingOutcome::default();
+
+    for processor in processors.iter_mut() {
+        let strategy = processor.get_default_strategy().unwrap_or(strategy);
+        outcome.combine_with(&processor.process(max, strategy));
+    }
+
+    outcome
 }
 
 impl PutsSnapshot for ProcessorMount {
     fn put_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
-        if let Some(ref name) = self.name {
-            let mut new_level = Snapshot::default();
-            self.put_values_into_snapshot(&mut new_level, descriptive);
-            into.items
-                .push((name.clone(), ItemKind::Snapshot(new_level)));
-        } else {
-            self.put_values_into_snapshot(into, descriptive);
-        }
+        self.put_level(into, descriptive, false)
+    }
+
+    fn put_exported_snapshot(&self, into: &mut Snapshot, descriptive: bool) {
+        self.put_level(into, descriptive, true)
     }
 }
 
@@ -602,3 +1509,690 @@ impl Descriptive for ProcessorMount {
         self.description.as_ref().map(|n| &**n)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::instruments::{Counter, ManualOffsetClock, Panel};
+    use crate::snapshot::{FindItem, ItemKind};
+    use crate::{TelemetryTransmitter, TransmitsTelemetryData};
+
+    #[test]
+    fn a_batch_updates_instruments_for_each_observation() {
+        let (tx, mut processor): (TelemetryTransmitter<i32>, _) =
+            TelemetryProcessor::new_pair_without_name();
+        processor.add_cockpit(
+            Cockpit::without_name()
+                .panel(Panel::named(1, "panel").counter(Counter::new("count").for_label(1))),
+        );
+
+        let observations = (0..1_000)
+            .map(|i| Observation::observed_one_now(if i % 10 == 0 { 2 } else { 1 }))
+            .collect();
+        tx.transmit_many(observations);
+
+        let outcome = processor.process(1, ProcessingStrategy::ProcessAll);
+        assert_eq!(outcome.processed, 1_000);
+        assert_eq!(outcome.instruments_updated, 900);
+
+        let mut snapshot = Snapshot::default();
+        processor.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("panel/count"),
+            FindItem::Found(&ItemKind::UInt(900))
+        );
+    }
+
+    #[test]
+    fn identical_observations_are_processed_once_per_batch() {
+        let (tx, processor): (TelemetryTransmitter<i32>, _) =
+            TelemetryProcessor::new_pair_without_name();
+        let mut processor = processor.dedup_window(10);
+        processor.add_cockpit(
+            Cockpit::without_name()
+                .panel(Panel::named(1, "panel").counter(Counter::new("count").for_label(1))),
+        );
+
+        let timestamp = Instant::now();
+        tx.observed_one_value(1, 5, timestamp)
+            .observed_one_value(1, 5, timestamp)
+            .observed_one_value(1, 6, timestamp)
+            .observed_one_value(1, 5, timestamp + Duration::from_millis(1));
+
+        let outcome = processor.process(100, ProcessingStrategy::ProcessAll);
+        assert_eq!(outcome.processed, 3);
+        assert_eq!(outcome.dropped, 1);
+
+        tx.observed_one_value(1, 5, timestamp);
+        let outcome = processor.process(100, ProcessingStrategy::ProcessAll);
+        assert_eq!(outcome.processed, 1);
+
+        let mut snapshot = Snapshot::default();
+        processor.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("panel/count"),
+            FindItem::Found(&ItemKind::UInt(4))
+        );
+    }
+
+    #[test]
+    fn processing_stats_count_dropped_observations() {
+        let (tx, processor): (TelemetryTransmitter<i32>, _) =
+            TelemetryProcessor::new_pair_without_name();
+        let mut processor = processor.processing_stats_enabled(true);
+        processor.add_cockpit(
+            Cockpit::without_name()
+                .panel(Panel::named(1, "panel").counter(Counter::new("count").for_label(1))),
+        );
+
+        let stale = Instant::now() - Duration::from_secs(120);
+        tx.observed_one(1, stale)
+            .observed_one(1, stale)
+            .observed_one_now(1)
+            .observed_one_now(1)
+            .observed_one_now(1);
+
+        let strategy = ProcessingStrategy::DropOlderThan(Duration::from_secs(60));
+        processor.process(2, strategy);
+        processor.process(100, strategy);
+
+        let mut snapshot = Snapshot::default();
+        processor.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("_processing/processed"),
+            FindItem::Found(&ItemKind::UInt(3))
+        );
+        assert_eq!(
+            snapshot.find("_processing/dropped"),
+            FindItem::Found(&ItemKind::UInt(2))
+        );
+        assert_eq!(
+            snapshot.find("_processing/instruments_updated"),
+            FindItem::Found(&ItemKind::UInt(3))
+        );
+        assert_eq!(
+            snapshot.find("panel/count"),
+            FindItem::Found(&ItemKind::UInt(3))
+        );
+    }
+
+    #[test]
+    fn the_outcome_reports_the_throughput() {
+        let (tx, mut processor): (TelemetryTransmitter<i32>, _) =
+            TelemetryProcessor::new_pair_without_name();
+        processor.add_cockpit(
+            Cockpit::without_name()
+                .panel(Panel::named(1, "panel").counter(Counter::new("count").for_label(1))),
+        );
+
+        for _ in 0..1_000 {
+            tx.observed_one_now(1);
+        }
+
+        let outcome = processor.process(10_000, ProcessingStrategy::ProcessAll);
+        assert_eq!(outcome.processed, 1_000);
+        assert!(outcome.elapsed > Duration::from_secs(0));
+        let rate = outcome.observations_per_second().unwrap();
+        assert!(rate.is_finite() && rate > 0.0);
+
+        assert_eq!(ProcessingOutcome::default().observations_per_second(), None);
+    }
+
+    #[test]
+    fn the_transmitter_is_a_sink_for_many_tasks() {
+        use futures::{stream, Future, Stream};
+
+        let (tx, mut processor): (TelemetryTransmitter<i32>, _) =
+            TelemetryProcessor::new_pair_without_name();
+        processor.add_cockpit(
+            Cockpit::without_name()
+                .panel(Panel::named(1, "panel").counter(Counter::new("count").for_label(1))),
+        );
+
+        let tasks: Vec<_> = (0..4)
+            .map(|_| {
+                let tx = tx.clone();
+                std::thread::spawn(move || {
+                    let observations = (0..250).map(|_| Observation::observed_one_now(1));
+                    let (_observations, _tx) = stream::iter_ok::<_, ()>(observations)
+                        .forward(tx)
+                        .wait()
+                        .unwrap();
+                })
+            })
+            .collect();
+        tasks.into_iter().for_each(|t| t.join().unwrap());
+
+        processor.process(10_000, ProcessingStrategy::ProcessAll);
+
+        let mut snapshot = Snapshot::default();
+        processor.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("panel/count"),
+            FindItem::Found(&ItemKind::UInt(1_000))
+        );
+    }
+
+    #[test]
+    fn keeps_the_most_recent_observations() {
+        let (tx, processor): (TelemetryTransmitter<i32>, _) =
+            TelemetryProcessor::new_pair_without_name();
+        let mut processor = processor.keep_recent_observations(3);
+
+        assert!(processor.recent_observations().is_empty());
+
+        for label in 1..=5 {
+            tx.observed_one_now(label);
+        }
+        processor.process(100, ProcessingStrategy::ProcessAll);
+
+        let labels: Vec<i32> = processor
+            .recent_observations()
+            .iter()
+            .map(|obs| *obs.label())
+            .collect();
+        assert_eq!(labels, vec![3, 4, 5]);
+    }
+
+    #[test]
+    fn drain_processes_all_pending_messages() {
+        let (tx, mut processor) = TelemetryProcessor::new_pair_without_name();
+        processor.add_cockpit(
+            Cockpit::without_name().panel(Panel::named(1, "panel").counter(Counter::new("count"))),
+        );
+
+        for _ in 0..25_000 {
+            tx.observed_one_now(1);
+        }
+
+        let outcome = processor.drain(ProcessingStrategy::ProcessAll);
+        assert_eq!(outcome.processed, 25_000);
+        assert!(!processor.is_disconnected());
+
+        tx.observed_one_now(1);
+        drop(tx);
+
+        let snapshot = processor.flush(false);
+        assert_eq!(
+            snapshot.find("panel/count"),
+            FindItem::Found(&ItemKind::UInt(25_001))
+        );
+        assert!(processor.is_disconnected());
+    }
+
+    #[test]
+    fn a_manual_clock_decides_which_observations_are_too_old() {
+        let clock = ManualOffsetClock::default();
+        let (tx, processor) = TelemetryProcessor::new_pair_without_name();
+        let mut processor = processor.clock(clock.clone());
+        processor.add_cockpit(
+            Cockpit::without_name().panel(Panel::named(1, "panel").counter(Counter::new("count"))),
+        );
+
+        tx.observed_one(1, clock.now());
+        clock.advance_n_seconds(30);
+        tx.observed_one(1, clock.now());
+        clock.advance_n_seconds(45);
+
+        let outcome = processor.process(
+            100,
+            ProcessingStrategy::DropOlderThan(Duration::from_secs(60)),
+        );
+        assert_eq!(outcome.processed, 1);
+        assert_eq!(outcome.dropped, 1);
+    }
+
+    #[test]
+    fn a_strategy_override_keeps_stale_observations() {
+        let clock = ManualOffsetClock::default();
+        let (tx, processor) = TelemetryProcessor::new_pair_without_name();
+        let mut processor = processor.clock(clock.clone()).strategy_override(|label| {
+            if *label == "error" {
+                Some(ProcessingStrategy::ProcessAll)
+            } else {
+                None
+            }
+        });
+        processor.add_cockpit(
+            Cockpit::without_name()
+                .panel(Panel::named("latency", "latency").counter(Counter::new("count")))
+                .panel(Panel::named("error", "error").counter(Counter::new("count"))),
+        );
+
+        tx.observed_one("latency", clock.now());
+        tx.observed_one("error", clock.now());
+        clock.advance_n_seconds(120);
+
+        let outcome = processor.process(
+            100,
+            ProcessingStrategy::DropOlderThan(Duration::from_secs(60)),
+        );
+        assert_eq!(outcome.processed, 1);
+        assert_eq!(outcome.dropped, 1);
+
+        let mut snapshot = Snapshot::default();
+        processor.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("error/count"),
+            FindItem::Found(&ItemKind::UInt(1))
+        );
+        assert_eq!(
+            snapshot.find("latency/count"),
+            FindItem::Found(&ItemKind::UInt(0))
+        );
+    }
+
+    #[test]
+    fn parallel_processing_has_the_same_outcome() {
+        fn mount_with_processors(threads: usize) -> ProcessorMount {
+            let mut mount = ProcessorMount::new("mount");
+            mount.set_processing_threads(threads);
+
+            for n in 0..7 {
+                let (tx, mut processor): (TelemetryTransmitter<i32>, _) =
+                    TelemetryProcessor::new_pair(format!("processor_{}", n));
+                processor.add_cockpit(
+                    Cockpit::without_name().panel(
+                        Panel::named(1, "panel").counter(Counter::new("count").for_label(1)),
+                    ),
+                );
+
+                let stale = Instant::now() - Duration::from_secs(120);
+                for i in 0..(n * 90) {
+                    if i % 3 == 0 {
+                        tx.observed_one(2, stale);
+                    } else {
+                        tx.observed_one_now(1);
+                    }
+                }
+                mount.add_processor(processor);
+            }
+
+            mount
+        }
+
+        let strategy = ProcessingStrategy::DropOlderThan(Duration::from_secs(60));
+        let sequential = mount_with_processors(1).process(1_000, strategy);
+        let mut parallel_mount = mount_with_processors(3);
+        let parallel = parallel_mount.process(1_000, strategy);
+
+        assert_eq!(parallel.processed, sequential.processed);
+        assert_eq!(parallel.dropped, sequential.dropped);
+        assert_eq!(parallel.instruments_updated, sequential.instruments_updated);
+        assert_eq!(parallel.processed, 1_260);
+        assert_eq!(parallel.dropped, 630);
+
+        let mut snapshot = Snapshot::default();
+        parallel_mount.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("mount/processor_6/panel/count"),
+            FindItem::Found(&ItemKind::UInt(360))
+        );
+    }
+
+    #[test]
+    fn the_convenience_methods_update_the_instruments() {
+        use crate::instruments::{Gauge, Histogram};
+
+        let (tx, mut processor): (TelemetryTransmitter<i32>, _) =
+            TelemetryProcessor::new_pair_without_name();
+        processor.add_cockpit(
+            Cockpit::without_name().panel(
+                Panel::named(vec![1, 2], "panel")
+                    .counter(Counter::new("count").for_label(1))
+                    .gauge(Gauge::new("gauge").for_label(2))
+                    .histogram(Histogram::new("histogram").for_label(2)),
+            ),
+        );
+
+        tx.observed_one_now(1)
+            .observed_now(1, 4)
+            .observed_one_value_now(2, 42);
+        processor.process(100, ProcessingStrategy::ProcessAll);
+
+        let mut snapshot = Snapshot::default();
+        processor.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("panel/count"),
+            FindItem::Found(&ItemKind::UInt(5))
+        );
+        assert_eq!(
+            snapshot.find("panel/gauge"),
+            FindItem::Found(&ItemKind::Int(42))
+        );
+        assert_eq!(
+            snapshot.find("panel/histogram/sum"),
+            FindItem::Found(&ItemKind::Int(42))
+        );
+    }
+
+    #[test]
+    fn a_removed_processor_is_no_longer_in_the_snapshot() {
+        let mut mount = ProcessorMount::default();
+        let mut transmitters = Vec::new();
+        for name in &["a", "b"] {
+            let (tx, mut processor): (TelemetryTransmitter<i32>, _) =
+                TelemetryProcessor::new_pair(*name);
+            processor.add_cockpit(
+                Cockpit::without_name()
+                    .panel(Panel::named(1, "panel").counter(Counter::new("count").for_label(1))),
+            );
+            tx.observed_one_now(1);
+            transmitters.push(tx);
+            mount.add_processor(processor);
+        }
+        mount.process(100, ProcessingStrategy::ProcessAll);
+
+        assert!(mount.remove_processor("c").is_none());
+        let removed = mount.remove_processor("a").unwrap();
+        assert_eq!(removed.name(), Some("a"));
+        assert_eq!(mount.processors().len(), 1);
+
+        let mut snapshot = Snapshot::default();
+        mount.put_snapshot(&mut snapshot, false);
+        assert_eq!(snapshot.find("a/panel/count"), FindItem::NotFound);
+        assert_eq!(
+            snapshot.find("b/panel/count"),
+            FindItem::Found(&ItemKind::UInt(1))
+        );
+    }
+
+    #[test]
+    fn observation_ages_are_reported() {
+        let clock = ManualOffsetClock::default();
+        let (tx, processor): (TelemetryTransmitter<i32>, _) =
+            TelemetryProcessor::new_pair_without_name();
+        let mut processor = processor
+            .clock(clock.clone())
+            .observation_age_stats_enabled(true);
+
+        clock.advance_n_seconds(120);
+        tx.observed_one(1, clock.seconds_in_the_past(10))
+            .observed_one(1, clock.seconds_in_the_past(30))
+            .observed_one(1, clock.seconds_in_the_past(90));
+
+        processor.process(
+            100,
+            ProcessingStrategy::DropOlderThan(Duration::from_secs(60)),
+        );
+
+        let mut snapshot = Snapshot::default();
+        processor.put_exported_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("_observation_age_max_ms"),
+            FindItem::Found(&ItemKind::UInt(30_000))
+        );
+        assert_eq!(
+            snapshot.find("_observation_age_mean_ms"),
+            FindItem::Found(&ItemKind::UInt(20_000))
+        );
+        assert_eq!(
+            snapshot.find("_type__observation_age_max_ms"),
+            FindItem::Found(&ItemKind::Text("gauge".to_string()))
+        );
+
+        tx.observed_one(1, clock.seconds_in_the_past(5));
+        processor.process(
+            100,
+            ProcessingStrategy::DropOlderThan(Duration::from_secs(60)),
+        );
+
+        let mut snapshot = Snapshot::default();
+        processor.put_exported_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("_observation_age_max_ms"),
+            FindItem::Found(&ItemKind::UInt(5_000))
+        );
+        assert_eq!(
+            snapshot.find("_observation_age_mean_ms"),
+            FindItem::Found(&ItemKind::UInt(5_000))
+        );
+    }
+
+    #[test]
+    fn components_can_be_added_via_the_transmitter() {
+        let (tx, mut processor): (TelemetryTransmitter<i32>, _) =
+            TelemetryProcessor::new_pair_without_name();
+
+        tx.add_cockpit(
+            Cockpit::new("cockpit")
+                .panel(Panel::named(1, "panel").counter(Counter::new("count").for_label(1))),
+        )
+        .add_panel_to_cockpit(
+            "cockpit".to_string(),
+            Panel::named(1, "added").counter(Counter::new("count").for_label(1)),
+        )
+        .add_handler(Counter::new("handler").for_label(1))
+        .observed_one_now(1)
+        .observed_one_now(1);
+
+        let outcome = processor.process(100, ProcessingStrategy::ProcessAll);
+        assert_eq!(outcome.processed, 5);
+
+        let mut snapshot = Snapshot::default();
+        processor.put_snapshot(&mut snapshot, false);
+        for path in &["cockpit/panel/count", "cockpit/added/count", "handler"] {
+            assert_eq!(
+                snapshot.find(path),
+                FindItem::Found(&ItemKind::UInt(2)),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn the_observation_mapper_rewrites_observations() {
+        let (tx, processor): (TelemetryTransmitter<i32>, _) =
+            TelemetryProcessor::new_pair_without_name();
+        let mut processor =
+            processor
+                .processing_stats_enabled(true)
+                .observation_mapper(|observation| match observation {
+                    Observation::ObservedOneValue {
+                        label: 1,
+                        value,
+                        timestamp,
+                    } => Some(Observation::ObservedOneValue {
+                        label: 1,
+                        value: (value.convert_to_i64()? * 10).into(),
+                        timestamp,
+                    }),
+                    Observation::ObservedOne { label: 2, .. } => None,
+                    other => Some(other),
+                });
+        processor.add_cockpit(Cockpit::without_name().panel(
+            Panel::named(1, "panel").gauge(crate::instruments::Gauge::new("gauge").for_label(1)),
+        ));
+
+        tx.observed_one_value_now(1, 5).observed_one_now(2);
+        let outcome = processor.process(100, ProcessingStrategy::ProcessAll);
+        assert_eq!(outcome.processed, 1);
+        assert_eq!(outcome.dropped, 1);
+
+        let mut snapshot = Snapshot::default();
+        processor.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("panel/gauge"),
+            FindItem::Found(&ItemKind::Int(50))
+        );
+    }
+
+    #[test]
+    fn the_observation_mapper_can_run_before_deciding() {
+        let stale = Instant::now() - Duration::from_secs(120);
+        let refresh = |observation: Observation<i32>| match observation {
+            Observation::ObservedOne { label, .. } => Some(Observation::ObservedOne {
+                label,
+                timestamp: Instant::now(),
+            }),
+            other => Some(other),
+        };
+        let strategy = ProcessingStrategy::DropOlderThan(Duration::from_secs(60));
+
+        for &before in &[false, true] {
+            let (tx, processor): (TelemetryTransmitter<i32>, _) =
+                TelemetryProcessor::new_pair_without_name();
+            let mut processor = processor
+                .observation_mapper(refresh)
+                .map_before_deciding(before);
+
+            tx.observed_one(1, stale);
+            let outcome = processor.process(100, strategy);
+            assert_eq!(outcome.processed, if before { 1 } else { 0 });
+        }
+    }
+
+    #[test]
+    fn sends_fail_once_the_processor_is_dropped() {
+        let (tx, processor): (TelemetryTransmitter<i32>, _) =
+            TelemetryProcessor::new_pair_without_name();
+        let synced = tx.synced();
+
+        tx.observed_one_now(1);
+        assert_eq!(tx.failed_sends(), 0);
+
+        drop(processor);
+
+        tx.observed_one_now(1).observed_now(1, 2);
+        synced.observed_one_now(1);
+        tx.clone().add_cockpit(Cockpit::without_name());
+
+        assert_eq!(tx.failed_sends(), 4);
+        assert_eq!(synced.failed_sends(), 4);
+    }
+
+    #[test]
+    fn queued_messages_are_counted_when_drained_on_drop() {
+        let (tx, processor): (TelemetryTransmitter<i32>, _) =
+            TelemetryProcessor::new_pair_without_name();
+        let mut processor = processor.drain_on_drop(true);
+
+        tx.observed_one_now(1);
+        processor.process(100, ProcessingStrategy::ProcessAll);
+
+        tx.observed_one_now(1)
+            .observed_one_now(1)
+            .add_cockpit(Cockpit::without_name());
+        tx.synced().observed_one_now(1);
+        drop(processor);
+
+        assert_eq!(tx.discarded_on_drop(), 4);
+        assert_eq!(tx.synced().discarded_on_drop(), 4);
+    }
+
+    #[test]
+    fn draining_on_drop_finishes_while_observations_are_transmitted() {
+        let (tx, processor): (TelemetryTransmitter<i32>, _) =
+            TelemetryProcessor::new_pair_without_name();
+        let processor = processor.drain_on_drop(true);
+
+        tx.observed_one_now(1);
+        let sender = {
+            let tx = tx.clone();
+            std::thread::spawn(
+                move || while tx.try_transmit(Observation::observed_one_now(1)).is_ok() {},
+            )
+        };
+        while processor.receiver.len() < 1_000 {}
+        drop(processor);
+        sender.join().unwrap();
+
+        assert!(tx.discarded_on_drop() >= 1);
+    }
+
+    #[test]
+    fn queued_messages_are_not_counted_by_default() {
+        let (tx, processor): (TelemetryTransmitter<i32>, _) =
+            TelemetryProcessor::new_pair_without_name();
+
+        tx.observed_one_now(1);
+        drop(processor);
+
+        assert_eq!(tx.discarded_on_drop(), 0);
+    }
+
+    #[test]
+    fn a_mount_uses_the_default_strategies_of_its_processors() {
+        let stale = Instant::now() - Duration::from_secs(120);
+        let mut mount = ProcessorMount::new("mount");
+
+        let (audit_tx, audit): (TelemetryTransmitter<i32>, _) =
+            TelemetryProcessor::new_pair("audit");
+        let audit = audit
+            .cockpit(
+                Cockpit::without_name()
+                    .panel(Panel::named(1, "panel").counter(Counter::new("count").for_label(1))),
+            )
+            .default_strategy(ProcessingStrategy::ProcessAll);
+        mount.add_processor(audit);
+
+        let (metrics_tx, metrics): (TelemetryTransmitter<i32>, _) =
+            TelemetryProcessor::new_pair("metrics");
+        let metrics = metrics
+            .cockpit(
+                Cockpit::without_name()
+                    .panel(Panel::named(1, "panel").counter(Counter::new("count").for_label(1))),
+            )
+            .default_strategy(ProcessingStrategy::DropOlderThan(Duration::from_secs(60)));
+        mount.add_processor(metrics);
+
+        for tx in &[audit_tx, metrics_tx] {
+            tx.observed_one(1, stale).observed_one_now(1);
+        }
+
+        let outcome = mount.process(100, ProcessingStrategy::DropAll);
+        assert_eq!(outcome.processed, 3);
+        assert_eq!(outcome.dropped, 1);
+
+        let mut snapshot = Snapshot::default();
+        mount.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("mount/audit/panel/count"),
+            FindItem::Found(&ItemKind::UInt(2))
+        );
+        assert_eq!(
+            snapshot.find("mount/metrics/panel/count"),
+            FindItem::Found(&ItemKind::UInt(1))
+        );
+    }
+
+    #[test]
+    fn the_flatten_policy_resolves_colliding_unnamed_processors() {
+        fn mount_with(policy: FlattenPolicy) -> ProcessorMount {
+            let mut mount = ProcessorMount::new("root").flatten_policy(policy);
+            for _ in 0..2 {
+                let (tx, processor): (TelemetryTransmitter<i32>, _) =
+                    TelemetryProcessor::new_pair_without_name();
+                let processor =
+                    processor.cockpit(Cockpit::without_name().panel(
+                        Panel::named(1, "panel").counter(Counter::new("count").for_label(1)),
+                    ));
+                tx.observed_one_now(1);
+                mount.add_processor(processor);
+            }
+            mount.process(100, ProcessingStrategy::ProcessAll);
+            mount
+        }
+
+        let mut snapshot = Snapshot::default();
+        mount_with(FlattenPolicy::Flatten).put_snapshot(&mut snapshot, false);
+        assert_eq!(snapshot.duplicate_paths(), vec!["root/panel".to_string()]);
+
+        let mut snapshot = Snapshot::default();
+        mount_with(FlattenPolicy::PrefixWithIndex).put_snapshot(&mut snapshot, false);
+        assert!(snapshot.duplicate_paths().is_empty());
+        assert_eq!(
+            snapshot.find("root/0/panel/count"),
+            FindItem::Found(&ItemKind::UInt(1))
+        );
+        assert_eq!(
+            snapshot.find("root/1/panel/count"),
+            FindItem::Found(&ItemKind::UInt(1))
+        );
+
+        let mut snapshot = Snapshot::default();
+        mount_with(FlattenPolicy::Reject).put_snapshot(&mut snapshot, false);
+        assert_eq!(snapshot.find("root/panel/count"), FindItem::NotFound);
+    }
+}
@@ -0,0 +1,229 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::cockpit::Cockpit;
+use crate::instruments::Panel;
+use crate::processor::Priority;
+use crate::{HandlesObservations, Observation, TransmitsTelemetryData};
+
+/// Transmits only every `n`th `Observation` to reduce the traffic
+/// to the backend for observations made at very high frequencies.
+///
+/// To keep totals approximately correct the count of
+/// a transmitted `Observation::Observed` or `Observation::ObservedOne`
+/// is multiplied by `n`. So is the count of an `Observation::ObservedValues`.
+/// An `Observation::ObservedOneValue` is transmitted as an
+/// `Observation::ObservedValues` with a count of `n`. So a `Counter` or
+/// `Meter` will show an estimate of the real number of observations and
+/// a `Histogram` records each sampled value `n` times.
+///
+/// The sampling is deterministic: The first `Observation` is transmitted
+/// followed by every `n`th `Observation`.
+///
+/// # Example
+///
+/// ```
+/// use metrix::*;
+/// use metrix::processor::TelemetryProcessor;
+///
+/// let (tx, _processor) = TelemetryProcessor::<&str>::new_pair_without_name();
+/// let sampling = SamplingTransmitter::new(tx, 10);
+///
+/// // Will be transmitted as `Observed { count: 10, .. }`
+/// sampling.observed_one_now("request");
+///
+/// // Skipped
+/// sampling.observed_one_now("request");
+/// ```
+pub struct SamplingTransmitter<T> {
+    inner: T,
+    rate: u64,
+    seen: AtomicU64,
+}
+
+impl<T> SamplingTransmitter<T> {
+    /// Creates a new `SamplingTransmitter` which transmits
+    /// every `rate`th `Observation` via `inner`.
+    ///
+    /// # Panics
+    ///
+    /// If `rate` is 0.
+    pub fn new(inner: T, rate: u64) -> Self {
+        if rate == 0 {
+            panic!("the sampling rate must be at least 1");
+        }
+
+        SamplingTransmitter {
+            inner,
+            rate,
+            seen: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the sampling rate
+    pub fn rate(&self) -> u64 {
+        self.rate
+    }
+
+    /// Returns the wrapped transmitter
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    // `is_multiple_of` is too recent
+    #[allow(clippy::manual_is_multiple_of)]
+    fn next_is_sampled(&self) -> bool {
+        self.seen.fetch_add(1, Ordering::Relaxed) % self.rate == 0
+    }
+
+    fn scale<L>(&self, observation: Observation<L>) -> Observation<L> {
+        match observation {
+            Observation::Observed {
+                label,
+                count,
+                timestamp,
+            } => Observation::Observed {
+                label,
+                count: count.saturating_mul(self.rate),
+                timestamp,
+            },
+            Observation::ObservedOne { label, timestamp } => Observation::Observed {
+                label,
+                count: self.rate,
+                timestamp,
+            },
+            Observation::ObservedValues {
+                label,
+                value,
+                count,
+                timestamp,
+            } => Observation::ObservedValues {
+                label,
+                value,
+                count: count.saturating_mul(self.rate),
+                timestamp,
+            },
+            Observation::ObservedOneValue {
+                label,
+                value,
+                timestamp,
+            } => Observation::ObservedValues {
+                label,
+                value,
+                count: self.rate,
+                timestamp,
+            },
+        }
+    }
+}
+
+impl<T: Clone> Clone for SamplingTransmitter<T> {
+    fn clone(&self) -> Self {
+        SamplingTransmitter {
+            inner: self.inner.clone(),
+            rate: self.rate,
+            seen: AtomicU64::new(self.seen.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+impl<L, T> TransmitsTelemetryData<L> for SamplingTransmitter<T>
+where
+    T: TransmitsTelemetryData<L>,
+{
+    fn transmit(&self, observation: Observation<L>) -> &Self {
+        if self.next_is_sampled() {
+            self.inner.transmit(self.scale(observation));
+        }
+        self
+    }
+
+    fn transmit_prioritized(&self, observation: Observation<L>, priority: Priority) -> &Self {
+        if self.next_is_sampled() {
+            self.inner
+                .transmit_prioritized(self.scale(observation), priority);
+        }
+        self
+    }
+
+    fn transmit_many(&self, observations: Vec<Observation<L>>) -> &Self {
+        let sampled: Vec<_> = observations
+            .into_iter()
+            .filter(|_| self.next_is_sampled())
+            .map(|obs| self.scale(obs))
+            .collect();
+        if !sampled.is_empty() {
+            self.inner.transmit_many(sampled);
+        }
+        self
+    }
+
+    fn add_handler<H: HandlesObservations<Label = L>>(&self, handler: H) -> &Self
+    where
+        L: Send + 'static,
+    {
+        self.inner.add_handler(handler);
+        self
+    }
+
+    fn add_cockpit(&self, cockpit: Cockpit<L>) -> &Self {
+        self.inner.add_cockpit(cockpit);
+        self
+    }
+
+    fn add_panel_to_cockpit(&self, cockpit_name: String, panel: Panel<L>) -> &Self {
+        self.inner.add_panel_to_cockpit(cockpit_name, panel);
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::instruments::{Counter, Gauge, Histogram};
+    use crate::processor::{ProcessesTelemetryMessages, ProcessingStrategy, TelemetryProcessor};
+    use crate::snapshot::{FindItem, ItemKind, Snapshot};
+    use crate::PutsSnapshot;
+
+    #[test]
+    fn transmits_every_nth_observation_scaled() {
+        let (tx, mut processor) = TelemetryProcessor::new_pair_without_name();
+        processor.add_cockpit(
+            Cockpit::without_name().panel(
+                Panel::named(1, "panel")
+                    .counter(Counter::new("count"))
+                    .gauge(Gauge::new("last"))
+                    .histogram(Histogram::new("values")),
+            ),
+        );
+
+        let sampling = SamplingTransmitter::new(tx, 10);
+        for _ in 0..1_000 {
+            sampling.observed_one_now(1);
+        }
+        for v in 0..95 {
+            sampling.observed_one_value_now(1, v);
+        }
+
+        let outcome = processor.process(10_000, ProcessingStrategy::ProcessAll);
+        assert_eq!(outcome.processed, 110);
+
+        let mut snapshot = Snapshot::default();
+        processor.put_snapshot(&mut snapshot, false);
+        assert_eq!(
+            snapshot.find("panel/count"),
+            FindItem::Found(&ItemKind::UInt(1_100))
+        );
+        assert_eq!(
+            snapshot.find("panel/values/count"),
+            FindItem::Found(&ItemKind::UInt(100))
+        );
+        assert_eq!(
+            snapshot.find("panel/last"),
+            FindItem::Found(&ItemKind::Int(90))
+        );
+    }
+}
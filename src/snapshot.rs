@@ -1,9 +1,12 @@
 //! Pulling data from the backend for monitoring
+use std::collections::BTreeMap;
 use std::fmt;
 use std::time::Duration;
 
 use json::{stringify, stringify_pretty, JsonValue};
 
+use crate::util::UNIT_FIELD_LABEL;
+
 /// A `Snapshot` which contains measured values
 /// at a point in time.
 #[derive(Debug, Clone, PartialEq)]
@@ -95,6 +98,266 @@ impl Snapshot {
         self.find_with_separator(path, '/')
     }
 
+    /// Returns the item on a path with a `.` as a separator,
+    /// e.g. `requests.count`.
+    ///
+    /// Same as `find` but with dots instead of slashes and `None`
+    /// if there is no such item.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use metrix::snapshot::*;
+    ///
+    /// let mut requests = Snapshot::default();
+    /// requests.push("count", ItemKind::UInt(42));
+    /// let mut snapshot = Snapshot::default();
+    /// snapshot.push("requests", ItemKind::Snapshot(requests));
+    ///
+    /// assert_eq!(snapshot.get("requests.count"), Some(&ItemKind::UInt(42)));
+    /// assert_eq!(snapshot.get("requests.mean"), None);
+    /// ```
+    pub fn get(&self, path: &str) -> Option<&ItemKind> {
+        match self.find_with_separator(path, '.') {
+            FindItem::Found(item) => Some(item),
+            FindItem::NotFound => None,
+        }
+    }
+
+    /// Creates a `Snapshot` with the changes since `previous`.
+    ///
+    /// Unsigned integers are considered counters and the value of
+    /// `previous` on the same path is subtracted. If the value went down
+    /// (e.g. the counter was reset) the current value is taken.
+    /// All other values are taken as they are from `self`.
+    ///
+    /// Paths only contained in `self` are taken as they are while
+    /// paths only contained in `previous` are omitted.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use metrix::snapshot::*;
+    ///
+    /// let mut previous = Snapshot::default();
+    /// previous.push("requests", ItemKind::UInt(10));
+    /// previous.push("in_flight", ItemKind::Int(3));
+    ///
+    /// let mut current = Snapshot::default();
+    /// current.push("requests", ItemKind::UInt(15));
+    /// current.push("in_flight", ItemKind::Int(1));
+    ///
+    /// let diff = current.diff(&previous);
+    ///
+    /// assert_eq!(diff.find("requests"), FindItem::Found(&ItemKind::UInt(5)));
+    /// assert_eq!(diff.find("in_flight"), FindItem::Found(&ItemKind::Int(1)));
+    /// ```
+    pub fn diff(&self, previous: &Snapshot) -> Snapshot {
+        let items = self
+            .items
+            .iter()
+            .map(|(name, item)| {
+                let previous_item = previous.items.iter().find(|(n, _)| n == name).map(|x| &x.1);
+                let diffed = match (item, previous_item) {
+                    (ItemKind::Snapshot(current), Some(ItemKind::Snapshot(previous))) => {
+                        ItemKind::Snapshot(current.diff(previous))
+                    }
+                    (ItemKind::UInt(current), Some(ItemKind::UInt(previous))) => {
+                        if current >= previous {
+                            ItemKind::UInt(current - previous)
+                        } else {
+                            ItemKind::UInt(*current)
+                        }
+                    }
+                    (item, _) => item.clone(),
+                };
+                (name.clone(), diffed)
+            })
+            .collect();
+
+        Snapshot { items }
+    }
+
+    /// Combines `other` into `self`.
+    ///
+    /// Nested `Snapshot`s on the same path are merged recursively.
+    /// For all other values on the same path the value of `other` wins.
+    /// Paths only contained in `other` are appended.
+    pub fn merge(&mut self, other: Snapshot) {
+        for (name, item) in other.items {
+            if let Some(idx) = self.items.iter().position(|(n, _)| *n == name) {
+                match (&mut self.items[idx].1, item) {
+                    (ItemKind::Snapshot(mine), ItemKind::Snapshot(theirs)) => mine.merge(theirs),
+                    (mine, theirs) => *mine = theirs,
+                }
+            } else {
+                self.items.push((name, item));
+            }
+        }
+    }
+
+    /// Returns a copy of the nested `Snapshot` at the given path
+    /// where the segments are separated by dots.
+    ///
+    /// An empty path returns a copy of the whole `Snapshot`.
+    /// Returns `None` if there is nothing at the path or the item
+    /// at the path is not a `Snapshot`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use metrix::snapshot::*;
+    ///
+    /// let mut tenant = Snapshot::default();
+    /// tenant.push("requests", ItemKind::UInt(3));
+    ///
+    /// let mut tenants = Snapshot::default();
+    /// tenants.push("a", ItemKind::Snapshot(tenant.clone()));
+    ///
+    /// let mut snapshot = Snapshot::default();
+    /// snapshot.push("tenants", ItemKind::Snapshot(tenants));
+    ///
+    /// assert_eq!(snapshot.subtree("tenants.a"), Some(tenant));
+    /// assert_eq!(snapshot.subtree("tenants.a.requests"), None);
+    /// assert_eq!(snapshot.subtree("tenants.b"), None);
+    /// ```
+    pub fn subtree(&self, path: &str) -> Option<Snapshot> {
+        if path.is_empty() {
+            return Some(self.clone());
+        }
+
+        let mut current = self;
+        for segment in path.split('.') {
+            current = match current.items.iter().find(|(n, _)| n == segment) {
+                Some((_, ItemKind::Snapshot(snapshot))) => snapshot,
+                _ => return None,
+            };
+        }
+        Some(current.clone())
+    }
+
+    /// Returns a copy of this `Snapshot` which only contains the
+    /// values whose full path matches the predicate.
+    ///
+    /// The predicate is called with the dot separated path of every
+    /// value which is not a `Snapshot`, e.g. `tenants.a.requests`.
+    /// Nested `Snapshot`s left empty are omitted.
+    pub fn filter<P: Fn(&str) -> bool>(&self, predicate: P) -> Snapshot {
+        self.filter_internal("", &predicate)
+    }
+
+    fn filter_internal<P: Fn(&str) -> bool>(&self, prefix: &str, predicate: &P) -> Snapshot {
+        let items = self
+            .items
+            .iter()
+            .filter_map(|(name, item)| {
+                let path = join_path(prefix, name, ".");
+                match item {
+                    ItemKind::Snapshot(snapshot) => {
+                        let filtered = snapshot.filter_internal(&path, predicate);
+                        if filtered.items.is_empty() {
+                            None
+                        } else {
+                            Some((name.clone(), ItemKind::Snapshot(filtered)))
+                        }
+                    }
+                    item if predicate(&path) => Some((name.clone(), item.clone())),
+                    _ => None,
+                }
+            })
+            .collect();
+
+        Snapshot { items }
+    }
+
+    /// Returns all numeric and boolean values keyed by their dot
+    /// separated path, e.g. `tenants.a.requests`.
+    ///
+    /// Booleans become 1.0 for `true` and 0.0 for `false`. Texts are
+    /// skipped. If a path occurs more than once the last value wins.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use metrix::snapshot::{ItemKind, Snapshot};
+    ///
+    /// let mut tenant = Snapshot::default();
+    /// tenant.push("requests", ItemKind::UInt(3));
+    /// let mut snapshot = Snapshot::default();
+    /// snapshot.push("tenant", ItemKind::Snapshot(tenant));
+    ///
+    /// let flat = snapshot.to_flat_map();
+    /// assert_eq!(flat.get("tenant.requests"), Some(&3.0));
+    /// ```
+    pub fn to_flat_map(&self) -> BTreeMap<String, f64> {
+        self.to_flat_map_with_separator(".")
+    }
+
+    /// Same as `to_flat_map` but with a configurable separator.
+    pub fn to_flat_map_with_separator(&self, separator: &str) -> BTreeMap<String, f64> {
+        self.flatten(separator)
+            .into_iter()
+            .filter_map(|(path, item)| match item {
+                ItemKind::Boolean(v) => Some((path, if *v { 1.0 } else { 0.0 })),
+                item => item.as_f64().map(|v| (path, v)),
+            })
+            .collect()
+    }
+
+    /// Returns all values which are not a nested `Snapshot` with
+    /// their paths joined by `separator`, e.g. `.` for Graphite or
+    /// `_` for Prometheus.
+    ///
+    /// The values are in the order of the items.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use metrix::snapshot::*;
+    ///
+    /// let inner = Snapshot {
+    ///     items: vec![("count".to_string(), ItemKind::UInt(42))],
+    /// };
+    /// let snapshot = Snapshot {
+    ///     items: vec![("requests".to_string(), ItemKind::Snapshot(inner))],
+    /// };
+    ///
+    /// let flat = snapshot.flatten(".");
+    ///
+    /// assert_eq!(flat, vec![("requests.count".to_string(), &ItemKind::UInt(42))]);
+    /// ```
+    pub fn flatten(&self, separator: &str) -> Vec<(String, &ItemKind)> {
+        let mut flat = Vec::new();
+        walk_snapshot(self, |path, item| {
+            let path = path.iter().fold(String::new(), |prefix, name| {
+                join_path(&prefix, name, separator)
+            });
+            flat.push((path, item))
+        });
+        flat
+    }
+
+    /// Returns the paths separated by `/` which occur more than once.
+    ///
+    /// Every such path is only returned once.
+    pub(crate) fn duplicate_paths(&self) -> Vec<String> {
+        let mut duplicates = Vec::new();
+        self.put_duplicate_paths("", &mut duplicates);
+        duplicates
+    }
+
+    fn put_duplicate_paths(&self, prefix: &str, duplicates: &mut Vec<String>) {
+        for (idx, (name, item)) in self.items.iter().enumerate() {
+            let path = join_path(prefix, name, "/");
+            if self.items[..idx].iter().any(|(n, _)| n == name) && !duplicates.contains(&path) {
+                duplicates.push(path.clone());
+            }
+            if let ItemKind::Snapshot(snapshot) = item {
+                snapshot.put_duplicate_paths(&path, duplicates);
+            }
+        }
+    }
+
     /// Output JSON with default settings.
     pub fn to_default_json(&self) -> String {
         self.to_json_internal(&JsonConfig::default())
@@ -105,6 +368,86 @@ impl Snapshot {
         self.to_json_internal(config)
     }
 
+    /// Renders the `Snapshot` as an indented tree for humans, e.g.
+    /// for debugging or a CLI.
+    ///
+    /// Each value is a `name: value` line and the items of a nested
+    /// `Snapshot` are indented by two more spaces below a `name:` line.
+    /// Known units (`_unit_[name]` or `[name]_unit`) are appended to the
+    /// item they belong to instead of being rendered on their own.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use metrix::snapshot::*;
+    ///
+    /// let mut latency = Snapshot::default();
+    /// latency.push("count", ItemKind::UInt(3));
+    ///
+    /// let mut snapshot = Snapshot::default();
+    /// snapshot.push("latency", ItemKind::Snapshot(latency));
+    /// snapshot.push("_unit_latency", ItemKind::Text("milliseconds".to_string()));
+    ///
+    /// assert_eq!(
+    ///     snapshot.to_pretty_string(),
+    ///     "latency [milliseconds]:\n  count: 3\n"
+    /// );
+    /// ```
+    pub fn to_pretty_string(&self) -> String {
+        let mut out = String::new();
+        self.put_pretty(0, &mut out);
+        out
+    }
+
+    fn put_pretty(&self, depth: usize, out: &mut String) {
+        let indent = "  ".repeat(depth);
+        for (name, item) in &self.items {
+            if self.is_unit_of_sibling(name) {
+                continue;
+            }
+
+            let unit = self.unit_of(name);
+            match item {
+                ItemKind::Snapshot(inner) => {
+                    if let Some(unit) = unit {
+                        out.push_str(&format!("{}{} [{}]:\n", indent, name, unit));
+                    } else {
+                        out.push_str(&format!("{}{}:\n", indent, name));
+                    }
+                    inner.put_pretty(depth + 1, out);
+                }
+                value => {
+                    if let Some(unit) = unit {
+                        out.push_str(&format!("{}{}: {} {}\n", indent, name, value, unit));
+                    } else {
+                        out.push_str(&format!("{}{}: {}\n", indent, name, value));
+                    }
+                }
+            }
+        }
+    }
+
+    fn unit_of(&self, name: &str) -> Option<&str> {
+        let candidates = [
+            format!("{}_{}", UNIT_FIELD_LABEL, name),
+            format!("{}_unit", name),
+        ];
+        self.items.iter().find_map(|(n, item)| match item {
+            ItemKind::Text(unit) if candidates.contains(n) => Some(unit.as_str()),
+            _ => None,
+        })
+    }
+
+    fn is_unit_of_sibling(&self, name: &str) -> bool {
+        let owner = name
+            .strip_prefix(&format!("{}_", UNIT_FIELD_LABEL))
+            .or_else(|| name.strip_suffix("_unit"));
+        match owner {
+            Some(owner) => self.items.iter().any(|(n, _)| n == owner),
+            None => false,
+        }
+    }
+
     fn to_json_internal(&self, config: &JsonConfig) -> String {
         let data = self.to_json_value(config);
 
@@ -211,6 +554,121 @@ where
     }
 }
 
+/// Visits the items of a `Snapshot` while `visit_snapshot` walks
+/// through it.
+///
+/// The path passed to the methods contains the names of the nested
+/// `Snapshot`s leading to the item followed by the name of the item.
+/// It is never empty. `parent` is the `Snapshot` containing the item
+/// so that its siblings, e.g. the description, can be looked up.
+pub trait VisitsSnapshot<'a> {
+    /// Called with a nested `Snapshot` before its items are visited.
+    ///
+    /// If `false` is returned the items are not visited. This allows
+    /// to put a group of values like a histogram at once.
+    fn enter(&mut self, path: &[&'a str], parent: &'a Snapshot, snapshot: &'a Snapshot) -> bool {
+        let _ = (path, parent, snapshot);
+        true
+    }
+
+    /// Called after the items of a nested `Snapshot` have been
+    /// visited. Not called if `enter` returned `false`.
+    fn leave(&mut self, path: &[&'a str]) {
+        let _ = path;
+    }
+
+    /// Called with every item which is not a nested `Snapshot`.
+    fn value(&mut self, path: &[&'a str], parent: &'a Snapshot, item: &'a ItemKind);
+}
+
+/// Walks through the given `Snapshot` in the order of its items
+/// and calls the `visitor` for every item.
+///
+/// This is the traversal all exporters build on.
+pub fn visit_snapshot<'a, V>(snapshot: &'a Snapshot, visitor: &mut V)
+where
+    V: VisitsSnapshot<'a> + ?Sized,
+{
+    let mut path = Vec::new();
+    visit(snapshot, &mut path, visitor);
+}
+
+fn visit<'a, V>(snapshot: &'a Snapshot, path: &mut Vec<&'a str>, visitor: &mut V)
+where
+    V: VisitsSnapshot<'a> + ?Sized,
+{
+    for (name, item) in &snapshot.items {
+        path.push(name);
+        match item {
+            ItemKind::Snapshot(ref inner) => {
+                if visitor.enter(path, snapshot, inner) {
+                    visit(inner, path, visitor);
+                    visitor.leave(path);
+                }
+            }
+            value => visitor.value(path, snapshot, value),
+        }
+        path.pop();
+    }
+}
+
+/// Calls `f` with every value of the given `Snapshot` which is
+/// not a nested `Snapshot` itself.
+///
+/// The path contains the names of the nested `Snapshot`s leading
+/// to the value followed by the name of the value. It is never empty.
+///
+/// # Example
+///
+/// ```
+/// use metrix::snapshot::walk_snapshot;
+/// use metrix::snapshot::*;
+///
+/// let inner = Snapshot {
+///     items: vec![("count".to_string(), ItemKind::UInt(42))],
+/// };
+///
+/// let snapshot = Snapshot {
+///     items: vec![
+///         ("requests".to_string(), ItemKind::Snapshot(inner)),
+///         ("up".to_string(), ItemKind::Boolean(true)),
+///     ],
+/// };
+///
+/// let mut paths = Vec::new();
+/// walk_snapshot(&snapshot, |path, _item| paths.push(path.join("/")));
+///
+/// assert_eq!(paths, vec!["requests/count", "up"]);
+/// ```
+pub fn walk_snapshot<'a, F>(snapshot: &'a Snapshot, f: F)
+where
+    F: FnMut(&[&'a str], &'a ItemKind),
+{
+    visit_snapshot(snapshot, &mut Values(f));
+}
+
+struct Values<F>(F);
+
+impl<'a, F> VisitsSnapshot<'a> for Values<F>
+where
+    F: FnMut(&[&'a str], &'a ItemKind),
+{
+    fn value(&mut self, path: &[&'a str], _parent: &'a Snapshot, item: &'a ItemKind) {
+        (self.0)(path, item)
+    }
+}
+
+/// Appends `name` to `path` with `separator` in between.
+///
+/// If `path` is empty the result is `name`.
+pub fn join_path(path: &str, name: &str, separator: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}{}{}", path, separator, name)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum FindItem<'a> {
     Found(&'a ItemKind),
@@ -335,6 +793,42 @@ pub enum ItemKind {
 }
 
 impl ItemKind {
+    /// Returns the value if it is an `UInt` or a non negative `Int`.
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            ItemKind::UInt(v) => Some(v),
+            ItemKind::Int(v) if v >= 0 => Some(v as u64),
+            _ => None,
+        }
+    }
+
+    /// Returns the value if it is an `Int` or an `UInt` which fits into an `i64`.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            ItemKind::Int(v) => Some(v),
+            ItemKind::UInt(v) if v <= i64::MAX as u64 => Some(v as i64),
+            _ => None,
+        }
+    }
+
+    /// Returns the value if it is a `Float`, an `Int` or an `UInt`.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            ItemKind::Float(v) => Some(v),
+            ItemKind::Int(v) => Some(v as f64),
+            ItemKind::UInt(v) => Some(v as f64),
+            _ => None,
+        }
+    }
+
+    /// Returns the value if it is a `Boolean`.
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            ItemKind::Boolean(v) => Some(v),
+            _ => None,
+        }
+    }
+
     fn to_json_value(&self, config: &JsonConfig) -> JsonValue {
         match *self {
             ItemKind::Text(ref v) => v.clone().into(),
@@ -486,3 +980,222 @@ impl From<Snapshot> for ItemKind {
         ItemKind::Snapshot(what)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn typed_accessors() {
+        assert_eq!(ItemKind::UInt(1).as_u64(), Some(1));
+        assert_eq!(ItemKind::Int(1).as_u64(), Some(1));
+        assert_eq!(ItemKind::Int(-1).as_u64(), None);
+        assert_eq!(ItemKind::UInt(u64::MAX).as_i64(), None);
+        assert_eq!(ItemKind::Int(-1).as_i64(), Some(-1));
+        assert_eq!(ItemKind::Int(-1).as_f64(), Some(-1.0));
+        assert_eq!(ItemKind::Boolean(true).as_bool(), Some(true));
+        assert_eq!(ItemKind::Boolean(true).as_u64(), None);
+        assert_eq!(ItemKind::Text("1".to_string()).as_f64(), None);
+    }
+
+    #[test]
+    fn read_a_numeric_value_on_a_dotted_path() {
+        let mut requests = Snapshot::default();
+        requests.push("count", ItemKind::UInt(42));
+        requests.push("mean", ItemKind::Float(1.5));
+        let mut snapshot = Snapshot::default();
+        snapshot.push("requests", ItemKind::Snapshot(requests));
+
+        let count = snapshot.get("requests.count").and_then(ItemKind::as_u64);
+        assert_eq!(count, Some(42));
+
+        let mean = snapshot.get("requests.mean").and_then(ItemKind::as_f64);
+        assert_eq!(mean, Some(1.5));
+
+        assert_eq!(snapshot.get("requests.unknown"), None);
+    }
+
+    #[test]
+    fn diff_subtracts_counters_and_keeps_gauges() {
+        let mut previous_panel = Snapshot::default();
+        previous_panel.push("count", ItemKind::UInt(10));
+        previous_panel.push("gauge", ItemKind::Int(7));
+        previous_panel.push("gone", ItemKind::UInt(1));
+        let mut previous = Snapshot::default();
+        previous.push("panel", ItemKind::Snapshot(previous_panel));
+
+        let mut current_panel = Snapshot::default();
+        current_panel.push("count", ItemKind::UInt(15));
+        current_panel.push("gauge", ItemKind::Int(-2));
+        current_panel.push("new", ItemKind::UInt(3));
+        let mut current = Snapshot::default();
+        current.push("panel", ItemKind::Snapshot(current_panel));
+        current.push("reset", ItemKind::UInt(1));
+
+        let diff = current.diff(&previous);
+
+        assert_eq!(
+            diff.find("panel/count"),
+            FindItem::Found(&ItemKind::UInt(5))
+        );
+        assert_eq!(
+            diff.find("panel/gauge"),
+            FindItem::Found(&ItemKind::Int(-2))
+        );
+        assert_eq!(diff.find("panel/new"), FindItem::Found(&ItemKind::UInt(3)));
+        assert_eq!(diff.find("panel/gone"), FindItem::NotFound);
+        assert_eq!(diff.find("reset"), FindItem::Found(&ItemKind::UInt(1)));
+    }
+
+    #[test]
+    fn merge_combines_trees() {
+        let mut a_panel = Snapshot::default();
+        a_panel.push("count", ItemKind::UInt(1));
+        a_panel.push("gauge", ItemKind::Int(1));
+        let mut a = Snapshot::default();
+        a.push("panel", ItemKind::Snapshot(a_panel));
+        a.push("only_a", ItemKind::Boolean(true));
+
+        let mut b_panel = Snapshot::default();
+        b_panel.push("gauge", ItemKind::Int(2));
+        b_panel.push("meter", ItemKind::Float(0.5));
+        let mut b = Snapshot::default();
+        b.push("panel", ItemKind::Snapshot(b_panel));
+        b.push("only_b", ItemKind::Boolean(false));
+
+        a.merge(b);
+
+        assert_eq!(a.find("panel/count"), FindItem::Found(&ItemKind::UInt(1)));
+        assert_eq!(a.find("panel/gauge"), FindItem::Found(&ItemKind::Int(2)));
+        assert_eq!(
+            a.find("panel/meter"),
+            FindItem::Found(&ItemKind::Float(0.5))
+        );
+        assert_eq!(a.find("only_a"), FindItem::Found(&ItemKind::Boolean(true)));
+        assert_eq!(a.find("only_b"), FindItem::Found(&ItemKind::Boolean(false)));
+        assert_eq!(a.items.len(), 3);
+    }
+
+    #[test]
+    fn pretty_string_indents_nested_snapshots() {
+        let mut latency = Snapshot::default();
+        latency.push("count", ItemKind::UInt(3));
+        latency.push("mean", ItemKind::Float(1.5));
+        let mut panel = Snapshot::default();
+        panel.push("_unit_latency", ItemKind::Text("milliseconds".to_string()));
+        panel.push("latency", ItemKind::Snapshot(latency));
+        panel.push("cpu", ItemKind::Float(2.5));
+        panel.push("cpu_unit", ItemKind::Text("seconds".to_string()));
+        let mut snapshot = Snapshot::default();
+        snapshot.push("panel", ItemKind::Snapshot(panel));
+        snapshot.push("healthy", ItemKind::Boolean(true));
+
+        let expected = "\
+panel:
+  latency [milliseconds]:
+    count: 3
+    mean: 1.5
+  cpu: 2.5 seconds
+healthy: true
+";
+        assert_eq!(snapshot.to_pretty_string(), expected);
+    }
+
+    #[test]
+    fn filter_to_a_single_subtree() {
+        let mut a = Snapshot::default();
+        a.push("requests", ItemKind::UInt(1));
+        a.push("errors", ItemKind::UInt(0));
+        let mut b = Snapshot::default();
+        b.push("requests", ItemKind::UInt(2));
+
+        let mut tenants = Snapshot::default();
+        tenants.push("a", ItemKind::Snapshot(a.clone()));
+        tenants.push("b", ItemKind::Snapshot(b));
+        let mut snapshot = Snapshot::default();
+        snapshot.push("tenants", ItemKind::Snapshot(tenants));
+        snapshot.push("uptime", ItemKind::UInt(10));
+
+        let filtered = snapshot.filter(|path| path.starts_with("tenants.a."));
+
+        let mut expected_tenants = Snapshot::default();
+        expected_tenants.push("a", ItemKind::Snapshot(a.clone()));
+        let mut expected = Snapshot::default();
+        expected.push("tenants", ItemKind::Snapshot(expected_tenants));
+        assert_eq!(filtered, expected);
+
+        assert_eq!(filtered.subtree("tenants.a"), Some(a));
+        assert_eq!(snapshot.subtree(""), Some(snapshot.clone()));
+        assert_eq!(snapshot.subtree("uptime"), None);
+    }
+
+    #[test]
+    fn flatten_a_nested_snapshot() {
+        let mut quantiles = Snapshot::default();
+        quantiles.push("p50", ItemKind::Int(-3));
+        let mut latencies = Snapshot::default();
+        latencies.push("count", ItemKind::UInt(2));
+        latencies.push("mean", ItemKind::Float(1.5));
+        latencies.push("quantiles", ItemKind::Snapshot(quantiles));
+        latencies.push("_unit", ItemKind::Text("ms".to_string()));
+        let mut snapshot = Snapshot::default();
+        snapshot.push("latencies", ItemKind::Snapshot(latencies));
+        snapshot.push("healthy", ItemKind::Boolean(true));
+        snapshot.push("degraded", ItemKind::Boolean(false));
+        snapshot.push("empty", ItemKind::Snapshot(Snapshot::default()));
+
+        let flat = snapshot.to_flat_map();
+
+        let expected: BTreeMap<String, f64> = vec![
+            ("latencies.count", 2.0),
+            ("latencies.mean", 1.5),
+            ("latencies.quantiles.p50", -3.0),
+            ("healthy", 1.0),
+            ("degraded", 0.0),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+        assert_eq!(flat, expected);
+    }
+
+    #[test]
+    fn the_separator_of_flattened_paths_is_configurable() {
+        let histogram = Snapshot {
+            items: vec![
+                ("count".to_string(), ItemKind::UInt(10)),
+                ("sum".to_string(), ItemKind::Int(20)),
+            ],
+        };
+        let snapshot = Snapshot {
+            items: vec![
+                ("latency".to_string(), ItemKind::Snapshot(histogram)),
+                ("up".to_string(), ItemKind::Boolean(true)),
+            ],
+        };
+
+        let keys = |separator| -> Vec<String> {
+            snapshot
+                .flatten(separator)
+                .into_iter()
+                .map(|(path, _)| path)
+                .collect()
+        };
+
+        assert_eq!(keys("."), vec!["latency.count", "latency.sum", "up"]);
+        assert_eq!(keys("_"), vec!["latency_count", "latency_sum", "up"]);
+        assert_eq!(
+            keys(".")
+                .iter()
+                .map(|k| k.replace('.', "_"))
+                .collect::<Vec<_>>(),
+            keys("_")
+        );
+
+        let flat = snapshot.to_flat_map_with_separator("/");
+        assert_eq!(
+            flat.keys().collect::<Vec<_>>(),
+            vec!["latency/count", "latency/sum", "up"]
+        );
+    }
+}
@@ -1,10 +1,189 @@
 use std::fmt;
+use std::time::Instant;
 
+use crate::instruments::fundamentals::SharedClock;
+use crate::instruments::Clock;
 use crate::snapshot::{ItemKind, Snapshot};
-use crate::Descriptive;
+use crate::{Descriptive, PutsSnapshot, TimeUnit};
 
 const TITLE_FIELD_LABEL: &str = "_title";
 const DESCRIPTION_FIELD_LABEL: &str = "_description";
+pub(crate) const UNIT_FIELD_LABEL: &str = "_unit";
+const ACTIVE_FIELD_LABEL: &str = "_active";
+const INACTIVE_FIELD_LABEL: &str = "_inactive";
+const UPDATED_SECS_AGO_FIELD_LABEL: &str = "_updated_secs_ago";
+const TYPE_FIELD_LABEL: &str = "_type";
+const GAUGE_TYPE: &str = "gauge";
+
+/// The keys of the fields marking whether a component
+/// with inactivity tracking is active.
+#[derive(Clone)]
+pub(crate) struct ActivityMarkers {
+    active: String,
+    inactive: String,
+}
+
+impl ActivityMarkers {
+    pub fn new<A: Into<String>, I: Into<String>>(active: A, inactive: I) -> Self {
+        ActivityMarkers {
+            active: active.into(),
+            inactive: inactive.into(),
+        }
+    }
+
+    pub fn put(&self, is_active: bool, into: &mut Snapshot) {
+        into.items
+            .push((self.inactive.clone(), ItemKind::Boolean(!is_active)));
+        into.items
+            .push((self.active.clone(), ItemKind::Boolean(is_active)));
+    }
+
+    /// Puts the markers with the name of an instrument as a postfix
+    /// for instruments which do not have their own `Snapshot`.
+    pub fn put_postfixed(&self, is_active: bool, name: &str, into: &mut Snapshot) {
+        into.items.push((
+            format!("{}_{}", self.inactive, name),
+            ItemKind::Boolean(!is_active),
+        ));
+        into.items.push((
+            format!("{}_{}", self.active, name),
+            ItemKind::Boolean(is_active),
+        ));
+    }
+}
+
+/// Tracks when an instrument was updated last if enabled.
+///
+/// The time is taken from the `Clock` of the instrument.
+#[derive(Default)]
+pub(crate) struct UpdateTracker {
+    enabled: bool,
+    updated_at: Option<Instant>,
+    clock: SharedClock,
+}
+
+impl UpdateTracker {
+    /// Sets the `Clock` of the instrument. The last update is forgotten.
+    pub fn set_clock(&mut self, clock: SharedClock) {
+        self.clock = clock;
+        self.updated_at = None;
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.updated_at = None;
+        }
+    }
+
+    pub fn updated(&mut self) {
+        if self.enabled {
+            self.updated_at = Some(self.clock.now());
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.updated_at = None;
+    }
+
+    /// Returns a tracker with the same setting which was never updated
+    pub fn template(&self) -> Self {
+        UpdateTracker {
+            enabled: self.enabled,
+            updated_at: None,
+            clock: self.clock.clone(),
+        }
+    }
+
+    fn secs_ago(&self, updated_at: Instant) -> f64 {
+        self.clock
+            .now()
+            .saturating_duration_since(updated_at)
+            .as_secs_f64()
+    }
+
+    /// Puts `_updated_secs_ago` into a nested `Snapshot` of an instrument
+    pub fn put(&self, into: &mut Snapshot) {
+        if let Some(updated_at) = self.updated_at {
+            into.items.push((
+                UPDATED_SECS_AGO_FIELD_LABEL.to_string(),
+                self.secs_ago(updated_at).into(),
+            ));
+        }
+    }
+
+    /// Puts `_updated_secs_ago_[name]` next to the value of an instrument
+    pub fn put_postfixed(&self, name: &str, into: &mut Snapshot) {
+        if let Some(updated_at) = self.updated_at {
+            into.items.push((
+                format!("{}_{}", UPDATED_SECS_AGO_FIELD_LABEL, name),
+                self.secs_ago(updated_at).into(),
+            ));
+        }
+    }
+}
+
+impl Default for ActivityMarkers {
+    fn default() -> Self {
+        ActivityMarkers::new(ACTIVE_FIELD_LABEL, INACTIVE_FIELD_LABEL)
+    }
+}
+
+/// Calls `put_exported_snapshot` on `component` if the `Snapshot`
+/// will be exported and `put_snapshot` otherwise.
+pub fn put_snapshot_of<T>(component: &T, into: &mut Snapshot, descriptive: bool, exported: bool)
+where
+    T: PutsSnapshot + ?Sized,
+{
+    if exported {
+        component.put_exported_snapshot(into, descriptive)
+    } else {
+        component.put_snapshot(into, descriptive)
+    }
+}
+
+/// Puts `_type_[name]` next to an unsigned value which can go down,
+/// e.g. a count which starts over with each exported `Snapshot`.
+///
+/// Exporters which derive the type from the value would render it as
+/// a counter otherwise. See `is_gauge_type`.
+pub fn put_gauge_type(name: &str, into: &mut Snapshot) {
+    into.items.push((
+        format!("{}_{}", TYPE_FIELD_LABEL, name),
+        ItemKind::Text(GAUGE_TYPE.to_string()),
+    ));
+}
+
+/// Returns true if the value named `name` within `snapshot`
+/// was marked with `put_gauge_type`.
+pub fn is_gauge_type(snapshot: &Snapshot, name: &str) -> bool {
+    let type_name = format!("{}_{}", TYPE_FIELD_LABEL, name);
+    snapshot.items.iter().any(|(n, item)| match item {
+        ItemKind::Text(t) => *n == type_name && t == GAUGE_TYPE,
+        _ => false,
+    })
+}
+
+/// Puts a placeholder for a value named `name` for `PutsSnapshot::put_names`
+pub fn put_name(name: &str, into: &mut Snapshot) {
+    into.items.push((name.to_string(), ItemKind::Boolean(true)));
+}
+
+/// Returns the paths separated by `/` which `put_names` puts more
+/// than once. See `PutsSnapshot::put_names`.
+pub fn validate_names<F>(put_names: F) -> Result<(), Vec<String>>
+where
+    F: FnOnce(&mut Snapshot),
+{
+    let mut names = Snapshot::default();
+    put_names(&mut names);
+    let duplicates = names.duplicate_paths();
+    if duplicates.is_empty() {
+        Ok(())
+    } else {
+        Err(duplicates)
+    }
+}
 
 pub fn put_default_descriptives<T>(what: &T, into: &mut Snapshot, add_descriptive_parts: bool)
 where
@@ -46,6 +225,27 @@ pub fn put_postfixed_descriptives<T>(
     }
 }
 
+/// Puts the name of the `TimeUnit` as `_unit_[field_label_postfix]`
+/// if there is a unit.
+pub fn put_postfixed_time_unit(
+    time_unit: Option<TimeUnit>,
+    field_label_postfix: &str,
+    into: &mut Snapshot,
+    add_descriptive_parts: bool,
+) {
+    if !add_descriptive_parts {
+        return;
+    }
+
+    if let Some(time_unit) = time_unit {
+        let label = format!("{}_{}", UNIT_FIELD_LABEL, field_label_postfix);
+        if !into.items.iter().any(|(n, _)| n == &label) {
+            into.items
+                .push((label, ItemKind::Text(time_unit.name().to_string())));
+        }
+    }
+}
+
 pub fn put_descriptives<T>(
     what: &T,
     title_field_label: &str,
@@ -83,6 +283,20 @@ pub fn put_descriptives<T>(
     }
 }
 
+/// Replaces every character of `name` which is neither an ASCII
+/// letter, a digit, `_` nor one of `allowed` by `_`.
+pub fn sanitize_name(name: &str, allowed: &[char]) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || allowed.contains(&c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
 #[cfg(feature = "log")]
 #[inline]
 pub fn log_error<T: fmt::Display>(message: T) {